@@ -0,0 +1,126 @@
+#![cfg_attr(not(test), no_std)]
+
+// The copy loop behind cat, written against the uapi IO traits so it can be
+// tested on the host with in-memory readers and writers.
+
+use uapi::io::{IoRead, IoWrite};
+use uapi::SysResult;
+
+/// Stream everything `r` yields to `w` until EOF. Returns the bytes copied.
+pub fn cat_one<R: IoRead, W: IoWrite>(r: &R, w: &W, buf: &mut [u8]) -> SysResult<usize> {
+    let mut total = 0;
+    loop {
+        let n = r.read(buf)?;
+        if n == 0 {
+            return Ok(total);
+        }
+        w.write_all(&buf[..n])?;
+        total += n;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::{Cell, RefCell};
+    use uapi::SysErr;
+
+    /// Hands out `chunks` one per read, then EOF.
+    struct ChunkReader<'a> {
+        chunks: &'a [&'a [u8]],
+        next: Cell<usize>,
+    }
+
+    impl IoRead for ChunkReader<'_> {
+        fn read(&self, buf: &mut [u8]) -> SysResult<usize> {
+            let i = self.next.get();
+            let Some(chunk) = self.chunks.get(i) else { return Ok(0) };
+            self.next.set(i + 1);
+            buf[..chunk.len()].copy_from_slice(chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    /// A file's contents, read as far as each buffer allows.
+    struct FileReader<'a> {
+        data: &'a [u8],
+        pos: Cell<usize>,
+    }
+
+    impl IoRead for FileReader<'_> {
+        fn read(&self, buf: &mut [u8]) -> SysResult<usize> {
+            let rest = &self.data[self.pos.get()..];
+            let n = rest.len().min(buf.len());
+            buf[..n].copy_from_slice(&rest[..n]);
+            self.pos.set(self.pos.get() + n);
+            Ok(n)
+        }
+    }
+
+    /// Accepts at most 3 bytes per write so `write_all` has to loop.
+    struct SlowWriter {
+        out: RefCell<([u8; 64], usize)>,
+    }
+
+    impl IoWrite for SlowWriter {
+        fn write(&self, buf: &[u8]) -> SysResult<usize> {
+            let mut out = self.out.borrow_mut();
+            let (data, len) = &mut *out;
+            let n = buf.len().min(3).min(data.len() - *len);
+            if n == 0 {
+                return Err(SysErr::Fail);
+            }
+            data[*len..*len + n].copy_from_slice(&buf[..n]);
+            *len += n;
+            Ok(n)
+        }
+    }
+
+    /// Collects everything written to it.
+    struct VecWriter {
+        out: RefCell<Vec<u8>>,
+    }
+
+    impl IoWrite for VecWriter {
+        fn write(&self, buf: &[u8]) -> SysResult<usize> {
+            self.out.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn test_cat_one_copies_every_chunk() {
+        let chunks: [&[u8]; 3] = [b"hello ", b"from", b" several chunks\n"];
+        let r = ChunkReader { chunks: &chunks, next: Cell::new(0) };
+        let w = SlowWriter { out: RefCell::new(([0; 64], 0)) };
+        let mut buf = [0u8; 32];
+        assert_eq!(cat_one(&r, &w, &mut buf), Ok(26));
+        let out = w.out.borrow();
+        assert_eq!(&out.0[..out.1], b"hello from several chunks\n");
+    }
+
+    #[test]
+    fn test_cat_one_empty_and_write_failure() {
+        let r = ChunkReader { chunks: &[], next: Cell::new(0) };
+        let w = SlowWriter { out: RefCell::new(([0; 64], 0)) };
+        let mut buf = [0u8; 8];
+        assert_eq!(cat_one(&r, &w, &mut buf), Ok(0));
+
+        let chunks: [&[u8]; 1] = [b"0123456789"];
+        let r = ChunkReader { chunks: &chunks, next: Cell::new(0) };
+        let w = SlowWriter { out: RefCell::new(([0; 64], 60)) };
+        let mut buf = [0u8; 16];
+        assert_eq!(cat_one(&r, &w, &mut buf), Err(SysErr::Fail));
+    }
+
+    #[test]
+    fn test_cat_one_file_larger_than_the_buffer() {
+        // Several buffer-fulls and a partial one, like a big file through cat's 4 KiB buffer
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let r = FileReader { data: &data, pos: Cell::new(0) };
+        let w = VecWriter { out: RefCell::new(Vec::new()) };
+        let mut buf = [0u8; 4096];
+        assert_eq!(cat_one(&r, &w, &mut buf), Ok(10_000));
+        assert_eq!(*w.out.borrow(), data);
+    }
+}
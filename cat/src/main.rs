@@ -1,62 +1,59 @@
 #![no_std]
 #![no_main]
 
-use usys::{println, eprintln, cstr, CStrBuf, read_line_stdin, open, IoRead, STDOUT, exit};
+use cat::cat_one;
+use usys::{eprintln, exit, open, read_line_stdin, println, CStrBuf, STDOUT};
 
-#[no_mangle]
-pub extern "C" fn _start(_argc: usize, _argv: *const *const u8, _envp: *const *const u8) -> ! {
-        println!("enter a path (default: hello.txt): ");
-        let mut line = [0u8; 256];
-        let n = read_line_stdin(&mut line).unwrap_or(0);
-
-        // choose default if empty
-        let path_cstr = if n == 0 {
-            cstr!("hello.txt")
-        } else {
-            // trim trailing spaces (optional)
-            let mut end = n;
-            while end > 0 && (line[end-1] == b' ' || line[end-1] == b'\t') { end -= 1; }
-            let s = core::str::from_utf8(&line[..end]).unwrap_or("");
-            // Build an owned C string
-            let owned = CStrBuf::<256>::from_str(s).unwrap();
-            // Keep it in scope while using it:
-            let c = owned.as_cstr();
-            // Use it right away (or store `owned`)
-            match open(c) {
-                Ok(fd) => {
-                    let mut buf = [0u8; 256];
-                    loop {
-                        match fd.read(&mut buf) {
-                            Ok(0) => break,
-                            Ok(m) => { let _ = STDOUT.write(&buf[..m]); }
-                            Err(_) => { eprintln!("read error"); break; }
-                        }
-                    }
-                }
-                Err(_) => eprintln!("open failed"),
-            }
-            exit(); // early exit to keep example short
-        };
+const BUF_SIZE: usize = 4096;
 
-        // If empty line, default path:
-        match open(path_cstr) {
-            Ok(fd) => {
-                let mut buf = [0u8; 256];
-                loop {
-                    match fd.read(&mut buf) {
-                        Ok(0) => break,
-                        Ok(m) => { let _ = STDOUT.write(&buf[..m]); }
-                        Err(_) => { eprintln!("read error"); break; }
-                    }
-                }
+/// Print one file; open and read errors are reported on stderr.
+fn cat_path(path: &str, buf: &mut [u8]) {
+    let Ok(cpath) = CStrBuf::<256>::from_str(path) else {
+        eprintln!("cat: {}: bad path", path);
+        return;
+    };
+    match open(cpath.as_cstr()) {
+        Ok(fd) => {
+            if cat_one(&fd, &STDOUT, buf).is_err() {
+                eprintln!("cat: {}: read error", path);
             }
-            Err(_) => eprintln!("open failed"),
+            let _ = fd.close();
         }
+        Err(_) => eprintln!("cat: {}: cannot open", path),
+    }
+}
 
+/// # Safety
+/// Only the kernel calls this, with argv/envp on the initial user stack.
+#[no_mangle]
+pub unsafe extern "C" fn _start(argc: usize, argv: *const *const u8, _envp: *const *const u8) -> ! {
+    usys::init_args(argc, argv);
+    let mut buf = [0u8; BUF_SIZE];
+
+    if usys::args().count() > 1 {
+        for path in usys::args().skip(1) {
+            cat_path(path, &mut buf);
+        }
         exit();
     }
 
+    // No arguments: ask for a path instead
+    println!("enter a path (default: hello.txt): ");
+    let mut line = [0u8; 256];
+    let n = read_line_stdin(&mut line).unwrap_or(0);
+    let mut end = n;
+    while end > 0 && (line[end - 1] == b' ' || line[end - 1] == b'\t') {
+        end -= 1;
+    }
+    let path = match core::str::from_utf8(&line[..end]).unwrap_or("") {
+        "" => "hello.txt",
+        s => s,
+    };
+    cat_path(path, &mut buf);
+    exit();
+}
+
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
     loop {}
-}
\ No newline at end of file
+}
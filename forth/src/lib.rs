@@ -1,62 +1,372 @@
 #![cfg_attr(not(test), no_std)]
 
-// Forth interpreter core logic - can be tested on host
+// Forth interpreter core logic - can be tested on host. With the `alloc`
+// feature the data stack and the dictionary of user words grow on the heap;
+// without it (the forth.elf default) they are fixed arrays.
 
-// Maximum stack depth
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+
+// Maximum stack depth (without `alloc`)
 pub const STACK_SIZE: usize = 64;
 
+// Most user words the fixed dictionary holds, and the bytes of name and
+// body text they share (without `alloc`)
+pub const MAX_WORDS: usize = 32;
+pub const DICT_BYTES: usize = 2048;
+
+// Longest body one fixed-dictionary word may have
+pub const MAX_DEF_LEN: usize = 128;
+
+// Longest number `format_number` writes: a sign and 32 binary digits
+const NUMBER_LEN: usize = 33;
+
+// Words longer than this are never folded to lower case; no built-in is
+const FOLD_LEN: usize = 16;
+
+// Deepest nesting of user words calling user words, by default. Each level
+// takes about 2.5 KiB of stack, and forth.elf's 16 KiB stack also holds the
+// interpreter itself.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 4;
+
+// Bytes of `s"` text kept for `type`; older strings are overwritten once it
+// wraps, so an address is only good until a few more strings are made
+pub const STRING_POOL: usize = 256;
+
+// Words one `eval` may execute before giving up (guards against runaway loops)
+pub const DEFAULT_STEP_LIMIT: usize = 1_000_000;
+
+// Most words one line may contain
+pub const MAX_TOKENS: usize = 128;
+
+// Deepest nesting of begin loops within a line
+pub const MAX_NESTING: usize = 16;
+
+// Size of the captured output buffer used when no sink is set
+pub const OUTPUT_SIZE: usize = 256;
+
+// SGR sequences used when `color` is on
+const SGR_RED: &str = "\x1b[31m";
+const SGR_RESET: &str = "\x1b[0m";
+
+// Where printing words send their text: straight to a sink (the terminal in
+// the real binary) or, without one, into a buffer that tests can inspect.
+struct Output {
+    buf: [u8; OUTPUT_SIZE],
+    len: usize,
+    sink: Option<fn(&str)>,
+}
+
+impl Write for Output {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if let Some(sink) = self.sink {
+            sink(s);
+            return Ok(());
+        }
+        // Truncate silently once the buffer is full
+        let n = core::cmp::min(s.len(), OUTPUT_SIZE - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+// The data stack: a fixed array of STACK_SIZE cells, or a Vec with `alloc`
+#[cfg(not(feature = "alloc"))]
+struct Stack {
+    cells: [i32; STACK_SIZE],
+    len: usize, // next free slot
+}
+
+#[cfg(not(feature = "alloc"))]
+impl Stack {
+    const fn new() -> Self {
+        Stack { cells: [0; STACK_SIZE], len: 0 }
+    }
+
+    fn push(&mut self, val: i32) -> Result<(), &'static str> {
+        let slot = self.cells.get_mut(self.len).ok_or("Stack overflow")?;
+        *slot = val;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<i32> {
+        self.len = self.len.checked_sub(1)?;
+        Some(self.cells[self.len])
+    }
+
+    fn as_slice(&self) -> &[i32] {
+        &self.cells[..self.len]
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+#[cfg(feature = "alloc")]
+struct Stack {
+    cells: Vec<i32>,
+}
+
+#[cfg(feature = "alloc")]
+impl Stack {
+    const fn new() -> Self {
+        Stack { cells: Vec::new() }
+    }
+
+    fn push(&mut self, val: i32) -> Result<(), &'static str> {
+        self.cells.push(val);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<i32> {
+        self.cells.pop()
+    }
+
+    fn as_slice(&self) -> &[i32] {
+        &self.cells
+    }
+
+    fn clear(&mut self) {
+        self.cells.clear();
+    }
+}
+
+// Words defined with `: name ... ;`, kept as their body text and looked up
+// newest first, so a redefinition hides the old word. Without `alloc` names
+// and bodies are packed into one DICT_BYTES buffer.
+#[cfg(not(feature = "alloc"))]
+struct Dictionary {
+    text: [u8; DICT_BYTES],
+    used: usize,
+    // (start in text, name length, body length); the body follows the name
+    entries: [(usize, usize, usize); MAX_WORDS],
+    count: usize,
+}
+
+// A copy of a word's body, so it can run while the dictionary changes
+#[cfg(not(feature = "alloc"))]
+struct Body {
+    buf: [u8; MAX_DEF_LEN],
+    len: usize,
+}
+
+#[cfg(not(feature = "alloc"))]
+impl Body {
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl Dictionary {
+    const fn new() -> Self {
+        Dictionary { text: [0; DICT_BYTES], used: 0, entries: [(0, 0, 0); MAX_WORDS], count: 0 }
+    }
+
+    fn define(&mut self, name: &str, body: &str) -> Result<(), &'static str> {
+        if body.len() > MAX_DEF_LEN {
+            return Err("Definition too long");
+        }
+        let start = self.used;
+        let end = start + name.len() + body.len();
+        if self.count == MAX_WORDS || end > DICT_BYTES {
+            return Err("Dictionary full");
+        }
+        self.text[start..start + name.len()].copy_from_slice(name.as_bytes());
+        self.text[start + name.len()..end].copy_from_slice(body.as_bytes());
+        self.entries[self.count] = (start, name.len(), body.len());
+        self.count += 1;
+        self.used = end;
+        Ok(())
+    }
+
+    fn find(&self, name: &str, ignore_case: bool) -> Option<usize> {
+        (0..self.count).rev().find(|&i| {
+            let (start, name_len, _) = self.entries[i];
+            let stored = &self.text[start..start + name_len];
+            if ignore_case { stored.eq_ignore_ascii_case(name.as_bytes()) } else { stored == name.as_bytes() }
+        })
+    }
+
+    fn body(&self, idx: usize) -> Body {
+        let (start, name_len, body_len) = self.entries[idx];
+        let mut body = Body { buf: [0; MAX_DEF_LEN], len: body_len };
+        body.buf[..body_len].copy_from_slice(&self.text[start + name_len..start + name_len + body_len]);
+        body
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+}
+
+#[cfg(feature = "alloc")]
+struct Dictionary {
+    entries: Vec<(String, String)>,
+}
+
+#[cfg(feature = "alloc")]
+type Body = String;
+
+#[cfg(feature = "alloc")]
+impl Dictionary {
+    const fn new() -> Self {
+        Dictionary { entries: Vec::new() }
+    }
+
+    fn define(&mut self, name: &str, body: &str) -> Result<(), &'static str> {
+        self.entries.push((String::from(name), String::from(body)));
+        Ok(())
+    }
+
+    fn find(&self, name: &str, ignore_case: bool) -> Option<usize> {
+        self.entries.iter().rposition(|(n, _)| if ignore_case { n.eq_ignore_ascii_case(name) } else { n == name })
+    }
+
+    fn body(&self, idx: usize) -> Body {
+        self.entries[idx].1.clone()
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
 // Forth interpreter state
 pub struct Forth {
-    stack: [i32; STACK_SIZE],
-    sp: usize, // stack pointer (points to next free slot)
+    stack: Stack,
+    dict: Dictionary,
+    out: Output,
+    /// Print negative numbers in red from `.` and `.s` (for fb_console)
+    pub color: bool,
+    /// Match built-ins and user words regardless of case, so `DUP` is `dup`
+    pub case_insensitive: bool,
+    base: u32, // for reading and printing numbers, set by `hex` and `decimal`
+    clock: Option<fn() -> u64>, // milliseconds since boot, for `millis`
+    sleep: Option<fn(u64)>,     // wait this many milliseconds, for `ms`
+    step_limit: usize,
+    steps_left: usize, // budget remaining in the current eval
+    max_call_depth: usize,
+    call_depth: usize, // user words currently running
+    current_word: Option<usize>, // innermost of them, for `recurse`
+    strings: [u8; STRING_POOL],
+    strings_end: usize, // where the next `s"` string goes
 }
 
 impl Forth {
     pub fn new() -> Self {
         Forth {
-            stack: [0; STACK_SIZE],
-            sp: 0,
+            stack: Stack::new(),
+            dict: Dictionary::new(),
+            out: Output { buf: [0; OUTPUT_SIZE], len: 0, sink: None },
+            color: false,
+            case_insensitive: true,
+            base: 10,
+            clock: None,
+            sleep: None,
+            step_limit: DEFAULT_STEP_LIMIT,
+            steps_left: DEFAULT_STEP_LIMIT,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            call_depth: 0,
+            current_word: None,
+            strings: [0; STRING_POOL],
+            strings_end: 0,
         }
     }
 
-    pub fn push(&mut self, val: i32) -> Result<(), &'static str> {
-        if self.sp >= STACK_SIZE {
-            Err("Stack overflow")
-        } else {
-            self.stack[self.sp] = val;
-            self.sp += 1;
-            Ok(())
+    // Cap the number of words a single `eval` may execute
+    pub fn set_step_limit(&mut self, limit: usize) {
+        self.step_limit = limit;
+    }
+
+    // Cap how deeply user words may call each other (and themselves)
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    // Charge one word against the step budget
+    fn step(&mut self) -> Result<(), &'static str> {
+        if self.steps_left == 0 {
+            return Err("Step limit exceeded");
         }
+        self.steps_left -= 1;
+        Ok(())
     }
 
-    pub fn pop(&mut self) -> Result<i32, &'static str> {
-        if self.sp == 0 {
-            Err("Stack underflow")
+    // Send output to `sink` instead of the internal buffer
+    pub fn set_sink(&mut self, sink: fn(&str)) {
+        self.out.sink = Some(sink);
+    }
+
+    // Where `millis` reads the time; forth.elf passes usys::uptime_ms
+    pub fn set_clock(&mut self, clock: fn() -> u64) {
+        self.clock = Some(clock);
+    }
+
+    // How `ms` waits; forth.elf passes usys::sleep_ms
+    pub fn set_sleep(&mut self, sleep: fn(u64)) {
+        self.sleep = Some(sleep);
+    }
+
+    // Text printed so far (only collected when no sink is set)
+    pub fn output(&self) -> &str {
+        core::str::from_utf8(&self.out.buf[..self.out.len]).unwrap_or("")
+    }
+
+    pub fn clear_output(&mut self) {
+        self.out.len = 0;
+    }
+
+    // Print one number followed by a space, as `.` and `.s` do
+    fn print_number(&mut self, val: i32) {
+        let mut buf = [0; NUMBER_LEN];
+        let text = format_number(val as i64, self.base, &mut buf);
+        if self.color && val < 0 {
+            let _ = write!(self.out, "{}{}{} ", SGR_RED, text, SGR_RESET);
         } else {
-            self.sp -= 1;
-            Ok(self.stack[self.sp])
+            let _ = write!(self.out, "{} ", text);
         }
     }
 
+    pub fn push(&mut self, val: i32) -> Result<(), &'static str> {
+        self.stack.push(val)
+    }
+
+    pub fn pop(&mut self) -> Result<i32, &'static str> {
+        self.stack.pop().ok_or("Stack underflow")
+    }
+
     pub fn peek(&self) -> Result<i32, &'static str> {
-        if self.sp == 0 {
-            Err("Stack empty")
-        } else {
-            Ok(self.stack[self.sp - 1])
-        }
+        self.stack.as_slice().last().copied().ok_or("Stack empty")
     }
 
     pub fn depth(&self) -> usize {
-        self.sp
+        self.stack.as_slice().len()
     }
 
     pub fn stack_contents(&self) -> &[i32] {
-        &self.stack[..self.sp]
+        self.stack.as_slice()
+    }
+
+    // Number of words defined with `:` (redefinitions count again)
+    pub fn word_count(&self) -> usize {
+        self.dict.len()
     }
 
-    // Execute a single word
+    // Execute a single word: a user word if one has this name, else a built-in
     pub fn execute_word(&mut self, word: &str) -> Result<(), &'static str> {
-        match word {
+        if let Some(idx) = self.dict.find(word, self.case_insensitive) {
+            return self.call(idx);
+        }
+        let mut buf = [0; FOLD_LEN];
+        match self.fold(word, &mut buf) {
             // Arithmetic operations (using checked arithmetic to prevent overflow)
             "+" => {
                 let b = self.pop()?;
@@ -96,6 +406,11 @@ impl Forth {
                 let result = a.checked_rem(b).ok_or("Arithmetic overflow")?;
                 self.push(result)?;
             }
+            "abs" => {
+                let a = self.pop()?;
+                let result = a.checked_abs().ok_or("Arithmetic overflow")?;
+                self.push(result)?;
+            }
             
             // Stack manipulation
             "dup" => {
@@ -112,15 +427,13 @@ impl Forth {
                 self.push(a)?;
             }
             "over" => {
-                if self.sp < 2 {
-                    return Err("Stack underflow");
-                }
-                let val = self.stack[self.sp - 2];
+                let cells = self.stack.as_slice();
+                let val = *cells.len().checked_sub(2).and_then(|i| cells.get(i)).ok_or("Stack underflow")?;
                 self.push(val)?;
             }
             "rot" => {
                 // ( a b c -- b c a )
-                if self.sp < 3 {
+                if self.depth() < 3 {
                     return Err("Stack underflow");
                 }
                 let c = self.pop()?;
@@ -130,6 +443,13 @@ impl Forth {
                 self.push(c)?;
                 self.push(a)?;
             }
+            "depth" => {
+                // ( -- n ) n counts the cells below it, not itself
+                self.push(self.depth() as i32)?;
+            }
+            "clearstack" | "sp!" => {
+                self.stack.clear();
+            }
             
             // Comparison operations
             "=" => {
@@ -147,6 +467,11 @@ impl Forth {
                 let a = self.pop()?;
                 self.push(if a > b { -1 } else { 0 })?;
             }
+            "u<" => {
+                let b = self.pop()? as u32;
+                let a = self.pop()? as u32;
+                self.push(if a < b { -1 } else { 0 })?;
+            }
             
             // Logical operations
             "and" => {
@@ -168,12 +493,95 @@ impl Forth {
                 let a = self.pop()?;
                 self.push(!a)?;
             }
+            "lshift" => {
+                let n = shift_count(self.pop()?)?;
+                let a = self.pop()?;
+                self.push(((a as u32) << n) as i32)?;
+            }
+            "rshift" => {
+                // Logical: zeros shift in from the top
+                let n = shift_count(self.pop()?)?;
+                let a = self.pop()?;
+                self.push(((a as u32) >> n) as i32)?;
+            }
+            "arshift" => {
+                // Arithmetic: the sign bit is copied down
+                let n = shift_count(self.pop()?)?;
+                let a = self.pop()?;
+                self.push(a >> n)?;
+            }
             "negate" => {
                 let a = self.pop()?;
                 let result = a.checked_neg().ok_or("Arithmetic overflow")?;
                 self.push(result)?;
             }
             
+            // I/O operations
+            "." => {
+                let val = self.pop()?;
+                self.print_number(val);
+                let _ = self.out.write_str("\n");
+            }
+            "u." => {
+                // Same cell, read as unsigned
+                let val = self.pop()? as u32;
+                let mut buf = [0; NUMBER_LEN];
+                let _ = writeln!(self.out, "{} ", format_number(val as i64, self.base, &mut buf));
+            }
+            ".r" => {
+                // ( n width -- ) right-justify n in width columns, no space after
+                let width = self.pop()?;
+                let val = self.pop()?;
+                let mut buf = [0; NUMBER_LEN];
+                let text = format_number(val as i64, self.base, &mut buf);
+                let width = usize::try_from(width).unwrap_or(0);
+                let _ = write!(self.out, "{:>width$}", text, width = width);
+            }
+            "millis" => {
+                // ( -- ms ) uptime, wrapping after about 24 days up
+                let clock = self.clock.ok_or("No clock")?;
+                self.push(clock() as i32)?;
+            }
+            "ms" => {
+                // ( n -- ) wait n milliseconds
+                let sleep = self.sleep.ok_or("No sleep hook")?;
+                let n = u64::try_from(self.pop()?).map_err(|_| "Invalid duration")?;
+                sleep(n);
+            }
+            "hex" => self.base = 16,
+            "decimal" => self.base = 10,
+            ".s" => {
+                let _ = write!(self.out, "<{}> ", self.depth());
+                for i in 0..self.depth() {
+                    self.print_number(self.stack.as_slice()[i]);
+                }
+                let _ = self.out.write_str("\n");
+            }
+            "cr" => {
+                let _ = self.out.write_str("\n");
+            }
+            "emit" => {
+                let val = self.pop()?;
+                if (0..=127).contains(&val) {
+                    let _ = self.out.write_char(val as u8 as char);
+                } else {
+                    return Err("Invalid character code");
+                }
+            }
+            "recurse" => {
+                // Call the word whose body this is again
+                let idx = self.current_word.ok_or("recurse outside a definition")?;
+                self.call(idx)?;
+            }
+            "type" => {
+                // ( addr len -- ) print a string made by s"
+                let len = usize::try_from(self.pop()?).map_err(|_| "Invalid string")?;
+                let addr = usize::try_from(self.pop()?).map_err(|_| "Invalid string")?;
+                let bytes = addr.checked_add(len).and_then(|end| self.strings.get(addr..end)).ok_or("Invalid string")?;
+                let text = core::str::from_utf8(bytes).map_err(|_| "Invalid string")?;
+                let _ = self.out.write_str(text);
+            }
+
             // Constants
             "true" => {
                 self.push(-1)?;
@@ -188,7 +596,7 @@ impl Forth {
             
             _ => {
                 // Try to parse as a number
-                if let Some(num) = parse_number(word) {
+                if let Some(num) = parse_number_in(word, self.base) {
                     self.push(num)?;
                 } else {
                     return Err("Unknown word");
@@ -198,70 +606,314 @@ impl Forth {
         Ok(())
     }
 
-    // Evaluate a line of Forth code
+    // Evaluate a line of Forth code. The line is split into words first and
+    // loop words are resolved to jump targets, so control flow can move
+    // backwards and forwards through it. `: name ... ;` defines a word whose
+    // body runs the same way each time it is called.
     pub fn eval(&mut self, line: &str) -> Result<(), &'static str> {
-        let words = line.split_whitespace();
-        for word in words {
-            self.execute_word(word)?;
+        self.steps_left = self.step_limit;
+        self.run(line)
+    }
+
+    // Run a line or a user word's body against the current step budget
+    fn run(&mut self, line: &str) -> Result<(), &'static str> {
+        let words = tokenize(line, self.case_insensitive)?;
+        let jumps = resolve_jumps(&words, self.case_insensitive)?;
+
+        let mut pc = 0;
+        while pc < words.len() {
+            self.step()?;
+            let mut buf = [0; FOLD_LEN];
+            pc = match self.fold(words.get(pc), &mut buf) {
+                "begin" => pc + 1,
+                // ( flag -- ) loop back to begin until the flag is true
+                "until" => {
+                    if self.pop()? == 0 { jumps[pc] as usize } else { pc + 1 }
+                }
+                // ( flag -- ) leave the loop once the flag is false
+                "while" => {
+                    if self.pop()? == 0 { jumps[pc] as usize } else { pc + 1 }
+                }
+                "repeat" => jumps[pc] as usize,
+                // The next word is the text, closing quote included
+                ".\"" => {
+                    let _ = self.out.write_str(string_text(words.get(pc + 1)));
+                    pc + 2
+                }
+                "s\"" => {
+                    let (addr, len) = self.store_string(string_text(words.get(pc + 1)))?;
+                    self.push(addr as i32)?;
+                    self.push(len as i32)?;
+                    pc + 2
+                }
+                // Everything up to the matching `;` is the definition
+                ":" => {
+                    let end = jumps[pc] as usize;
+                    self.define(&words, pc + 1, end - 1)?;
+                    end
+                }
+                _ => {
+                    self.execute_word(words.get(pc))?;
+                    pc + 1
+                }
+            };
+        }
+        Ok(())
+    }
+
+    // Run user word `idx`, counting it against the call depth
+    fn call(&mut self, idx: usize) -> Result<(), &'static str> {
+        if self.call_depth >= self.max_call_depth {
+            return Err("Call depth exceeded");
+        }
+        let body = self.dict.body(idx);
+        let caller = self.current_word.replace(idx);
+        self.call_depth += 1;
+        let result = self.run(body.as_str());
+        self.call_depth -= 1;
+        self.current_word = caller;
+        result
+    }
+
+    // Add a word from words[start..end], the name and body between `:` and `;`
+    fn define(&mut self, words: &Words, start: usize, end: usize) -> Result<(), &'static str> {
+        if start == end {
+            return Err("Missing name");
+        }
+        let name = words.get(start);
+        let mut buf = [0; FOLD_LEN];
+        if matches!(self.fold(name, &mut buf), ":" | ";" | "begin" | "until" | "while" | "repeat" | ".\"" | "s\"") {
+            return Err("Invalid name");
+        }
+        let body = words.text(start + 1, end);
+        // Catch unbalanced loops now rather than on every call
+        resolve_jumps(&tokenize(body, self.case_insensitive)?, self.case_insensitive)?;
+        self.dict.define(name, body)
+    }
+
+    // `word` as built-ins are matched: lower-cased if case_insensitive
+    fn fold<'a>(&self, word: &'a str, buf: &'a mut [u8; FOLD_LEN]) -> &'a str {
+        if self.case_insensitive { fold_case(word, buf) } else { word }
+    }
+
+    // Copy `text` into the string pool, returning its (addr, len)
+    fn store_string(&mut self, text: &str) -> Result<(usize, usize), &'static str> {
+        let len = text.len();
+        if len > STRING_POOL {
+            return Err("String too long");
+        }
+        if self.strings_end + len > STRING_POOL {
+            self.strings_end = 0;
+        }
+        let addr = self.strings_end;
+        self.strings[addr..addr + len].copy_from_slice(text.as_bytes());
+        self.strings_end += len;
+        Ok((addr, len))
+    }
+}
+
+// The words of a line, as byte ranges into it. Kept small because a run of
+// nested user words holds one per level on the stack.
+struct Words<'a> {
+    line: &'a str,
+    spans: [(u16, u16); MAX_TOKENS],
+    len: usize,
+}
+
+// Jump targets are word indexes, which must fit in a byte
+const _: () = assert!(MAX_TOKENS < u8::MAX as usize);
+
+impl<'a> Words<'a> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, i: usize) -> &'a str {
+        let (start, end) = self.spans[i];
+        &self.line[start as usize..end as usize]
+    }
+
+    // The text from words[start] through words[end - 1], as written
+    fn text(&self, start: usize, end: usize) -> &'a str {
+        if start == end {
+            return "";
+        }
+        &self.line[self.spans[start].0 as usize..self.spans[end - 1].1 as usize]
+    }
+
+    fn push(&mut self, start: usize, end: usize) -> Result<(), &'static str> {
+        if self.len == MAX_TOKENS || end > u16::MAX as usize {
+            return Err("Line too long");
         }
+        self.spans[self.len] = (start as u16, end as u16);
+        self.len += 1;
         Ok(())
     }
 }
 
-// Parse a number (handles negative numbers)
-pub fn parse_number(s: &str) -> Option<i32> {
-    if s.is_empty() {
-        return None;
+// Split a line into words at whitespace. After `."` or `s"` everything up to
+// and including the next `"` is one word, spaces and all, so the text comes
+// through untouched. Definitions keep their body text as written, which
+// splits the same way again.
+fn tokenize(line: &str, ignore_case: bool) -> Result<Words<'_>, &'static str> {
+    let mut words = Words { line, spans: [(0, 0); MAX_TOKENS], len: 0 };
+    let mut pos = 0;
+    loop {
+        let rest = &line[pos..];
+        let start = pos + (rest.len() - rest.trim_start().len());
+        if start == line.len() {
+            return Ok(words);
+        }
+        let end = line[start..].find(char::is_whitespace).map_or(line.len(), |n| start + n);
+        words.push(start, end)?;
+        pos = end;
+
+        let word = &line[start..end];
+        if word == ".\"" || word == "s\"" || (ignore_case && word == "S\"") {
+            // Skip the one space that ends the word itself
+            let space = line[end..].chars().next().ok_or("Unterminated string")?;
+            let text = end + space.len_utf8();
+            let quote = line[text..].find('"').ok_or("Unterminated string")?;
+            words.push(text, text + quote + 1)?;
+            pos = text + quote + 1;
+        }
     }
-    
-    // Special case: i32::MIN cannot be parsed as positive then negated
-    if s == "-2147483648" {
-        return Some(i32::MIN);
-    }
-    
-    let mut result = 0i32;
-    let mut chars = s.chars();
-    let mut negative = false;
-    let mut has_digits = false;
-    
-    // Check for negative sign
-    if let Some(first) = chars.next() {
-        if first == '-' {
-            negative = true;
-            // Check if there's at least one digit after the minus sign
-            if chars.as_str().is_empty() {
-                return None;
-            }
-        } else if let Some(digit) = first.to_digit(10) {
-            result = digit as i32;
-            has_digits = true;
-        } else {
-            return None;
+}
+
+// Lower-case copy of `word` in `buf`, or `word` itself if it has no upper
+// case letters or is too long to be a built-in
+fn fold_case<'a>(word: &'a str, buf: &'a mut [u8; FOLD_LEN]) -> &'a str {
+    if word.len() > FOLD_LEN || !word.bytes().any(|b| b.is_ascii_uppercase()) {
+        return word;
+    }
+    let lower = &mut buf[..word.len()];
+    lower.copy_from_slice(word.as_bytes());
+    lower.make_ascii_lowercase();
+    core::str::from_utf8(lower).unwrap_or(word)
+}
+
+// The text of a `."` or `s"` string word, without its closing quote
+fn string_text(word: &str) -> &str {
+    &word[..word.len() - 1]
+}
+
+// Work out where each loop word transfers control to: `until` and `repeat`
+// go back to just after their `begin`, `while` goes to just after `repeat`.
+// A `:` jumps past its `;`; the words in between belong to the definition
+// and are resolved when it is made.
+fn resolve_jumps(words: &Words, ignore_case: bool) -> Result<[u8; MAX_TOKENS], &'static str> {
+    let mut jumps = [0u8; MAX_TOKENS];
+    // Open loops: (index of begin, index of its while if seen)
+    let mut open = [(0usize, None::<usize>); MAX_NESTING];
+    let mut depth = 0;
+    let mut skip_to = 0;
+
+    for i in 0..words.len() {
+        if i < skip_to {
+            continue;
+        }
+        let word = words.get(i);
+        let mut buf = [0; FOLD_LEN];
+        match if ignore_case { fold_case(word, &mut buf) } else { word } {
+            ":" => {
+                let semi = (i + 1..words.len()).find(|&j| words.get(j) == ";").ok_or("Unterminated definition")?;
+                if (i + 1..semi).any(|j| words.get(j) == ":") {
+                    return Err("Nested definition");
+                }
+                skip_to = semi + 1;
+                jumps[i] = skip_to as u8;
+            }
+            ";" => return Err("Unexpected ;"),
+            "begin" => {
+                if depth == MAX_NESTING {
+                    return Err("Loops nested too deeply");
+                }
+                open[depth] = (i, None);
+                depth += 1;
+            }
+            "while" => {
+                if depth == 0 || open[depth - 1].1.is_some() {
+                    return Err("Unbalanced loop");
+                }
+                open[depth - 1].1 = Some(i);
+            }
+            "until" => {
+                if depth == 0 || open[depth - 1].1.is_some() {
+                    return Err("Unbalanced loop");
+                }
+                depth -= 1;
+                jumps[i] = (open[depth].0 + 1) as u8;
+            }
+            "repeat" => {
+                if depth == 0 {
+                    return Err("Unbalanced loop");
+                }
+                depth -= 1;
+                let (begin, while_at) = open[depth];
+                let while_at = while_at.ok_or("Unbalanced loop")?;
+                jumps[i] = (begin + 1) as u8;
+                jumps[while_at] = (i + 1) as u8;
+            }
+            _ => {}
         }
+    }
+    if depth != 0 {
+        return Err("Unbalanced loop");
+    }
+    Ok(jumps)
+}
+
+// Validate a shift count; Rust panics on shifts of 32 or more
+fn shift_count(n: i32) -> Result<u32, &'static str> {
+    if (0..32).contains(&n) {
+        Ok(n as u32)
     } else {
+        Err("Shift out of range")
+    }
+}
+
+// Parse a decimal number (handles negative numbers)
+pub fn parse_number(s: &str) -> Option<i32> {
+    parse_number_in(s, 10)
+}
+
+// Parse a number in `base`, with an optional leading `-`
+pub fn parse_number_in(s: &str, base: u32) -> Option<i32> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    if digits.is_empty() {
         return None;
     }
-    
-    // Parse remaining digits
-    for c in chars {
-        if let Some(digit) = c.to_digit(10) {
-            result = result.checked_mul(10)?;
-            result = result.checked_add(digit as i32)?;
-            has_digits = true;
-        } else {
+    // Wide enough to hold -i32::MIN before it is negated
+    let mut magnitude = 0i64;
+    for c in digits.chars() {
+        magnitude = magnitude * base as i64 + c.to_digit(base)? as i64;
+        if magnitude > 1 << 31 {
             return None;
         }
     }
-    
-    if !has_digits {
-        return None;
+    i32::try_from(if negative { -magnitude } else { magnitude }).ok()
+}
+
+// Write `val` in `base` into `buf`, with a leading `-` if negative
+fn format_number(val: i64, base: u32, buf: &mut [u8; NUMBER_LEN]) -> &str {
+    let mut at = NUMBER_LEN;
+    let mut rest = val.unsigned_abs();
+    loop {
+        at -= 1;
+        buf[at] = char::from_digit((rest % base as u64) as u32, base).unwrap_or('?') as u8;
+        rest /= base as u64;
+        if rest == 0 {
+            break;
+        }
     }
-    
-    if negative {
-        result = result.checked_neg()?;
+    if val < 0 {
+        at -= 1;
+        buf[at] = b'-';
     }
-    
-    Some(result)
+    core::str::from_utf8(&buf[at..]).unwrap_or("")
 }
 
 #[cfg(test)]
@@ -281,6 +933,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(not(feature = "alloc"))]
     fn test_stack_overflow() {
         let mut forth = Forth::new();
         // Fill the stack
@@ -388,6 +1041,26 @@ mod tests {
         assert_eq!(forth.pop(), Ok(2));
     }
 
+    #[test]
+    fn test_depth() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("1 2 3 depth").is_ok());
+        assert_eq!(forth.stack_contents(), &[1, 2, 3, 3]);
+
+        let mut forth = Forth::new();
+        assert!(forth.eval("depth").is_ok());
+        assert_eq!(forth.stack_contents(), &[0]);
+    }
+
+    #[test]
+    fn test_clearstack() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("1 2 clearstack depth").is_ok());
+        assert_eq!(forth.stack_contents(), &[0]);
+        assert!(forth.eval("5 6 sp!").is_ok());
+        assert_eq!(forth.depth(), 0);
+    }
+
     #[test]
     fn test_comparison_equal() {
         let mut forth = Forth::new();
@@ -417,6 +1090,18 @@ mod tests {
         assert_eq!(forth.pop(), Ok(-1)); // true
     }
 
+    #[test]
+    fn test_unsigned_less_than() {
+        let mut forth = Forth::new();
+        // -1 is 0xFFFFFFFF as unsigned, so it sorts after 1
+        assert!(forth.eval("1 -1 u<").is_ok());
+        assert_eq!(forth.pop(), Ok(-1)); // true
+        assert!(forth.eval("-1 1 u<").is_ok());
+        assert_eq!(forth.pop(), Ok(0)); // false
+        assert!(forth.eval("-1 1 <").is_ok());
+        assert_eq!(forth.pop(), Ok(-1)); // signed < disagrees
+    }
+
     #[test]
     fn test_logical_and() {
         let mut forth = Forth::new();
@@ -445,6 +1130,28 @@ mod tests {
         assert_eq!(forth.pop(), Ok(-1));
     }
 
+    #[test]
+    fn test_shifts() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("1 4 lshift").is_ok());
+        assert_eq!(forth.pop(), Ok(16));
+        assert!(forth.eval("-16 2 arshift").is_ok());
+        assert_eq!(forth.pop(), Ok(-4));
+        assert!(forth.eval("16 2 rshift").is_ok());
+        assert_eq!(forth.pop(), Ok(4));
+        // rshift is logical, so the sign bit does not spread
+        assert!(forth.eval("-1 28 rshift").is_ok());
+        assert_eq!(forth.pop(), Ok(15));
+    }
+
+    #[test]
+    fn test_shift_out_of_range() {
+        let mut forth = Forth::new();
+        assert_eq!(forth.eval("1 40 lshift"), Err("Shift out of range"));
+        assert_eq!(forth.eval("1 32 rshift"), Err("Shift out of range"));
+        assert_eq!(forth.eval("1 -1 arshift"), Err("Shift out of range"));
+    }
+
     #[test]
     fn test_negate() {
         let mut forth = Forth::new();
@@ -457,6 +1164,18 @@ mod tests {
         let mut forth = Forth::new();
         // i32::MIN cannot be negated
         assert_eq!(forth.eval("-2147483648 negate"), Err("Arithmetic overflow"));
+        assert_eq!(forth.depth(), 0);
+    }
+
+    #[test]
+    fn test_abs() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("-5 abs 5 abs 0 abs").is_ok());
+        assert_eq!(forth.stack_contents(), &[5, 5, 0]);
+        // Same limit as negate
+        assert_eq!(forth.eval("-2147483648 abs"), Err("Arithmetic overflow"));
+        assert!(forth.eval("-2147483647 abs").is_ok());
+        assert_eq!(forth.pop(), Ok(i32::MAX));
     }
 
     #[test]
@@ -536,6 +1255,48 @@ mod tests {
         assert_eq!(parse_number("9999999999999"), None);
     }
 
+    #[test]
+    fn test_parse_number_in_base() {
+        assert_eq!(parse_number("-2147483648"), Some(i32::MIN));
+        assert_eq!(parse_number("2147483648"), None);
+        assert_eq!(parse_number_in("ff", 16), Some(255));
+        assert_eq!(parse_number_in("-7FFFFFFF", 16), Some(-i32::MAX));
+        assert_eq!(parse_number_in("g", 16), None);
+        assert_eq!(parse_number_in("101", 2), Some(5));
+    }
+
+    #[test]
+    fn test_dot_r_right_justifies() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("42 5 .r").is_ok());
+        assert_eq!(forth.output(), "   42");
+        forth.clear_output();
+        // A value wider than the field is printed whole
+        assert!(forth.eval("-12345 3 .r").is_ok());
+        assert_eq!(forth.output(), "-12345");
+        forth.clear_output();
+        assert!(forth.eval("7 0 .r 7 -2 .r").is_ok());
+        assert_eq!(forth.output(), "77");
+        forth.clear_output();
+        // `.` leaves a space after the number
+        assert!(forth.eval("42 .").is_ok());
+        assert!(forth.output().starts_with("42 "));
+    }
+
+    #[test]
+    fn test_hex_and_decimal() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("hex ff .").is_ok());
+        assert_eq!(forth.output(), "ff \n");
+        forth.clear_output();
+        assert!(forth.eval("-1 . -1 u. 1f 6 .r").is_ok());
+        assert_eq!(forth.output(), "-1 \nffffffff \n    1f");
+        forth.clear_output();
+        assert!(forth.eval("10 decimal .s").is_ok());
+        assert_eq!(forth.output(), "<1> 16 \n");
+        assert_eq!(forth.eval("ff"), Err("Unknown word"));
+    }
+
     #[test]
     fn test_stack_contents() {
         let mut forth = Forth::new();
@@ -557,10 +1318,379 @@ mod tests {
         assert_eq!(forth.depth(), 0);
     }
 
+    #[test]
+    fn test_dot_prints_and_pops() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("42 .").is_ok());
+        assert_eq!(forth.output(), "42 \n");
+        assert_eq!(forth.depth(), 0);
+    }
+
+    #[test]
+    fn test_unsigned_dot() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("-1 u.").is_ok());
+        assert_eq!(forth.output(), "4294967295 \n");
+        forth.clear_output();
+        assert!(forth.eval("42 u.").is_ok());
+        assert_eq!(forth.output(), "42 \n");
+    }
+
+    #[test]
+    fn test_dot_s_keeps_stack() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("1 -2 3 .s").is_ok());
+        assert_eq!(forth.output(), "<3> 1 -2 3 \n");
+        assert_eq!(forth.depth(), 3);
+    }
+
+    #[test]
+    fn test_emit_and_cr() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("72 emit 105 emit cr").is_ok());
+        assert_eq!(forth.output(), "Hi\n");
+        assert_eq!(forth.eval("200 emit"), Err("Invalid character code"));
+    }
+
+    #[test]
+    fn test_dot_quote_prints_text() {
+        let mut forth = Forth::new();
+        assert!(forth.eval(".\" hello\"").is_ok());
+        assert_eq!(forth.output(), "hello");
+        forth.clear_output();
+        // Inner spaces are kept, and words after the quote still run
+        assert!(forth.eval(".\" a  b \" 1 .").is_ok());
+        assert_eq!(forth.output(), "a  b 1 \n");
+        assert_eq!(forth.eval(".\" no end"), Err("Unterminated string"));
+        assert_eq!(forth.eval(".\""), Err("Unterminated string"));
+    }
+
+    #[test]
+    fn test_s_quote_and_type() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("s\" hi\" type").is_ok());
+        assert_eq!(forth.output(), "hi");
+        assert_eq!(forth.depth(), 0);
+        // The address and length stay usable on later lines
+        assert!(forth.eval("s\" there\"").is_ok());
+        assert_eq!(forth.peek(), Ok(5));
+        forth.clear_output();
+        assert!(forth.eval("type").is_ok());
+        assert_eq!(forth.output(), "there");
+        assert_eq!(forth.eval("0 1000 type"), Err("Invalid string"));
+        assert_eq!(forth.eval("-1 2 type"), Err("Invalid string"));
+    }
+
+    #[test]
+    fn test_string_pool_wraps() {
+        let mut forth = Forth::new();
+        let long = "x".repeat(STRING_POOL - 2);
+        assert!(forth.eval(&format!("s\" {}\" drop drop", long)).is_ok());
+        assert!(forth.eval("s\" abc\"").is_ok());
+        // Did not fit after the first string, so it starts again at 0
+        assert_eq!(forth.stack_contents(), &[0, 3]);
+        let too_long = "y".repeat(STRING_POOL + 1);
+        assert_eq!(forth.eval(&format!("s\" {}\"", too_long)), Err("String too long"));
+    }
+
+    #[test]
+    fn test_strings_in_definitions() {
+        let mut forth = Forth::new();
+        // A ; inside the text does not end the definition
+        assert!(forth.eval(": greet .\" hi; there\" cr ;").is_ok());
+        assert!(forth.eval("greet greet").is_ok());
+        assert_eq!(forth.output(), "hi; there\nhi; there\n");
+    }
+
+    #[test]
+    fn test_case_insensitive_words() {
+        let mut forth = Forth::new();
+        assert!(forth.case_insensitive);
+        assert!(forth.eval("3 DUP Dup dup + + +").is_ok());
+        assert_eq!(forth.pop(), Ok(12));
+        // Loop words, strings and user words fold too
+        assert!(forth.eval(": SQ DUP * ; 3 Sq 2 BEGIN 1 - DUP 0 = UNTIL").is_ok());
+        assert_eq!(forth.stack_contents(), &[9, 0]);
+        assert!(forth.eval("S\" Hi\" TYPE").is_ok());
+        // ...but not the text itself
+        assert_eq!(forth.output(), "Hi");
+        assert_eq!(forth.eval(": BEGIN 1 ;"), Err("Invalid name"));
+    }
+
+    #[test]
+    fn test_case_sensitive_words() {
+        let mut forth = Forth::new();
+        forth.case_insensitive = false;
+        assert!(forth.eval("3 dup").is_ok());
+        assert_eq!(forth.eval("DUP"), Err("Unknown word"));
+        assert_eq!(forth.eval("Dup"), Err("Unknown word"));
+        assert!(forth.eval(": Sq 1 ; : sq 2 ; Sq sq").is_ok());
+        assert_eq!(forth.stack_contents(), &[3, 3, 1, 2]);
+        assert_eq!(forth.eval("BEGIN"), Err("Unknown word"));
+    }
+
+    // ( n -- n! ), using begin/while as the "if n > 1" test
+    const FACT: &str = ": fact dup 1 > begin while dup 1 - recurse * 0 repeat ;";
+
+    #[test]
+    fn test_recursive_factorial() {
+        let mut forth = Forth::new();
+        assert!(forth.eval(FACT).is_ok());
+        assert!(forth.eval("4 fact").is_ok());
+        assert_eq!(forth.pop(), Ok(24));
+        forth.set_max_call_depth(10);
+        assert!(forth.eval("10 fact").is_ok());
+        assert_eq!(forth.pop(), Ok(3628800));
+        assert_eq!(forth.depth(), 0);
+    }
+
+    #[test]
+    fn test_call_depth_limit() {
+        let mut forth = Forth::new();
+        assert!(forth.eval(FACT).is_ok());
+        assert_eq!(forth.eval("5 fact"), Err("Call depth exceeded"));
+        // Unbounded recursion stops at the limit too
+        assert!(forth.eval(": forever 1 recurse ;").is_ok());
+        assert!(forth.eval("clearstack").is_ok());
+        assert_eq!(forth.eval("forever"), Err("Call depth exceeded"));
+        assert_eq!(forth.depth(), DEFAULT_MAX_CALL_DEPTH);
+        // The depth unwinds after an error
+        assert!(forth.eval("clearstack").is_ok());
+        assert!(forth.eval("4 fact").is_ok());
+        assert_eq!(forth.pop(), Ok(24));
+        // Nesting different words counts the same
+        assert!(forth.eval(": a 1 ; : b a ; : c b ; : d c ; : e d ; d").is_ok());
+        assert_eq!(forth.eval("e"), Err("Call depth exceeded"));
+    }
+
+    #[test]
+    fn test_recurse_calls_its_own_definition() {
+        let mut forth = Forth::new();
+        assert_eq!(forth.eval("recurse"), Err("recurse outside a definition"));
+        // ( n -- 0 ) counts down by recursing; outer must not run again
+        assert!(forth.eval(": down dup begin while 1 - recurse 0 repeat ;").is_ok());
+        assert!(forth.eval(": outer 7 swap down ;").is_ok());
+        assert!(forth.eval("2 outer").is_ok());
+        assert_eq!(forth.stack_contents(), &[7, 0]);
+        assert_eq!(forth.eval("recurse"), Err("recurse outside a definition"));
+    }
+
+    #[test]
+    fn test_millis_reads_the_clock() {
+        fn clock() -> u64 {
+            12_345
+        }
+        let mut forth = Forth::new();
+        assert_eq!(forth.eval("millis"), Err("No clock"));
+        forth.set_clock(clock);
+        assert!(forth.eval("millis millis -").is_ok());
+        assert_eq!(forth.pop(), Ok(0));
+        assert!(forth.eval("millis").is_ok());
+        assert_eq!(forth.pop(), Ok(12_345));
+    }
+
+    #[test]
+    fn test_ms_calls_the_sleep_hook() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static SLEPT: AtomicU64 = AtomicU64::new(0);
+        fn sleep(ms: u64) {
+            SLEPT.fetch_add(ms, Ordering::Relaxed);
+        }
+        let mut forth = Forth::new();
+        assert_eq!(forth.eval("10 ms"), Err("No sleep hook"));
+        forth.set_sleep(sleep);
+        assert!(forth.eval("10 ms 5 ms").is_ok());
+        assert_eq!(SLEPT.load(Ordering::Relaxed), 15);
+        assert_eq!(forth.eval("-1 ms"), Err("Invalid duration"));
+        assert_eq!(SLEPT.load(Ordering::Relaxed), 15);
+    }
+
+    #[test]
+    fn test_color_marks_negatives_red() {
+        let mut forth = Forth::new();
+        forth.color = true;
+        assert!(forth.eval("-7 .").is_ok());
+        assert!(forth.output().contains("\x1b[31m-7\x1b[0m"));
+
+        forth.clear_output();
+        assert!(forth.eval("7 .").is_ok());
+        assert!(!forth.output().contains("\x1b["));
+        assert_eq!(forth.output(), "7 \n");
+
+        forth.clear_output();
+        assert!(forth.eval("5 -5 .s").is_ok());
+        assert_eq!(forth.output(), "<2> 5 \x1b[31m-5\x1b[0m \n");
+    }
+
+    #[test]
+    fn test_color_off_by_default() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("-7 .").is_ok());
+        assert_eq!(forth.output(), "-7 \n");
+    }
+
+    #[test]
+    fn test_step_limit_stops_eval() {
+        let mut forth = Forth::new();
+        forth.set_step_limit(3);
+        assert_eq!(forth.eval("1 2 3 4"), Err("Step limit exceeded"));
+        assert_eq!(forth.stack_contents(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_step_limit_resets_per_eval() {
+        let mut forth = Forth::new();
+        forth.set_step_limit(3);
+        assert!(forth.eval("1 2 +").is_ok());
+        assert!(forth.eval("3 +").is_ok());
+        assert_eq!(forth.pop(), Ok(6));
+    }
+
+    #[test]
+    fn test_begin_until_countdown() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("5 begin 1 - dup 0 = until").is_ok());
+        assert_eq!(forth.stack_contents(), &[0]);
+    }
+
+    #[test]
+    fn test_begin_while_repeat() {
+        let mut forth = Forth::new();
+        assert!(forth.eval("0 begin dup 5 < while 1 + repeat").is_ok());
+        assert_eq!(forth.stack_contents(), &[5]);
+        // A false flag on the first pass skips the body entirely
+        assert!(forth.eval("drop 9 begin dup 5 < while 1 + repeat").is_ok());
+        assert_eq!(forth.stack_contents(), &[9]);
+    }
+
+    #[test]
+    fn test_nested_loops() {
+        let mut forth = Forth::new();
+        // Sum 3+2+1 with an inner loop that spins once per outer pass
+        assert!(forth.eval("0 3 begin dup 1 begin 1 - dup 0 = until drop rot + swap 1 - dup 0 = until drop").is_ok());
+        assert_eq!(forth.stack_contents(), &[6]);
+    }
+
+    #[test]
+    fn test_unbalanced_loops() {
+        let mut forth = Forth::new();
+        assert_eq!(forth.eval("begin 1"), Err("Unbalanced loop"));
+        assert_eq!(forth.eval("1 until"), Err("Unbalanced loop"));
+        assert_eq!(forth.eval("begin 1 repeat"), Err("Unbalanced loop"));
+        assert_eq!(forth.eval("begin 1 while 1 until"), Err("Unbalanced loop"));
+        // Nothing ran before the error was found
+        assert_eq!(forth.depth(), 0);
+    }
+
+    #[test]
+    fn test_infinite_loop_hits_step_limit() {
+        let mut forth = Forth::new();
+        forth.set_step_limit(1000);
+        assert_eq!(forth.eval("begin 0 until"), Err("Step limit exceeded"));
+    }
+
     #[test]
     fn test_multiple_spaces() {
         let mut forth = Forth::new();
         assert!(forth.eval("  5   10   +  ").is_ok());
         assert_eq!(forth.pop(), Ok(15));
     }
+
+    #[test]
+    fn test_colon_definition() {
+        let mut forth = Forth::new();
+        assert!(forth.eval(": sq dup * ; 7 sq").is_ok());
+        assert_eq!(forth.pop(), Ok(49));
+        // Later lines can use it, and other words can call it
+        assert!(forth.eval(": cube dup sq * ;").is_ok());
+        assert!(forth.eval("3 cube").is_ok());
+        assert_eq!(forth.pop(), Ok(27));
+        assert_eq!(forth.word_count(), 2);
+    }
+
+    #[test]
+    fn test_redefinition_hides_old_word() {
+        let mut forth = Forth::new();
+        assert!(forth.eval(": five 5 ; : five 55 ; five").is_ok());
+        assert_eq!(forth.pop(), Ok(55));
+        // User words come before built-ins
+        assert!(forth.eval(": dup 1 ; 9 dup").is_ok());
+        assert_eq!(forth.stack_contents(), &[9, 1]);
+    }
+
+    #[test]
+    fn test_definition_with_loop() {
+        let mut forth = Forth::new();
+        assert!(forth.eval(": countdown begin dup . 1 - dup 0 = until drop ;").is_ok());
+        assert!(forth.eval("3 countdown").is_ok());
+        assert_eq!(forth.output(), "3 \n2 \n1 \n");
+        assert_eq!(forth.depth(), 0);
+    }
+
+    #[test]
+    fn test_bad_definitions() {
+        let mut forth = Forth::new();
+        assert_eq!(forth.eval(": sq dup *"), Err("Unterminated definition"));
+        assert_eq!(forth.eval(": ;"), Err("Missing name"));
+        assert_eq!(forth.eval(": a : b ; ;"), Err("Nested definition"));
+        assert_eq!(forth.eval("1 ;"), Err("Unexpected ;"));
+        assert_eq!(forth.eval(": begin 1 ;"), Err("Invalid name"));
+        assert_eq!(forth.eval(": bad begin 1 ;"), Err("Unbalanced loop"));
+        assert_eq!(forth.word_count(), 0);
+        // Nothing ran from the rejected lines
+        assert_eq!(forth.depth(), 0);
+    }
+
+    #[test]
+    #[cfg(not(feature = "alloc"))]
+    fn test_fixed_dictionary_fills_up() {
+        let mut forth = Forth::new();
+        for i in 0..MAX_WORDS {
+            let mut line = String::new();
+            write!(line, ": w{} {} ;", i, i).unwrap();
+            assert!(forth.eval(&line).is_ok());
+        }
+        assert_eq!(forth.eval(": extra 1 ;"), Err("Dictionary full"));
+        assert!(forth.eval("w0 w31 +").is_ok());
+        assert_eq!(forth.pop(), Ok(31));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_heap_dictionary_takes_long_definitions() {
+        let mut forth = Forth::new();
+        // Each body is longer than MAX_DEF_LEN, and the two together
+        // longer than DICT_BYTES
+        let body = format!("1{}+", " ".repeat(DICT_BYTES / 2 + MAX_DEF_LEN));
+        assert!(forth.eval(&format!(": a {} ;", body)).is_ok());
+        assert!(forth.eval(&format!(": b {} ;", body)).is_ok());
+        assert!(forth.eval("0 a b").is_ok());
+        assert_eq!(forth.pop(), Ok(2));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_heap_dictionary_holds_100_words() {
+        let mut forth = Forth::new();
+        for i in 0..100 {
+            assert!(forth.eval(&format!(": w{} {} ;", i, i)).is_ok());
+        }
+        assert_eq!(forth.word_count(), 100);
+        assert!(forth.eval("w0 w50 w99 + +").is_ok());
+        assert_eq!(forth.pop(), Ok(149));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_heap_stack_grows_past_fixed_size() {
+        let mut forth = Forth::new();
+        for i in 0..1000 {
+            assert!(forth.push(i).is_ok());
+        }
+        assert_eq!(forth.depth(), 1000);
+        assert!(forth.eval("depth").is_ok());
+        assert_eq!(forth.pop(), Ok(1000));
+        assert_eq!(forth.pop(), Ok(999));
+        assert_eq!(forth.stack_contents()[..3], [0, 1, 2]);
+    }
 }
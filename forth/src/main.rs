@@ -29,47 +29,9 @@ fn read_line(buf: &mut [u8]) -> usize {
     len
 }
 
-// Execute a word with I/O operations
-fn execute_word_with_io(forth: &mut Forth, word: &str) -> Result<(), &'static str> {
-    match word {
-        // I/O operations that need usys
-        "." => {
-            let val = forth.pop()?;
-            println!("{} ", val);
-        }
-        ".s" => {
-            print!("<{}> ", forth.depth());
-            for val in forth.stack_contents() {
-                print!("{} ", val);
-            }
-            println!();
-        }
-        "cr" => {
-            println!();
-        }
-        "emit" => {
-            let val = forth.pop()?;
-            if val >= 0 && val <= 127 {
-                print!("{}", val as u8 as char);
-            } else {
-                return Err("Invalid character code");
-            }
-        }
-        // For all other words, use the library implementation
-        _ => {
-            forth.execute_word(word)?;
-        }
-    }
-    Ok(())
-}
-
-// Evaluate a line with I/O support
-fn eval_with_io(forth: &mut Forth, line: &str) -> Result<(), &'static str> {
-    let words = line.split_whitespace();
-    for word in words {
-        execute_word_with_io(forth, word)?;
-    }
-    Ok(())
+// Forth output goes straight to the terminal
+fn print_str(s: &str) {
+    print!("{}", s);
 }
 
 #[no_mangle]
@@ -79,7 +41,13 @@ pub extern "C" fn _start(_argc: usize, _argv: *const *const u8, _envp: *const *c
     println!("Type 'words' for available words, or 'bye' to exit");
     println!();
     
+    // read_line echoes for itself
+    let _ = usys::set_raw_mode(true);
+
     let mut forth = Forth::new();
+    forth.set_sink(print_str);
+    forth.set_clock(usys::uptime_ms);
+    forth.set_sleep(usys::sleep_ms);
     let mut input_buf = [0u8; 128];
     
     loop {
@@ -101,18 +69,23 @@ pub extern "C" fn _start(_argc: usize, _argv: *const *const u8, _envp: *const *c
         
         if input.trim() == "words" {
             println!("Available words:");
-            println!("  Arithmetic: + - * / mod");
-            println!("  Stack:      dup drop swap over rot");
-            println!("  I/O:        . .s cr emit");
-            println!("  Comparison: = < >");
+            println!("  Arithmetic: + - * / mod abs");
+            println!("  Stack:      dup drop swap over rot depth clearstack sp!");
+            println!("  I/O:        . u. .r .s cr emit .\" s\" type");
+            println!("  Base:       hex decimal");
+            println!("  Time:       millis ms");
+            println!("  Comparison: = < > u<");
             println!("  Logical:    and or xor invert negate");
+            println!("  Shifts:     lshift rshift arshift");
             println!("  Constants:  true false");
+            println!("  Loops:      begin until while repeat");
+            println!("  Defining:   : ; recurse");
             println!("  Special:    words bye");
             continue;
         }
         
         // Evaluate the input
-        match eval_with_io(&mut forth, input) {
+        match forth.eval(input) {
             Ok(()) => {
                 // Success - show "ok" on next iteration
             }
@@ -1,32 +1,201 @@
 use crate::display::DisplayMode;
+use crate::elf::{DEFAULT_USER_STACK_BYTES, USER_STACK_TOP};
+use uapi::LogLevel;
 
 // Global display mode configuration
 // SAFETY: This is only accessed during single-threaded kernel initialization.
 // In a multi-threaded environment, this should use AtomicU8 or similar.
 static mut DISPLAY_MODE: DisplayMode = DisplayMode::Ansi;
 
-/// Parse kernel command line arguments
-/// In a full implementation, this would read from device tree /chosen/bootargs
-/// For this scaffold, we support testing via cmdline string parameter
-pub fn parse_cmdline(s: &str) {
-    for param in s.split_whitespace() {
-        if let Some(v) = param.strip_prefix("display=") {
-            unsafe {
-                DISPLAY_MODE = match v {
+// Requested timer tick rate from `tickhz=` (0 = use the default)
+static mut TICK_HZ: u64 = 0;
+
+// Requested framebuffer size from `fbres=WxH` ((0, 0) = the driver's default)
+static mut FB_RES: (usize, usize) = (0, 0);
+
+/// First user program, loaded at boot and again whenever a program exits
+pub const DEFAULT_INIT: &str = "shell.elf";
+
+// Program chosen with `init=`
+static mut INIT: &str = DEFAULT_INIT;
+
+// User stack size from `stacksize=`, already checked against the VA layout
+static mut STACK_BYTES: usize = DEFAULT_USER_STACK_BYTES;
+
+/// Iterator over the whitespace-separated tokens of a command line:
+/// `key=value` yields `(key, Some(value))` and a bare flag `(flag, None)`.
+pub struct KeyVals<'a> {
+    tokens: core::str::SplitWhitespace<'a>,
+}
+
+impl<'a> KeyVals<'a> {
+    pub fn new(s: &'a str) -> Self {
+        Self { tokens: s.split_whitespace() }
+    }
+}
+
+impl<'a> Iterator for KeyVals<'a> {
+    type Item = (&'a str, Option<&'a str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tok = self.tokens.next()?;
+        Some(match tok.split_once('=') {
+            Some((k, v)) => (k, Some(v)),
+            None => (tok, None),
+        })
+    }
+}
+
+/// Options recognized on the command line; `None` where not given.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Options<'a> {
+    pub display: Option<DisplayMode>,
+    /// Framebuffer width and height from `fbres=WxH`
+    pub fbres: Option<(usize, usize)>,
+    pub loglevel: Option<LogLevel>,
+    pub tickhz: Option<u64>,
+    /// First user program from `init=<name>`
+    pub init: Option<&'a str>,
+    pub logcolor: Option<bool>,
+    pub kbdrepeat: Option<bool>,
+    /// User stack size in bytes from `stacksize=<n>[K|M]`
+    pub stacksize: Option<usize>,
+}
+
+impl<'a> Options<'a> {
+    /// Parse a whole command line, skipping keys we don't know.
+    #[allow(dead_code)]
+    pub fn parse(s: &'a str) -> Self {
+        let mut opts = Self::default();
+        for (key, value) in KeyVals::new(s) {
+            opts.set(key, value);
+        }
+        opts
+    }
+
+    /// The program to start first: `init=` if given, else [`DEFAULT_INIT`]
+    pub fn init_program(&self) -> &'a str {
+        self.init.unwrap_or(DEFAULT_INIT)
+    }
+
+    /// Record one option. Returns false for an unknown key. Switches such
+    /// as `logcolor` given without a value mean "on".
+    pub fn set(&mut self, key: &str, value: Option<&'a str>) -> bool {
+        let v = value.unwrap_or("");
+        match key {
+            "display" => {
+                self.display = Some(match v {
                     "gpu" => DisplayMode::Gpu,
                     _ => DisplayMode::Ansi,
-                }
+                })
             }
+            "fbres" => self.fbres = parse_res(v),
+            "loglevel" => self.loglevel = LogLevel::from_str(v),
+            "tickhz" => self.tickhz = v.parse().ok(),
+            "init" => self.init = value.filter(|v| !v.is_empty()),
+            "logcolor" => self.logcolor = Some(value.is_none() || !matches!(v, "0" | "off" | "no")),
+            "kbdrepeat" => self.kbdrepeat = Some(value.is_none() || matches!(v, "1" | "on" | "yes")),
+            "stacksize" => self.stacksize = parse_size(v),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// `WxH`, both nonzero
+fn parse_res(v: &str) -> Option<(usize, usize)> {
+    let (w, h) = v.split_once('x')?;
+    match (w.parse(), h.parse()) {
+        (Ok(w), Ok(h)) if w > 0 && h > 0 => Some((w, h)),
+        _ => None,
+    }
+}
+
+/// Byte count with an optional `K` or `M` suffix
+fn parse_size(v: &str) -> Option<usize> {
+    let (digits, unit) = match v.as_bytes().last()? {
+        b'k' | b'K' => (&v[..v.len() - 1], 1024),
+        b'm' | b'M' => (&v[..v.len() - 1], 1024 * 1024),
+        _ => (v, 1),
+    };
+    digits.parse::<usize>().ok()?.checked_mul(unit)
+}
+
+/// Parse kernel command line arguments
+/// In a full implementation, this would read from device tree /chosen/bootargs
+/// For this scaffold, we support testing via cmdline string parameter
+pub fn parse_cmdline(s: &'static str) {
+    let mut opts = Options::default();
+    for (key, value) in KeyVals::new(s) {
+        if !opts.set(key, value) {
+            crate::kdebug!("cmdline: ignoring unknown option '{}'", key);
+        }
+    }
+
+    unsafe {
+        if let Some(mode) = opts.display {
+            DISPLAY_MODE = mode;
+        }
+        if let Some(hz) = opts.tickhz {
+            TICK_HZ = hz;
+        }
+        if let Some(res) = opts.fbres {
+            FB_RES = res;
+        }
+        INIT = opts.init_program();
+    }
+    if let Some(bytes) = opts.stacksize {
+        if crate::elf::stack_region(USER_STACK_TOP, bytes).is_some() {
+            unsafe { STACK_BYTES = bytes; }
+        } else {
+            crate::kwarn!("cmdline: stacksize={} does not fit below {:#x}, using {}", bytes, USER_STACK_TOP, DEFAULT_USER_STACK_BYTES);
         }
     }
+    if let Some(level) = opts.loglevel {
+        crate::logging::set_log_level(level);
+    }
+    if let Some(on) = opts.logcolor {
+        crate::logging::set_color(on);
+    }
+    if let Some(on) = opts.kbdrepeat {
+        crate::keyboard::set_repeat(on);
+    }
 }
 
 pub fn display_mode() -> DisplayMode {
     unsafe { DISPLAY_MODE }
 }
 
-/// Set display mode directly (for testing)
+/// Timer tick rate requested on the command line, if any
+pub fn tick_hz() -> Option<u64> {
+    match unsafe { TICK_HZ } {
+        0 => None,
+        hz => Some(hz),
+    }
+}
+
+/// Name of the first user program (`init=`, default `shell.elf`)
+pub fn init_program() -> &'static str {
+    unsafe { INIT }
+}
+
+/// Where every user program gets its stack, as `(bottom, top)`, sized by
+/// `stacksize=` (default 16 KiB)
+pub fn user_stack() -> (usize, usize) {
+    crate::elf::stack_region(USER_STACK_TOP, unsafe { STACK_BYTES })
+        .expect("stack size is checked when the command line is parsed")
+}
+
+/// Framebuffer size requested with `fbres=`, if any
 #[allow(dead_code)]
+pub fn fb_resolution() -> Option<(usize, usize)> {
+    match unsafe { FB_RES } {
+        (0, 0) => None,
+        res => Some(res),
+    }
+}
+
+/// Set display mode directly (the SET_DISPLAY_MODE syscall and tests)
 pub fn set_display_mode(mode: DisplayMode) {
     unsafe { DISPLAY_MODE = mode; }
 }
@@ -0,0 +1,229 @@
+//! ANSI/VT100 escape sequence parser shared by the consoles. The UART
+//! console hands bytes straight to the host terminal, which interprets them
+//! itself; the framebuffer console feeds every byte through a [`Parser`] and
+//! acts on the [`AnsiAction`]s. Kept free of hardware access so kernel_lib
+//! can test it.
+
+// Most numeric parameters kept from one CSI sequence
+const MAX_PARAMS: usize = 4;
+
+/// What a byte fed to the parser asks the console to do
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AnsiAction {
+    None,
+    Print(u8),
+    /// `ESC[nm`: select graphic rendition; only the first parameter of a
+    /// combined `ESC[1;31m` is reported
+    Sgr(u8),
+    /// Move the cursor to 0-based (column, row)
+    CursorTo(u16, u16),
+    ShowCursor(bool),
+    /// `ESC[2J`
+    ClearScreen,
+    /// `ESC[nK`: erase to end of line (0), from line start (1) or the whole line (2)
+    EraseInLine(u16),
+    /// `ESC[nJ`: erase to end of screen (0) or from screen start (1)
+    EraseInDisplay(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Normal,
+    Esc,
+    Csi,
+}
+
+/// Minimal escape parser: `ESC[r;cH` (and `f`) move the cursor,
+/// `ESC[?25l`/`ESC[?25h` hide and show it, `ESC[K` and `ESC[J` erase and
+/// `ESC[m` sets attributes; other sequences are swallowed. A sequence may be
+/// split across any number of `feed` calls.
+pub struct Parser {
+    state: State,
+    params: [u16; MAX_PARAMS],
+    idx: usize,
+    private: bool,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Self { state: State::Normal, params: [0; MAX_PARAMS], idx: 0, private: false }
+    }
+
+    /// True while in the middle of an escape sequence
+    #[allow(dead_code)]
+    pub fn in_sequence(&self) -> bool {
+        self.state != State::Normal
+    }
+
+    pub fn feed(&mut self, c: u8) -> AnsiAction {
+        match self.state {
+            State::Normal => {
+                if c == 0x1b {
+                    self.state = State::Esc;
+                    AnsiAction::None
+                } else {
+                    AnsiAction::Print(c)
+                }
+            }
+            State::Esc => {
+                if c == b'[' {
+                    self.state = State::Csi;
+                    self.params = [0; MAX_PARAMS];
+                    self.idx = 0;
+                    self.private = false;
+                } else {
+                    self.state = State::Normal;
+                }
+                AnsiAction::None
+            }
+            State::Csi => match c {
+                b'?' => {
+                    self.private = true;
+                    AnsiAction::None
+                }
+                b'0'..=b'9' => {
+                    if self.idx < MAX_PARAMS {
+                        let p = &mut self.params[self.idx];
+                        *p = p.saturating_mul(10).saturating_add((c - b'0') as u16);
+                    }
+                    AnsiAction::None
+                }
+                b';' => {
+                    self.idx += 1;
+                    AnsiAction::None
+                }
+                0x40..=0x7e => {
+                    self.state = State::Normal;
+                    self.dispatch(c)
+                }
+                _ => {
+                    // Malformed sequence: drop it
+                    self.state = State::Normal;
+                    AnsiAction::None
+                }
+            },
+        }
+    }
+
+    fn dispatch(&self, fin: u8) -> AnsiAction {
+        let p0 = self.params[0];
+        match fin {
+            b'H' | b'f' if !self.private => {
+                // 1-based row;col, missing or 0 means 1
+                let row = p0.max(1) - 1;
+                let col = self.params[1].max(1) - 1;
+                AnsiAction::CursorTo(col, row)
+            }
+            b'h' | b'l' if self.private && p0 == 25 => AnsiAction::ShowCursor(fin == b'h'),
+            b'K' if !self.private => AnsiAction::EraseInLine(p0),
+            b'J' if !self.private && p0 == 2 => AnsiAction::ClearScreen,
+            b'J' if !self.private => AnsiAction::EraseInDisplay(p0),
+            b'm' if !self.private => AnsiAction::Sgr(p0.min(u8::MAX as u16) as u8),
+            _ => AnsiAction::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `bytes` and collect everything but `None`
+    fn actions(p: &mut Parser, bytes: &[u8]) -> Vec<AnsiAction> {
+        bytes.iter().map(|&c| p.feed(c)).filter(|a| *a != AnsiAction::None).collect()
+    }
+
+    fn one(seq: &[u8]) -> AnsiAction {
+        let mut p = Parser::new();
+        let got = actions(&mut p, seq);
+        assert!(!p.in_sequence(), "{:?} left the parser mid-sequence", seq);
+        assert_eq!(got.len(), 1, "{:?} gave {:?}", seq, got);
+        got[0]
+    }
+
+    #[test]
+    fn test_plain_bytes_pass_through() {
+        let mut p = Parser::new();
+        let got = actions(&mut p, b"hi\n\t\x08");
+        let want: Vec<_> = b"hi\n\t\x08".iter().map(|&c| AnsiAction::Print(c)).collect();
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn test_cursor_sequences() {
+        assert_eq!(one(b"\x1b[5;10H"), AnsiAction::CursorTo(9, 4));
+        assert_eq!(one(b"\x1b[5;10f"), AnsiAction::CursorTo(9, 4));
+        assert_eq!(one(b"\x1b[H"), AnsiAction::CursorTo(0, 0));
+        assert_eq!(one(b"\x1b[0;0H"), AnsiAction::CursorTo(0, 0));
+        assert_eq!(one(b"\x1b[7H"), AnsiAction::CursorTo(0, 6));
+        assert_eq!(one(b"\x1b[;3H"), AnsiAction::CursorTo(2, 0));
+        assert_eq!(one(b"\x1b[?25l"), AnsiAction::ShowCursor(false));
+        assert_eq!(one(b"\x1b[?25h"), AnsiAction::ShowCursor(true));
+    }
+
+    #[test]
+    fn test_erase_and_clear() {
+        assert_eq!(one(b"\x1b[K"), AnsiAction::EraseInLine(0));
+        assert_eq!(one(b"\x1b[1K"), AnsiAction::EraseInLine(1));
+        assert_eq!(one(b"\x1b[2K"), AnsiAction::EraseInLine(2));
+        assert_eq!(one(b"\x1b[J"), AnsiAction::EraseInDisplay(0));
+        assert_eq!(one(b"\x1b[1J"), AnsiAction::EraseInDisplay(1));
+        assert_eq!(one(b"\x1b[2J"), AnsiAction::ClearScreen);
+    }
+
+    #[test]
+    fn test_sgr_reports_first_parameter() {
+        assert_eq!(one(b"\x1b[m"), AnsiAction::Sgr(0));
+        assert_eq!(one(b"\x1b[1m"), AnsiAction::Sgr(1));
+        assert_eq!(one(b"\x1b[31;1m"), AnsiAction::Sgr(31));
+        assert_eq!(one(b"\x1b[999m"), AnsiAction::Sgr(255));
+    }
+
+    #[test]
+    fn test_state_persists_across_calls() {
+        let mut p = Parser::new();
+        // One byte per call, as write_char delivers them
+        for &c in b"\x1b[12;3" {
+            assert_eq!(p.feed(c), AnsiAction::None);
+            assert!(p.in_sequence());
+        }
+        assert_eq!(p.feed(b'H'), AnsiAction::CursorTo(2, 11));
+        assert!(!p.in_sequence());
+
+        // Split between two writes, right after ESC and after '['
+        assert!(actions(&mut p, b"ab\x1b").starts_with(&[AnsiAction::Print(b'a')]));
+        assert!(p.in_sequence());
+        assert_eq!(actions(&mut p, b"["), []);
+        assert_eq!(actions(&mut p, b"?25lz"), [AnsiAction::ShowCursor(false), AnsiAction::Print(b'z')]);
+    }
+
+    #[test]
+    fn test_unknown_and_malformed_sequences_are_swallowed() {
+        let mut p = Parser::new();
+        // Unknown final byte, private SGR, and ESC without '['
+        assert_eq!(actions(&mut p, b"\x1b[5n\x1b[?1m\x1b7"), []);
+        assert!(!p.in_sequence());
+        // A control byte inside a CSI drops the sequence, ending where it was
+        assert_eq!(actions(&mut p, b"\x1b[3\x01x"), [AnsiAction::Print(b'x')]);
+        // ESC ESC: the second one is eaten as the unknown escape
+        assert_eq!(actions(&mut p, b"\x1b\x1bA"), [AnsiAction::Print(b'A')]);
+        // `?25` on its own only means something with h/l
+        assert_eq!(actions(&mut p, b"\x1b[25l\x1b[?7h"), []);
+    }
+
+    #[test]
+    fn test_parameter_overflow_saturates() {
+        // Extra parameters are ignored, huge ones saturate
+        assert_eq!(one(b"\x1b[2;3;4;5;6;7H"), AnsiAction::CursorTo(2, 1));
+        assert_eq!(one(b"\x1b[99999999;1H"), AnsiAction::CursorTo(0, u16::MAX - 1));
+        // Parameters are reset for each new sequence
+        let mut p = Parser::new();
+        assert_eq!(actions(&mut p, b"\x1b[4;4H\x1b[H"), [AnsiAction::CursorTo(3, 3), AnsiAction::CursorTo(0, 0)]);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod ansi;
+
 use crate::boot::cmdline;
 use crate::display::{fb_console, virtio_gpu};
 use core::fmt::Write;
@@ -7,12 +9,21 @@ pub fn init_console() {
 
     match cmdline::display_mode() {
         crate::display::DisplayMode::Gpu => {
-            let _ = writeln!(uart, "Attempting to initialize GPU display...");
-            if let Some(_vg) = virtio_gpu::VirtioGpu::probe() {
+            // Probe only once; switching back to GPU later reuses the device
+            let have_gpu = crate::display::get_framebuffer().is_some() || {
+                let _ = writeln!(uart, "Attempting to initialize GPU display...");
+                match virtio_gpu::VirtioGpu::probe() {
+                    Some(_vg) => {
+                        let _ = writeln!(uart, "Virtio GPU: {:?}", _vg);
+                        true
+                    }
+                    None => false,
+                }
+            };
+            if have_gpu {
                 match fb_console::init_fb_console() {
                     Ok(()) => {
                         let _ = writeln!(uart, "GPU framebuffer console initialized");
-                        let _ = writeln!(uart, "Virtio GPU: {:?}", _vg);
                         // Write a test message to the framebuffer console
                         fb_console::write_str("BogoKernel GPU Console\n");
                         fb_console::write_str("=====================\n");
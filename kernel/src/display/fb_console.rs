@@ -1,3 +1,4 @@
+use crate::console::ansi::{AnsiAction, Parser};
 use crate::display::{get_framebuffer};
 use spin::Mutex;
 
@@ -13,8 +14,48 @@ pub struct ConsoleState {
     pub fg_color: u32,  // Foreground color (XRGB8888)
     pub bg_color: u32,  // Background color (XRGB8888)
     pub cursor_visible: bool,  // Whether cursor is currently visible (for blinking)
+    pub cursor_shown: bool,  // Cleared by show_cursor(false) / ESC[?25l; blinking pauses
+    pub tab_width: usize,  // Columns between tab stops, see set_tab_width
+    ansi: Parser,
 }
 
+impl ConsoleState {
+    /// Move the cursor, clamping to the last column and row
+    pub fn move_cursor(&mut self, x: usize, y: usize) {
+        self.cursor_x = x.min(self.width_chars.saturating_sub(1));
+        self.cursor_y = y.min(self.height_chars.saturating_sub(1));
+    }
+
+    /// Whether the cursor bar is on screen: shown and in the "on" blink phase
+    pub fn cursor_drawn(&self) -> bool {
+        self.cursor_shown && self.cursor_visible
+    }
+
+    /// Step the cursor back one cell for backspace, wrapping from column 0
+    /// to the end of the previous line. Returns false at the top-left corner.
+    pub fn cursor_back(&mut self) -> bool {
+        if self.cursor_x > 0 {
+            self.cursor_x -= 1;
+        } else if self.cursor_y > 0 {
+            self.cursor_y -= 1;
+            self.cursor_x = self.width_chars.saturating_sub(1);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Column of the next tab stop after the cursor. A zero tab width is
+    /// treated as 1.
+    pub fn next_tab_stop(&self) -> usize {
+        let tab = self.tab_width.max(1);
+        (self.cursor_x / tab + 1) * tab
+    }
+}
+
+/// Tab stop spacing until set_tab_width is called
+pub const DEFAULT_TAB_WIDTH: usize = 8;
+
 static CONSOLE_STATE: Mutex<Option<ConsoleState>> = Mutex::new(None);
 
 pub fn init_fb_console() -> Result<(), ()> {
@@ -34,6 +75,9 @@ pub fn init_fb_console() -> Result<(), ()> {
             fg_color: 0x00FFFFFF,  // White text
             bg_color: 0x00000000,  // Black background
             cursor_visible: true,  // Start with visible cursor
+            cursor_shown: true,
+            tab_width: DEFAULT_TAB_WIDTH,
+            ansi: Parser::new(),
         };
         
         // Clear screen to background color
@@ -80,6 +124,91 @@ fn write_char_internal(c: u8) {
         None => return,
     };
     
+    match state.ansi.feed(c) {
+        AnsiAction::None => {}
+        AnsiAction::Print(c) => put_byte(fb, state, c),
+        // Colors and attributes aren't rendered yet
+        AnsiAction::Sgr(_) => {}
+        AnsiAction::CursorTo(x, y) => {
+            erase_cursor(fb, state);
+            state.move_cursor(x as usize, y as usize);
+        }
+        AnsiAction::ShowCursor(on) => {
+            erase_cursor(fb, state);
+            state.cursor_shown = on;
+        }
+        AnsiAction::ClearScreen => {
+            erase_cursor(fb, state);
+            erase_in_display(fb, state, 2);
+        }
+        AnsiAction::EraseInLine(mode) => {
+            erase_cursor(fb, state);
+            erase_in_line(fb, state, mode as usize);
+        }
+        AnsiAction::EraseInDisplay(mode) => {
+            erase_cursor(fb, state);
+            erase_in_display(fb, state, mode as usize);
+        }
+    }
+}
+
+/// Blank part of the cursor's row: from the cursor on (0), up to and
+/// including the cursor (1) or all of it (2)
+fn erase_in_line(fb: &dyn crate::display::Framebuffer, state: &ConsoleState, mode: usize) {
+    let (cx, cy, w) = (state.cursor_x, state.cursor_y, state.width_chars);
+    let rect = match mode {
+        0 => row_span_rect(cx, cy, w.saturating_sub(cx)),
+        1 => row_span_rect(0, cy, cx + 1),
+        2 => row_span_rect(0, cy, w),
+        _ => return,
+    };
+    fill_rect(fb, rect, state.bg_color);
+}
+
+/// Like `erase_in_line` for the whole screen around the cursor
+fn erase_in_display(fb: &dyn crate::display::Framebuffer, state: &ConsoleState, mode: usize) {
+    let (cy, w, h) = (state.cursor_y, state.width_chars, state.height_chars);
+    match mode {
+        0 => {
+            erase_in_line(fb, state, 0);
+            fill_rect(fb, rows_rect(cy + 1, h.saturating_sub(cy + 1), w), state.bg_color);
+        }
+        1 => {
+            fill_rect(fb, rows_rect(0, cy, w), state.bg_color);
+            erase_in_line(fb, state, 1);
+        }
+        2 => fill_rect(fb, rows_rect(0, h, w), state.bg_color),
+        _ => {}
+    }
+}
+
+/// Clear from the cursor to the end of its line (`ESC[K`).
+#[allow(dead_code)]
+pub fn clear_to_eol() {
+    clear_with(|fb, state| erase_in_line(fb, state, 0));
+}
+
+/// Clear from the cursor to the end of the screen (`ESC[J`).
+#[allow(dead_code)]
+pub fn clear_to_eos() {
+    clear_with(|fb, state| erase_in_display(fb, state, 0));
+}
+
+fn clear_with(f: impl FnOnce(&dyn crate::display::Framebuffer, &ConsoleState)) {
+    let fb = match get_framebuffer() {
+        Some(fb) => fb,
+        None => return,
+    };
+    if let Some(state) = CONSOLE_STATE.lock().as_ref() {
+        erase_cursor(fb, state);
+        f(fb, state);
+        draw_cursor(fb, state);
+    }
+    crate::display::flush_framebuffer();
+}
+
+/// Render one non-escape byte at the cursor and advance it
+fn put_byte(fb: &dyn crate::display::Framebuffer, state: &mut ConsoleState, c: u8) {
     match c {
         b'\n' => {
             // Newline: erase cursor before moving since we're not drawing anything
@@ -100,8 +229,8 @@ fn write_char_internal(c: u8) {
         b'\t' => {
             // Tab: erase cursor before moving
             erase_cursor(fb, state);
-            // Move to next tab stop (8 characters)
-            let next_tab = ((state.cursor_x / 8) + 1) * 8;
+            // Move to next tab stop
+            let next_tab = state.next_tab_stop();
             if next_tab < state.width_chars {
                 state.cursor_x = next_tab;
             } else {
@@ -113,16 +242,15 @@ fn write_char_internal(c: u8) {
             }
         }
         b'\x08' => {
-            // Backspace: erase cursor, move cursor back one position, clear the character
+            // Backspace: erase cursor, move cursor back one cell (possibly onto
+            // the previous line) and blank that cell
             erase_cursor(fb, state);
-            if state.cursor_x > 0 {
-                state.cursor_x -= 1;
-                // Clear the character at cursor position
-                draw_char(fb, state, b' ');
+            if state.cursor_back() {
+                clear_cell(fb, state.cursor_x, state.cursor_y, state.bg_color);
             }
         }
-        c if c >= 32 && c <= 126 => {
-            // Printable character: erase cursor first since it might be at this position
+        c if uapi::font::is_printable(c) => {
+            // Printable character (ASCII or CP437 0xA0-0xFF): erase cursor first since it might be at this position
             erase_cursor(fb, state);
             // Draw character (this overwrites where cursor was)
             draw_char(fb, state, c);
@@ -165,9 +293,50 @@ pub fn write_str(s: &str) {
     crate::display::flush_framebuffer();
 }
 
+/// Move the cursor to column `x`, row `y` (clamped to the screen)
+#[allow(dead_code)]
+pub fn set_cursor(x: usize, y: usize) {
+    let fb = match get_framebuffer() {
+        Some(fb) => fb,
+        None => return,
+    };
+    if let Some(state) = CONSOLE_STATE.lock().as_mut() {
+        erase_cursor(fb, state);
+        state.move_cursor(x, y);
+        // Restart the blink so the cursor shows up at its new position
+        state.cursor_visible = true;
+        draw_cursor(fb, state);
+    }
+    crate::display::flush_framebuffer();
+}
+
+/// Show or hide the cursor; a hidden cursor does not blink
+#[allow(dead_code)]
+pub fn show_cursor(on: bool) {
+    let fb = match get_framebuffer() {
+        Some(fb) => fb,
+        None => return,
+    };
+    if let Some(state) = CONSOLE_STATE.lock().as_mut() {
+        erase_cursor(fb, state);
+        state.cursor_shown = on;
+        state.cursor_visible = true;
+        draw_cursor(fb, state);
+    }
+    crate::display::flush_framebuffer();
+}
+
+/// Set the spacing of tab stops in columns (default 8). Zero is treated as 1.
+#[allow(dead_code)]
+pub fn set_tab_width(n: usize) {
+    if let Some(state) = CONSOLE_STATE.lock().as_mut() {
+        state.tab_width = n.max(1);
+    }
+}
+
 /// Draw the cursor at the current position
 fn draw_cursor(fb: &dyn crate::display::Framebuffer, state: &ConsoleState) {
-    if !state.cursor_visible {
+    if !state.cursor_drawn() {
         return;
     }
     
@@ -201,8 +370,8 @@ fn draw_cursor(fb: &dyn crate::display::Framebuffer, state: &ConsoleState) {
 
 /// Erase the cursor at the current position
 fn erase_cursor(fb: &dyn crate::display::Framebuffer, state: &ConsoleState) {
-    // Only erase if cursor is currently visible
-    if !state.cursor_visible {
+    // Only erase if cursor is currently on screen
+    if !state.cursor_drawn() {
         return;
     }
     
@@ -247,10 +416,13 @@ pub fn update_cursor() {
         None => return,
     };
     
-    // Erase current cursor
-    if state.cursor_visible {
-        erase_cursor(fb, state);
+    // A hidden cursor stays hidden
+    if !state.cursor_shown {
+        return;
     }
+
+    // Erase current cursor
+    erase_cursor(fb, state);
     
     // Toggle cursor visibility
     state.cursor_visible = !state.cursor_visible;
@@ -284,50 +456,129 @@ fn reset_cursor_blink() {
     draw_cursor(fb, state);
 }
 
-/// Draw a character at the current cursor position
-fn draw_char(fb: &dyn crate::display::Framebuffer, state: &ConsoleState, c: u8) {
+/// Pixels in one character cell
+pub const CELL_PIXELS: usize = uapi::font::FONT_WIDTH * uapi::font::FONT_HEIGHT;
+
+/// Pixel rectangle (x, y, width, height) covered by character cell (cx, cy)
+pub fn cell_rect(cx: usize, cy: usize) -> (usize, usize, usize, usize) {
+    (
+        cx * uapi::font::FONT_WIDTH,
+        cy * uapi::font::FONT_HEIGHT,
+        uapi::font::FONT_WIDTH,
+        uapi::font::FONT_HEIGHT,
+    )
+}
+
+/// Render `c` as a cell-sized block of pixels, row-major.
+/// Returns false for characters the font doesn't have.
+pub fn render_glyph(c: u8, fg: u32, bg: u32, out: &mut [u32; CELL_PIXELS]) -> bool {
     let bitmap = match uapi::font::get_char_bitmap(c) {
         Some(b) => b,
-        None => return,  // Unsupported character
+        None => return false,
     };
-    
+    for (row, &bitmap_row) in bitmap.iter().enumerate() {
+        for col in 0..uapi::font::FONT_WIDTH {
+            // Font rows are stored MSB-left: column 0 is bit 7
+            out[row * uapi::font::FONT_WIDTH + col] = if uapi::font::glyph_pixel(bitmap_row, col) {
+                fg
+            } else {
+                bg
+            };
+        }
+    }
+    true
+}
+
+/// Draw `c` into character cell (cx, cy) of the back buffer, clipped to the screen
+fn draw_glyph_at(fb: &dyn crate::display::Framebuffer, cx: usize, cy: usize, c: u8, fg: u32, bg: u32) {
+    let mut pixels = [0u32; CELL_PIXELS];
+    if !render_glyph(c, fg, bg, &mut pixels) {
+        return;  // Unsupported character
+    }
+
     let info = fb.info();
-    let x_pixel = state.cursor_x * uapi::font::FONT_WIDTH;
-    let y_pixel = state.cursor_y * uapi::font::FONT_HEIGHT;
+    let (x_pixel, y_pixel, w, h) = cell_rect(cx, cy);
     
     unsafe {
         let buf = fb.back_buffer() as *mut u32;
         
-        for row in 0..uapi::font::FONT_HEIGHT {
-            let bitmap_row = bitmap[row];
+        for row in 0..h {
             let y = y_pixel + row;
             if y >= info.height {
                 break;
             }
             
-            for col in 0..uapi::font::FONT_WIDTH {
+            for col in 0..w {
                 let x = x_pixel + col;
                 if x >= info.width {
                     break;
                 }
                 
-                // Check if pixel is set
-                // Font format: bit 7 (MSB) is leftmost pixel, bit 0 is rightmost
-                // So for column 0 (leftmost), we check bit 7; for column 7 (rightmost), we check bit 0
-                let pixel_set = (bitmap_row & (1 << (7 - col))) != 0;
-                let color = if pixel_set {
-                    state.fg_color
-                } else {
-                    state.bg_color
-                };
-                
                 let offset = y * info.width + x;
-                *buf.add(offset) = color;
+                *buf.add(offset) = pixels[row * w + col];
+            }
+        }
+    }
+}
+
+/// Draw a character at the current cursor position
+/// Pixel rectangle covering `cells` cells of row `cy` starting at column `cx`
+pub fn row_span_rect(cx: usize, cy: usize, cells: usize) -> (usize, usize, usize, usize) {
+    let (x, y, _, h) = cell_rect(cx, cy);
+    (x, y, cells * uapi::font::FONT_WIDTH, h)
+}
+
+/// Pixel rectangle covering `rows` full rows (of `width_chars` cells) from row `cy`
+pub fn rows_rect(cy: usize, rows: usize, width_chars: usize) -> (usize, usize, usize, usize) {
+    (
+        0,
+        cy * uapi::font::FONT_HEIGHT,
+        width_chars * uapi::font::FONT_WIDTH,
+        rows * uapi::font::FONT_HEIGHT,
+    )
+}
+
+/// Fill character cell (cx, cy) with `color`
+fn clear_cell(fb: &dyn crate::display::Framebuffer, cx: usize, cy: usize, color: u32) {
+    fill_rect(fb, cell_rect(cx, cy), color);
+}
+
+/// Fill a pixel rectangle (x, y, width, height), clipped to the screen
+fn fill_rect(fb: &dyn crate::display::Framebuffer, rect: (usize, usize, usize, usize), color: u32) {
+    let info = fb.info();
+    let (x_pixel, y_pixel, w, h) = rect;
+    unsafe {
+        let buf = fb.back_buffer() as *mut u32;
+        for y in y_pixel..(y_pixel + h).min(info.height) {
+            for x in x_pixel..(x_pixel + w).min(info.width) {
+                *buf.add(y * info.width + x) = color;
             }
         }
     }
 }
 
+fn draw_char(fb: &dyn crate::display::Framebuffer, state: &ConsoleState, c: u8) {
+    draw_glyph_at(fb, state.cursor_x, state.cursor_y, c, state.fg_color, state.bg_color);
+}
+
+/// Draw one glyph at cell (x, y) with its own colors and present only that
+/// cell. Independent of the text cursor, so a game can move a marker around
+/// without redrawing the screen.
+#[allow(dead_code)]
+pub fn draw_cell(x: usize, y: usize, ch: u8, fg: u32, bg: u32) {
+    let fb = match get_framebuffer() {
+        Some(fb) => fb,
+        None => return,
+    };
+    match CONSOLE_STATE.lock().as_ref() {
+        Some(state) if x < state.width_chars && y < state.height_chars => {}
+        _ => return,
+    }
+    draw_glyph_at(fb, x, y, ch, fg, bg);
+    let (px, py, w, h) = cell_rect(x, y);
+    crate::display::flush_framebuffer_rect(px, py, w, h);
+}
+
 /// Scroll the screen up by one line
 fn scroll_up(fb: &dyn crate::display::Framebuffer, state: &mut ConsoleState) {
     let info = fb.info();
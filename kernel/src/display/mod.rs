@@ -1,12 +1,56 @@
 pub mod fb_console;
 pub mod virtio_gpu;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DisplayMode {
     Ansi,
     Gpu,
 }
 
+impl DisplayMode {
+    /// Decode a `uapi::display_mode` value
+    pub fn from_raw(v: usize) -> Option<Self> {
+        match v {
+            uapi::display_mode::ANSI => Some(DisplayMode::Ansi),
+            uapi::display_mode::GPU => Some(DisplayMode::Gpu),
+            _ => None,
+        }
+    }
+
+    pub fn as_raw(self) -> usize {
+        match self {
+            DisplayMode::Ansi => uapi::display_mode::ANSI,
+            DisplayMode::Gpu => uapi::display_mode::GPU,
+        }
+    }
+}
+
+/// Why a `set_display_mode` request was rejected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModeError {
+    /// Not a `uapi::display_mode` value
+    Unknown,
+    /// GPU output asked for, but no framebuffer was set up at boot
+    NoFramebuffer,
+}
+
+/// Check a `set_display_mode` request: unknown values are rejected, and so is
+/// GPU output when no framebuffer was set up at boot.
+pub fn mode_for_request(raw: usize, have_framebuffer: bool) -> Result<DisplayMode, ModeError> {
+    match DisplayMode::from_raw(raw) {
+        Some(DisplayMode::Gpu) if !have_framebuffer => Err(ModeError::NoFramebuffer),
+        Some(mode) => Ok(mode),
+        None => Err(ModeError::Unknown),
+    }
+}
+
+/// What `SYNC` does for the display: present the framebuffer when there is
+/// one. False only if that flush failed; without a framebuffer there is
+/// nothing to flush, which counts as success.
+pub fn sync_display<F: FnOnce() -> bool>(have_framebuffer: bool, flush: F) -> bool {
+    !have_framebuffer || flush()
+}
+
 #[derive(Debug)]
 pub struct FramebufferInfo {
     pub width: usize,
@@ -14,6 +58,7 @@ pub struct FramebufferInfo {
     pub stride: usize,
     pub phys_addr: usize,
     pub size: usize,
+    pub format: u32, // VIRTIO_GPU_FORMAT_* of the pixels
 }
 
 pub trait Framebuffer {
@@ -41,3 +86,8 @@ pub fn get_framebuffer() -> Option<&'static dyn Framebuffer> {
 pub fn flush_framebuffer() -> bool {
     virtio_gpu::flush_gpu()
 }
+
+/// Present only the pixel rectangle at (x, y) of size w x h
+pub fn flush_framebuffer_rect(x: usize, y: usize, w: usize, h: usize) -> bool {
+    virtio_gpu::flush_gpu_rect(x, y, w, h)
+}
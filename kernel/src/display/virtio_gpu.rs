@@ -111,6 +111,7 @@ struct GpuCtrlHdr {
 }
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct GpuRect {
     x: u32,
     y: u32,
@@ -183,14 +184,60 @@ static mut GPU_RESP_BUF: [u8; GPU_RESPONSE_BUFFER_SIZE] = [0; GPU_RESPONSE_BUFFE
 // Global GPU instance
 static mut GLOBAL_GPU: Option<VirtioGpu> = None;
 
+/// The steps of presenting a frame. Drawing (the console and user programs)
+/// goes to the back buffer; the host only ever reads the scanout buffer, so a
+/// region must be copied across before it is transferred.
+trait PresentOps {
+    fn copy_to_scanout(&mut self, r: GpuRect);
+    fn transfer_to_host(&mut self, r: GpuRect) -> bool;
+    fn resource_flush(&mut self, r: GpuRect) -> bool;
+}
+
+/// Copy `r` to the scanout buffer, then transfer and flush it
+fn present_rect<P: PresentOps>(p: &mut P, r: GpuRect) -> bool {
+    p.copy_to_scanout(r);
+    let transferred = p.transfer_to_host(r);
+    let flushed = p.resource_flush(r);
+    transferred && flushed
+}
+
+/// Copy the pixels of `r` between two frames `stride_px` pixels wide
+fn copy_rect(src: &[u32], dst: &mut [u32], stride_px: usize, r: GpuRect) {
+    let (x, w) = (r.x as usize, r.width as usize);
+    for y in r.y as usize..(r.y + r.height) as usize {
+        let start = y * stride_px + x;
+        dst[start..start + w].copy_from_slice(&src[start..start + w]);
+    }
+}
+
 /// Flush framebuffer changes to the GPU display
 /// Returns true if successful, false if no GPU or flush failed
 #[allow(static_mut_refs)]
 pub fn flush_gpu() -> bool {
     unsafe {
         if let Some(ref mut gpu) = GLOBAL_GPU {
-            gpu.flush_display();
-            true
+            gpu.flush_display()
+        } else {
+            false
+        }
+    }
+}
+
+/// Present just one rectangle (clipped to the screen), e.g. a single cell
+#[allow(static_mut_refs)]
+pub fn flush_gpu_rect(x: usize, y: usize, w: usize, h: usize) -> bool {
+    unsafe {
+        if let Some(ref mut gpu) = GLOBAL_GPU {
+            if x >= gpu.info.width || y >= gpu.info.height {
+                return false;
+            }
+            let r = GpuRect {
+                x: x as u32,
+                y: y as u32,
+                width: w.min(gpu.info.width - x) as u32,
+                height: h.min(gpu.info.height - y) as u32,
+            };
+            present_rect(gpu, r)
         } else {
             false
         }
@@ -200,7 +247,8 @@ pub fn flush_gpu() -> bool {
 #[derive(Debug)]
 pub struct VirtioGpu {
     info: FramebufferInfo,
-    back: *mut u8,
+    back: *mut u8,    // drawn to by the console and mapped into user space
+    scanout: *mut u8, // GPU resource backing, only written by present
     mmio_base: usize,
     resource_id: u32,
     queue: Option<Virtqueue>,
@@ -279,10 +327,16 @@ impl VirtioGpu {
         const H: usize = 1080;
         const SIZE: usize = W * H * 4;
 
-        ktrace!("[VirtIO-GPU] Framebuffer: {}x{} = {} bytes", W, H, SIZE);
+        // `fbres=` may ask for a smaller mode; the buffers are sized for W x H
+        let (width, height) = crate::boot::cmdline::fb_resolution()
+            .map(|(w, h)| (w.min(W), h.min(H)))
+            .unwrap_or((W, H));
+
+        ktrace!("[VirtIO-GPU] Framebuffer: {}x{} = {} bytes", width, height, width * height * 4);
 
-        // Allocate static framebuffer
+        // Allocate static framebuffers: one to draw into, one the GPU reads
         static mut BUF: [u8; SIZE] = [0; SIZE];
+        static mut SCANOUT_BUF: [u8; SIZE] = [0; SIZE];
 
         // Allocate virtqueue memory in a contiguous block
         // This is required for VirtIO MMIO version 1
@@ -469,11 +523,12 @@ impl VirtioGpu {
 
             // Create VirtioGpu instance
             let fb_info = FramebufferInfo {
-                width: W,
-                height: H,
-                stride: W * 4,
+                width,
+                height,
+                stride: width * 4,
                 phys_addr: BUF.as_ptr() as usize,
-                size: SIZE,
+                size: width * height * 4,
+                format: VIRTIO_GPU_FORMAT_B8G8R8X8_UNORM,
             };
 
             ktrace!(
@@ -493,6 +548,7 @@ impl VirtioGpu {
             GLOBAL_GPU = Some(VirtioGpu {
                 info: fb_info,
                 back: BUF.as_mut_ptr(),
+                scanout: SCANOUT_BUF.as_mut_ptr(),
                 mmio_base,
                 resource_id: 1, // Resource ID for our framebuffer
                 queue: Some(queue),
@@ -682,7 +738,7 @@ impl VirtioGpu {
         ktrace!("[VirtIO-GPU] ========================================");
 
         let resource_id = self.resource_id;
-        let fb_addr = self.back as usize;
+        let fb_addr = self.scanout as usize;
         let width = self.info.width as u32;
         let height = self.info.height as u32;
 
@@ -818,16 +874,35 @@ impl VirtioGpu {
         }
     }
 
-    // Flush framebuffer to display
+    // Present the whole back buffer on the display
+    fn flush_display(&mut self) -> bool {
+        let full = GpuRect {
+            x: 0,
+            y: 0,
+            width: self.info.width as u32,
+            height: self.info.height as u32,
+        };
+        present_rect(self, full)
+    }
+}
+
+impl PresentOps for VirtioGpu {
+    fn copy_to_scanout(&mut self, r: GpuRect) {
+        let pixels = self.info.size / 4;
+        unsafe {
+            let src = core::slice::from_raw_parts(self.back as *const u32, pixels);
+            let dst = core::slice::from_raw_parts_mut(self.scanout as *mut u32, pixels);
+            copy_rect(src, dst, self.info.stride / 4, r);
+        }
+    }
+
     #[allow(static_mut_refs)]
-    fn flush_display(&mut self) {
+    fn transfer_to_host(&mut self, r: GpuRect) -> bool {
         let resource_id = self.resource_id;
-        let width = self.info.width as u32;
-        let height = self.info.height as u32;
 
         unsafe {
             // Transfer to host
-            ktrace!("[VirtIO-GPU]   TRANSFER_TO_HOST_2D: {}x{}", width, height);
+            ktrace!("[VirtIO-GPU]   TRANSFER_TO_HOST_2D: {}x{}", r.width, r.height);
             let transfer_cmd = GpuTransferToHost2D {
                 hdr: GpuCtrlHdr {
                     hdr_type: VIRTIO_GPU_CMD_TRANSFER_TO_HOST_2D,
@@ -836,13 +911,9 @@ impl VirtioGpu {
                     ctx_id: 0,
                     padding: 0,
                 },
-                r: GpuRect {
-                    x: 0,
-                    y: 0,
-                    width,
-                    height,
-                },
-                offset: 0,
+                r,
+                // Byte offset of the rectangle's first pixel in the backing
+                offset: (r.y as usize * self.info.stride + r.x as usize * 4) as u64,
                 resource_id,
                 padding: 0,
             };
@@ -862,7 +933,15 @@ impl VirtioGpu {
             } else {
                 kwarn!("[VirtIO-GPU]   TRANSFER command succeeded");
             }
+            success
+        }
+    }
+
+    #[allow(static_mut_refs)]
+    fn resource_flush(&mut self, r: GpuRect) -> bool {
+        let resource_id = self.resource_id;
 
+        unsafe {
             // Flush resource
             ktrace!("[VirtIO-GPU]   RESOURCE_FLUSH: resource_id={}", resource_id);
             let flush_cmd = GpuResourceFlush {
@@ -873,12 +952,7 @@ impl VirtioGpu {
                     ctx_id: 0,
                     padding: 0,
                 },
-                r: GpuRect {
-                    x: 0,
-                    y: 0,
-                    width,
-                    height,
-                },
+                r,
                 resource_id,
                 padding: 0,
             };
@@ -898,6 +972,7 @@ impl VirtioGpu {
             } else {
                 kwarn!("[VirtIO-GPU]   FLUSH command succeeded");
             }
+            success
         }
     }
 }
@@ -3,6 +3,7 @@
 //! Responsibilities:
 //! - Parse ELF headers and PT_LOAD segments
 //! - Map segments at their `p_vaddr` with Sv39 (U=1; R/W/X from ELF flags)
+//! - Relocate PIE (`ET_DYN`) images to `PIE_LOAD_BIAS` via `R_RISCV_RELATIVE`
 //! - Copy file bytes; zero BSS tail
 //! - Build a user stack (argc/argv/envp) and return entry & SP
 //!
@@ -17,13 +18,14 @@
 
 use core::mem::size_of;
 
-use goblin::elf::{header, program_header, Elf};
+use goblin::elf::{header, program_header, reloc, Elf};
 use riscv::register::sstatus;
+use uapi::MAX_ARGS;
 
 use crate::sv39::{self, PTE_A, PTE_D, PTE_R, PTE_U, PTE_V, PTE_W, PTE_X};
 
 /// Loader errors (compact, no strings in the happy path)
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ElfLoadError {
     Short,
     BadMagic,
@@ -32,8 +34,37 @@ pub enum ElfLoadError {
     PhOutOfBounds,
     SatpNotSet,
     SegmentOverflow,
+    BadReloc,
+    BadVaddr,
+    SegmentOverlap,
+    StackOverflow,
+    OutOfUserPages,
+    /// More than `MAX_PHNUM` program headers
+    TooManyHeaders,
+    /// The segments together would map more than `MAX_IMAGE_BYTES`
+    ImageTooLarge,
+    /// `e_entry` is not inside an executable segment
+    BadEntry,
 }
 
+/// Lowest VA a user segment may occupy (page 0 stays unmapped to catch NULL).
+pub const USER_IMAGE_START: usize = 0x1000;
+/// End of the user image window; MMIO (UART at `0x1000_0000`) and the kernel sit above.
+pub const USER_IMAGE_END: usize = 0x1000_0000;
+
+/// Maximum number of `PT_LOAD` segments tracked for overlap checks.
+const MAX_SEGMENTS: usize = 16;
+
+/// Most program headers accepted, checked before goblin reads them.
+pub const MAX_PHNUM: usize = 32;
+/// Most memory the segments of one image may map, in whole pages. Half the
+/// 1 MiB user page pool, so the stack and heap still get some.
+pub const MAX_IMAGE_BYTES: usize = 512 * 1024;
+
+/// Load address for position-independent (`ET_DYN`) executables.
+/// Matches where fixed-address user programs are linked.
+pub const PIE_LOAD_BIAS: usize = 0x0001_0000;
+
 pub struct Loaded {
     pub entry_va: usize,
     pub user_sp: usize,
@@ -73,10 +104,23 @@ unsafe fn write_user_usize(va: usize, val: usize) {
 /* ---------- mapping & copying helpers ---------- */
 
 /// Map one VA page to a fresh PA page with flags, return PA.
-unsafe fn map_user_page(root: *mut u64, va_page: usize, flags: u64) -> usize {
-    let pa = unsafe { sv39::alloc_user_page() };
+unsafe fn map_user_page(root: *mut u64, va_page: usize, flags: u64) -> Result<usize, ElfLoadError> {
+    let pa = unsafe { sv39::alloc_user_page() }.ok_or(ElfLoadError::OutOfUserPages)?;
     unsafe { sv39::map_4k(root, va_page, pa, flags) };
-    pa
+    Ok(pa)
+}
+
+/// Run `load`; if it fails, run `cleanup` so no half-built image stays mapped.
+pub fn load_or_cleanup<T, L, C>(load: L, cleanup: C) -> Result<T, ElfLoadError>
+where
+    L: FnOnce() -> Result<T, ElfLoadError>,
+    C: FnOnce(),
+{
+    let r = load();
+    if r.is_err() {
+        cleanup();
+    }
+    r
 }
 
 /// Copy `len` bytes into a **physical** address (visible via kernel identity map).
@@ -99,20 +143,178 @@ pub fn pte_flags_from_pf(pf: u32) -> u64 {
     f
 }
 
+/// Compute an `R_RISCV_RELATIVE` fixup for an image loaded at `bias`.
+/// Returns `(target_va, value)`: `bias + addend` is stored at `bias + r_offset`.
+#[inline]
+#[allow(clippy::cast_possible_truncation)] // rv64 only: usize is 64 bits
+pub fn relative_reloc(bias: usize, r_offset: u64, r_addend: i64) -> (usize, usize) {
+    (
+        bias.wrapping_add(r_offset as usize),
+        bias.wrapping_add_signed(r_addend as isize),
+    )
+}
+
+/// True if the half-open ranges `[a_start, a_end)` and `[b_start, b_end)` share a byte.
+#[inline]
+pub fn ranges_overlap(a_start: usize, a_end: usize, b_start: usize, b_end: usize) -> bool {
+    a_start < b_end && b_start < a_end
+}
+
+/// True if `[vaddr, vaddr + memsz)` lies inside the user image window.
+#[inline]
+pub fn segment_in_user_range(vaddr: usize, memsz: usize) -> bool {
+    match vaddr.checked_add(memsz) {
+        Some(end) => vaddr >= USER_IMAGE_START && end <= USER_IMAGE_END,
+        None => false,
+    }
+}
+
+/// Top of the user stack: the end of the image window, well clear of the
+/// boot-time `USER_CODE_VA`/`USER_STACK_VA` pages at `0x4000_0000`.
+pub const USER_STACK_TOP: usize = USER_IMAGE_END;
+/// Stack size unless `stacksize=` asks for another
+pub const DEFAULT_USER_STACK_BYTES: usize = 16 * 1024;
+/// Largest stack `stacksize=` may ask for; stack pages come out of the 1 MiB user pool.
+pub const MAX_USER_STACK_BYTES: usize = 256 * 1024;
+
+/// The stack `[bottom, top)` for `bytes` below `top`, rounded up to whole
+/// pages. `None` if `bytes` is zero or above [`MAX_USER_STACK_BYTES`], or
+/// if the stack and the unmapped guard page below it don't fit in the
+/// image window.
+pub fn stack_region(top: usize, bytes: usize) -> Option<(usize, usize)> {
+    if bytes == 0 || bytes > MAX_USER_STACK_BYTES || !top.is_multiple_of(4096) || top > USER_IMAGE_END {
+        return None;
+    }
+    let bottom = top.checked_sub(bytes.next_multiple_of(4096))?;
+    (bottom.checked_sub(4096)? >= USER_IMAGE_START).then_some((bottom, top))
+}
+
+/// True if `[vaddr, vaddr + memsz)` touches the stack `(bottom, top)` or
+/// the guard page below it, which has to stay unmapped to catch overflows.
+#[inline]
+pub fn stack_overlaps_segment(stack: (usize, usize), vaddr: usize, memsz: usize) -> bool {
+    let (bottom, top) = stack;
+    ranges_overlap(vaddr, vaddr.saturating_add(memsz), bottom.saturating_sub(4096), top)
+}
+
+/// The page-aligned VA range `[start, end)` to map for `p_memsz` bytes at
+/// `p_vaddr`. A pure-BSS segment (no file bytes) needs the same pages as
+/// any other; `page_copy_plan` then zeroes all of them.
+#[inline]
+pub fn segment_pages(p_vaddr: usize, p_memsz: usize, page: usize) -> (usize, usize) {
+    (p_vaddr & !(page - 1), (p_vaddr + p_memsz).next_multiple_of(page))
+}
+
+/// `total` plus the pages mapped for `p_memsz` bytes at `p_vaddr`, or
+/// `ImageTooLarge` once that passes `MAX_IMAGE_BYTES`.
+#[inline]
+pub fn add_image_bytes(total: usize, p_vaddr: usize, p_memsz: usize, page: usize) -> Result<usize, ElfLoadError> {
+    let (start, end) = segment_pages(p_vaddr, p_memsz, page);
+    total
+        .checked_add(end - start)
+        .filter(|&t| t <= MAX_IMAGE_BYTES)
+        .ok_or(ElfLoadError::ImageTooLarge)
+}
+
+/// True if `entry` lies in one of `segments`, given as `(start, end,
+/// executable)`, that is executable.
+#[inline]
+pub fn entry_in_exec_segment(entry: usize, segments: &[(usize, usize, bool)]) -> bool {
+    segments.iter().any(|&(start, end, exec)| exec && (start..end).contains(&entry))
+}
+
+/// True if the word a relocation writes at `va` lies wholly inside one of
+/// the image's `segments` (`(start, end, executable)`). Anything else could
+/// be kernel memory, which is mapped in the same page table.
+#[inline]
+pub fn reloc_in_segments(va: usize, segments: &[(usize, usize, bool)]) -> bool {
+    va.checked_add(size_of::<usize>())
+        .is_some_and(|end| segments.iter().any(|&(start, seg_end, _)| start <= va && end <= seg_end))
+}
+
+/// What to do with the VA page at `cur_va` while loading a segment of
+/// `p_filesz` file bytes and `p_memsz` memory bytes at `p_vaddr`: copy
+/// `copy_len` file bytes to `copy_off` in the page, then zero `zero_len`
+/// bytes (the BSS) at `zero_off`. Offsets are from the start of the page,
+/// and the file bytes to copy start at `cur_va + copy_off - p_vaddr`.
+/// Bytes of the page outside the segment are left alone, as are file bytes
+/// past `p_memsz`.
+#[inline]
+pub fn page_copy_plan(p_vaddr: usize, p_filesz: usize, p_memsz: usize, cur_va: usize, page: usize) -> (usize, usize, usize, usize) {
+    let page_end = cur_va + page;
+    // The part of the segment inside this page, and how much of it is file
+    let start = p_vaddr.clamp(cur_va, page_end);
+    let mem_end = (p_vaddr + p_memsz).clamp(start, page_end);
+    let file_end = (p_vaddr + p_filesz).clamp(start, mem_end);
+    let copy_off = start - cur_va;
+    let copy_len = file_end - start;
+    (copy_off, copy_len, copy_off + copy_len, mem_end - file_end)
+}
+
+/// Apply the `.rela.dyn` relocations of a PIE image mapped at `bias`.
+/// Only `R_RISCV_RELATIVE` (and `R_RISCV_NONE`) are supported, and only
+/// into the image's own `segments`.
+unsafe fn apply_relocations(
+    elf: &Elf<'_>,
+    bias: usize,
+    segments: &[(usize, usize, bool)],
+    root: *mut u64,
+) -> Result<(), ElfLoadError> {
+    for rela in &elf.dynrelas {
+        match rela.r_type {
+            reloc::R_RISCV_NONE => continue,
+            reloc::R_RISCV_RELATIVE => {}
+            _ => return Err(ElfLoadError::BadReloc),
+        }
+        let (va, value) = relative_reloc(bias, rela.r_offset, rela.r_addend.unwrap_or(0));
+        if va % size_of::<usize>() != 0 || !reloc_in_segments(va, segments) {
+            return Err(ElfLoadError::BadReloc);
+        }
+        // Patch through the physical page so read-only (RELRO) pages work too
+        let pa = unsafe { sv39::translate(root, va) }.ok_or(ElfLoadError::BadReloc)?;
+        unsafe { core::ptr::write(pa as *mut usize, value) };
+    }
+    Ok(())
+}
+
 /* ---------- public API ---------- */
 
+/// Map `image` and its stack into the current page table. On failure every
+/// user mapping is dropped and the user page pool is reset, so the caller
+/// never sees a partially loaded program.
 pub fn load_user_elf(
     image: &[u8],
     user_stack_top_va: usize,
     user_stack_bytes: usize,
     argv: &[&str],
     envp: &[&str],
+) -> Result<Loaded, ElfLoadError> {
+    load_or_cleanup(
+        || load_user_elf_inner(image, user_stack_top_va, user_stack_bytes, argv, envp),
+        || unsafe {
+            sv39::clear_user_mappings();
+            sv39::reset_user_pages();
+            riscv::asm::sfence_vma_all();
+        },
+    )
+}
+
+fn load_user_elf_inner(
+    image: &[u8],
+    user_stack_top_va: usize,
+    user_stack_bytes: usize,
+    argv: &[&str],
+    envp: &[&str],
 ) -> Result<Loaded, ElfLoadError> {
     unsafe {
         if image.len() < size_of::<goblin::elf::header::Header>() {
             return Err(ElfLoadError::Short);
         }
 
+        // Refuse absurd header counts before goblin reads them all
+        let hdr = Elf::parse_header(image).map_err(|_| ElfLoadError::BadMagic)?;
+        if usize::from(hdr.e_phnum) > MAX_PHNUM { return Err(ElfLoadError::TooManyHeaders); }
+
         // Parse ELF
         let elf = Elf::parse(image).map_err(|_| ElfLoadError::BadMagic)?;
 
@@ -135,27 +337,30 @@ pub fn load_user_elf(
             return Err(ElfLoadError::SatpNotSet);
         }
 
+        // PIE images are linked at 0 and shifted by a fixed load bias
+        let bias = if elf.header.e_type == header::ET_DYN { PIE_LOAD_BIAS } else { 0 };
+
         // Map PT_LOAD segments
         let page = 4096usize;
         let mut max_brk = 0usize;
+        let mut image_bytes = 0usize;
+        let mut segments: heapless::Vec<(usize, usize, bool), MAX_SEGMENTS> = heapless::Vec::new();
+        let stack_bottom = user_stack_top_va.saturating_sub(user_stack_bytes);
 
         for ph in &elf.program_headers {
             if ph.p_type != program_header::PT_LOAD {
                 continue;
             }
 
-            let p_vaddr  = ph.p_vaddr as usize;
+            let p_vaddr  = bias.checked_add(ph.p_vaddr as usize).ok_or(ElfLoadError::BadVaddr)?;
             let p_offset = ph.p_offset as usize;
             let p_filesz = ph.p_filesz as usize;
             let p_memsz  = ph.p_memsz as usize;
             let p_flags  = ph.p_flags as u32;
 
-            // Safety bounds: file bytes must lie within the ELF image
-            if p_offset
-                .checked_add(p_filesz)
-                .map(|end| end <= image.len())
-                .unwrap_or(false) == false
-            {
+            // Safety bounds: file bytes must lie within the ELF image. A
+            // pure-BSS segment reads nothing, so its offset doesn't matter.
+            if p_filesz > 0 && p_offset.checked_add(p_filesz).is_none_or(|end| end > image.len()) {
                 return Err(ElfLoadError::PhOutOfBounds);
             }
 
@@ -163,52 +368,59 @@ pub fn load_user_elf(
                 continue; // nothing to map
             }
 
-            // Update max_brk
+            // Reject segments outside the user window, over the stack, or over each other
+            if !segment_in_user_range(p_vaddr, p_memsz) {
+                return Err(ElfLoadError::BadVaddr);
+            }
             let seg_end = p_vaddr + p_memsz;
+            if stack_overlaps_segment((stack_bottom, user_stack_top_va), p_vaddr, p_memsz)
+                || segments.iter().any(|&(s, e, _)| ranges_overlap(p_vaddr, seg_end, s, e))
+            {
+                return Err(ElfLoadError::SegmentOverlap);
+            }
+            let exec = p_flags & program_header::PF_X != 0;
+            segments.push((p_vaddr, seg_end, exec)).map_err(|_| ElfLoadError::SegmentOverflow)?;
+            image_bytes = add_image_bytes(image_bytes, p_vaddr, p_memsz, page)?;
+
+            // Update max_brk
             if seg_end > max_brk {
                 max_brk = seg_end;
             }
 
             // Page-aligned mapping range
-            let va0   = p_vaddr & !(page - 1);
-            let head  = p_vaddr - va0;
-            let vaend = (p_vaddr + p_memsz + page - 1) & !(page - 1);
+            let (va0, vaend) = segment_pages(p_vaddr, p_memsz, page);
 
             let flags = pte_flags_from_pf(p_flags);
 
             let mut cur_va = va0;
-            let mut copied = 0usize;
 
             while cur_va < vaend {
-                let pa = map_user_page(root, cur_va, flags);
-
-                // Content for this VA page
-                let page_off   = if cur_va == va0 { head } else { 0 };
-                let page_space = page - page_off;
-
-                let file_left  = p_filesz.saturating_sub(copied);
-                let file_chunk = core::cmp::min(file_left, page_space);
-
-                // Copy file bytes
-                if file_chunk > 0 {
-                    let src = image.as_ptr().wrapping_add(p_offset + copied);
-                    memcpy_pa(pa + page_off, src, file_chunk);
-                    copied += file_chunk;
+                let pa = map_user_page(root, cur_va, flags)?;
+
+                // Copy file bytes, then zero the BSS in this page
+                let (copy_off, copy_len, zero_off, zero_len) =
+                    page_copy_plan(p_vaddr, p_filesz, p_memsz, cur_va, page);
+                if copy_len > 0 {
+                    let src = image.as_ptr().wrapping_add(p_offset + (cur_va + copy_off - p_vaddr));
+                    memcpy_pa(pa + copy_off, src, copy_len);
                 }
-
-                // Zero the remaining BSS in this page
-                let seg_end   = p_vaddr + p_memsz;
-                let page_end  = cur_va + page;
-                let mem_covered = if page_end > seg_end { seg_end.saturating_sub(cur_va) } else { page };
-                if mem_covered > page_off + file_chunk {
-                    let zero_len = mem_covered - (page_off + file_chunk);
-                    memzero_pa(pa + page_off + file_chunk, zero_len);
+                if zero_len > 0 {
+                    memzero_pa(pa + zero_off, zero_len);
                 }
 
                 cur_va += page;
             }
         }
 
+        let entry_va = bias
+            .checked_add(elf.header.e_entry as usize)
+            .filter(|&va| entry_in_exec_segment(va, &segments))
+            .ok_or(ElfLoadError::BadEntry)?;
+
+        if bias != 0 {
+            apply_relocations(&elf, bias, &segments, root)?;
+        }
+
         // Build user stack
         let (sp, envp_va, argv_va, argc) =
             setup_user_stack(user_stack_top_va, user_stack_bytes, argv, envp, root)?;
@@ -218,7 +430,7 @@ pub fn load_user_elf(
         // Let's keep it exact, sys_brk will handle page alignment.
 
         Ok(Loaded {
-            entry_va: elf.header.e_entry as usize,
+            entry_va,
             user_sp: sp,
             argc,
             argv_va,
@@ -230,75 +442,114 @@ pub fn load_user_elf(
 
 /* ---------- user stack layout ---------- */
 
-unsafe fn setup_user_stack(
+/// Where each argv/envp string and pointer vector lands on the user stack.
+pub struct StackPlan {
+    pub arg_ptrs: heapless::Vec<usize, MAX_ARGS>,
+    pub env_ptrs: heapless::Vec<usize, MAX_ARGS>,
+    pub envp_va: usize,
+    pub argv_va: usize,
+    pub sp: usize,
+}
+
+#[inline]
+fn stack_down(sp: usize, n: usize, bottom: usize) -> Result<usize, ElfLoadError> {
+    match sp.checked_sub(n) {
+        Some(v) if v >= bottom => Ok(v),
+        _ => Err(ElfLoadError::StackOverflow),
+    }
+}
+
+/// Lay out argv/envp below `user_stack_top_va` without touching memory.
+///
+/// Strings go at the top, then the envp and argv vectors, then argc.
+/// Fails with `SegmentOverflow` past `MAX_ARGS` entries and with
+/// `StackOverflow` if the layout does not fit in `user_stack_bytes`.
+pub fn plan_user_stack(
     user_stack_top_va: usize,
     user_stack_bytes: usize,
     argv: &[&str],
     envp: &[&str],
-    root: *mut u64,
-) -> Result<(usize, usize, usize, usize), ElfLoadError> {
-    // Map stack pages U=RW
-    let stack_pages = (user_stack_bytes + 4095) / 4096;
-    let mut va = (user_stack_top_va - stack_pages * 4096) & !4095;
-    for _ in 0..stack_pages {
-        unsafe { map_user_page(root, va, PTE_V | PTE_U | PTE_R | PTE_W | PTE_A | PTE_D) };
-        va += 4096;
+) -> Result<StackPlan, ElfLoadError> {
+    if argv.len() > MAX_ARGS || envp.len() > MAX_ARGS {
+        return Err(ElfLoadError::SegmentOverflow);
     }
+    let bottom = user_stack_top_va.saturating_sub(user_stack_bytes);
+    let word = size_of::<usize>();
 
-    // Strings at top, then pointer vectors, then argc.
-    let mut sp = user_stack_top_va;
-    sp -= 16; // tiny guard so trailing NUL sits inside the last page
+    let mut sp = stack_down(user_stack_top_va, 16, bottom)?; // tiny guard so trailing NUL sits inside the last page
 
-    // env strings
-    let mut env_ptrs: heapless::Vec<usize, 32> = heapless::Vec::new();
+    let mut env_ptrs = heapless::Vec::new();
     for &s in envp {
-        let b = s.as_bytes();
-        sp -= b.len() + 1;
-        unsafe { write_user_bytes(sp, b);
-        write_user_bytes(sp + b.len(), &[0]); }
+        sp = stack_down(sp, s.len() + 1, bottom)?;
         env_ptrs.push(sp).map_err(|_| ElfLoadError::SegmentOverflow)?;
     }
 
-    // argv strings
-    let mut arg_ptrs: heapless::Vec<usize, 32> = heapless::Vec::new();
+    let mut arg_ptrs = heapless::Vec::new();
     for &s in argv {
-        let b = s.as_bytes();
-        sp -= b.len() + 1;
-        unsafe { write_user_bytes(sp, b);
-        write_user_bytes(sp + b.len(), &[0]); }
+        sp = stack_down(sp, s.len() + 1, bottom)?;
         arg_ptrs.push(sp).map_err(|_| ElfLoadError::SegmentOverflow)?;
     }
 
     // 16-byte alignment before vectors
     sp &= !15;
 
-    // envp NULL + vector (in reverse so argv[0] is lowest)
-    sp -= size_of::<usize>();
-    unsafe { write_user_usize(sp, 0) };
-    for &p in env_ptrs.iter().rev() {
-        sp -= size_of::<usize>();
-        unsafe { write_user_usize(sp, p) };
-    }
+    // envp vector + NULL, then argv vector + NULL (argv[0] is lowest)
+    sp = stack_down(sp, (env_ptrs.len() + 1) * word, bottom)?;
     let envp_va = sp;
+    sp = stack_down(sp, (arg_ptrs.len() + 1) * word, bottom)?;
+    let argv_va = sp;
 
-    // argv NULL + vector
-    sp -= size_of::<usize>();
-    unsafe { write_user_usize(sp, 0) };
-    for &p in arg_ptrs.iter().rev() {
-        sp -= size_of::<usize>();
-        unsafe { write_user_usize(sp, p) };
+    // argc, then a final align (some ABIs like it; harmless otherwise)
+    sp = stack_down(sp, word, bottom)? & !15;
+    if sp < bottom {
+        return Err(ElfLoadError::StackOverflow);
     }
-    let argv_va = sp;
 
-    // argc
-    let argc = arg_ptrs.len();
-    sp -= size_of::<usize>();
-    unsafe { write_user_usize(sp, argc) };
+    Ok(StackPlan { arg_ptrs, env_ptrs, envp_va, argv_va, sp })
+}
 
-    // final align (some ABIs like it; harmless otherwise)
-    sp &= !15;
+unsafe fn setup_user_stack(
+    user_stack_top_va: usize,
+    user_stack_bytes: usize,
+    argv: &[&str],
+    envp: &[&str],
+    root: *mut u64,
+) -> Result<(usize, usize, usize, usize), ElfLoadError> {
+    // Validate the layout before mapping anything
+    let plan = plan_user_stack(user_stack_top_va, user_stack_bytes, argv, envp)?;
+
+    // Map stack pages U=RW
+    let stack_pages = user_stack_bytes.div_ceil(4096);
+    let mut va = (user_stack_top_va - stack_pages * 4096) & !4095;
+    for _ in 0..stack_pages {
+        unsafe { map_user_page(root, va, PTE_V | PTE_U | PTE_R | PTE_W | PTE_A | PTE_D) }?;
+        va += 4096;
+    }
+
+    // Strings
+    for (&s, &p) in envp.iter().zip(plan.env_ptrs.iter()).chain(argv.iter().zip(plan.arg_ptrs.iter())) {
+        unsafe {
+            write_user_bytes(p, s.as_bytes());
+            write_user_bytes(p + s.len(), &[0]);
+        }
+    }
+
+    // Pointer vectors, each NULL-terminated
+    let word = size_of::<usize>();
+    for (i, &p) in plan.env_ptrs.iter().enumerate() {
+        unsafe { write_user_usize(plan.envp_va + i * word, p) };
+    }
+    unsafe { write_user_usize(plan.envp_va + plan.env_ptrs.len() * word, 0) };
+    for (i, &p) in plan.arg_ptrs.iter().enumerate() {
+        unsafe { write_user_usize(plan.argv_va + i * word, p) };
+    }
+    unsafe { write_user_usize(plan.argv_va + plan.arg_ptrs.len() * word, 0) };
+
+    // argc just below argv
+    let argc = plan.arg_ptrs.len();
+    unsafe { write_user_usize(plan.argv_va - word, argc) };
 
-    Ok((sp, envp_va, argv_va, argc))
+    Ok((plan.sp, plan.envp_va, plan.argv_va, argc))
 }
 
 #[cfg(test)]
@@ -11,6 +11,7 @@ use std::vec::Vec;
 #[cfg(test)]
 use std::string::String;
 
+use core::sync::atomic::{AtomicUsize, Ordering};
 use spin::Mutex;
 
 pub struct File {
@@ -91,50 +92,190 @@ pub fn lookup(name: &str) -> Option<&'static File> {
 
 /// A writable file stored in kernel memory
 pub struct WritableFile {
+    /// Handle open fds hold on to. Unlike the position in `WRITABLE_FILES`
+    /// it doesn't change when other entries are removed, and isn't reused.
+    pub id: usize,
     pub name: String,
     pub data: Vec<u8>,
     pub mode: u32,
+    /// Last modification time, in seconds since boot
+    pub mtime: u64,
+    /// Directory marker created by `mkdir`; never holds data
+    pub is_dir: bool,
 }
 
+/// Current time for mtime stamps (seconds since boot; 0 in host tests)
+#[cfg(not(test))]
+fn now() -> u64 {
+    crate::timer::seconds()
+}
+#[cfg(test)]
+fn now() -> u64 {
+    0
+}
+
+/// All writable files. This lives in kernel memory and is never reset by
+/// `exit`/`exec`, so files created by one program are still there for the next.
+///
+/// Embedded files are not copied in here: they are served straight from
+/// `FILES` until something writes to one, which creates a writable entry of
+/// the same name that shadows it. Removing the shadow brings the embedded
+/// file back; the embedded copy itself can't be changed or removed.
 static WRITABLE_FILES: Mutex<Vec<WritableFile>> = Mutex::new(Vec::new());
 
-/// Create or truncate a writable file
-pub fn create_file(name: &str) -> Result<usize, ()> {
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn next_id() -> usize {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The writable entry with handle `id`, if it hasn't been removed
+fn by_id(files: &mut [WritableFile], id: usize) -> Option<&mut WritableFile> {
+    files.iter_mut().find(|f| f.id == id)
+}
+
+/// Mode of an embedded file that has no writable shadow
+fn embedded_mode(name: &str) -> u32 {
+    if name.ends_with(".elf") { 0o755 } else { 0o644 }
+}
+
+/// Index of the writable entry for `name`, first copying the embedded file
+/// of that name into the writable layer if there is no entry yet.
+fn shadow_index(files: &mut Vec<WritableFile>, name: &str) -> Option<usize> {
+    if let Some(idx) = files.iter().position(|f| f.name == name) {
+        return Some(idx);
+    }
+    let f = lookup(name)?;
+    files.push(WritableFile {
+        id: next_id(),
+        name: String::from(f.name),
+        data: Vec::from(f.data),
+        mode: embedded_mode(f.name),
+        mtime: 0,
+        is_dir: false,
+    });
+    Some(files.len() - 1)
+}
+
+/// Everything visible in the filesystem with its metadata: the writable
+/// layer, then the embedded files it doesn't shadow. This is the one place
+/// that knows how the two layers combine.
+fn visible_entries(files: &[WritableFile]) -> impl Iterator<Item = (&str, FileStat)> {
+    let embedded = FILES.iter().filter(move |e| !files.iter().any(|f| f.name == e.name)).map(|e| {
+        let st = FileStat {
+            size: e.data.len(),
+            mode: embedded_mode(e.name),
+            is_writable: false,
+            mtime: 0,
+            is_dir: false,
+        };
+        (e.name, st)
+    });
+    files
+        .iter()
+        .map(|f| {
+            let st = FileStat {
+                size: f.data.len(),
+                mode: f.mode,
+                is_writable: true,
+                mtime: f.mtime,
+                is_dir: f.is_dir,
+            };
+            (f.name.as_str(), st)
+        })
+        .chain(embedded)
+}
+
+/// One file as listed by `iter_files`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileInfo {
+    pub name: String,
+    pub size: usize,
+    pub mode: u32,
+    /// Served from the writable layer (including shadows of embedded files)
+    pub is_writable: bool,
+    pub is_dir: bool,
+}
+
+/// Every file in the filesystem, each name once: a writable entry hides the
+/// embedded file it shadows. Iterates over a snapshot, so the filesystem
+/// isn't locked while the caller looks at the entries.
+pub fn iter_files() -> impl Iterator<Item = FileInfo> {
+    let files = WRITABLE_FILES.lock();
+    let snapshot: Vec<FileInfo> = visible_entries(&files)
+        .map(|(name, st)| FileInfo {
+            name: String::from(name),
+            size: st.size,
+            mode: st.mode,
+            is_writable: st.is_writable,
+            is_dir: st.is_dir,
+        })
+        .collect();
+    snapshot.into_iter()
+}
+
+/// Create or truncate a writable file, returning its id. Truncating an
+/// embedded file creates an empty writable shadow of it.
+pub fn create_file(name: &str) -> Result<usize, FsError> {
     let mut files = WRITABLE_FILES.lock();
     
     // Check if file already exists
     for (idx, f) in files.iter().enumerate() {
         if f.name == name {
+            if f.is_dir {
+                return Err(FsError::IsDir);
+            }
             // Truncate existing file
             files[idx].data.clear();
             files[idx].mode = 0o600;
-            return Ok(idx);
+            files[idx].mtime = now();
+            return Ok(files[idx].id);
         }
     }
     
     // Create new file
+    let id = next_id();
     files.push(WritableFile {
+        id,
         name: String::from(name),
         data: Vec::new(),
         mode: 0o600,
+        mtime: now(),
+        is_dir: false,
     });
-    Ok(files.len() - 1)
+    Ok(id)
 }
 
-/// Lookup a writable file by name, returns index
+/// Create a directory marker. Fails if anything already has that name.
+pub fn mkdir(name: &str) -> Result<(), FsError> {
+    let name = name.trim_end_matches('/');
+    if name.is_empty() {
+        return Err(FsError::Invalid);
+    }
+    if file_exists(name) {
+        return Err(FsError::Exists);
+    }
+    WRITABLE_FILES.lock().push(WritableFile {
+        id: next_id(),
+        name: String::from(name),
+        data: Vec::new(),
+        mode: 0o755,
+        mtime: now(),
+        is_dir: true,
+    });
+    Ok(())
+}
+
+/// Lookup a writable file by name, returns its id
 pub fn lookup_writable(name: &str) -> Option<usize> {
     let files = WRITABLE_FILES.lock();
-    files.iter().position(|f| f.name == name)
+    files.iter().find(|f| f.name == name).map(|f| f.id)
 }
 
 /// Write data to a writable file at the given offset
-pub fn write_file(idx: usize, offset: usize, data: &[u8]) -> Result<usize, ()> {
+pub fn write_file(id: usize, offset: usize, data: &[u8]) -> Result<usize, FsError> {
     let mut files = WRITABLE_FILES.lock();
-    if idx >= files.len() {
-        return Err(());
-    }
-    
-    let file = &mut files[idx];
+    let file = by_id(&mut files, id).ok_or(FsError::NotFound)?;
     
     // Extend file if needed
     let end_pos = offset + data.len();
@@ -144,63 +285,159 @@ pub fn write_file(idx: usize, offset: usize, data: &[u8]) -> Result<usize, ()> {
     
     // Write data
     file.data[offset..end_pos].copy_from_slice(data);
+    file.mtime = now();
     Ok(data.len())
 }
 
+/// Most bytes a single `write` syscall puts into a file
+pub const WRITE_CHUNK: usize = 4096;
+
+/// Write the first `WRITE_CHUNK` bytes at most of `data`, as one `write`
+/// syscall does. Returns how many were written; callers that need all of
+/// `data` written call again with the rest.
+pub fn write_file_chunk(id: usize, offset: usize, data: &[u8]) -> Result<usize, FsError> {
+    let n = core::cmp::min(data.len(), WRITE_CHUNK);
+    write_file(id, offset, &data[..n])
+}
+
 /// Read data from a writable file
-pub fn read_file(idx: usize, offset: usize, buf: &mut [u8]) -> Result<usize, ()> {
-    let files = WRITABLE_FILES.lock();
-    if idx >= files.len() {
-        return Err(());
+pub fn read_file(id: usize, offset: usize, buf: &mut [u8]) -> Result<usize, FsError> {
+    read_file_with(id, offset, buf.len(), |src| {
+        buf[..src.len()].copy_from_slice(src);
+        src.len()
+    })
+}
+
+/// Hand `f` up to `len` bytes of writable file `id` starting at `offset`
+/// (fewer at the end of the file, none past it) without copying them out
+/// first. The filesystem stays locked while `f` runs.
+pub fn read_file_with<R>(id: usize, offset: usize, len: usize, f: impl FnOnce(&[u8]) -> R) -> Result<R, FsError> {
+    let mut files = WRITABLE_FILES.lock();
+    let data = &by_id(&mut files, id).ok_or(FsError::NotFound)?.data;
+    let available = data.get(offset..).unwrap_or(&[]);
+    Ok(f(&available[..core::cmp::min(len, available.len())]))
+}
+
+/// Resize a writable file to exactly `len` bytes, zero-filling when growing.
+/// Returns the new length.
+pub fn truncate_file(id: usize, len: usize) -> Result<usize, FsError> {
+    let mut files = WRITABLE_FILES.lock();
+    let file = by_id(&mut files, id).ok_or(FsError::NotFound)?;
+    file.data.resize(len, 0);
+    file.mtime = now();
+    Ok(len)
+}
+
+/// Copy `src` (writable or embedded) to a new or truncated writable file `dst`.
+/// `dst` takes `src`'s mode and is independent of it afterwards.
+/// Returns the number of bytes copied.
+pub fn copy_file(src: &str, dst: &str) -> Result<usize, FsError> {
+    if src == dst {
+        return Err(FsError::Invalid);
     }
-    
-    let file = &files[idx];
-    if offset >= file.data.len() {
-        return Ok(0);
+    let mut files = WRITABLE_FILES.lock();
+
+    let (data, mode) = match files.iter().find(|f| f.name == src) {
+        Some(f) => (f.data.clone(), f.mode),
+        None => {
+            let f = lookup(src).ok_or(FsError::NotFound)?;
+            (Vec::from(f.data), embedded_mode(f.name))
+        }
+    };
+    let len = data.len();
+
+    match files.iter_mut().find(|f| f.name == dst) {
+        Some(f) => {
+            f.data = data;
+            f.mode = mode;
+            f.mtime = now();
+        }
+        None => files.push(WritableFile {
+            id: next_id(),
+            name: String::from(dst),
+            data,
+            mode,
+            mtime: now(),
+            is_dir: false,
+        }),
     }
-    
-    let available = &file.data[offset..];
-    let to_read = core::cmp::min(buf.len(), available.len());
-    buf[..to_read].copy_from_slice(&available[..to_read]);
-    Ok(to_read)
+    Ok(len)
 }
 
 /// Get the size of a writable file
-pub fn file_size(idx: usize) -> Option<usize> {
-    let files = WRITABLE_FILES.lock();
-    files.get(idx).map(|f| f.data.len())
+pub fn file_size(id: usize) -> Option<usize> {
+    let mut files = WRITABLE_FILES.lock();
+    by_id(&mut files, id).map(|f| f.data.len())
 }
 
-/// Delete a writable file
-pub fn unlink_file(name: &str) -> Result<(), ()> {
+/// Delete a writable file. Deleting the shadow of an embedded file uncovers
+/// the embedded one again; embedded files themselves can't be deleted.
+pub fn unlink_file(name: &str) -> Result<(), FsError> {
     let mut files = WRITABLE_FILES.lock();
     if let Some(idx) = files.iter().position(|f| f.name == name) {
         files.remove(idx);
         Ok(())
     } else {
-        Err(())
+        Err(FsError::NotFound)
     }
 }
 
-/// Change file mode/permissions
-pub fn chmod_file(name: &str, mode: u32) -> Result<(), ()> {
+/// Rename `src` to `dst`, replacing any file already called `dst`.
+/// Fails if `src` doesn't exist or `dst` is a directory. `src` keeps its
+/// id, so fds open on it follow the rename; fds on the replaced `dst` go stale.
+pub fn rename_file(src: &str, dst: &str) -> Result<(), FsError> {
     let mut files = WRITABLE_FILES.lock();
-    if let Some(f) = files.iter_mut().find(|f| f.name == name) {
-        f.mode = mode;
-        Ok(())
-    } else {
-        Err(())
+    if !files.iter().any(|f| f.name == src) {
+        return Err(FsError::NotFound);
     }
+    if src == dst {
+        return Ok(());
+    }
+    if let Some(dst_idx) = files.iter().position(|f| f.name == dst) {
+        if files[dst_idx].is_dir {
+            return Err(FsError::IsDir);
+        }
+        files.remove(dst_idx);
+    }
+    let src_file = files.iter_mut().find(|f| f.name == src).ok_or(FsError::NotFound)?;
+    src_file.name = String::from(dst);
+    Ok(())
+}
+
+/// Replace the contents of `name` with `data` by writing a temporary file
+/// and renaming it over the target, so nothing ever sees a half-written
+/// file. The target keeps its mode. On failure the original is untouched.
+pub fn replace_file(name: &str, data: &[u8]) -> Result<(), FsError> {
+    let mode = match stat_file(name) {
+        Some(st) if st.is_dir => return Err(FsError::IsDir),
+        Some(st) if st.mode & 0o200 == 0 => return Err(FsError::Access),
+        Some(st) => st.mode,
+        None => 0o600,
+    };
+    let mut tmp = String::from(name);
+    tmp.push_str(".tmp~");
+    let idx = create_file(&tmp)?;
+    let done = write_file(idx, 0, data)
+        .and_then(|_| chmod_file(&tmp, mode))
+        .and_then(|_| rename_file(&tmp, name));
+    if done.is_err() {
+        let _ = unlink_file(&tmp);
+    }
+    done
+}
+
+/// Change file mode/permissions. An embedded file is copied into the
+/// writable layer first.
+pub fn chmod_file(name: &str, mode: u32) -> Result<(), FsError> {
+    let mut files = WRITABLE_FILES.lock();
+    let idx = shadow_index(&mut files, name).ok_or(FsError::NotFound)?;
+    files[idx].mode = mode;
+    Ok(())
 }
 
 /// Check if a file exists (writable or read-only)
 pub fn file_exists(name: &str) -> bool {
-    // Check writable files first
-    if lookup_writable(name).is_some() {
-        return true;
-    }
-    // Check read-only files
-    lookup(name).is_some()
+    stat_file(name).is_some()
 }
 
 /// Get file metadata
@@ -208,32 +445,85 @@ pub struct FileStat {
     pub size: usize,
     pub mode: u32,
     pub is_writable: bool,
+    /// Seconds since boot; embedded files report 0
+    pub mtime: u64,
+    pub is_dir: bool,
 }
 
 pub fn stat_file(name: &str) -> Option<FileStat> {
-    // Check writable files first
     let files = WRITABLE_FILES.lock();
-    if let Some(f) = files.iter().find(|f| f.name == name) {
-        return Some(FileStat {
-            size: f.data.len(),
-            mode: f.mode,
-            is_writable: true,
-        });
-    }
-    drop(files);
-    
-    // Check read-only files
-    if let Some(f) = lookup(name) {
-        return Some(FileStat {
-            size: f.data.len(),
-            mode: 0o444, // read-only
-            is_writable: false,
-        });
+    let st = visible_entries(&files).find(|(n, _)| *n == name).map(|(_, st)| st);
+    st
+}
+
+/// Why a filesystem operation failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    /// No such file, or a stale writable-file id
+    NotFound,
+    /// The owner permission bits don't allow it
+    Access,
+    /// The name is already taken
+    Exists,
+    /// A directory where a file is needed
+    IsDir,
+    /// An empty name, or a copy onto itself
+    Invalid,
+}
+
+/// Check whether `name` may be opened for reading, or with write intent
+/// when `write` is set, going by its owner read/write mode bits.
+pub fn check_access(name: &str, write: bool) -> Result<(), FsError> {
+    let st = stat_file(name).ok_or(FsError::NotFound)?;
+    let bit = if write { 0o200 } else { 0o400 };
+    if st.mode & bit != 0 {
+        Ok(())
+    } else {
+        Err(FsError::Access)
+    }
+}
+
+/// Resolve a command name the way the shell runs programs: `name` itself if
+/// it is a program (an executable mode bit or an `.elf` name), otherwise
+/// `name.elf`. There are no PATH directories yet, so only the root is searched.
+pub fn which(name: &str) -> Option<String> {
+    if name.is_empty() {
+        return None;
+    }
+    let is_program = |path: &str| match stat_file(path) {
+        Some(st) => !st.is_dir && (st.mode & 0o111 != 0 || path.ends_with(".elf")),
+        None => false,
+    };
+    if is_program(name) {
+        return Some(String::from(name));
+    }
+    let mut with_ext = String::from(name);
+    with_ext.push_str(".elf");
+    if is_program(&with_ext) {
+        return Some(with_ext);
     }
-    
     None
 }
 
+/// Turn `path` into a file name relative to the root, the form names are
+/// stored in. Absolute paths ignore `cwd`; others are joined onto it. `.`,
+/// empty components and a `..` at the root are dropped, so `"/"` and
+/// `"a/.."` both give `""`.
+pub fn resolve_path(cwd: &str, path: &str) -> String {
+    let base = if path.starts_with('/') { "" } else { cwd };
+    let mut parts: Vec<&str> = Vec::new();
+    for comp in base.split('/').chain(path.split('/')) {
+        match comp {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            c => parts.push(c),
+        }
+    }
+    parts.join("/")
+}
+
 /// List writable files - returns number of files and writes names to buffer
 /// Each filename is null-terminated in the buffer
 pub fn list_writable_files(buf: &mut [u8]) -> usize {
@@ -241,8 +531,8 @@ pub fn list_writable_files(buf: &mut [u8]) -> usize {
     let mut offset = 0usize;
     let mut count = 0usize;
     
-    for file in files.iter() {
-        let name_bytes = file.name.as_bytes();
+    for (name, _) in visible_entries(&files) {
+        let name_bytes = name.as_bytes();
         // +1 for null terminator
         if offset + name_bytes.len() + 1 > buf.len() {
             break; // Buffer full
@@ -262,22 +552,70 @@ pub fn list_writable_files(buf: &mut [u8]) -> usize {
     count
 }
 
-/// Initialize writable filesystem with embedded files
-/// This moves all files from the read-only RAMFS to the writable filesystem
-pub fn init_writable_fs() {
-    let mut files = WRITABLE_FILES.lock();
-    
-    // Copy all embedded files to writable filesystem
-    for file in FILES {
-        files.push(WritableFile {
-            name: String::from(file.name),
-            data: Vec::from(file.data),
-            mode: if file.name.ends_with(".elf") { 0o755 } else { 0o644 },
-        });
+/// Measure the first `count` NUL-terminated names in `buf` as filled by
+/// `list_writable_files`. Stops at the end of `buf`, so a missing terminator
+/// can't run past it, and at an empty name, which is unused zeroed space.
+/// Returns (complete names, bytes they use).
+pub fn name_list_len(buf: &[u8], count: usize) -> (usize, usize) {
+    let mut names = 0usize;
+    let mut used = 0usize;
+    for (i, &b) in buf.iter().enumerate() {
+        if names == count {
+            break;
+        }
+        if b == 0 {
+            if i == used {
+                break;
+            }
+            names += 1;
+            used = i + 1;
+        }
+    }
+    (names, used)
+}
+
+/// Pack directory entries into `buf` in the `uapi::dirent` record format,
+/// starting with the `start`-th file. Only whole records are written.
+/// Returns the number of bytes used.
+pub fn list_dirents(buf: &mut [u8], start: usize) -> usize {
+    let files = WRITABLE_FILES.lock();
+    let mut pos = 0usize;
+
+    for (name, st) in visible_entries(&files).skip(start) {
+        match uapi::dirent::encode(buf, pos, name, st.size as u64, st.mode, st.is_dir) {
+            Some(next) => pos = next,
+            None => break, // Buffer full
+        }
+    }
+
+    pos
+}
+
+/// CRC-32 of `data`: the IEEE 802.3 polynomial as used by zlib and PNG
+/// (reflected, initial value and final xor all ones).
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// CRC-32 of the file `name`, computed in place without copying it. `None`
+/// if there is no such file or it is a directory.
+pub fn checksum_file(name: &str) -> Option<u32> {
+    let files = WRITABLE_FILES.lock();
+    match files.iter().find(|f| f.name == name) {
+        Some(f) if f.is_dir => None,
+        Some(f) => Some(crc32(&f.data)),
+        None => lookup(name).map(|f| crc32(f.data)),
     }
 }
 
-/// Lookup a file by name in the writable filesystem
+/// Lookup a file by name, in the writable layer and then the embedded files
 /// Returns a copy of the file data if found
 /// 
 /// Note: This function clones the file data to avoid holding the filesystem
@@ -287,22 +625,33 @@ pub fn init_writable_fs() {
 /// 3. Program execution is not a hot path, so the overhead is acceptable
 pub fn get_file_data(name: &str) -> Option<Vec<u8>> {
     let files = WRITABLE_FILES.lock();
-    files.iter().find(|f| f.name == name).map(|f| f.data.clone())
+    match files.iter().find(|f| f.name == name) {
+        Some(f) => Some(f.data.clone()),
+        None => lookup(name).map(|f| Vec::from(f.data)),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     
-    // Helper to reset filesystem state between tests
-    fn reset_fs() {
+    /// Held by every test that touches the global file table, since the
+    /// test harness runs them in parallel
+    static FS_TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    // Helper to reset filesystem state between tests; keep the guard alive
+    // for the whole test
+    fn reset_fs() -> std::sync::MutexGuard<'static, ()> {
+        let guard = FS_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
         let mut files = WRITABLE_FILES.lock();
         files.clear();
+        NEXT_ID.store(0, Ordering::Relaxed);
+        guard
     }
 
     #[test]
     fn test_create_file() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let result = create_file("test.txt");
         assert!(result.is_ok());
@@ -316,7 +665,7 @@ mod tests {
 
     #[test]
     fn test_create_file_truncates_existing() {
-        reset_fs();
+        let _fs = reset_fs();
         
         // Create file with some data
         let idx1 = create_file("test.txt").unwrap();
@@ -333,7 +682,7 @@ mod tests {
 
     #[test]
     fn test_write_and_read_file() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let idx = create_file("test.txt").unwrap();
         let data = b"Hello, World!";
@@ -351,7 +700,7 @@ mod tests {
 
     #[test]
     fn test_write_at_offset() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let idx = create_file("test.txt").unwrap();
         
@@ -370,7 +719,7 @@ mod tests {
 
     #[test]
     fn test_read_at_offset() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let idx = create_file("test.txt").unwrap();
         write_file(idx, 0, b"Hello, World!").unwrap();
@@ -384,7 +733,7 @@ mod tests {
 
     #[test]
     fn test_read_beyond_end() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let idx = create_file("test.txt").unwrap();
         write_file(idx, 0, b"Hello").unwrap();
@@ -397,7 +746,7 @@ mod tests {
 
     #[test]
     fn test_read_partial() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let idx = create_file("test.txt").unwrap();
         write_file(idx, 0, b"Hello").unwrap();
@@ -409,9 +758,314 @@ mod tests {
         assert_eq!(&buf[..], b"Hel");
     }
 
+    #[test]
+    fn test_truncate_file_down() {
+        let _fs = reset_fs();
+
+        let idx = create_file("test.txt").unwrap();
+        write_file(idx, 0, b"Hello, World!").unwrap();
+
+        assert_eq!(truncate_file(idx, 5), Ok(5));
+        assert_eq!(file_size(idx), Some(5));
+
+        // Data after len is gone
+        let mut buf = vec![0u8; 16];
+        let read = read_file(idx, 0, &mut buf).unwrap();
+        assert_eq!(&buf[..read], b"Hello");
+    }
+
+    #[test]
+    fn test_truncate_file_up() {
+        let _fs = reset_fs();
+
+        let idx = create_file("test.txt").unwrap();
+        write_file(idx, 0, b"Hi").unwrap();
+
+        assert_eq!(truncate_file(idx, 6), Ok(6));
+
+        // New bytes are zero-filled
+        let mut buf = vec![0xFFu8; 6];
+        let read = read_file(idx, 0, &mut buf).unwrap();
+        assert_eq!(read, 6);
+        assert_eq!(&buf[..], b"Hi\0\0\0\0");
+    }
+
+    #[test]
+    fn test_truncate_file_invalid_index() {
+        let _fs = reset_fs();
+
+        assert!(truncate_file(0, 10).is_err());
+        create_file("test.txt").unwrap();
+        assert!(truncate_file(1, 10).is_err());
+    }
+
+    #[test]
+    fn test_user_files_survive_exec_boundary() {
+        let _fs = reset_fs();
+
+        let idx = create_file("mydata.txt").unwrap();
+        write_file(idx, 0, b"keep me").unwrap();
+
+        // What exit/exec does with the filesystem: fetch the next image
+        assert!(get_file_data("shell.elf").is_some());
+
+        let idx = lookup_writable("mydata.txt").unwrap();
+        let mut buf = vec![0u8; 16];
+        let read = read_file(idx, 0, &mut buf).unwrap();
+        assert_eq!(&buf[..read], b"keep me");
+
+        let mut names = vec![0u8; 4096];
+        let count = list_writable_files(&mut names);
+        assert_eq!(count, FILES.len() + 1);
+    }
+
+    #[test]
+    fn test_embedded_files_read_from_files() {
+        let _fs = reset_fs();
+
+        // Nothing is copied until something writes
+        assert!(WRITABLE_FILES.lock().is_empty());
+        assert_eq!(lookup_writable("etc/motd"), None);
+        assert_eq!(get_file_data("etc/motd").as_deref(), Some(ETC_MOTD));
+        let st = stat_file("etc/motd").unwrap();
+        assert!(!st.is_writable);
+        assert_eq!(st.size, ETC_MOTD.len());
+        assert_eq!(stat_file("shell.elf").unwrap().mode, 0o755);
+        assert!(WRITABLE_FILES.lock().is_empty());
+
+        // They are still listed
+        let mut names = vec![0u8; 4096];
+        assert_eq!(list_writable_files(&mut names), FILES.len());
+        // ...and can't be removed
+        assert_eq!(unlink_file("etc/motd"), Err(FsError::NotFound));
+    }
+
+    #[test]
+    fn test_read_file_with_borrows_the_range() {
+        let _fs = reset_fs();
+
+        let idx = create_file("big.bin").unwrap();
+        let data: Vec<u8> = (0..10240u32).map(|i| (i % 251) as u8).collect();
+        write_file(idx, 0, &data).unwrap();
+        assert_eq!(read_file_with(idx, 0, 10240, |s| s.to_vec()).unwrap(), data);
+        assert_eq!(read_file_with(idx, 10000, 4096, |s| s.len()), Ok(240));
+        assert_eq!(read_file_with(idx, 10240, 16, |s| s.len()), Ok(0));
+        assert_eq!(read_file_with(idx, 20000, 16, |s| s.len()), Ok(0));
+        assert_eq!(read_file_with(idx + 1, 0, 16, |s| s.len()), Err(FsError::NotFound));
+    }
+
+    #[test]
+    fn test_crc32_known_vectors() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"a"), 0xE8B7_BE43);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414F_A339);
+    }
+
+    #[test]
+    fn test_checksum_file_matches_contents() {
+        let _fs = reset_fs();
+
+        assert_eq!(checksum_file("etc/motd"), Some(crc32(ETC_MOTD)));
+        let idx = create_file("sum.txt").unwrap();
+        write_file(idx, 0, b"123456789").unwrap();
+        assert_eq!(checksum_file("sum.txt"), Some(0xCBF4_3926));
+        create_file("empty").unwrap();
+        assert_eq!(checksum_file("empty"), Some(0));
+
+        mkdir("dir").unwrap();
+        assert_eq!(checksum_file("dir"), None);
+        assert_eq!(checksum_file("missing"), None);
+    }
+
+    #[test]
+    fn test_iter_files_prefers_writable_shadow() {
+        let _fs = reset_fs();
+
+        // Only the embedded layer to begin with
+        let all: Vec<FileInfo> = iter_files().collect();
+        assert_eq!(all.len(), FILES.len());
+        assert!(all.iter().all(|f| !f.is_writable));
+
+        let idx = create_file("dungeon.map").unwrap();
+        write_file(idx, 0, b"shadowed").unwrap();
+        create_file("new.txt").unwrap();
+        mkdir("docs").unwrap();
+
+        let all: Vec<FileInfo> = iter_files().collect();
+        assert_eq!(all.len(), FILES.len() + 2);
+        let map: Vec<&FileInfo> = all.iter().filter(|f| f.name == "dungeon.map").collect();
+        assert_eq!(map.len(), 1);
+        assert!(map[0].is_writable);
+        assert_eq!(map[0].size, 8);
+        let motd = all.iter().find(|f| f.name == "etc/motd").unwrap();
+        assert_eq!((motd.size, motd.mode, motd.is_writable), (ETC_MOTD.len(), 0o644, false));
+        assert!(all.iter().find(|f| f.name == "docs").unwrap().is_dir);
+
+        // stat_file and file_exists see the same merged view
+        assert_eq!(stat_file("dungeon.map").unwrap().size, 8);
+        assert!(file_exists("new.txt") && file_exists("shell.elf"));
+        assert!(!file_exists("missing.txt"));
+    }
+
+    #[test]
+    fn test_writing_embedded_file_creates_shadow() {
+        let _fs = reset_fs();
+
+        let idx = create_file("etc/motd").unwrap();
+        write_file(idx, 0, b"New message\n").unwrap();
+        assert!(stat_file("etc/motd").unwrap().is_writable);
+        assert_eq!(get_file_data("etc/motd").as_deref(), Some(&b"New message\n"[..]));
+        // The embedded copy is untouched and the name is listed once
+        assert_eq!(lookup("etc/motd").unwrap().data, ETC_MOTD);
+        let mut names = vec![0u8; 4096];
+        assert_eq!(list_writable_files(&mut names), FILES.len());
+
+        // Dropping the shadow uncovers the original
+        unlink_file("etc/motd").unwrap();
+        assert_eq!(get_file_data("etc/motd").as_deref(), Some(ETC_MOTD));
+
+        // chmod copies the contents across before changing the mode
+        chmod_file("etc/motd", 0o600).unwrap();
+        let idx = lookup_writable("etc/motd").unwrap();
+        let mut buf = vec![0u8; 64];
+        let read = read_file(idx, 0, &mut buf).unwrap();
+        assert_eq!(&buf[..read], ETC_MOTD);
+        assert_eq!(stat_file("etc/motd").unwrap().mode, 0o600);
+    }
+
+    #[test]
+    fn test_copy_file() {
+        let _fs = reset_fs();
+
+        let src = create_file("a.txt").unwrap();
+        write_file(src, 0, b"copy me please").unwrap();
+
+        assert_eq!(copy_file("a.txt", "b.txt"), Ok(14));
+
+        // Byte-for-byte equal
+        let dst = lookup_writable("b.txt").unwrap();
+        let mut buf = vec![0u8; 32];
+        let read = read_file(dst, 0, &mut buf).unwrap();
+        assert_eq!(&buf[..read], b"copy me please");
+
+        // Independent afterwards
+        write_file(src, 0, b"CHANGED").unwrap();
+        truncate_file(dst, 4).unwrap();
+        let read = read_file(dst, 0, &mut buf).unwrap();
+        assert_eq!(&buf[..read], b"copy");
+        let read = read_file(src, 0, &mut buf).unwrap();
+        assert_eq!(&buf[..read], b"CHANGED please");
+    }
+
+    #[test]
+    fn test_copy_file_from_embedded() {
+        let _fs = reset_fs();
+
+        // etc/motd only exists in the read-only RAMFS here
+        let size = copy_file("etc/motd", "motd.txt").unwrap();
+        assert_eq!(size, lookup("etc/motd").unwrap().data.len());
+        let stat = stat_file("motd.txt").unwrap();
+        assert!(stat.is_writable);
+        assert_eq!(stat.size, size);
+    }
+
+    #[test]
+    fn test_copy_file_errors() {
+        let _fs = reset_fs();
+
+        assert_eq!(copy_file("missing.txt", "b.txt"), Err(FsError::NotFound));
+        create_file("a.txt").unwrap();
+        assert_eq!(copy_file("a.txt", "a.txt"), Err(FsError::Invalid));
+        assert!(lookup_writable("b.txt").is_none());
+    }
+
+    #[test]
+    fn test_list_writable_files_fills_to_buffer_edge() {
+        let _fs = reset_fs();
+        create_file("a.txt").unwrap();
+        create_file("bb.txt").unwrap();
+
+        // Exactly "a.txt\0bb.txt\0"
+        let mut buf = vec![0xffu8; 13];
+        assert_eq!(list_writable_files(&mut buf), 2);
+        assert_eq!(&buf[..], b"a.txt\0bb.txt\0");
+        assert_eq!(name_list_len(&buf, 2), (2, 13));
+    }
+
+    #[test]
+    fn test_list_writable_files_drops_truncated_name() {
+        let _fs = reset_fs();
+        create_file("a.txt").unwrap();
+        create_file("bb.txt").unwrap();
+
+        // One byte short of the second name: it is left out entirely
+        let mut buf = vec![0xffu8; 12];
+        assert_eq!(list_writable_files(&mut buf), 1);
+        assert_eq!(&buf[..6], b"a.txt\0");
+        assert!(buf[6..].iter().all(|&b| b == 0xff));
+
+        // Asking for more names than were written only counts whole ones
+        assert_eq!(name_list_len(&buf, 2), (1, 6));
+        assert_eq!(name_list_len(b"abc", 1), (0, 0));
+        assert_eq!(name_list_len(b"a\0b\0", 1), (1, 2));
+        // Zeroed space after the last name is not an entry
+        assert_eq!(name_list_len(b"a\0\0\0", 3), (1, 2));
+    }
+
+    #[test]
+    fn test_list_dirents() {
+        let _fs = reset_fs();
+
+        let idx = create_file("a.txt").unwrap();
+        write_file(idx, 0, b"12345").unwrap();
+        create_file("bb.txt").unwrap();
+
+        let mut buf = vec![0u8; 256];
+        let used = list_dirents(&mut buf, 0);
+        let (a, next) = uapi::dirent::decode(&buf[..used], 0).unwrap();
+        assert_eq!(a.name(), "a.txt");
+        assert_eq!(a.size, 5);
+        assert_eq!(a.mode, 0o600);
+        let (b, next) = uapi::dirent::decode(&buf[..used], next).unwrap();
+        assert_eq!(b.name(), "bb.txt");
+        // Embedded files follow the writable layer
+        let (e, _) = uapi::dirent::decode(&buf[..used], next).unwrap();
+        assert_eq!(e.name(), FILES[0].name);
+        assert_eq!(e.mode, 0o644);
+
+        // Resume from the second entry
+        let used = list_dirents(&mut buf, 1);
+        assert_eq!(uapi::dirent::decode(&buf[..used], 0).unwrap().0.name(), "bb.txt");
+
+        // Directories carry the dirent flag
+        mkdir("sub").unwrap();
+        let used = list_dirents(&mut buf, 2);
+        let (d, _) = uapi::dirent::decode(&buf[..used], 0).unwrap();
+        assert_eq!(d.name(), "sub");
+        assert!(d.is_dir);
+
+        // Only whole records fit
+        let mut small = vec![0u8; uapi::dirent::HEADER_LEN + 6];
+        assert_eq!(list_dirents(&mut small, 0), uapi::dirent::HEADER_LEN + 5);
+    }
+
+    #[test]
+    fn test_large_write_is_short() {
+        let _fs = reset_fs();
+        let idx = create_file("big.bin").unwrap();
+        let data: Vec<u8> = (0..5000u32).map(|i| i as u8).collect();
+
+        assert_eq!(write_file_chunk(idx, 0, &data), Ok(4096));
+        assert_eq!(file_size(idx), Some(4096));
+        assert_eq!(write_file_chunk(idx, 4096, &data[4096..]), Ok(904));
+        assert_eq!(file_size(idx), Some(5000));
+        assert_eq!(get_file_data("big.bin").unwrap(), data);
+    }
+
     #[test]
     fn test_file_size() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let idx = create_file("test.txt").unwrap();
         
@@ -429,7 +1083,7 @@ mod tests {
 
     #[test]
     fn test_unlink_file() {
-        reset_fs();
+        let _fs = reset_fs();
         
         create_file("test.txt").unwrap();
         assert!(lookup_writable("test.txt").is_some());
@@ -444,7 +1098,7 @@ mod tests {
 
     #[test]
     fn test_unlink_nonexistent() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let result = unlink_file("nonexistent.txt");
         assert!(result.is_err());
@@ -452,7 +1106,7 @@ mod tests {
 
     #[test]
     fn test_chmod_file() {
-        reset_fs();
+        let _fs = reset_fs();
         
         create_file("test.txt").unwrap();
         
@@ -467,7 +1121,7 @@ mod tests {
 
     #[test]
     fn test_chmod_nonexistent() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let result = chmod_file("nonexistent.txt", 0o755);
         assert!(result.is_err());
@@ -475,7 +1129,7 @@ mod tests {
 
     #[test]
     fn test_file_exists() {
-        reset_fs();
+        let _fs = reset_fs();
         
         assert!(!file_exists("test.txt"));
         
@@ -485,7 +1139,7 @@ mod tests {
 
     #[test]
     fn test_stat_file() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let idx = create_file("test.txt").unwrap();
         write_file(idx, 0, b"Hello").unwrap();
@@ -494,11 +1148,182 @@ mod tests {
         assert_eq!(stat.size, 5);
         assert_eq!(stat.mode, 0o600);
         assert!(stat.is_writable);
+        assert!(!stat.is_dir);
+    }
+
+    #[test]
+    fn test_stat_mkdir_reports_directory() {
+        let _fs = reset_fs();
+
+        assert_eq!(mkdir("docs/"), Ok(()));
+        let stat = stat_file("docs").unwrap();
+        assert!(stat.is_dir);
+        assert_eq!(stat.size, 0);
+        assert_eq!(stat.mode, 0o755);
+
+        // Names are unique across files and directories
+        assert_eq!(mkdir("docs"), Err(FsError::Exists));
+        create_file("notes.txt").unwrap();
+        assert_eq!(mkdir("notes.txt"), Err(FsError::Exists));
+        assert!(!stat_file("notes.txt").unwrap().is_dir);
+        // and a directory can't be truncated into a file
+        assert_eq!(create_file("docs"), Err(FsError::IsDir));
+    }
+
+    #[test]
+    fn test_read_only_mode_rejects_write_open() {
+        let _fs = reset_fs();
+
+        create_file("ro.txt").unwrap();
+        chmod_file("ro.txt", 0o400).unwrap();
+        assert_eq!(check_access("ro.txt", false), Ok(()));
+        assert_eq!(check_access("ro.txt", true), Err(FsError::Access));
+
+        chmod_file("ro.txt", 0o600).unwrap();
+        assert_eq!(check_access("ro.txt", true), Ok(()));
+
+        // Mode 0 can't even be read
+        chmod_file("ro.txt", 0).unwrap();
+        assert_eq!(check_access("ro.txt", false), Err(FsError::Access));
+
+        // Embedded files are 0o644: writing one creates a shadow
+        assert_eq!(check_access("etc/motd", false), Ok(()));
+        assert_eq!(check_access("etc/motd", true), Ok(()));
+        assert_eq!(check_access("missing", false), Err(FsError::NotFound));
+    }
+
+    #[test]
+    fn test_rename_file_replaces_target() {
+        let _fs = reset_fs();
+
+        let a = create_file("a").unwrap();
+        write_file(a, 0, b"new").unwrap();
+        let b = create_file("b").unwrap();
+        write_file(b, 0, b"old").unwrap();
+
+        assert_eq!(rename_file("a", "b"), Ok(()));
+        assert!(!file_exists("a"));
+        let idx = lookup_writable("b").unwrap();
+        let mut buf = [0u8; 8];
+        assert_eq!(read_file(idx, 0, &mut buf), Ok(3));
+        assert_eq!(&buf[..3], b"new");
+
+        assert_eq!(rename_file("missing", "c"), Err(FsError::NotFound));
+        mkdir("d").unwrap();
+        assert_eq!(rename_file("b", "d"), Err(FsError::IsDir));
+        assert!(file_exists("b"));
+    }
+
+    #[test]
+    fn test_ids_survive_removing_other_files() {
+        let _fs = reset_fs();
+
+        let a = create_file("a").unwrap();
+        let b = create_file("b").unwrap();
+        write_file(b, 0, b"bee").unwrap();
+        let c = create_file("c").unwrap();
+        write_file(c, 0, b"sea").unwrap();
+
+        // Removing an earlier entry doesn't move the ids after it
+        unlink_file("a").unwrap();
+        let mut buf = [0u8; 8];
+        assert_eq!(read_file(b, 0, &mut buf), Ok(3));
+        assert_eq!(&buf[..3], b"bee");
+        assert_eq!(file_size(a), None);
+
+        // The renamed file keeps its id; the one it replaced goes stale
+        rename_file("c", "b").unwrap();
+        assert_eq!(lookup_writable("b"), Some(c));
+        assert_eq!(read_file(c, 0, &mut buf), Ok(3));
+        assert_eq!(&buf[..3], b"sea");
+        assert_eq!(write_file(b, 0, b"x"), Err(FsError::NotFound));
+
+        // Ids aren't handed out again
+        assert!(create_file("a").unwrap() > c);
+    }
+
+    #[test]
+    fn test_replace_file_swaps_in_new_contents() {
+        let _fs = reset_fs();
+
+        let idx = create_file("cfg").unwrap();
+        write_file(idx, 0, b"a much longer original").unwrap();
+        chmod_file("cfg", 0o644).unwrap();
+
+        assert_eq!(replace_file("cfg", b"short"), Ok(()));
+        let idx = lookup_writable("cfg").unwrap();
+        let mut buf = [0u8; 32];
+        assert_eq!(read_file(idx, 0, &mut buf), Ok(5));
+        assert_eq!(&buf[..5], b"short");
+        let st = stat_file("cfg").unwrap();
+        assert_eq!(st.mode, 0o644);
+        assert!(!file_exists("cfg.tmp~"));
+
+        // A new name is created
+        assert_eq!(replace_file("fresh", b"x"), Ok(()));
+        assert_eq!(stat_file("fresh").unwrap().size, 1);
+    }
+
+    #[test]
+    fn test_failed_replace_leaves_original() {
+        let _fs = reset_fs();
+
+        let idx = create_file("locked").unwrap();
+        write_file(idx, 0, b"keep me").unwrap();
+        chmod_file("locked", 0o400).unwrap();
+        assert_eq!(replace_file("locked", b"clobbered"), Err(FsError::Access));
+
+        let idx = lookup_writable("locked").unwrap();
+        let mut buf = [0u8; 16];
+        assert_eq!(read_file(idx, 0, &mut buf), Ok(7));
+        assert_eq!(&buf[..7], b"keep me");
+
+        mkdir("dir").unwrap();
+        assert_eq!(replace_file("dir", b"data"), Err(FsError::IsDir));
+        assert!(stat_file("dir").unwrap().is_dir);
+        assert!(!file_exists("dir.tmp~"));
+    }
+
+    #[test]
+    fn test_which_finds_programs() {
+        let _fs = reset_fs();
+
+        // Embedded programs resolve with or without the extension
+        assert_eq!(which("rogue").as_deref(), Some("rogue.elf"));
+        assert_eq!(which("rogue.elf").as_deref(), Some("rogue.elf"));
+        assert_eq!(which("nope"), None);
+        assert_eq!(which(""), None);
+
+        // Data files and directories are not commands
+        let idx = create_file("notes").unwrap();
+        write_file(idx, 0, b"text").unwrap();
+        assert_eq!(which("notes"), None);
+        chmod_file("notes", 0o755).unwrap();
+        assert_eq!(which("notes").as_deref(), Some("notes"));
+        mkdir("bin.elf").unwrap();
+        assert_eq!(which("bin"), None);
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        // Relative paths join onto the working directory
+        assert_eq!(resolve_path("", "etc/motd"), "etc/motd");
+        assert_eq!(resolve_path("etc", "motd"), "etc/motd");
+        assert_eq!(resolve_path("/etc/", "motd"), "etc/motd");
+        // Absolute paths ignore it
+        assert_eq!(resolve_path("home", "/etc/motd"), "etc/motd");
+        assert_eq!(resolve_path("home", "/"), "");
+        // . and .. (which stops at the root)
+        assert_eq!(resolve_path("a/b", "."), "a/b");
+        assert_eq!(resolve_path("a/b", ".."), "a");
+        assert_eq!(resolve_path("a/b", "../../.."), "");
+        assert_eq!(resolve_path("a", "./b/../c//d/"), "a/c/d");
+        assert_eq!(resolve_path("", ""), "");
     }
 
     #[test]
     fn test_stat_nonexistent() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let stat = stat_file("nonexistent.txt");
         assert!(stat.is_none());
@@ -506,7 +1331,7 @@ mod tests {
 
     #[test]
     fn test_multiple_files() {
-        reset_fs();
+        let _fs = reset_fs();
         
         // Create multiple files
         let idx1 = create_file("file1.txt").unwrap();
@@ -533,7 +1358,7 @@ mod tests {
 
     #[test]
     fn test_lookup_writable() {
-        reset_fs();
+        let _fs = reset_fs();
         
         assert!(lookup_writable("test.txt").is_none());
         
@@ -545,7 +1370,7 @@ mod tests {
 
     #[test]
     fn test_write_file_invalid_index() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let result = write_file(999, 0, b"data");
         assert!(result.is_err());
@@ -553,7 +1378,7 @@ mod tests {
 
     #[test]
     fn test_read_file_invalid_index() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let mut buf = vec![0u8; 10];
         let result = read_file(999, 0, &mut buf);
@@ -562,7 +1387,7 @@ mod tests {
 
     #[test]
     fn test_file_size_invalid_index() {
-        reset_fs();
+        let _fs = reset_fs();
         
         let size = file_size(999);
         assert!(size.is_none());
@@ -1,4 +1,4 @@
-use linked_list_allocator::LockedHeap;
+use linked_list_allocator::{Heap, LockedHeap};
 
 #[global_allocator]
 static ALLOC: LockedHeap = LockedHeap::empty();
@@ -15,3 +15,13 @@ pub fn init() {
     assert!(size > 0, "heap size must be > 0");
     unsafe { ALLOC.lock().init(start as *mut u8, size) };
 }
+
+/// Bytes in use, bytes free and total size of `heap`.
+pub fn heap_stats(heap: &Heap) -> (usize, usize, usize) {
+    (heap.used(), heap.free(), heap.size())
+}
+
+/// Current kernel heap usage as `(used, free, total)` bytes.
+pub fn stats() -> (usize, usize, usize) {
+    heap_stats(&ALLOC.lock())
+}
@@ -5,6 +5,7 @@
 //! This module implements a VirtIO input device driver that handles keyboard events.
 //! It provides a unified input buffer that can be read by user programs via stdin.
 
+use crate::keymap::{KeyState, RepeatLimiter};
 use crate::klog;
 use core::mem::size_of;
 use spin::Mutex;
@@ -36,12 +37,23 @@ const VIRTIO_STATUS_DRIVER: u32 = 2;
 const VIRTIO_STATUS_FEATURES_OK: u32 = 8;
 const VIRTIO_STATUS_DRIVER_OK: u32 = 4;
 
+// VirtIO input config space layout (offsets from VIRTIO_MMIO_CONFIG)
+const VIRTIO_INPUT_CFG_SELECT: usize = 0;
+const VIRTIO_INPUT_CFG_SUBSEL: usize = 1;
+const VIRTIO_INPUT_CFG_SIZE: usize = 2;
+const VIRTIO_INPUT_CFG_DATA: usize = 8;
+const VIRTIO_INPUT_CFG_DATA_SIZE: usize = 128;
+
+// Config space selectors
+const VIRTIO_INPUT_CFG_ID_NAME: u8 = 0x01;
+const VIRTIO_INPUT_CFG_EV_BITS: u8 = 0x11;
+
 // Virtqueue descriptor flags
 const VIRTQ_DESC_F_WRITE: u16 = 2;
 
 // VirtIO input event types (from Linux input.h)
 const EV_SYN: u16 = 0x00;
-const EV_KEY: u16 = 0x01;
+const EV_KEY: u16 = crate::keymap::EV_KEY;
 const EV_REL: u16 = 0x02;
 const EV_ABS: u16 = 0x03;
 const EV_MSC: u16 = 0x04;
@@ -55,9 +67,7 @@ const EV_FF_STATUS: u16 = 0x17;
 const EV_MAX: u16 = 0x1f;
 const EV_CNT: u16 = EV_MAX + 1;
 
-// Modifier keys
-const KEY_LEFTSHIFT: u16 = 42;
-const KEY_RIGHTSHIFT: u16 = 54;
+// Modifier keys (Shift and Caps Lock live in keymap.rs)
 const KEY_LEFTCTRL: u16 = 29;
 const KEY_RIGHTCTRL: u16 = 97;
 const KEY_LEFTALT: u16 = 56;
@@ -152,6 +162,20 @@ impl InputBuffer {
         Some(byte)
     }
 
+    /// Pop up to `out.len()` bytes into `out` without waiting for more:
+    /// exactly `min(out.len(), self.len())`, oldest first. Returns the count.
+    pub fn read(&mut self, out: &mut [u8]) -> usize {
+        let mut n = 0;
+        while n < out.len() {
+            match self.pop() {
+                Some(b) => out[n] = b,
+                None => break,
+            }
+            n += 1;
+        }
+        n
+    }
+
     /// Check if the buffer is empty.
     #[allow(dead_code)]
     pub fn is_empty(&self) -> bool {
@@ -172,9 +196,20 @@ impl InputBuffer {
 /// Global input buffer shared between keyboard and serial
 static INPUT_BUFFER: Mutex<InputBuffer> = Mutex::new(InputBuffer::new());
 
-static SHIFT: Mutex<bool> = Mutex::new(false);
-static CAPS: Mutex<bool> = Mutex::new(false);
+static KEYS: Mutex<KeyState> = Mutex::new(KeyState::new());
+static REPEAT_LIMIT: Mutex<RepeatLimiter> = Mutex::new(RepeatLimiter::new());
 static CTRL: Mutex<bool> = Mutex::new(false);
+
+/// Whether held keys autorepeat (off by default)
+static REPEAT: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+/// Fastest autorepeat rate in characters per second
+const REPEAT_HZ: u64 = 20;
+
+/// Turn autorepeat of held keys on or off.
+pub fn set_repeat(on: bool) {
+    REPEAT.store(on, core::sync::atomic::Ordering::Relaxed);
+}
 static ALT: Mutex<bool> = Mutex::new(false);
 
 /// Push a byte to the global input buffer (used by both keyboard and serial).
@@ -189,6 +224,11 @@ pub fn pop_input() -> Option<u8> {
     buf.pop()
 }
 
+/// Pop whatever is buffered, up to `out.len()` bytes, without waiting.
+pub fn read_input(out: &mut [u8]) -> usize {
+    INPUT_BUFFER.lock().read(out)
+}
+
 /// Check if there's input available in the buffer.
 pub fn has_input() -> bool {
     let buf = INPUT_BUFFER.lock();
@@ -295,15 +335,23 @@ pub fn init() -> bool {
                 base
             );
 
-            // Check if this is a keyboard (subtype in config space)
-            // For VirtIO input, the config space contains device identification
-            let select_byte =
-                unsafe { core::ptr::read_volatile((base + VIRTIO_MMIO_CONFIG) as *const u8) };
-            klog!(
-                uapi::LogLevel::Info,
-                "[Keyboard]   Config select byte: 0x{:02x}",
-                select_byte
-            );
+            // A tablet or mouse is also an input device; only bind ones
+            // that can send letter keys
+            let mut name = [0u8; VIRTIO_INPUT_CFG_DATA_SIZE];
+            let name_len = read_input_config(base, VIRTIO_INPUT_CFG_ID_NAME, 0, &mut name);
+            let name = core::str::from_utf8(&name[..name_len]).unwrap_or("?");
+            let mut bits = [0u8; VIRTIO_INPUT_CFG_DATA_SIZE];
+            let bits_len =
+                read_input_config(base, VIRTIO_INPUT_CFG_EV_BITS, EV_KEY as u8, &mut bits);
+            if !crate::keymap::reports_keyboard_keys(&bits[..bits_len]) {
+                klog!(
+                    uapi::LogLevel::Info,
+                    "[Keyboard]   Skipping '{}': no keyboard keys",
+                    name
+                );
+                continue;
+            }
+            klog!(uapi::LogLevel::Info, "[Keyboard]   Device name: '{}'", name);
 
             // Initialize the keyboard device
             if init_device(base) {
@@ -323,6 +371,22 @@ pub fn init() -> bool {
     false
 }
 
+/// Read one entry of the VirtIO input config space into `out`, returning the
+/// number of valid bytes (0 if the device has nothing for this selector).
+fn read_input_config(mmio_base: usize, select: u8, subsel: u8, out: &mut [u8]) -> usize {
+    let cfg = mmio_base + VIRTIO_MMIO_CONFIG;
+    unsafe {
+        core::ptr::write_volatile((cfg + VIRTIO_INPUT_CFG_SELECT) as *mut u8, select);
+        core::ptr::write_volatile((cfg + VIRTIO_INPUT_CFG_SUBSEL) as *mut u8, subsel);
+        let size = core::ptr::read_volatile((cfg + VIRTIO_INPUT_CFG_SIZE) as *const u8) as usize;
+        let n = size.min(out.len()).min(VIRTIO_INPUT_CFG_DATA_SIZE);
+        for (i, b) in out[..n].iter_mut().enumerate() {
+            *b = core::ptr::read_volatile((cfg + VIRTIO_INPUT_CFG_DATA + i) as *const u8);
+        }
+        n
+    }
+}
+
 /// Initialize a VirtIO input device at the given MMIO base address.
 #[allow(static_mut_refs)]
 fn init_device(mmio_base: usize) -> bool {
@@ -509,181 +573,32 @@ fn process_event(event: &VirtioInputEvent) {
         event.value
     );
 
-    // Register shift state
-    if event.event_type == EV_KEY && (event.code == KEY_LEFTSHIFT || event.code == KEY_RIGHTSHIFT) {
-        match event.value {
-            1 => {
-                *SHIFT.lock() = true;
-            }
-            0 => {
-                *SHIFT.lock() = false;
-            }
-            _ => {}
+    // Track modifiers and convert Linux key code to ASCII
+    let now = crate::timer::ticks();
+    let mut keys = KEYS.lock();
+    let ascii = if event.event_type == EV_KEY && event.value == 2 {
+        let min_ticks = (crate::timer::tick_hz() / REPEAT_HZ).max(1);
+        if REPEAT.load(core::sync::atomic::Ordering::Relaxed) && REPEAT_LIMIT.lock().allow(now, min_ticks) {
+            keys.repeat(event.code)
+        } else {
+            None
         }
-    }
-
-    // Only process key press events (value=1 means key down)
-    if event.event_type != EV_KEY || event.value != 1 {
-        return;
-    }
-
-    // Convert Linux key code to ASCII
-    if let Some(ascii) = keycode_to_ascii(event.code) {
+    } else {
+        let ascii = keys.process(event.event_type, event.code, event.value);
+        if ascii.is_some() {
+            REPEAT_LIMIT.lock().pressed(now);
+        }
+        ascii
+    };
+    drop(keys);
+    if let Some(ascii) = ascii {
         push_input(ascii);
     }
 }
 
-/// Convert a Linux key code to ASCII character.
-/// This handles standard US keyboard layout.
-fn keycode_to_ascii(code: u16) -> Option<u8> {
-    // Linux key codes (from linux/input-event-codes.h)
-    // This is a subset covering common keys
-    match *SHIFT.lock() {
-        true => {
-            match code {
-                // Number row
-                2 => Some(b'!'),
-                3 => Some(b'"'),
-                4 => Some(b'#'),
-                5 => Some(b'$'),
-                6 => Some(b'%'),
-                7 => Some(b'&'),
-                8 => Some(b'/'),
-                9 => Some(b'('),
-                10 => Some(b')'),
-                11 => Some(b'='),
-                12 => Some(b'?'),
-                13 => Some(b'`'),
-                14 => Some(0x08), // Backspace
-
-                // Top row (QWERTY)
-                15 => Some(b'\t'), // Tab
-                16 => Some(b'Q'),
-                17 => Some(b'W'),
-                18 => Some(b'E'),
-                19 => Some(b'R'),
-                20 => Some(b'T'),
-                21 => Some(b'Y'),
-                22 => Some(b'U'),
-                23 => Some(b'I'),
-                24 => Some(b'O'),
-                25 => Some(b'P'),
-                26 => Some(b'{'),
-                27 => Some(b'}'),
-                28 => Some(b'\n'), // Enter
-
-                // Home row (ASDF)
-                30 => Some(b'A'),
-                31 => Some(b'S'),
-                32 => Some(b'D'),
-                33 => Some(b'F'),
-                34 => Some(b'G'),
-                35 => Some(b'H'),
-                36 => Some(b'J'),
-                37 => Some(b'K'),
-                38 => Some(b'L'),
-                39 => Some(b':'),
-                40 => Some(b'\''),
-                41 => Some(b'*'),
-                43 => Some(b'\\'),
-
-                // Bottom row (ZXCV)
-                44 => Some(b'Z'),
-                45 => Some(b'X'),
-                46 => Some(b'C'),
-                47 => Some(b'V'),
-                48 => Some(b'B'),
-                49 => Some(b'N'),
-                50 => Some(b'M'),
-                51 => Some(b';'),
-                52 => Some(b':'),
-                53 => Some(b'_'),
-
-                // Space
-                57 => Some(b' '),
-
-                // Arrow keys (send ANSI escape sequences would require multiple bytes)
-                // For now, skip them
-
-                // Escape
-                1 => Some(0x1b),
-
-                _ => None,
-            }
-        }
-        false => {
-            match code {
-                // Number row
-                2 => Some(b'1'),
-                3 => Some(b'2'),
-                4 => Some(b'3'),
-                5 => Some(b'4'),
-                6 => Some(b'5'),
-                7 => Some(b'6'),
-                8 => Some(b'7'),
-                9 => Some(b'8'),
-                10 => Some(b'9'),
-                11 => Some(b'0'),
-                12 => Some(b'+'),
-                13 => Some(b'\\'),
-                14 => Some(0x08), // Backspace
-
-                // Top row (QWERTY)
-                15 => Some(b'\t'), // Tab
-                16 => Some(b'q'),
-                17 => Some(b'w'),
-                18 => Some(b'e'),
-                19 => Some(b'r'),
-                20 => Some(b't'),
-                21 => Some(b'y'),
-                22 => Some(b'u'),
-                23 => Some(b'i'),
-                24 => Some(b'o'),
-                25 => Some(b'p'),
-                26 => Some(b'['),
-                27 => Some(b']'),
-                28 => Some(b'\n'), // Enter
-
-                // Home row (ASDF)
-                30 => Some(b'a'),
-                31 => Some(b's'),
-                32 => Some(b'd'),
-                33 => Some(b'f'),
-                34 => Some(b'g'),
-                35 => Some(b'h'),
-                36 => Some(b'j'),
-                37 => Some(b'k'),
-                38 => Some(b'l'),
-                39 => Some(b';'),
-                40 => Some(b'\''),
-                41 => Some(b'\''),
-                43 => Some(b'\\'),
-
-                // Bottom row (ZXCV)
-                44 => Some(b'z'),
-                45 => Some(b'x'),
-                46 => Some(b'c'),
-                47 => Some(b'v'),
-                48 => Some(b'b'),
-                49 => Some(b'n'),
-                50 => Some(b'm'),
-                51 => Some(b','),
-                52 => Some(b'.'),
-                53 => Some(b'-'),
-
-                // Space
-                57 => Some(b' '),
-
-                // Arrow keys (send ANSI escape sequences would require multiple bytes)
-                // For now, skip them
-
-                // Escape
-                1 => Some(0x1b),
-
-                _ => None,
-            }
-        }
-    }
+/// Whether Caps Lock is on (e.g. for a prompt indicator)
+pub fn caps_on() -> bool {
+    KEYS.lock().caps_on()
 }
 
 /// Check if keyboard is initialized.
@@ -0,0 +1,316 @@
+//! Linux input keycodes to ASCII, plus the modifier state that affects the
+//! translation. Kept free of driver state so kernel_lib can test it on the host.
+
+// VirtIO input event type for keys (from Linux input.h)
+pub const EV_KEY: u16 = 0x01;
+
+// Modifier and lock keys
+pub const KEY_LEFTSHIFT: u16 = 42;
+pub const KEY_RIGHTSHIFT: u16 = 54;
+pub const KEY_CAPSLOCK: u16 = 58;
+
+pub const KEY_A: u16 = 30;
+
+/// True if an `EV_KEY` capability bitmap (bit N set = device can send key
+/// code N) describes a keyboard. Mice and tablets only report `BTN_*` codes,
+/// so requiring a letter key is enough to tell them apart.
+pub fn reports_keyboard_keys(bitmap: &[u8]) -> bool {
+    let byte = (KEY_A / 8) as usize;
+    byte < bitmap.len() && bitmap[byte] & (1 << (KEY_A % 8)) != 0
+}
+
+/// Modifier and lock state carried between key events
+pub struct KeyState {
+    shift: bool,
+    caps: bool,
+}
+
+impl KeyState {
+    pub const fn new() -> Self {
+        Self { shift: false, caps: false }
+    }
+
+    /// Whether Caps Lock is currently on
+    pub fn caps_on(&self) -> bool {
+        self.caps
+    }
+
+    /// Feed one input event; returns the byte it types, if any.
+    /// `value` is 1 for key down, 0 for key up and 2 for autorepeat.
+    pub fn process(&mut self, event_type: u16, code: u16, value: u32) -> Option<u8> {
+        if event_type != EV_KEY {
+            return None;
+        }
+        match code {
+            KEY_LEFTSHIFT | KEY_RIGHTSHIFT => {
+                match value {
+                    1 => self.shift = true,
+                    0 => self.shift = false,
+                    _ => {}
+                }
+                return None;
+            }
+            KEY_CAPSLOCK => {
+                // Toggles on key down only and never types anything
+                if value == 1 {
+                    self.caps = !self.caps;
+                }
+                return None;
+            }
+            _ => {}
+        }
+
+        // Only key presses type characters
+        if value != 1 {
+            return None;
+        }
+        let b = keycode_to_ascii(code, self.shift)?;
+        // Caps Lock flips the case of letters only, so Shift+Caps gives lowercase
+        if self.caps && b.is_ascii_alphabetic() {
+            Some(b ^ 0x20)
+        } else {
+            Some(b)
+        }
+    }
+}
+
+impl KeyState {
+    /// Translate an autorepeat of a held key as if it had been pressed again.
+    /// Modifiers and Caps Lock never repeat.
+    pub fn repeat(&mut self, code: u16) -> Option<u8> {
+        match code {
+            KEY_LEFTSHIFT | KEY_RIGHTSHIFT | KEY_CAPSLOCK => None,
+            _ => self.process(EV_KEY, code, 1),
+        }
+    }
+}
+
+impl Default for KeyState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Bounds the rate of autorepeat: a repeat types only if at least
+/// `min_ticks` timer ticks have passed since the last character from a key.
+pub struct RepeatLimiter {
+    last: u64,
+}
+
+impl RepeatLimiter {
+    pub const fn new() -> Self {
+        Self { last: 0 }
+    }
+
+    /// Note that a key press typed a character at tick `now`.
+    pub fn pressed(&mut self, now: u64) {
+        self.last = now;
+    }
+
+    /// Whether an autorepeat at tick `now` may type. Counts as an emit if so.
+    pub fn allow(&mut self, now: u64, min_ticks: u64) -> bool {
+        if now.wrapping_sub(self.last) >= min_ticks {
+            self.last = now;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Default for RepeatLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Unshifted US layout indexed by Linux key code (linux/input-event-codes.h),
+/// from `KEY_RESERVED` (0) to `KEY_SPACE` (57). 0 means the key types nothing.
+const US_BASE: [u8; 58] = [
+    0, 0x1b, // reserved, Esc
+    b'1', b'2', b'3', b'4', b'5', b'6', b'7', b'8', b'9', b'0', b'-', b'=',
+    0x08, b'\t', // Backspace, Tab
+    b'q', b'w', b'e', b'r', b't', b'y', b'u', b'i', b'o', b'p', b'[', b']',
+    b'\n', 0, // Enter, left Ctrl
+    b'a', b's', b'd', b'f', b'g', b'h', b'j', b'k', b'l', b';', b'\'', b'`',
+    0, b'\\', // left Shift, backslash
+    b'z', b'x', b'c', b'v', b'b', b'n', b'm', b',', b'.', b'/',
+    0, b'*', 0, b' ', // right Shift, keypad *, left Alt, Space
+];
+
+/// `US_BASE` with Shift held.
+const US_SHIFT: [u8; 58] = [
+    0, 0x1b,
+    b'!', b'@', b'#', b'$', b'%', b'^', b'&', b'*', b'(', b')', b'_', b'+',
+    0x08, b'\t',
+    b'Q', b'W', b'E', b'R', b'T', b'Y', b'U', b'I', b'O', b'P', b'{', b'}',
+    b'\n', 0,
+    b'A', b'S', b'D', b'F', b'G', b'H', b'J', b'K', b'L', b':', b'"', b'~',
+    0, b'|',
+    b'Z', b'X', b'C', b'V', b'B', b'N', b'M', b'<', b'>', b'?',
+    0, b'*', 0, b' ',
+];
+
+/// Keypad keys outside the main table. Num Lock is assumed on, so these
+/// type the same with or without Shift.
+fn keypad_to_ascii(code: u16) -> Option<u8> {
+    match code {
+        71 => Some(b'7'),
+        72 => Some(b'8'),
+        73 => Some(b'9'),
+        74 => Some(b'-'),
+        75 => Some(b'4'),
+        76 => Some(b'5'),
+        77 => Some(b'6'),
+        78 => Some(b'+'),
+        79 => Some(b'1'),
+        80 => Some(b'2'),
+        81 => Some(b'3'),
+        82 => Some(b'0'),
+        83 => Some(b'.'),
+        96 => Some(b'\n'), // keypad Enter
+        98 => Some(b'/'),
+        117 => Some(b'='),
+        _ => None,
+    }
+}
+
+/// Convert a Linux key code to ASCII character using the US layout.
+/// Modifiers, function keys and arrows (which would need multi-byte escape
+/// sequences) return `None`.
+pub fn keycode_to_ascii(code: u16, shift: bool) -> Option<u8> {
+    let table = if shift { &US_SHIFT } else { &US_BASE };
+    match table.get(code as usize) {
+        Some(&0) => None,
+        Some(&b) => Some(b),
+        None => keypad_to_ascii(code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caps_lock_toggles_without_typing() {
+        let mut keys = KeyState::new();
+        assert!(!keys.caps_on());
+        assert_eq!(keys.process(EV_KEY, KEY_CAPSLOCK, 1), None);
+        assert!(keys.caps_on());
+        // Releasing or autorepeating Caps doesn't toggle again
+        assert_eq!(keys.process(EV_KEY, KEY_CAPSLOCK, 2), None);
+        assert_eq!(keys.process(EV_KEY, KEY_CAPSLOCK, 0), None);
+        assert!(keys.caps_on());
+
+        assert_eq!(keys.process(EV_KEY, 30, 1), Some(b'A'));
+        // Digits are not affected
+        assert_eq!(keys.process(EV_KEY, 2, 1), Some(b'1'));
+
+        assert_eq!(keys.process(EV_KEY, KEY_CAPSLOCK, 1), None);
+        assert!(!keys.caps_on());
+        assert_eq!(keys.process(EV_KEY, 30, 1), Some(b'a'));
+    }
+
+    #[test]
+    fn test_caps_and_shift_cancel_for_letters() {
+        let mut keys = KeyState::new();
+        keys.process(EV_KEY, KEY_CAPSLOCK, 1);
+        assert_eq!(keys.process(EV_KEY, KEY_LEFTSHIFT, 1), None);
+        assert_eq!(keys.process(EV_KEY, 30, 1), Some(b'a'));
+        keys.process(EV_KEY, KEY_LEFTSHIFT, 0);
+        assert_eq!(keys.process(EV_KEY, 30, 1), Some(b'A'));
+    }
+
+    #[test]
+    fn test_keyboard_bitmap_predicate() {
+        let mut bits = [0u8; 128];
+        assert!(!reports_keyboard_keys(&bits));
+        bits[(KEY_A / 8) as usize] |= 1 << (KEY_A % 8);
+        assert!(reports_keyboard_keys(&bits));
+
+        // A mouse reports BTN_LEFT/BTN_RIGHT/BTN_MIDDLE (0x110..=0x112) only
+        let mut mouse = [0u8; 128];
+        mouse[0x110 / 8] = 0b111;
+        assert!(!reports_keyboard_keys(&mouse));
+
+        // A short bitmap that stops before KEY_A
+        assert!(!reports_keyboard_keys(&[0xff; 3]));
+    }
+
+    #[test]
+    fn test_us_layout_spot_checks() {
+        let cases: [(u16, bool, u8); 24] = [
+            (2, false, b'1'),
+            (11, false, b'0'),
+            (12, false, b'-'),
+            (13, false, b'='),
+            (12, true, b'_'),
+            (13, true, b'+'),
+            (3, true, b'@'),
+            (7, true, b'^'),
+            (26, false, b'['),
+            (27, true, b'}'),
+            (39, false, b';'),
+            (40, false, b'\''),
+            (40, true, b'"'),
+            (41, false, b'`'),
+            (41, true, b'~'),
+            (43, false, b'\\'),
+            (43, true, b'|'),
+            (51, false, b','),
+            (52, true, b'>'),
+            (53, false, b'/'),
+            (53, true, b'?'),
+            (55, false, b'*'),
+            (79, false, b'1'),
+            (96, true, b'\n'),
+        ];
+        for (code, shift, want) in cases {
+            assert_eq!(keycode_to_ascii(code, shift), Some(want), "code {} shift {}", code, shift);
+        }
+    }
+
+    #[test]
+    fn test_modifiers_and_unknown_codes_type_nothing() {
+        for code in [0, 29, KEY_LEFTSHIFT, KEY_RIGHTSHIFT, 56, KEY_CAPSLOCK, 59, 103, 999] {
+            assert_eq!(keycode_to_ascii(code, false), None, "code {}", code);
+            assert_eq!(keycode_to_ascii(code, true), None, "code {}", code);
+        }
+    }
+
+    #[test]
+    fn test_repeat_limiter_spaces_out_repeats() {
+        let mut rl = RepeatLimiter::new();
+        rl.pressed(100);
+        // Too soon after the press
+        assert!(!rl.allow(101, 3));
+        assert!(!rl.allow(102, 3));
+        assert!(rl.allow(103, 3));
+        // The emit restarts the interval
+        assert!(!rl.allow(104, 3));
+        assert!(!rl.allow(105, 3));
+        assert!(rl.allow(110, 3));
+        // A new press resets it too
+        rl.pressed(111);
+        assert!(!rl.allow(112, 3));
+    }
+
+    #[test]
+    fn test_repeat_types_but_modifiers_dont() {
+        let mut keys = KeyState::new();
+        assert_eq!(keys.repeat(30), Some(b'a'));
+        assert_eq!(keys.repeat(KEY_CAPSLOCK), None);
+        assert!(!keys.caps_on());
+        keys.process(EV_KEY, KEY_LEFTSHIFT, 1);
+        assert_eq!(keys.repeat(KEY_LEFTSHIFT), None);
+        assert_eq!(keys.repeat(30), Some(b'A'));
+    }
+
+    #[test]
+    fn test_only_key_down_types() {
+        let mut keys = KeyState::new();
+        assert_eq!(keys.process(EV_KEY, 30, 0), None);
+        assert_eq!(keys.process(0x02, 30, 1), None); // EV_REL
+        assert_eq!(keys.process(EV_KEY, 30, 1), Some(b'a'));
+    }
+}
@@ -8,6 +8,24 @@
 #[cfg(not(test))]
 extern crate alloc;
 
+/// Keycode translation, shared as-is with the kernel's keyboard driver
+pub mod keymap;
+
+/// Saved trap registers, shared as-is with trap.rs
+pub mod trapframe;
+
+/// Parent/child bookkeeping for spawn, shared as-is with trap.rs
+pub mod proc;
+
+/// Syscall table lookups, shared as-is with trap.rs
+pub mod syscall;
+
+/// Canonical-mode line discipline, shared as-is with the kernel's stdin
+pub mod tty;
+
+/// Embedded and writable files, shared as-is with the kernel's RAMFS
+pub mod fs;
+
 /// SV39 paging helper functions
 pub mod sv39 {
     /// Calculate PPN (Physical Page Number) from physical address
@@ -40,6 +58,21 @@ pub mod sv39 {
     pub const USER_PA_POOL_START: usize = 0x8800_0000 - 0x0100_000 - 0x10000;
     pub const USER_PA_POOL_END: usize = 0x8800_0000 - 0x10000;
 
+    /// A named physical range `[start, end)` used by the layout checks.
+    pub type Region = (&'static str, usize, usize);
+
+    /// First pair of regions that share at least one byte, if any.
+    pub fn find_overlap(regions: &[Region]) -> Option<(Region, Region)> {
+        for (i, &a) in regions.iter().enumerate() {
+            for &b in &regions[i + 1..] {
+                if a.1 < b.2 && b.1 < a.2 {
+                    return Some((a, b));
+                }
+            }
+        }
+        None
+    }
+
     pub const USER_CODE_VA: usize = USER_VA_BASE + 0x0000_0000;
     pub const USER_STACK_VA: usize = USER_VA_BASE + 0x0000_1000;
 
@@ -48,10 +81,103 @@ pub mod sv39 {
     pub const SIZE_2M: usize = 1 << 21;
     pub const SIZE_1G: usize = 1 << 30;
 
+    /// User VA window for the framebuffer mapping
+    pub const FB_VA_BASE: usize = 0x3000_0000;
+    pub const FB_VA_LIMIT: usize = 0x4000_0000;
+
+    /// `(first_pa, pages, user_va)` for mapping a framebuffer, as in sv39.rs
+    pub fn fb_map_plan(fb_pa: usize, fb_size: usize) -> Option<(usize, usize, usize)> {
+        if fb_size == 0 {
+            return None;
+        }
+        let offset = fb_pa & (SIZE_4K - 1);
+        let pages = offset.checked_add(fb_size)?.div_ceil(SIZE_4K);
+        let end = FB_VA_BASE.checked_add(pages.checked_mul(SIZE_4K)?)?;
+        if end > FB_VA_LIMIT {
+            return None;
+        }
+        Some((fb_pa - offset, pages, FB_VA_BASE + offset))
+    }
+
+    pub const MMAP_VA_BASE: usize = 0x2000_0000;
+    pub const MMAP_VA_LIMIT: usize = FB_VA_BASE;
+
+    /// `(va, pages)` for an anonymous mapping, as in sv39.rs
+    pub fn mmap_plan(next: usize, len: usize) -> Option<(usize, usize)> {
+        if len == 0 || next < MMAP_VA_BASE || !next.is_multiple_of(SIZE_4K) {
+            return None;
+        }
+        let pages = len.div_ceil(SIZE_4K);
+        let end = next.checked_add(pages.checked_mul(SIZE_4K)?)?;
+        if end > MMAP_VA_LIMIT {
+            return None;
+        }
+        Some((next, pages))
+    }
+
+    /// True if heap growth would reach the stack or a reserved window, as in sv39.rs
+    pub fn brk_collides(cur_brk: usize, new_brk: usize, stack: (usize, usize)) -> bool {
+        let start = cur_brk.next_multiple_of(SIZE_4K);
+        let Some(end) = new_brk.checked_next_multiple_of(SIZE_4K) else {
+            return true;
+        };
+        let (bottom, top) = stack;
+        let reserved = [
+            (bottom.saturating_sub(SIZE_4K), top),
+            (MMAP_VA_BASE, MMAP_VA_LIMIT),
+            (FB_VA_BASE, FB_VA_LIMIT),
+        ];
+        start < end && reserved.iter().any(|&(s, e)| start < e && s < end)
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
 
+        #[test]
+        fn test_mmap_plan_rounds_up_to_pages() {
+            assert_eq!(mmap_plan(MMAP_VA_BASE, 1), Some((MMAP_VA_BASE, 1)));
+            assert_eq!(mmap_plan(MMAP_VA_BASE, SIZE_4K), Some((MMAP_VA_BASE, 1)));
+            assert_eq!(mmap_plan(MMAP_VA_BASE + SIZE_4K, SIZE_4K + 1), Some((MMAP_VA_BASE + SIZE_4K, 2)));
+        }
+
+        #[test]
+        fn test_mmap_plan_stays_in_window() {
+            assert_eq!(mmap_plan(MMAP_VA_BASE, 0), None);
+            // The window ends where the framebuffer window starts
+            let window = MMAP_VA_LIMIT - MMAP_VA_BASE;
+            assert!(mmap_plan(MMAP_VA_BASE, window).is_some());
+            assert_eq!(mmap_plan(MMAP_VA_BASE, window + 1), None);
+            assert_eq!(mmap_plan(MMAP_VA_LIMIT - SIZE_4K, 2 * SIZE_4K), None);
+            assert_eq!(mmap_plan(MMAP_VA_BASE, usize::MAX), None);
+        }
+
+        #[test]
+        fn test_brk_collides_with_stack() {
+            let stack = (0x0fff_c000, 0x1000_0000);
+            let guard = stack.0 - SIZE_4K;
+            // Growth ending right at the guard page is fine, one byte more is not
+            assert!(!brk_collides(0x12000, guard, stack));
+            assert!(brk_collides(0x12000, guard + 1, stack));
+            // Overlapping or jumping past the whole stack
+            assert!(brk_collides(0x12000, stack.0 + 0x100, stack));
+            assert!(brk_collides(0x12000, stack.1 + SIZE_4K, stack));
+            // A break already inside the last heap page adds no pages
+            assert!(!brk_collides(guard - 0x800, guard - 0x10, stack));
+            assert!(!brk_collides(0x12000, 0x12000, stack));
+        }
+
+        #[test]
+        fn test_brk_collides_with_windows() {
+            // No stack known yet: only the fixed windows count
+            let none = (0, 0);
+            assert!(!brk_collides(0x12000, MMAP_VA_BASE, none));
+            assert!(brk_collides(0x12000, MMAP_VA_BASE + 1, none));
+            assert!(brk_collides(MMAP_VA_LIMIT, FB_VA_BASE + SIZE_4K, none));
+            assert!(!brk_collides(FB_VA_LIMIT, FB_VA_LIMIT + SIZE_4K, none));
+            assert!(brk_collides(0x12000, usize::MAX, none));
+        }
+
         #[test]
         fn test_ppn_calculation() {
             assert_eq!(ppn(0x0000), 0);
@@ -126,6 +252,70 @@ pub mod sv39 {
             assert!(USER_PA_POOL_END <= DRAM_BASE + DRAM_SIZE);
         }
 
+        #[test]
+        fn test_fb_map_plan_1080p() {
+            // 1920x1080x4 = 8_294_400 bytes, exactly 2025 pages
+            let (pa, pages, va) = fb_map_plan(0x8100_0000, 1920 * 1080 * 4).unwrap();
+            assert_eq!(pa, 0x8100_0000);
+            assert_eq!(pages, 2025);
+            assert_eq!(va, FB_VA_BASE);
+            // The mapping ends well inside the window, below the user stack region
+            assert_eq!(FB_VA_BASE + pages * SIZE_4K, 0x307e_9000);
+            assert!(FB_VA_BASE + pages * SIZE_4K <= USER_VA_BASE);
+        }
+
+        #[test]
+        fn test_fb_map_plan_unaligned_base() {
+            // A buffer starting mid-page needs an extra page and keeps its offset
+            let (pa, pages, va) = fb_map_plan(0x8100_0010, 2 * SIZE_4K).unwrap();
+            assert_eq!(pa, 0x8100_0000);
+            assert_eq!(pages, 3);
+            assert_eq!(va, FB_VA_BASE + 0x10);
+            assert_eq!(fb_map_plan(0x8100_0000, 1).map(|p| p.1), Some(1));
+        }
+
+        #[test]
+        fn test_fb_map_plan_rejects_bad_sizes() {
+            assert_eq!(fb_map_plan(0x8100_0000, 0), None);
+            // Exactly fills the window, then one byte too many
+            let window = FB_VA_LIMIT - FB_VA_BASE;
+            assert!(fb_map_plan(0x8100_0000, window).is_some());
+            assert_eq!(fb_map_plan(0x8100_0000, window + 1), None);
+            assert_eq!(fb_map_plan(0x8100_0000, usize::MAX), None);
+        }
+
+        #[test]
+        fn test_overlap_disjoint_regions() {
+            let heap = ("heap", 0x8030_0000, USER_PA_POOL_START);
+            let pool = ("pool", USER_PA_POOL_START, USER_PA_POOL_END);
+            let stack = ("stack", USER_PA_POOL_END, DRAM_BASE + DRAM_SIZE);
+            // Touching ends are fine, the ranges are half-open
+            assert!(find_overlap(&[heap, pool, stack]).is_none());
+            assert!(find_overlap(&[]).is_none());
+            assert!(find_overlap(&[heap]).is_none());
+        }
+
+        #[test]
+        fn test_overlap_heap_into_pool() {
+            // The old layout: heap ran up to the stack headroom
+            let heap = ("heap", 0x8030_0000, 0x87ff_0000);
+            let pool = ("pool", USER_PA_POOL_START, USER_PA_POOL_END);
+            let stack = ("stack", 0x87ff_0000, 0x8800_0000);
+            assert_eq!(find_overlap(&[heap, pool, stack]), Some((heap, pool)));
+        }
+
+        #[test]
+        fn test_overlap_reports_first_pair_in_order() {
+            let a = ("a", 0x1000, 0x2000);
+            let b = ("b", 0x3000, 0x4000);
+            let c = ("c", 0x3800, 0x5000);
+            let inside = ("inside", 0x1800, 0x1900);
+            assert_eq!(find_overlap(&[a, b, c]), Some((b, c)));
+            assert_eq!(find_overlap(&[a, b, inside]), Some((a, inside)));
+            // A one-byte overlap still counts
+            assert!(find_overlap(&[("x", 0, 0x1001), ("y", 0x1000, 0x2000)]).is_some());
+        }
+
         #[test]
         fn test_user_address_space() {
             assert_eq!(USER_CODE_VA, USER_VA_BASE);
@@ -149,10 +339,489 @@ pub mod elf {
         f
     }
 
+    /// Compute an `R_RISCV_RELATIVE` fixup for an image loaded at `bias`.
+    /// Returns `(target_va, value)`: `bias + addend` is stored at `bias + r_offset`.
+    #[inline]
+    pub fn relative_reloc(bias: usize, r_offset: u64, r_addend: i64) -> (usize, usize) {
+        (
+            bias.wrapping_add(r_offset as usize),
+            bias.wrapping_add_signed(r_addend as isize),
+        )
+    }
+
+    /// Lowest VA a user segment may occupy.
+    pub const USER_IMAGE_START: usize = 0x1000;
+    /// End of the user image window (MMIO and the kernel sit above).
+    pub const USER_IMAGE_END: usize = 0x1000_0000;
+
+    /// True if the half-open ranges `[a_start, a_end)` and `[b_start, b_end)` share a byte.
+    #[inline]
+    pub fn ranges_overlap(a_start: usize, a_end: usize, b_start: usize, b_end: usize) -> bool {
+        a_start < b_end && b_start < a_end
+    }
+
+    /// True if `[vaddr, vaddr + memsz)` lies inside the user image window.
+    #[inline]
+    pub fn segment_in_user_range(vaddr: usize, memsz: usize) -> bool {
+        match vaddr.checked_add(memsz) {
+            Some(end) => vaddr >= USER_IMAGE_START && end <= USER_IMAGE_END,
+            None => false,
+        }
+    }
+
+    /// Most program headers accepted, checked before goblin reads them.
+    pub const MAX_PHNUM: usize = 32;
+    /// Most memory the segments of one image may map, in whole pages. Half the
+    /// 1 MiB user page pool, so the stack and heap still get some.
+    pub const MAX_IMAGE_BYTES: usize = 512 * 1024;
+
+    /// Top of the user stack: the end of the image window.
+    pub const USER_STACK_TOP: usize = USER_IMAGE_END;
+    pub const DEFAULT_USER_STACK_BYTES: usize = 16 * 1024;
+    pub const MAX_USER_STACK_BYTES: usize = 256 * 1024;
+
+    /// The stack `[bottom, top)` for `bytes` below `top`, rounded up to
+    /// whole pages, if it and its guard page fit in the image window.
+    pub fn stack_region(top: usize, bytes: usize) -> Option<(usize, usize)> {
+        if bytes == 0 || bytes > MAX_USER_STACK_BYTES || !top.is_multiple_of(4096) || top > USER_IMAGE_END {
+            return None;
+        }
+        let bottom = top.checked_sub(bytes.next_multiple_of(4096))?;
+        (bottom.checked_sub(4096)? >= USER_IMAGE_START).then_some((bottom, top))
+    }
+
+    /// True if `[vaddr, vaddr + memsz)` touches the stack or its guard page.
+    #[inline]
+    pub fn stack_overlaps_segment(stack: (usize, usize), vaddr: usize, memsz: usize) -> bool {
+        let (bottom, top) = stack;
+        ranges_overlap(vaddr, vaddr.saturating_add(memsz), bottom.saturating_sub(4096), top)
+    }
+
+    /// The page-aligned VA range `[start, end)` to map for `p_memsz` bytes at
+    /// `p_vaddr`. A pure-BSS segment (no file bytes) needs the same pages as
+    /// any other; `page_copy_plan` then zeroes all of them.
+    #[inline]
+    pub fn segment_pages(p_vaddr: usize, p_memsz: usize, page: usize) -> (usize, usize) {
+        (p_vaddr & !(page - 1), (p_vaddr + p_memsz).next_multiple_of(page))
+    }
+
+    /// `total` plus the pages mapped for `p_memsz` bytes at `p_vaddr`, or
+    /// `ImageTooLarge` once that passes `MAX_IMAGE_BYTES`.
+    #[inline]
+    pub fn add_image_bytes(total: usize, p_vaddr: usize, p_memsz: usize, page: usize) -> Result<usize, ElfLoadError> {
+        let (start, end) = segment_pages(p_vaddr, p_memsz, page);
+        total
+            .checked_add(end - start)
+            .filter(|&t| t <= MAX_IMAGE_BYTES)
+            .ok_or(ElfLoadError::ImageTooLarge)
+    }
+
+    /// True if `entry` lies in one of `segments`, given as `(start, end,
+    /// executable)`, that is executable.
+    #[inline]
+    pub fn entry_in_exec_segment(entry: usize, segments: &[(usize, usize, bool)]) -> bool {
+        segments.iter().any(|&(start, end, exec)| exec && (start..end).contains(&entry))
+    }
+
+    /// True if the word a relocation writes at `va` lies wholly inside one of
+    /// the image's `segments` (`(start, end, executable)`). Anything else could
+    /// be kernel memory, which is mapped in the same page table.
+    #[inline]
+    pub fn reloc_in_segments(va: usize, segments: &[(usize, usize, bool)]) -> bool {
+        va.checked_add(core::mem::size_of::<usize>())
+            .is_some_and(|end| segments.iter().any(|&(start, seg_end, _)| start <= va && end <= seg_end))
+    }
+
+    /// What to do with the VA page at `cur_va` while loading a segment of
+    /// `p_filesz` file bytes and `p_memsz` memory bytes at `p_vaddr`: copy
+    /// `copy_len` file bytes to `copy_off` in the page, then zero `zero_len`
+    /// bytes (the BSS) at `zero_off`. Offsets are from the start of the page,
+    /// and the file bytes to copy start at `cur_va + copy_off - p_vaddr`.
+    /// Bytes of the page outside the segment are left alone, as are file bytes
+    /// past `p_memsz`.
+    #[inline]
+    pub fn page_copy_plan(p_vaddr: usize, p_filesz: usize, p_memsz: usize, cur_va: usize, page: usize) -> (usize, usize, usize, usize) {
+        let page_end = cur_va + page;
+        // The part of the segment inside this page, and how much of it is file
+        let start = p_vaddr.clamp(cur_va, page_end);
+        let mem_end = (p_vaddr + p_memsz).clamp(start, page_end);
+        let file_end = (p_vaddr + p_filesz).clamp(start, mem_end);
+        let copy_off = start - cur_va;
+        let copy_len = file_end - start;
+        (copy_off, copy_len, copy_off + copy_len, mem_end - file_end)
+    }
+
+    /// Loader errors, as in elf.rs
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum ElfLoadError {
+        Short,
+        BadMagic,
+        Not64LE,
+        NotRiscv,
+        PhOutOfBounds,
+        SatpNotSet,
+        SegmentOverflow,
+        BadReloc,
+        BadVaddr,
+        SegmentOverlap,
+        StackOverflow,
+        OutOfUserPages,
+        /// More than `MAX_PHNUM` program headers
+        TooManyHeaders,
+        /// The segments together would map more than `MAX_IMAGE_BYTES`
+        ImageTooLarge,
+        /// `e_entry` is not inside an executable segment
+        BadEntry,
+    }
+
+    /// Run `load`; if it fails, run `cleanup` so no half-built image stays mapped.
+    pub fn load_or_cleanup<T, L, C>(load: L, cleanup: C) -> Result<T, ElfLoadError>
+    where
+        L: FnOnce() -> Result<T, ElfLoadError>,
+        C: FnOnce(),
+    {
+        let r = load();
+        if r.is_err() {
+            cleanup();
+        }
+        r
+    }
+
+    pub use uapi::MAX_ARGS;
+
+    /// Where each argv/envp string and pointer vector lands on the user stack.
+    pub struct StackPlan {
+        pub arg_ptrs: heapless::Vec<usize, MAX_ARGS>,
+        pub env_ptrs: heapless::Vec<usize, MAX_ARGS>,
+        pub envp_va: usize,
+        pub argv_va: usize,
+        pub sp: usize,
+    }
+
+    #[inline]
+    fn stack_down(sp: usize, n: usize, bottom: usize) -> Result<usize, ElfLoadError> {
+        match sp.checked_sub(n) {
+            Some(v) if v >= bottom => Ok(v),
+            _ => Err(ElfLoadError::StackOverflow),
+        }
+    }
+
+    /// Lay out argv/envp below `user_stack_top_va` without touching memory.
+    ///
+    /// Strings go at the top, then the envp and argv vectors, then argc.
+    /// Fails with `SegmentOverflow` past `MAX_ARGS` entries and with
+    /// `StackOverflow` if the layout does not fit in `user_stack_bytes`.
+    pub fn plan_user_stack(
+        user_stack_top_va: usize,
+        user_stack_bytes: usize,
+        argv: &[&str],
+        envp: &[&str],
+    ) -> Result<StackPlan, ElfLoadError> {
+        if argv.len() > MAX_ARGS || envp.len() > MAX_ARGS {
+            return Err(ElfLoadError::SegmentOverflow);
+        }
+        let bottom = user_stack_top_va.saturating_sub(user_stack_bytes);
+        let word = core::mem::size_of::<usize>();
+
+        let mut sp = stack_down(user_stack_top_va, 16, bottom)?;
+        let mut env_ptrs = heapless::Vec::new();
+        for &s in envp {
+            sp = stack_down(sp, s.len() + 1, bottom)?;
+            env_ptrs.push(sp).map_err(|_| ElfLoadError::SegmentOverflow)?;
+        }
+        let mut arg_ptrs = heapless::Vec::new();
+        for &s in argv {
+            sp = stack_down(sp, s.len() + 1, bottom)?;
+            arg_ptrs.push(sp).map_err(|_| ElfLoadError::SegmentOverflow)?;
+        }
+        sp &= !15;
+        sp = stack_down(sp, (env_ptrs.len() + 1) * word, bottom)?;
+        let envp_va = sp;
+        sp = stack_down(sp, (arg_ptrs.len() + 1) * word, bottom)?;
+        let argv_va = sp;
+        sp = stack_down(sp, word, bottom)? & !15;
+        if sp < bottom {
+            return Err(ElfLoadError::StackOverflow);
+        }
+        Ok(StackPlan { arg_ptrs, env_ptrs, envp_va, argv_va, sp })
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
 
+        #[test]
+        fn test_out_of_user_pages_is_distinct() {
+            use ElfLoadError::*;
+            let others = [
+                Short, BadMagic, Not64LE, NotRiscv, PhOutOfBounds, SatpNotSet,
+                SegmentOverflow, BadReloc, BadVaddr, SegmentOverlap, StackOverflow,
+                TooManyHeaders, ImageTooLarge, BadEntry,
+            ];
+            assert!(others.iter().all(|&e| e != OutOfUserPages));
+        }
+
+        /// Pool that runs dry after `left` pages
+        fn alloc_from(left: &mut usize, next_pa: &mut usize) -> Option<usize> {
+            *left = left.checked_sub(1)?;
+            *next_pa += 0x1000;
+            Some(*next_pa)
+        }
+
+        #[test]
+        fn test_failed_load_unmaps_partial_image() {
+            let mapped: core::cell::RefCell<Vec<(usize, usize)>> = Default::default();
+            let (mut left, mut next_pa) = (3, 0x8000_0000);
+            let r: Result<(), ElfLoadError> = load_or_cleanup(
+                || {
+                    // Five pages wanted, the fourth allocation fails
+                    for i in 0..5 {
+                        let pa = alloc_from(&mut left, &mut next_pa).ok_or(ElfLoadError::OutOfUserPages)?;
+                        mapped.borrow_mut().push((0x10000 + i * 0x1000, pa));
+                    }
+                    Ok(())
+                },
+                || mapped.borrow_mut().clear(),
+            );
+            assert_eq!(r, Err(ElfLoadError::OutOfUserPages));
+            assert!(mapped.borrow().is_empty());
+        }
+
+        #[test]
+        fn test_successful_load_keeps_mappings() {
+            let mut cleaned = false;
+            let r = load_or_cleanup(|| Ok(7), || cleaned = true);
+            assert_eq!(r, Ok(7));
+            assert!(!cleaned);
+        }
+
+        #[test]
+        fn test_plan_user_stack_40_args() {
+            let args = ["arg"; 40];
+            let plan = plan_user_stack(0x4000_8000, 16 * 1024, &args, &["PATH=/"]).unwrap();
+            assert_eq!(plan.arg_ptrs.len(), 40);
+            assert_eq!(plan.env_ptrs.len(), 1);
+            assert_eq!(plan.envp_va - plan.argv_va, 41 * 8);
+            assert!(plan.sp >= 0x4000_8000 - 16 * 1024);
+            assert_eq!(plan.sp % 16, 0);
+            // Strings don't overlap each other or the vectors
+            for w in plan.arg_ptrs.windows(2) {
+                assert_eq!(w[0] - w[1], 4);
+            }
+            assert!(plan.envp_va + 2 * 8 <= *plan.arg_ptrs.last().unwrap());
+        }
+
+        #[test]
+        fn test_plan_user_stack_limits() {
+            let args = ["x"; MAX_ARGS + 1];
+            assert!(matches!(
+                plan_user_stack(0x4000_8000, 16 * 1024, &args, &[]),
+                Err(ElfLoadError::SegmentOverflow)
+            ));
+            assert!(matches!(
+                plan_user_stack(0x4000_8000, 16 * 1024, &[], &args),
+                Err(ElfLoadError::SegmentOverflow)
+            ));
+            // A stack too small for the strings fails cleanly
+            let long = ["0123456789abcdef0123456789abcdef"; 8];
+            assert!(matches!(
+                plan_user_stack(0x4000_8000, 128, &long, &[]),
+                Err(ElfLoadError::StackOverflow)
+            ));
+            // So does one too small for even the guard
+            assert!(matches!(plan_user_stack(0x4000_8000, 8, &[], &[]), Err(ElfLoadError::StackOverflow)));
+        }
+
+        #[test]
+        fn test_ranges_overlap_adjacent() {
+            assert!(!ranges_overlap(0x10000, 0x11000, 0x11000, 0x12000));
+            assert!(!ranges_overlap(0x11000, 0x12000, 0x10000, 0x11000));
+        }
+
+        #[test]
+        fn test_ranges_overlap_disjoint() {
+            assert!(!ranges_overlap(0x10000, 0x10100, 0x20000, 0x20100));
+        }
+
+        #[test]
+        fn test_ranges_overlap_overlapping() {
+            assert!(ranges_overlap(0x10000, 0x11001, 0x11000, 0x12000));
+            assert!(ranges_overlap(0x10000, 0x20000, 0x12000, 0x13000));
+            assert!(ranges_overlap(0x12000, 0x13000, 0x10000, 0x20000));
+        }
+
+        #[test]
+        fn test_segment_in_user_range() {
+            assert!(segment_in_user_range(0x10000, 0x1000));
+            assert!(!segment_in_user_range(0, 0x1000));
+            assert!(!segment_in_user_range(0x8020_0000, 0x1000));
+            assert!(!segment_in_user_range(USER_IMAGE_END - 0x10, 0x20));
+            assert!(!segment_in_user_range(usize::MAX - 0x10, 0x20));
+        }
+
+        /// Apply `page_copy_plan` to every page of a segment loaded from
+        /// `file`, in 16-byte pages, starting from a page of 0xff bytes
+        fn load_pages(p_vaddr: usize, file: &[u8], p_memsz: usize) -> Vec<u8> {
+            const PAGE: usize = 16;
+            let (va0, vaend) = segment_pages(p_vaddr, p_memsz, PAGE);
+            let mut mem = vec![0xff; vaend - va0];
+            for cur_va in (va0..vaend).step_by(PAGE) {
+                let (copy_off, copy_len, zero_off, zero_len) =
+                    page_copy_plan(p_vaddr, file.len(), p_memsz, cur_va, PAGE);
+                assert!(copy_off + copy_len <= PAGE && zero_off + zero_len <= PAGE);
+                let page = &mut mem[cur_va - va0..][..PAGE];
+                if copy_len > 0 {
+                    let src = cur_va + copy_off - p_vaddr;
+                    page[copy_off..][..copy_len].copy_from_slice(&file[src..][..copy_len]);
+                }
+                page[zero_off..][..zero_len].fill(0);
+            }
+            mem
+        }
+
+        #[test]
+        fn test_page_copy_plan_bss_tail() {
+            // Aligned segment: 6 file bytes then 4 of BSS, rest of the page untouched
+            assert_eq!(page_copy_plan(0x1000, 6, 10, 0x1000, 4096), (0, 6, 6, 4));
+            let mem = load_pages(0x1000, b"abcdef", 10);
+            assert_eq!(mem, b"abcdef\0\0\0\0\xff\xff\xff\xff\xff\xff");
+            // All BSS
+            assert_eq!(page_copy_plan(0x1000, 0, 0x20, 0x1000, 4096), (0, 0, 0, 0x20));
+        }
+
+        #[test]
+        fn test_image_bytes_cap() {
+            // Whole pages are counted, even for a few bytes
+            assert_eq!(add_image_bytes(0, 0x10010, 0x20, 4096), Ok(4096));
+            assert_eq!(add_image_bytes(4096, 0x10ff0, 0x20, 4096), Ok(4096 + 8192));
+            // Segments adding up to exactly the cap are fine, one more page is not
+            let mut total = 0;
+            for i in 0..MAX_IMAGE_BYTES / 0x10000 {
+                total = add_image_bytes(total, 0x10000 + i * 0x10000, 0x10000, 4096).unwrap();
+            }
+            assert_eq!(total, MAX_IMAGE_BYTES);
+            assert_eq!(add_image_bytes(total, 0x0100_0000, 1, 4096), Err(ElfLoadError::ImageTooLarge));
+            // One segment claiming far too much, and a total that would overflow
+            assert_eq!(add_image_bytes(0, 0x10000, 0x0800_0000, 4096), Err(ElfLoadError::ImageTooLarge));
+            assert_eq!(add_image_bytes(usize::MAX, 0x10000, 1, 4096), Err(ElfLoadError::ImageTooLarge));
+        }
+
+        #[test]
+        fn test_reloc_must_land_in_a_segment() {
+            let segments = [(0x10000, 0x14000, false), (0x15000, 0x16008, true)];
+            assert!(reloc_in_segments(0x10000, &segments));
+            assert!(reloc_in_segments(0x13ff8, &segments));
+            assert!(reloc_in_segments(0x16000, &segments));
+            // Straddling the end, in the gap, or in kernel memory
+            assert!(!reloc_in_segments(0x13ffc, &segments));
+            assert!(!reloc_in_segments(0x14800, &segments));
+            assert!(!reloc_in_segments(0x8020_0000, &segments));
+            assert!(!reloc_in_segments(usize::MAX - 3, &segments));
+            assert!(!reloc_in_segments(0x10000, &[]));
+        }
+
+        #[test]
+        fn test_entry_must_be_in_an_executable_segment() {
+            // rodata, text and bss, as the user programs are laid out
+            let segments = [(0x10000, 0x143db, false), (0x153dc, 0x1c652, true), (0x1d658, 0x1d660, false)];
+            assert!(entry_in_exec_segment(0x153dc, &segments));
+            assert!(entry_in_exec_segment(0x1c651, &segments));
+            // Past the end of text, in a data segment, or outside them all
+            assert!(!entry_in_exec_segment(0x1c652, &segments));
+            assert!(!entry_in_exec_segment(0x10000, &segments));
+            assert!(!entry_in_exec_segment(0x1d65c, &segments));
+            assert!(!entry_in_exec_segment(0x0, &segments));
+            assert!(!entry_in_exec_segment(0x8000_0000, &segments));
+            assert!(!entry_in_exec_segment(0x153dc, &[]));
+        }
+
+        #[test]
+        fn test_pure_bss_segment_maps_and_zeroes_every_page() {
+            // filesz 0, memsz 8192: two whole pages, nothing copied
+            let (va0, vaend) = segment_pages(0x4000, 8192, 4096);
+            assert_eq!((va0, vaend), (0x4000, 0x6000));
+            for cur_va in (va0..vaend).step_by(4096) {
+                assert_eq!(page_copy_plan(0x4000, 0, 8192, cur_va, 4096), (0, 0, 0, 4096));
+            }
+            // brk starts at vaddr + memsz, right at the end of the mapping
+            assert_eq!(0x4000 + 8192, vaend);
+            // Unaligned, it spills into a third page
+            assert_eq!(segment_pages(0x4010, 8192, 4096), (0x4000, 0x7000));
+            assert_eq!(load_pages(0x4005, &[], 20), [0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        }
+
+        #[test]
+        fn test_page_copy_plan_multiple_pages() {
+            // 40 file bytes and 8 of BSS at an aligned vaddr span three pages
+            let file: Vec<u8> = (1..=40).collect();
+            assert_eq!(page_copy_plan(0x2000, 40, 48, 0x2000, 16), (0, 16, 16, 0));
+            assert_eq!(page_copy_plan(0x2000, 40, 48, 0x2010, 16), (0, 16, 16, 0));
+            assert_eq!(page_copy_plan(0x2000, 40, 48, 0x2020, 16), (0, 8, 8, 8));
+            let mem = load_pages(0x2000, &file, 48);
+            assert_eq!(&mem[..40], &file[..]);
+            assert_eq!(&mem[40..], [0; 8]);
+            // BSS running into pages with no file bytes at all
+            assert_eq!(page_copy_plan(0x2000, 8, 64, 0x2020, 16), (0, 0, 0, 16));
+            assert_eq!(page_copy_plan(0x2000, 8, 60, 0x2030, 16), (0, 0, 0, 12));
+        }
+
+        #[test]
+        fn test_page_copy_plan_unaligned_vaddr() {
+            // Starts 5 bytes into its first page and ends 3 bytes into the third
+            assert_eq!(page_copy_plan(0x3005, 20, 30, 0x3000, 16), (5, 11, 16, 0));
+            assert_eq!(page_copy_plan(0x3005, 20, 30, 0x3010, 16), (0, 9, 9, 7));
+            assert_eq!(page_copy_plan(0x3005, 20, 30, 0x3020, 16), (0, 0, 0, 3));
+            let file: Vec<u8> = (1..=20).collect();
+            let mem = load_pages(0x3005, &file, 30);
+            // Bytes before the segment and after its end are not written
+            assert_eq!(&mem[..5], [0xff; 5]);
+            assert_eq!(&mem[5..25], &file[..]);
+            assert_eq!(&mem[25..35], [0; 10]);
+            assert_eq!(&mem[35..], [0xff; 13]);
+            // Small segment inside one page, and file bytes past memsz ignored
+            assert_eq!(page_copy_plan(0x3008, 2, 4, 0x3000, 16), (8, 2, 10, 2));
+            assert_eq!(page_copy_plan(0x3008, 8, 4, 0x3000, 16), (8, 4, 12, 0));
+        }
+
+        #[test]
+        fn test_stack_region_sizes_and_limits() {
+            assert_eq!(stack_region(USER_STACK_TOP, DEFAULT_USER_STACK_BYTES), Some((0x0fff_c000, 0x1000_0000)));
+            // Rounded up to whole pages
+            assert_eq!(stack_region(USER_STACK_TOP, 100), Some((0x0fff_f000, 0x1000_0000)));
+            assert_eq!(stack_region(USER_STACK_TOP, 0), None);
+            assert_eq!(stack_region(USER_STACK_TOP, MAX_USER_STACK_BYTES + 1), None);
+            // Outside the image window, unaligned, or no room for the guard page
+            assert_eq!(stack_region(0x4000_8000, 4096), None);
+            assert_eq!(stack_region(0x8000_0800, 4096), None);
+            assert_eq!(stack_region(0x2000, 4096), None);
+            assert_eq!(stack_region(0x3000, 4096), Some((0x2000, 0x3000)));
+        }
+
+        #[test]
+        fn test_stack_does_not_overlap_segments() {
+            let stack = stack_region(USER_STACK_TOP, 64 * 1024).unwrap();
+            // Programs are linked at 0x10000
+            assert!(!stack_overlaps_segment(stack, 0x10000, 0x8000));
+            assert!(!stack_overlaps_segment(stack, 0x10000, stack.0 - 0x1000 - 0x10000));
+            // Reaching into the guard page, the stack, or past its top
+            assert!(stack_overlaps_segment(stack, 0x10000, stack.0 - 0x1000 - 0x10000 + 1));
+            assert!(stack_overlaps_segment(stack, stack.0 + 0x100, 0x10));
+            assert!(stack_overlaps_segment(stack, 0x10000, USER_IMAGE_END - 0x10000));
+            assert!(!stack_overlaps_segment(stack, stack.1, 0x1000));
+            assert!(!stack_overlaps_segment(stack, 0x10000, 0));
+        }
+
+        #[test]
+        fn test_relative_reloc_math() {
+            let (va, value) = relative_reloc(0x10000, 0x2008, 0x1234);
+            assert_eq!(va, 0x12008);
+            assert_eq!(value, 0x11234);
+            assert_eq!(relative_reloc(0, 0x100, 0x40), (0x100, 0x40));
+            let (_, value) = relative_reloc(0x10000, 0, -0x10);
+            assert_eq!(value, 0xFFF0);
+        }
+
         #[test]
         fn test_pte_flags_from_pf_no_perms() {
             let flags = pte_flags_from_pf(0);
@@ -222,3 +891,1487 @@ pub mod elf {
         }
     }
 }
+
+/// Timer arithmetic from timer.rs
+pub mod timer {
+    /// BogoMIPS scaled by 100 (for XX.YY format) from `loops` delay iterations
+    /// that took `elapsed` timer cycles at `timebase` Hz.
+    /// Uses `u128` intermediates; returns 0 if `elapsed` is 0.
+    pub fn bogomips_from(loops: u64, timebase: u64, elapsed: u64) -> u64 {
+        if elapsed == 0 {
+            return 0;
+        }
+        // x * 100 / (e * 1_000_000) == x / (e * 10_000), and loops * timebase fits in u128
+        let scaled = (loops as u128 * timebase as u128) / (elapsed as u128 * 10_000);
+        u64::try_from(scaled).unwrap_or(u64::MAX)
+    }
+
+    /// Convert `ticks` of `interval` cycles each at `timebase` Hz to milliseconds.
+    pub fn ticks_to_ms(ticks: u64, interval: u64, timebase: u64) -> u64 {
+        let ms = (ticks as u128 * interval as u128 * 1000) / timebase as u128;
+        u64::try_from(ms).unwrap_or(u64::MAX)
+    }
+
+    /// Convert `ticks` of `interval` cycles each at `timebase` Hz to whole seconds.
+    pub fn ticks_to_secs(ticks: u64, interval: u64, timebase: u64) -> u64 {
+        let secs = (ticks as u128 * interval as u128) / timebase as u128;
+        u64::try_from(secs).unwrap_or(u64::MAX)
+    }
+
+    /// Default cycles per tick (10ms at 10 MHz)
+    pub const DEFAULT_TICK_INTERVAL: u64 = 100_000;
+
+    /// Seconds since boot, for fs.rs mtime stamps. Nothing ticks in the
+    /// library, so it is always 0.
+    pub fn seconds() -> u64 {
+        0
+    }
+
+    /// Timebase cycles per tick for a requested rate; falls back to the default
+    /// when no rate is given or it is 0 or faster than the timebase itself.
+    pub fn interval_for_hz(tick_hz: Option<u64>, timebase: u64) -> u64 {
+        match tick_hz {
+            Some(hz) if hz > 0 && hz <= timebase => timebase / hz,
+            _ => DEFAULT_TICK_INTERVAL,
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const TIMEBASE_HZ: u64 = 10_000_000;
+        const TICK_INTERVAL: u64 = 100_000; // 10ms
+
+        #[test]
+        fn test_interval_for_hz() {
+            assert_eq!(interval_for_hz(Some(100), TIMEBASE_HZ), 100_000);
+            assert_eq!(interval_for_hz(Some(1000), TIMEBASE_HZ), 10_000);
+            assert_eq!(interval_for_hz(Some(TIMEBASE_HZ), TIMEBASE_HZ), 1);
+        }
+
+        #[test]
+        fn test_interval_for_hz_default_fallback() {
+            assert_eq!(interval_for_hz(None, TIMEBASE_HZ), DEFAULT_TICK_INTERVAL);
+            assert_eq!(interval_for_hz(Some(0), TIMEBASE_HZ), DEFAULT_TICK_INTERVAL);
+            assert_eq!(interval_for_hz(Some(TIMEBASE_HZ + 1), TIMEBASE_HZ), DEFAULT_TICK_INTERVAL);
+        }
+
+        #[test]
+        fn test_ticks_to_ms() {
+            assert_eq!(ticks_to_ms(0, TICK_INTERVAL, TIMEBASE_HZ), 0);
+            assert_eq!(ticks_to_ms(50, TICK_INTERVAL, TIMEBASE_HZ), 500);
+            assert_eq!(ticks_to_ms(100_000, TICK_INTERVAL, TIMEBASE_HZ), 1_000_000);
+        }
+
+        #[test]
+        fn test_ticks_to_secs() {
+            assert_eq!(ticks_to_secs(0, TICK_INTERVAL, TIMEBASE_HZ), 0);
+            assert_eq!(ticks_to_secs(50, TICK_INTERVAL, TIMEBASE_HZ), 0); // 0.5s rounds down
+            assert_eq!(ticks_to_secs(100_000, TICK_INTERVAL, TIMEBASE_HZ), 1000);
+        }
+
+        #[test]
+        fn test_bogomips_from_typical() {
+            // 50M loops in exactly one second -> 50.00 BogoMIPS
+            assert_eq!(bogomips_from(50_000_000, TIMEBASE_HZ, TIMEBASE_HZ), 5000);
+            // Same loops in half the time doubles the rating
+            assert_eq!(bogomips_from(50_000_000, TIMEBASE_HZ, TIMEBASE_HZ / 2), 10000);
+        }
+
+        #[test]
+        fn test_bogomips_from_large_inputs() {
+            // loops * TIMEBASE_HZ overflows u64 here (> 1.8e19)
+            let loops = 4_000_000_000_000u64;
+            assert!(loops.checked_mul(TIMEBASE_HZ).is_none());
+            assert_eq!(bogomips_from(loops, TIMEBASE_HZ, TIMEBASE_HZ), 400_000_000);
+
+            // Result larger than u64 saturates instead of wrapping
+            assert_eq!(bogomips_from(u64::MAX, u64::MAX, 1), u64::MAX);
+        }
+
+        #[test]
+        fn test_bogomips_from_zero_elapsed() {
+            assert_eq!(bogomips_from(1000, TIMEBASE_HZ, 0), 0);
+            assert_eq!(bogomips_from(0, TIMEBASE_HZ, TIMEBASE_HZ), 0);
+        }
+    }
+}
+
+/// Syscall helpers from trap.rs
+pub mod trap {
+    use super::fs;
+
+    /// CSR numbers of the user counters (`rdcycle`, `rdtime`, `rdinstret`)
+    pub const CSR_CYCLE: u16 = 0xC00;
+    pub const CSR_TIME: u16 = 0xC01;
+    pub const CSR_INSTRET: u16 = 0xC02;
+
+    /// Decode a plain CSR read, `csrrs rd, csr, x0` (what `csrr`/`rdtime`
+    /// assemble to), into `(rd, csr)`. Anything else gives `None`.
+    pub fn decode_csr_read(inst: u32) -> Option<(usize, u16)> {
+        let opcode = inst & 0x7f;
+        let funct3 = (inst >> 12) & 0x7;
+        let rs1 = (inst >> 15) & 0x1f;
+        if opcode != 0x73 || funct3 != 0b010 || rs1 != 0 {
+            return None;
+        }
+        Some((((inst >> 7) & 0x1f) as usize, (inst >> 20) as u16))
+    }
+
+    /// True if `addr` is in the page just below a stack whose lowest byte is at
+    /// `stack_bottom`, i.e. the stack ran off its end. A bottom of 0 means no
+    /// stack is known.
+    pub fn in_stack_guard(addr: usize, stack_bottom: usize) -> bool {
+        stack_bottom != 0 && addr < stack_bottom && addr >= stack_bottom.saturating_sub(4096)
+    }
+
+    /// Feed `bytes` to `out`, inserting `\r` before each `\n` unless `raw`.
+    pub fn emit_bytes<F: FnMut(u8)>(bytes: &[u8], raw: bool, mut out: F) {
+        for &b in bytes {
+            if !raw && b == b'\n' {
+                out(b'\r');
+            }
+            out(b);
+        }
+    }
+
+    /// Bytes from `va` to the end of its page, at most `len`
+    pub fn cap_to_page(va: usize, len: usize) -> usize {
+        let page_end = (va + 4096) & !4095;
+        core::cmp::min(len, page_end.saturating_sub(va))
+    }
+
+    /// Why a read into a user buffer got nothing
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ReadError {
+        /// The buffer's first page isn't mapped
+        Unmapped,
+        /// The file itself couldn't be read
+        File(fs::FsError),
+    }
+
+    /// Read `len` bytes into the user buffer at `va` one page-sized piece at a
+    /// time, so a single `read` isn't limited to the page `va` starts in.
+    /// `mapped(page_va)` is asked before each page is touched and
+    /// `read_at(offset, va, n)` fills one piece; an unmapped page or a short
+    /// piece (end of file) ends the read. Fails only if nothing could be read
+    /// because the first page isn't mapped.
+    pub fn read_pages<M, F>(va: usize, len: usize, offset: usize, mut mapped: M, mut read_at: F) -> Result<usize, ReadError>
+    where
+        M: FnMut(usize) -> bool,
+        F: FnMut(usize, usize, usize) -> Result<usize, ReadError>,
+    {
+        let mut total = 0usize;
+        while total < len {
+            let piece_va = va + total;
+            if !mapped(piece_va & !4095) {
+                return if total == 0 { Err(ReadError::Unmapped) } else { Ok(total) };
+            }
+            let piece = cap_to_page(piece_va, len - total);
+            let n = read_at(offset + total, piece_va, piece)?;
+            total += n;
+            if n < piece {
+                break;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Scatter a sequential read across `segs` (`(va, len)` pairs) starting at `offset`.
+    /// `read_at(offset, va, len)` fills one segment; a short read ends the scatter.
+    /// Returns the total number of bytes read. A failing segment ends the
+    /// scatter too, and is only an error if nothing was read before it.
+    pub fn scatter_read<F, E>(segs: &[(usize, usize)], offset: usize, mut read_at: F) -> Result<usize, E>
+    where
+        F: FnMut(usize, usize, usize) -> Result<usize, E>,
+    {
+        let mut total = 0usize;
+        for &(va, len) in segs {
+            if len == 0 {
+                continue;
+            }
+            let n = match read_at(offset + total, va, len) {
+                Ok(n) => n,
+                Err(e) if total == 0 => return Err(e),
+                Err(_) => break,
+            };
+            total += n;
+            if n < len {
+                break;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Why a user string couldn't be copied in
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum CStrError {
+        /// NULL, or it runs into an unmapped page or off the end of memory
+        BadPointer,
+        /// No NUL within the allowed length
+        TooLong,
+    }
+
+    /// Copy the NUL-terminated string at `va` into `out`, following it across
+    /// page boundaries. `mapped(page_va)` is asked before the first byte of each
+    /// page is read and `read(va)` fetches one byte. Returns the length without
+    /// the NUL; fails if the string runs into an unmapped page or has no NUL
+    /// within `max` bytes (or `out.len()`).
+    pub fn copy_cstr<M, R>(va: usize, max: usize, out: &mut [u8], mut mapped: M, mut read: R) -> Result<usize, CStrError>
+    where
+        M: FnMut(usize) -> bool,
+        R: FnMut(usize) -> u8,
+    {
+        if va == 0 {
+            return Err(CStrError::BadPointer);
+        }
+        let max = max.min(out.len());
+        for (n, slot) in out[..max].iter_mut().enumerate() {
+            let addr = va.checked_add(n).ok_or(CStrError::BadPointer)?;
+            if (n == 0 || addr.is_multiple_of(4096)) && !mapped(addr & !4095) {
+                return Err(CStrError::BadPointer);
+            }
+            let b = read(addr);
+            if b == 0 {
+                return Ok(n);
+            }
+            *slot = b;
+        }
+        Err(CStrError::TooLong)
+    }
+
+    /// Call `f` with each pointer of the NULL-terminated array at `array_va`
+    /// (argv/envp), stopping after `max` entries. `read_word(va)` loads one
+    /// pointer. A NULL array is treated as empty.
+    pub fn walk_ptr_array<R, F>(array_va: usize, max: usize, mut read_word: R, mut f: F)
+    where
+        R: FnMut(usize) -> usize,
+        F: FnMut(usize),
+    {
+        if array_va == 0 {
+            return;
+        }
+        for i in 0..max {
+            let ptr = read_word(array_va + i * core::mem::size_of::<usize>());
+            if ptr == 0 {
+                break; // NULL terminator
+            }
+            f(ptr);
+        }
+    }
+
+    /// Whether a `read(0, ..)` has something to return without waiting.
+    pub fn stdin_ready(line_pending: bool, keyboard: bool, uart: bool) -> bool {
+        line_pending || keyboard || uart
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// What `mock_read` returns: the result, the user buffer, and the
+        /// `(va, len)` pieces it was filled in
+        type MockRead = (Result<usize, ReadError>, Vec<u8>, Vec<(usize, usize)>);
+
+        /// A read of `len` bytes at `va` from a file of `file` bytes, done the
+        /// way sys_read does it. Returns the bytes that landed in the user
+        /// buffer and the pieces they came in.
+        fn mock_read(file: &[u8], va: usize, len: usize, offset: usize, mapped_pages: usize) -> MockRead {
+            let mut user = vec![0u8; len];
+            let mut pieces = Vec::new();
+            let first_page = va & !4095;
+            let r = read_pages(
+                va,
+                len,
+                offset,
+                |page| page < first_page + mapped_pages * 4096,
+                |off, piece_va, n| {
+                    pieces.push((piece_va, n));
+                    let src = file.get(off..).unwrap_or(&[]);
+                    let n = n.min(src.len());
+                    let at = piece_va - va;
+                    user[at..at + n].copy_from_slice(&src[..n]);
+                    Ok(n)
+                },
+            );
+            (r, user, pieces)
+        }
+
+        #[test]
+        fn test_read_pages_fills_a_multi_page_buffer() {
+            let file: Vec<u8> = (0..10240u32).map(|i| (i * 7) as u8).collect();
+            let va = 0x4000_5100;
+
+            // One read(len=10240) gets everything, in pieces that stay in their page
+            let (r, user, pieces) = mock_read(&file, va, 10240, 0, 4);
+            assert_eq!(r, Ok(10240));
+            assert_eq!(user, file);
+            assert_eq!(pieces.iter().map(|p| p.1).sum::<usize>(), 10240);
+            assert!(pieces.iter().all(|&(pva, n)| (pva & !4095) == ((pva + n - 1) & !4095)));
+            assert_eq!(pieces[0], (va, 4096 - 0x100));
+
+            // The fd offset then sits at the end: the next read is empty
+            let offset = r.unwrap();
+            assert_eq!(mock_read(&file, va, 10240, offset, 4).0, Ok(0));
+
+            // Starting midway, the read stops at the end of the file
+            let (r, user, _) = mock_read(&file, va, 10240, 6000, 4);
+            assert_eq!(r, Ok(4240));
+            assert_eq!(&user[..4240], &file[6000..]);
+        }
+
+        #[test]
+        fn test_read_pages_stops_at_unmapped_page() {
+            let file = vec![1u8; 10240];
+            // Only the first page is mapped: a short read, not a fault
+            let (r, _, _) = mock_read(&file, 0x4000_5000, 10240, 0, 1);
+            assert_eq!(r, Ok(4096));
+            assert_eq!(mock_read(&file, 0x4000_5000, 10240, 0, 0).0, Err(ReadError::Unmapped));
+        }
+
+        #[test]
+        fn test_in_stack_guard() {
+            let bottom = 0x4000_4000;
+            assert!(in_stack_guard(bottom - 1, bottom));
+            assert!(in_stack_guard(bottom - 4096, bottom));
+            assert!(in_stack_guard(bottom - 8, bottom));
+            // Inside the stack, or further down than one page
+            assert!(!in_stack_guard(bottom, bottom));
+            assert!(!in_stack_guard(bottom - 4097, bottom));
+            // Nothing is a guard page before a program is loaded
+            assert!(!in_stack_guard(0, 0));
+        }
+
+        #[test]
+        fn test_decode_csr_read() {
+            // rdtime a0 = csrrs a0, time, x0
+            assert_eq!(decode_csr_read(0xc010_2573), Some((10, CSR_TIME)));
+            // rdcycle t6
+            assert_eq!(decode_csr_read(0xc000_2ff3), Some((31, CSR_CYCLE)));
+            // csrr s1, sstatus
+            assert_eq!(decode_csr_read(0x1000_24f3), Some((9, 0x100)));
+            // csrrs a0, time, a1 also sets bits, csrrw a0, time, x0 writes
+            assert_eq!(decode_csr_read(0xc015_a573), None);
+            assert_eq!(decode_csr_read(0xc010_1573), None);
+            // ecall, and an all-zero (always illegal) word
+            assert_eq!(decode_csr_read(0x0000_0073), None);
+            assert_eq!(decode_csr_read(0), None);
+        }
+
+        /// User memory for `copy_cstr`: `pages` lists the mapped page VAs.
+        fn mock_cstr(va: usize, max: usize, pages: &[usize], mem: &[(usize, &[u8])]) -> Result<Vec<u8>, CStrError> {
+            let mut out = [0u8; 8192];
+            let n = copy_cstr(
+                va,
+                max,
+                &mut out,
+                |page| pages.contains(&page),
+                |addr| {
+                    mem.iter()
+                        .find(|(base, bytes)| addr >= *base && addr < base + bytes.len())
+                        .map(|(base, bytes)| bytes[addr - base])
+                        .expect("read from an unchecked page")
+                },
+            )?;
+            Ok(out[..n].to_vec())
+        }
+
+        #[test]
+        fn test_copy_cstr_continues_onto_the_next_page() {
+            // "hello/world" with the split right at the page boundary
+            let mem: [(usize, &[u8]); 2] = [(0x1ffa, b"hello/"), (0x2000, b"world\0")];
+            let s = mock_cstr(0x1ffa, 255, &[0x1000, 0x2000], &mem).unwrap();
+            assert_eq!(s, b"hello/world");
+        }
+
+        #[test]
+        fn test_copy_cstr_stops_at_an_unmapped_page() {
+            let mem: [(usize, &[u8]); 1] = [(0x1ffa, b"hello/")];
+            assert_eq!(mock_cstr(0x1ffa, 255, &[0x1000], &mem), Err(CStrError::BadPointer));
+            // A string ending before the boundary never touches the next page
+            let mem: [(usize, &[u8]); 1] = [(0x1ffa, b"hi\0")];
+            assert_eq!(mock_cstr(0x1ffa, 255, &[0x1000], &mem).unwrap(), b"hi");
+            // nor does anything if the first page is missing
+            assert_eq!(mock_cstr(0x3000, 255, &[0x1000], &[]), Err(CStrError::BadPointer));
+        }
+
+        #[test]
+        fn test_copy_cstr_respects_max() {
+            let long = [b'a'; 300];
+            let mut bytes = long.to_vec();
+            bytes.push(0);
+            let mem: [(usize, &[u8]); 1] = [(0x1000, &bytes)];
+            assert_eq!(mock_cstr(0x1000, 255, &[0x1000], &mem), Err(CStrError::TooLong));
+            assert_eq!(mock_cstr(0x1000, 301, &[0x1000], &mem).unwrap().len(), 300);
+            assert_eq!(mock_cstr(0, 255, &[0x0], &mem), Err(CStrError::BadPointer));
+        }
+
+        #[test]
+        fn test_walk_ptr_array_stops_at_null() {
+            // envp at 0x100: two strings, then NULL, then garbage
+            let words = [0x2000usize, 0x2010, 0, 0xdead];
+            let read = |va: usize| words[(va - 0x100) / 8];
+            let mut seen = Vec::new();
+            walk_ptr_array(0x100, 64, read, |p| seen.push(p));
+            assert_eq!(seen, [0x2000, 0x2010]);
+
+            // At most `max` entries even without a terminator
+            let mut seen = Vec::new();
+            walk_ptr_array(0x100, 1, read, |p| seen.push(p));
+            assert_eq!(seen, [0x2000]);
+        }
+
+        #[test]
+        fn test_walk_ptr_array_null_array_is_empty() {
+            let mut called = false;
+            walk_ptr_array(0, 64, |_| panic!("read through NULL"), |_| called = true);
+            assert!(!called);
+        }
+
+        #[test]
+        fn test_stdin_ready_checks_every_source() {
+            assert!(!stdin_ready(false, false, false));
+            assert!(stdin_ready(false, true, false));
+            assert!(stdin_ready(false, false, true));
+            assert!(stdin_ready(false, true, true));
+            assert!(stdin_ready(true, false, false));
+        }
+
+        #[test]
+        fn test_emit_bytes_raw_is_verbatim() {
+            let input = b"\x00line\nbin\n\r\xff";
+            let mut out = Vec::new();
+            emit_bytes(input, true, |b| out.push(b));
+            assert_eq!(&out[..], &input[..]);
+        }
+
+        #[test]
+        fn test_emit_bytes_cooked_inserts_cr() {
+            let mut out = Vec::new();
+            emit_bytes(b"a\nb\n", false, |b| out.push(b));
+            assert_eq!(&out[..], b"a\r\nb\r\n");
+        }
+
+        #[test]
+        fn test_scatter_read_fills_in_order() {
+            // 10-byte file scattered into buffers of 4 and 8
+            let file: Vec<u8> = (0..10).collect();
+            let mut filled: Vec<(usize, Vec<u8>)> = Vec::new();
+            let total = scatter_read(&[(0x1000, 4), (0x2000, 8)], 0, |off, va, len| {
+                let end = core::cmp::min(off + len, file.len());
+                let chunk = file[off.min(end)..end].to_vec();
+                let n = chunk.len();
+                filled.push((va, chunk));
+                Ok::<_, ()>(n)
+            })
+            .unwrap();
+
+            assert_eq!(total, 10); // offset advances by the total
+            assert_eq!(filled.len(), 2);
+            assert_eq!(filled[0], (0x1000, vec![0, 1, 2, 3]));
+            assert_eq!(filled[1], (0x2000, vec![4, 5, 6, 7, 8, 9]));
+        }
+
+        #[test]
+        fn test_scatter_read_stops_after_short_segment() {
+            let mut calls = 0;
+            let total = scatter_read(&[(0x1000, 8), (0x2000, 8), (0x3000, 8)], 5, |_, _, _| {
+                calls += 1;
+                Ok::<_, ()>(3)
+            })
+            .unwrap();
+            assert_eq!(total, 3);
+            assert_eq!(calls, 1);
+        }
+
+        #[test]
+        fn test_scatter_read_skips_empty_and_propagates_errors() {
+            let total = scatter_read(&[(0x1000, 0), (0x2000, 4)], 0, |_, va, len| {
+                assert_eq!(va, 0x2000);
+                Ok::<_, ()>(len)
+            })
+            .unwrap();
+            assert_eq!(total, 4);
+            assert_eq!(scatter_read(&[(0x1000, 4)], 0, |_, _, _| Err::<usize, _>("bad")), Err("bad"));
+
+            // Once the first segment is in, a failing one just ends the read
+            let r = scatter_read(&[(0x1000, 4), (0x2000, 4)], 0, |_, va, len| {
+                if va == 0x1000 { Ok(len) } else { Err("bad") }
+            });
+            assert_eq!(r, Ok(4));
+        }
+
+        #[test]
+        fn test_scatter_read_fills_an_iovec_across_a_page() {
+            // The first iovec starts 0x100 before a page boundary and is 0x300
+            // long; all of it must be filled before the second gets anything
+            let file: Vec<u8> = (0..0x400u32).map(|i| i as u8).collect();
+            let segs = [(0x4000_5f00, 0x300), (0x4000_9000, 0x100)];
+            let mut pieces = Vec::new();
+            let total = scatter_read(&segs, 0, |off, va, len| {
+                read_pages(va, len, off, |_| true, |off, piece_va, n| {
+                    pieces.push((piece_va, file[off..off + n].to_vec()));
+                    Ok(n)
+                })
+            })
+            .unwrap();
+
+            assert_eq!(total, 0x400);
+            assert_eq!(pieces.len(), 3);
+            assert_eq!(pieces[0], (0x4000_5f00, file[..0x100].to_vec()));
+            assert_eq!(pieces[1], (0x4000_6000, file[0x100..0x300].to_vec()));
+            assert_eq!(pieces[2], (0x4000_9000, file[0x300..].to_vec()));
+        }
+    }
+}
+
+/// Kernel log ring buffer and level colors from logging.rs
+pub mod logging {
+    use uapi::LogLevel;
+
+    pub fn level_color(level: LogLevel) -> &'static str {
+        match level {
+            LogLevel::Trace => "\x1b[90m", // bright black
+            LogLevel::Debug => "\x1b[36m", // cyan
+            LogLevel::Info => "\x1b[32m",  // green
+            LogLevel::Warn => "\x1b[33m",  // yellow
+            LogLevel::Error => "\x1b[31m", // red
+        }
+    }
+
+    pub const COLOR_RESET: &str = "\x1b[0m";
+
+    pub fn level_tag(level: LogLevel) -> &'static str {
+        match level {
+            LogLevel::Trace => "[TRACE]",
+            LogLevel::Debug => "[DEBUG]",
+            LogLevel::Info => "[INFO]",
+            LogLevel::Warn => "[WARN]",
+            LogLevel::Error => "[ERROR]",
+        }
+    }
+
+    /// Write the level tag, colored if `color`, padded so messages line up.
+    pub fn write_prefix_with<W: core::fmt::Write>(w: &mut W, level: LogLevel, color: bool) {
+        let tag = level_tag(level);
+        if color {
+            let _ = w.write_str(level_color(level));
+            let _ = w.write_str(tag);
+            let _ = w.write_str(COLOR_RESET);
+        } else {
+            let _ = w.write_str(tag);
+        }
+        // "[ERROR] " is the widest prefix
+        for _ in tag.len()..8 {
+            let _ = w.write_char(' ');
+        }
+    }
+
+    /// Fixed-size byte ring: once full, new bytes overwrite the oldest ones.
+    pub struct LogRing<const N: usize> {
+        buf: [u8; N],
+        /// Index of the oldest byte.
+        start: usize,
+        len: usize,
+    }
+
+    impl<const N: usize> Default for LogRing<N> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl<const N: usize> LogRing<N> {
+        pub const fn new() -> Self {
+            Self { buf: [0; N], start: 0, len: 0 }
+        }
+
+        pub fn len(&self) -> usize {
+            self.len
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        /// Append `bytes`, dropping the oldest contents if they don't fit.
+        pub fn push(&mut self, bytes: &[u8]) {
+            // Only the last N bytes can survive anyway
+            let bytes = &bytes[bytes.len().saturating_sub(N)..];
+            for &b in bytes {
+                let end = (self.start + self.len) % N;
+                self.buf[end] = b;
+                if self.len == N {
+                    self.start = (self.start + 1) % N;
+                } else {
+                    self.len += 1;
+                }
+            }
+        }
+
+        /// The most recent `max` bytes (or all of them, if fewer), oldest first,
+        /// as two slices because the data may wrap around the end of the buffer.
+        pub fn recent(&self, max: usize) -> (&[u8], &[u8]) {
+            let n = core::cmp::min(max, self.len);
+            let first = (self.start + self.len - n) % N;
+            if first + n <= N {
+                (&self.buf[first..first + n], &[])
+            } else {
+                (&self.buf[first..], &self.buf[..first + n - N])
+            }
+        }
+
+        /// Copy the most recent bytes into `out`, oldest first. Returns the
+        /// number of bytes written.
+        pub fn copy_recent(&self, out: &mut [u8]) -> usize {
+            let (a, b) = self.recent(out.len());
+            out[..a.len()].copy_from_slice(a);
+            out[a.len()..a.len() + b.len()].copy_from_slice(b);
+            a.len() + b.len()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_level_color_mapping() {
+            assert_eq!(level_color(LogLevel::Error), "\x1b[31m");
+            assert_eq!(level_color(LogLevel::Warn), "\x1b[33m");
+            assert_eq!(level_color(LogLevel::Info), "\x1b[32m");
+            assert_eq!(level_color(LogLevel::Debug), "\x1b[36m");
+            assert_eq!(level_color(LogLevel::Trace), "\x1b[90m");
+        }
+
+        #[test]
+        fn test_color_wraps_only_the_tag() {
+            let mut s = String::new();
+            write_prefix_with(&mut s, LogLevel::Warn, true);
+            assert_eq!(s, "\x1b[33m[WARN]\x1b[0m  ");
+            let mut s = String::new();
+            write_prefix_with(&mut s, LogLevel::Error, false);
+            assert_eq!(s, "[ERROR] ");
+            let mut s = String::new();
+            write_prefix_with(&mut s, LogLevel::Info, false);
+            assert_eq!(s, "[INFO]  ");
+        }
+
+        fn recent_vec<const N: usize>(ring: &LogRing<N>, max: usize) -> Vec<u8> {
+            let (a, b) = ring.recent(max);
+            [a, b].concat()
+        }
+
+        #[test]
+        fn test_push_wraps_and_keeps_newest() {
+            let mut ring = LogRing::<8>::new();
+            ring.push(b"abcde");
+            assert_eq!(ring.len(), 5);
+            ring.push(b"fghij");
+            assert_eq!(ring.len(), 8);
+            assert_eq!(recent_vec(&ring, 8), b"cdefghij");
+            // One push longer than the ring keeps only its tail
+            ring.push(b"0123456789");
+            assert_eq!(recent_vec(&ring, 8), b"23456789");
+        }
+
+        #[test]
+        fn test_copy_recent_returns_latest_bytes_in_order() {
+            let mut ring = LogRing::<8>::new();
+            ring.push(b"hello ");
+            ring.push(b"world");
+            let mut out = [0u8; 4];
+            assert_eq!(ring.copy_recent(&mut out), 4);
+            assert_eq!(&out, b"orld");
+            let mut out = [0u8; 16];
+            assert_eq!(ring.copy_recent(&mut out), 8);
+            assert_eq!(&out[..8], b"lo world");
+        }
+
+        #[test]
+        fn test_empty_ring_reads_nothing() {
+            let ring = LogRing::<8>::new();
+            assert!(ring.is_empty());
+            let mut out = [0u8; 4];
+            assert_eq!(ring.copy_recent(&mut out), 0);
+        }
+    }
+}
+
+/// Stdin ring buffer from keyboard.rs
+pub mod keyboard {
+    pub const INPUT_BUFFER_SIZE: usize = 256;
+
+    /// Bytes from the keyboard and the UART waiting for `read(0, ..)`
+    pub struct InputBuffer {
+        buffer: [u8; INPUT_BUFFER_SIZE],
+        read_pos: usize,
+        write_pos: usize,
+    }
+
+    impl Default for InputBuffer {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl InputBuffer {
+        pub const fn new() -> Self {
+            Self { buffer: [0; INPUT_BUFFER_SIZE], read_pos: 0, write_pos: 0 }
+        }
+
+        /// Push a byte into the buffer. Returns false if buffer is full.
+        pub fn push(&mut self, byte: u8) -> bool {
+            let next_write = (self.write_pos + 1) % INPUT_BUFFER_SIZE;
+            if next_write == self.read_pos {
+                return false;
+            }
+            self.buffer[self.write_pos] = byte;
+            self.write_pos = next_write;
+            true
+        }
+
+        /// Pop a byte from the buffer. Returns None if buffer is empty.
+        pub fn pop(&mut self) -> Option<u8> {
+            if self.read_pos == self.write_pos {
+                return None;
+            }
+            let byte = self.buffer[self.read_pos];
+            self.read_pos = (self.read_pos + 1) % INPUT_BUFFER_SIZE;
+            Some(byte)
+        }
+
+        /// Pop up to `out.len()` bytes into `out` without waiting for more:
+        /// exactly `min(out.len(), self.len())`, oldest first. Returns the count.
+        pub fn read(&mut self, out: &mut [u8]) -> usize {
+            let mut n = 0;
+            while n < out.len() {
+                match self.pop() {
+                    Some(b) => out[n] = b,
+                    None => break,
+                }
+                n += 1;
+            }
+            n
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.read_pos == self.write_pos
+        }
+
+        pub fn len(&self) -> usize {
+            if self.write_pos >= self.read_pos {
+                self.write_pos - self.read_pos
+            } else {
+                INPUT_BUFFER_SIZE - self.read_pos + self.write_pos
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn filled(bytes: &[u8]) -> InputBuffer {
+            let mut ib = InputBuffer::new();
+            for &b in bytes {
+                assert!(ib.push(b));
+            }
+            ib
+        }
+
+        #[test]
+        fn test_read_takes_min_of_len_and_buffered() {
+            // Asking for less than is buffered
+            let mut ib = filled(b"hello");
+            let mut out = [0u8; 3];
+            assert_eq!(ib.read(&mut out), 3);
+            assert_eq!(&out, b"hel");
+            assert_eq!(ib.read_pos, 3);
+            assert_eq!(ib.len(), 2);
+
+            // Asking for more gets only what is there, and doesn't wait
+            let mut out = [0u8; 8];
+            assert_eq!(ib.read(&mut out), 2);
+            assert_eq!(&out[..2], b"lo");
+            assert_eq!(ib.read_pos, 5);
+            assert!(ib.is_empty());
+            assert_eq!(ib.read(&mut out), 0);
+            assert_eq!(ib.read(&mut []), 0);
+        }
+
+        #[test]
+        fn test_read_across_the_wrap() {
+            let mut ib = InputBuffer::new();
+            // Move the read position near the end of the ring
+            for _ in 0..INPUT_BUFFER_SIZE - 2 {
+                ib.push(0);
+                ib.pop();
+            }
+            for &b in b"wrap" {
+                assert!(ib.push(b));
+            }
+            assert_eq!(ib.write_pos, 2);
+            let mut out = [0u8; 4];
+            assert_eq!(ib.read(&mut out), 4);
+            assert_eq!(&out, b"wrap");
+            assert_eq!(ib.read_pos, 2);
+        }
+
+        #[test]
+        fn test_full_buffer_reads_back_in_order() {
+            // One slot stays free to tell full from empty
+            let mut ib = InputBuffer::new();
+            let n = (0..=255u8).take_while(|&b| ib.push(b)).count();
+            assert_eq!(n, INPUT_BUFFER_SIZE - 1);
+            let mut out = [0u8; INPUT_BUFFER_SIZE];
+            assert_eq!(ib.read(&mut out), INPUT_BUFFER_SIZE - 1);
+            assert!(out[..n].iter().enumerate().all(|(i, &b)| b as usize == i));
+        }
+    }
+}
+
+/// Heap usage accounting from kalloc.rs
+pub mod kalloc {
+    use linked_list_allocator::Heap;
+
+    pub fn heap_stats(heap: &Heap) -> (usize, usize, usize) {
+        (heap.used(), heap.free(), heap.size())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use core::alloc::Layout;
+
+        #[test]
+        fn test_used_plus_free_is_total() {
+            let mut arena = vec![0u8; 4096];
+            let mut heap = Heap::empty();
+            unsafe { heap.init(arena.as_mut_ptr(), arena.len()) };
+
+            let (used, free, total) = heap_stats(&heap);
+            assert_eq!(used, 0);
+            assert_eq!(free, total);
+            assert!(total <= 4096);
+
+            let layout = Layout::from_size_align(256, 8).unwrap();
+            let p = heap.allocate_first_fit(layout).unwrap();
+            let (used, free, total) = heap_stats(&heap);
+            assert!(used >= 256);
+            assert_eq!(used + free, total);
+
+            unsafe { heap.deallocate(p, layout) };
+            let (used, free, total) = heap_stats(&heap);
+            assert_eq!(used, 0);
+            assert_eq!(free, total);
+        }
+    }
+}
+
+/// Escape sequence parser (console/ansi.rs, shared)
+pub mod console {
+    pub mod ansi;
+}
+
+/// Cursor handling from display/fb_console.rs
+pub mod fb_console {
+    pub const CELL_PIXELS: usize = uapi::font::FONT_WIDTH * uapi::font::FONT_HEIGHT;
+
+    /// Pixel rectangle (x, y, width, height) covered by character cell (cx, cy)
+    pub fn cell_rect(cx: usize, cy: usize) -> (usize, usize, usize, usize) {
+        (
+            cx * uapi::font::FONT_WIDTH,
+            cy * uapi::font::FONT_HEIGHT,
+            uapi::font::FONT_WIDTH,
+            uapi::font::FONT_HEIGHT,
+        )
+    }
+
+    /// Pixel rectangle covering `cells` cells of row `cy` starting at column `cx`
+    pub fn row_span_rect(cx: usize, cy: usize, cells: usize) -> (usize, usize, usize, usize) {
+        let (x, y, _, h) = cell_rect(cx, cy);
+        (x, y, cells * uapi::font::FONT_WIDTH, h)
+    }
+
+    /// Pixel rectangle covering `rows` full rows (of `width_chars` cells) from row `cy`
+    pub fn rows_rect(cy: usize, rows: usize, width_chars: usize) -> (usize, usize, usize, usize) {
+        (
+            0,
+            cy * uapi::font::FONT_HEIGHT,
+            width_chars * uapi::font::FONT_WIDTH,
+            rows * uapi::font::FONT_HEIGHT,
+        )
+    }
+
+    pub fn render_glyph(c: u8, fg: u32, bg: u32, out: &mut [u32; CELL_PIXELS]) -> bool {
+        let bitmap = match uapi::font::get_char_bitmap(c) {
+            Some(b) => b,
+            None => return false,
+        };
+        for (row, &bitmap_row) in bitmap.iter().enumerate() {
+            for col in 0..uapi::font::FONT_WIDTH {
+                out[row * uapi::font::FONT_WIDTH + col] = if uapi::font::glyph_pixel(bitmap_row, col) {
+                    fg
+                } else {
+                    bg
+                };
+            }
+        }
+        true
+    }
+
+    /// The cursor-related part of fb_console's `ConsoleState`
+    pub struct ConsoleState {
+        pub cursor_x: usize,
+        pub cursor_y: usize,
+        pub width_chars: usize,
+        pub height_chars: usize,
+        pub cursor_visible: bool,
+        pub cursor_shown: bool,
+        pub tab_width: usize,
+    }
+
+    impl ConsoleState {
+        pub fn move_cursor(&mut self, x: usize, y: usize) {
+            self.cursor_x = x.min(self.width_chars.saturating_sub(1));
+            self.cursor_y = y.min(self.height_chars.saturating_sub(1));
+        }
+
+        pub fn cursor_drawn(&self) -> bool {
+            self.cursor_shown && self.cursor_visible
+        }
+
+        pub fn cursor_back(&mut self) -> bool {
+            if self.cursor_x > 0 {
+                self.cursor_x -= 1;
+            } else if self.cursor_y > 0 {
+                self.cursor_y -= 1;
+                self.cursor_x = self.width_chars.saturating_sub(1);
+            } else {
+                return false;
+            }
+            true
+        }
+
+        pub fn next_tab_stop(&self) -> usize {
+            let tab = self.tab_width.max(1);
+            (self.cursor_x / tab + 1) * tab
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn console() -> ConsoleState {
+            ConsoleState {
+                cursor_x: 0,
+                cursor_y: 0,
+                width_chars: 80,
+                height_chars: 25,
+                cursor_visible: true,
+                cursor_shown: true,
+                tab_width: 8,
+            }
+        }
+
+        #[test]
+        fn test_tab_stops_follow_tab_width() {
+            let mut c = console();
+            c.tab_width = 4;
+            c.cursor_x = 1;
+            assert_eq!(c.next_tab_stop(), 4);
+            c.cursor_x = 4;
+            assert_eq!(c.next_tab_stop(), 8);
+            c.tab_width = 8;
+            c.cursor_x = 1;
+            assert_eq!(c.next_tab_stop(), 8);
+            // Zero behaves like 1: every column is a stop
+            c.tab_width = 0;
+            assert_eq!(c.next_tab_stop(), 2);
+        }
+
+        #[test]
+        fn test_cell_rect() {
+            assert_eq!(cell_rect(0, 0), (0, 0, 8, 16));
+            assert_eq!(cell_rect(3, 2), (24, 32, 8, 16));
+            // Last cell of a 1920x1080 console (240x67 cells)
+            assert_eq!(cell_rect(239, 66), (1912, 1056, 8, 16));
+        }
+
+        #[test]
+        fn test_render_glyph_uses_cell_colors() {
+            let (fg, bg) = (0x00ff_ff00, 0x0000_0080);
+            let mut px = [0u32; CELL_PIXELS];
+            assert!(render_glyph(b'@', fg, bg, &mut px));
+            let bitmap = uapi::font::get_char_bitmap(b'@').unwrap();
+            for row in 0..uapi::font::FONT_HEIGHT {
+                for col in 0..uapi::font::FONT_WIDTH {
+                    let on = uapi::font::glyph_pixel(bitmap[row], col);
+                    assert_eq!(px[row * uapi::font::FONT_WIDTH + col], if on { fg } else { bg });
+                }
+            }
+            assert!(px.contains(&fg) && px.contains(&bg));
+            // A space is all background; unknown bytes render nothing
+            assert!(render_glyph(b' ', fg, bg, &mut px));
+            assert!(px.iter().all(|&p| p == bg));
+            assert!(!render_glyph(0x01, fg, bg, &mut px));
+        }
+
+        #[test]
+        fn test_move_cursor_clamps_to_screen() {
+            let mut st = console();
+            st.move_cursor(10, 5);
+            assert_eq!((st.cursor_x, st.cursor_y), (10, 5));
+            st.move_cursor(80, 25);
+            assert_eq!((st.cursor_x, st.cursor_y), (79, 24));
+            st.move_cursor(usize::MAX, 3);
+            assert_eq!((st.cursor_x, st.cursor_y), (79, 3));
+        }
+
+        #[test]
+        fn test_backspace_mid_line() {
+            let mut st = console();
+            st.move_cursor(10, 3);
+            assert!(st.cursor_back());
+            assert_eq!((st.cursor_x, st.cursor_y), (9, 3));
+        }
+
+        #[test]
+        fn test_backspace_at_column_zero_wraps_up() {
+            let mut st = console();
+            st.move_cursor(0, 3);
+            assert!(st.cursor_back());
+            assert_eq!((st.cursor_x, st.cursor_y), (79, 2));
+            // Nothing before the top-left cell
+            st.move_cursor(0, 0);
+            assert!(!st.cursor_back());
+            assert_eq!((st.cursor_x, st.cursor_y), (0, 0));
+        }
+
+        #[test]
+        fn test_hidden_cursor_is_not_drawn() {
+            let mut st = console();
+            assert!(st.cursor_drawn());
+            st.cursor_shown = false;
+            // Even in the "on" blink phase
+            assert!(st.cursor_visible);
+            assert!(!st.cursor_drawn());
+            st.cursor_shown = true;
+            st.cursor_visible = false;
+            assert!(!st.cursor_drawn());
+        }
+
+        #[test]
+        fn test_clear_to_eol_spans_rest_of_row() {
+            let width_chars = 80;
+            for cx in [0, 1, 37, 79] {
+                let (x, y, w, h) = row_span_rect(cx, 3, width_chars - cx);
+                assert_eq!(x, cx * uapi::font::FONT_WIDTH);
+                assert_eq!(y, 3 * uapi::font::FONT_HEIGHT);
+                assert_eq!(w, (width_chars - cx) * uapi::font::FONT_WIDTH);
+                assert_eq!(h, uapi::font::FONT_HEIGHT);
+                // Ends exactly at the right edge of the text area
+                assert_eq!(x + w, width_chars * uapi::font::FONT_WIDTH);
+            }
+        }
+
+        #[test]
+        fn test_clear_to_eos_rows_below_cursor() {
+            let (x, y, w, h) = rows_rect(11, 25 - 11, 80);
+            assert_eq!((x, y), (0, 11 * uapi::font::FONT_HEIGHT));
+            assert_eq!(w, 80 * uapi::font::FONT_WIDTH);
+            assert_eq!(y + h, 25 * uapi::font::FONT_HEIGHT);
+        }
+    }
+}
+
+/// Command line parsing from boot/cmdline.rs
+pub mod cmdline {
+    use crate::display::DisplayMode;
+    use uapi::LogLevel;
+
+    /// First user program, loaded at boot and again whenever a program exits
+    pub const DEFAULT_INIT: &str = "shell.elf";
+
+    /// Iterator over the whitespace-separated tokens of a command line:
+    /// `key=value` yields `(key, Some(value))` and a bare flag `(flag, None)`.
+    pub struct KeyVals<'a> {
+        tokens: core::str::SplitWhitespace<'a>,
+    }
+
+    impl<'a> KeyVals<'a> {
+        pub fn new(s: &'a str) -> Self {
+            Self { tokens: s.split_whitespace() }
+        }
+    }
+
+    impl<'a> Iterator for KeyVals<'a> {
+        type Item = (&'a str, Option<&'a str>);
+
+        fn next(&mut self) -> Option<Self::Item> {
+            let tok = self.tokens.next()?;
+            Some(match tok.split_once('=') {
+                Some((k, v)) => (k, Some(v)),
+                None => (tok, None),
+            })
+        }
+    }
+
+    /// Options recognized on the command line; `None` where not given.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct Options<'a> {
+        pub display: Option<DisplayMode>,
+        /// Framebuffer width and height from `fbres=WxH`
+        pub fbres: Option<(usize, usize)>,
+        pub loglevel: Option<LogLevel>,
+        pub tickhz: Option<u64>,
+        /// First user program from `init=<name>`
+        pub init: Option<&'a str>,
+        pub logcolor: Option<bool>,
+        pub kbdrepeat: Option<bool>,
+        /// User stack size in bytes from `stacksize=<n>[K|M]`
+        pub stacksize: Option<usize>,
+    }
+
+    impl<'a> Options<'a> {
+        /// Parse a whole command line, skipping keys we don't know.
+        pub fn parse(s: &'a str) -> Self {
+            let mut opts = Self::default();
+            for (key, value) in KeyVals::new(s) {
+                opts.set(key, value);
+            }
+            opts
+        }
+
+        /// The program to start first: `init=` if given, else [`DEFAULT_INIT`]
+        pub fn init_program(&self) -> &'a str {
+            self.init.unwrap_or(DEFAULT_INIT)
+        }
+
+        /// Record one option. Returns false for an unknown key. Switches such
+        /// as `logcolor` given without a value mean "on".
+        pub fn set(&mut self, key: &str, value: Option<&'a str>) -> bool {
+            let v = value.unwrap_or("");
+            match key {
+                "display" => {
+                    self.display = Some(match v {
+                        "gpu" => DisplayMode::Gpu,
+                        _ => DisplayMode::Ansi,
+                    })
+                }
+                "fbres" => self.fbres = parse_res(v),
+                "loglevel" => self.loglevel = LogLevel::from_str(v),
+                "tickhz" => self.tickhz = v.parse().ok(),
+                "init" => self.init = value.filter(|v| !v.is_empty()),
+                "logcolor" => self.logcolor = Some(value.is_none() || !matches!(v, "0" | "off" | "no")),
+                "kbdrepeat" => self.kbdrepeat = Some(value.is_none() || matches!(v, "1" | "on" | "yes")),
+                "stacksize" => self.stacksize = parse_size(v),
+                _ => return false,
+            }
+            true
+        }
+    }
+
+    /// `WxH`, both nonzero
+    fn parse_res(v: &str) -> Option<(usize, usize)> {
+        let (w, h) = v.split_once('x')?;
+        match (w.parse(), h.parse()) {
+            (Ok(w), Ok(h)) if w > 0 && h > 0 => Some((w, h)),
+            _ => None,
+        }
+    }
+
+    /// Byte count with an optional `K` or `M` suffix
+    fn parse_size(v: &str) -> Option<usize> {
+        let (digits, unit) = match v.as_bytes().last()? {
+            b'k' | b'K' => (&v[..v.len() - 1], 1024),
+            b'm' | b'M' => (&v[..v.len() - 1], 1024 * 1024),
+            _ => (v, 1),
+        };
+        digits.parse::<usize>().ok()?.checked_mul(unit)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const CMDLINE: &str = "display=gpu  tickhz=250 quiet fbres=800x600 init=forth.elf logcolor loglevel=DEBUG";
+
+        #[test]
+        fn test_key_vals_pairs_and_flags() {
+            let pairs: Vec<_> = KeyVals::new(CMDLINE).collect();
+            assert_eq!(
+                pairs,
+                [
+                    ("display", Some("gpu")),
+                    ("tickhz", Some("250")),
+                    ("quiet", None),
+                    ("fbres", Some("800x600")),
+                    ("init", Some("forth.elf")),
+                    ("logcolor", None),
+                    ("loglevel", Some("DEBUG")),
+                ]
+            );
+            // Only the first '=' splits; an empty value is still a value
+            assert_eq!(KeyVals::new("a=b=c x=").collect::<Vec<_>>(), [("a", Some("b=c")), ("x", Some(""))]);
+            assert_eq!(KeyVals::new("   ").next(), None);
+        }
+
+        #[test]
+        fn test_options_from_full_cmdline() {
+            let opts = Options::parse(CMDLINE);
+            assert_eq!(
+                opts,
+                Options {
+                    display: Some(DisplayMode::Gpu),
+                    fbres: Some((800, 600)),
+                    loglevel: Some(LogLevel::Debug),
+                    tickhz: Some(250),
+                    init: Some("forth.elf"),
+                    logcolor: Some(true),
+                    kbdrepeat: None,
+                    stacksize: None,
+                }
+            );
+
+            // Unknown keys are reported, bad values leave the option unset
+            let mut opts = Options::default();
+            assert!(!opts.set("quiet", None));
+            assert!(opts.set("fbres", Some("800")));
+            assert!(opts.set("tickhz", Some("fast")));
+            assert!(opts.set("logcolor", Some("off")));
+            assert!(opts.set("kbdrepeat", None));
+            assert_eq!(opts.fbres, None);
+            assert_eq!(opts.tickhz, None);
+            assert_eq!(opts.logcolor, Some(false));
+            assert_eq!(opts.kbdrepeat, Some(true));
+            assert_eq!(Options::parse("display=vga").display, Some(DisplayMode::Ansi));
+        }
+
+        #[test]
+        fn test_init_program_defaults_to_shell() {
+            assert_eq!(Options::parse("init=rogue.elf").init_program(), "rogue.elf");
+            assert_eq!(Options::parse("").init_program(), "shell.elf");
+            // An empty name doesn't replace the default
+            assert_eq!(Options::parse("init=").init, None);
+            assert_eq!(Options::parse("init= tickhz=100").init_program(), DEFAULT_INIT);
+        }
+
+        #[test]
+        fn test_stacksize_units() {
+            assert_eq!(Options::parse("stacksize=8192").stacksize, Some(8192));
+            assert_eq!(Options::parse("stacksize=64K").stacksize, Some(64 * 1024));
+            assert_eq!(Options::parse("stacksize=1m").stacksize, Some(1024 * 1024));
+            assert_eq!(Options::parse("stacksize=").stacksize, None);
+            assert_eq!(Options::parse("stacksize=K").stacksize, None);
+            assert_eq!(Options::parse("stacksize=lots").stacksize, None);
+            assert_eq!(Options::parse("stacksize=99999999999999999999M").stacksize, None);
+        }
+    }
+}
+
+/// Present path from display/virtio_gpu.rs
+pub mod virtio_gpu {
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct GpuRect {
+        pub x: u32,
+        pub y: u32,
+        pub width: u32,
+        pub height: u32,
+    }
+
+    pub trait PresentOps {
+        fn copy_to_scanout(&mut self, r: GpuRect);
+        fn transfer_to_host(&mut self, r: GpuRect) -> bool;
+        fn resource_flush(&mut self, r: GpuRect) -> bool;
+    }
+
+    pub fn present_rect<P: PresentOps>(p: &mut P, r: GpuRect) -> bool {
+        p.copy_to_scanout(r);
+        let transferred = p.transfer_to_host(r);
+        let flushed = p.resource_flush(r);
+        transferred && flushed
+    }
+
+    pub fn copy_rect(src: &[u32], dst: &mut [u32], stride_px: usize, r: GpuRect) {
+        let (x, w) = (r.x as usize, r.width as usize);
+        for y in r.y as usize..(r.y + r.height) as usize {
+            let start = y * stride_px + x;
+            dst[start..start + w].copy_from_slice(&src[start..start + w]);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const W: usize = 8;
+        const H: usize = 4;
+
+        /// A 8x4 back/scanout pair that records the command order
+        struct FakeGpu {
+            back: [u32; W * H],
+            scanout: [u32; W * H],
+            log: Vec<&'static str>,
+            scanout_at_transfer: [u32; W * H],
+        }
+
+        impl PresentOps for FakeGpu {
+            fn copy_to_scanout(&mut self, r: GpuRect) {
+                self.log.push("copy");
+                copy_rect(&self.back, &mut self.scanout, W, r);
+            }
+            fn transfer_to_host(&mut self, _r: GpuRect) -> bool {
+                self.log.push("transfer");
+                self.scanout_at_transfer = self.scanout;
+                true
+            }
+            fn resource_flush(&mut self, _r: GpuRect) -> bool {
+                self.log.push("flush");
+                true
+            }
+        }
+
+        #[test]
+        fn test_present_copies_before_transfer() {
+            let mut gpu = FakeGpu {
+                back: [7; W * H],
+                scanout: [0; W * H],
+                log: Vec::new(),
+                scanout_at_transfer: [0; W * H],
+            };
+            let full = GpuRect { x: 0, y: 0, width: W as u32, height: H as u32 };
+            assert!(present_rect(&mut gpu, full));
+            assert_eq!(gpu.log, ["copy", "transfer", "flush"]);
+            // The host saw the freshly drawn frame, not the stale scanout
+            assert!(gpu.scanout_at_transfer.iter().all(|&p| p == 7));
+        }
+
+        #[test]
+        fn test_copy_rect_only_touches_region() {
+            let back: Vec<u32> = (0..(W * H) as u32).collect();
+            let mut scanout = [u32::MAX; W * H];
+            copy_rect(&back, &mut scanout, W, GpuRect { x: 2, y: 1, width: 3, height: 2 });
+            for y in 0..H {
+                for x in 0..W {
+                    let i = y * W + x;
+                    let inside = (2..5).contains(&x) && (1..3).contains(&y);
+                    assert_eq!(scanout[i], if inside { back[i] } else { u32::MAX });
+                }
+            }
+        }
+    }
+}
+
+/// User-visible framebuffer reply from trap.rs `sys_get_fb_info`
+pub mod display {
+    /// Console output target, as in display/mod.rs
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum DisplayMode {
+        Ansi,
+        Gpu,
+    }
+
+    impl DisplayMode {
+        pub fn from_raw(v: usize) -> Option<Self> {
+            match v {
+                uapi::display_mode::ANSI => Some(DisplayMode::Ansi),
+                uapi::display_mode::GPU => Some(DisplayMode::Gpu),
+                _ => None,
+            }
+        }
+
+        pub fn as_raw(self) -> usize {
+            match self {
+                DisplayMode::Ansi => uapi::display_mode::ANSI,
+                DisplayMode::Gpu => uapi::display_mode::GPU,
+            }
+        }
+    }
+
+    /// Why a `set_display_mode` request was rejected
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum ModeError {
+        /// Not a `uapi::display_mode` value
+        Unknown,
+        /// GPU output asked for, but no framebuffer was set up at boot
+        NoFramebuffer,
+    }
+
+    pub fn mode_for_request(raw: usize, have_framebuffer: bool) -> Result<DisplayMode, ModeError> {
+        match DisplayMode::from_raw(raw) {
+            Some(DisplayMode::Gpu) if !have_framebuffer => Err(ModeError::NoFramebuffer),
+            Some(mode) => Ok(mode),
+            None => Err(ModeError::Unknown),
+        }
+    }
+
+    pub fn sync_display<F: FnOnce() -> bool>(have_framebuffer: bool, flush: F) -> bool {
+        !have_framebuffer || flush()
+    }
+
+    #[repr(C)]
+    pub struct FbInfoReply {
+        pub width: usize,
+        pub height: usize,
+        pub stride: usize,
+        pub addr: usize,
+        pub format: u32,
+        pub _reserved: u32,
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use core::mem::{align_of, offset_of, size_of};
+
+        #[test]
+        fn test_display_mode_raw_round_trip() {
+            for mode in [DisplayMode::Ansi, DisplayMode::Gpu] {
+                assert_eq!(DisplayMode::from_raw(mode.as_raw()), Some(mode));
+            }
+            assert_eq!(DisplayMode::Ansi.as_raw(), 0);
+            assert_eq!(DisplayMode::Gpu.as_raw(), 1);
+            assert_eq!(DisplayMode::from_raw(2), None);
+        }
+
+        #[test]
+        fn test_gpu_mode_needs_a_framebuffer() {
+            assert_eq!(mode_for_request(uapi::display_mode::GPU, false), Err(ModeError::NoFramebuffer));
+            assert_eq!(mode_for_request(uapi::display_mode::GPU, true), Ok(DisplayMode::Gpu));
+            // Falling back to the UART always works
+            assert_eq!(mode_for_request(uapi::display_mode::ANSI, false), Ok(DisplayMode::Ansi));
+            assert_eq!(mode_for_request(7, true), Err(ModeError::Unknown));
+        }
+
+        #[test]
+        fn test_sync_with_and_without_framebuffer() {
+            // No framebuffer: nothing to present, and flush is never tried
+            assert!(sync_display(false, || panic!("flushed without a framebuffer")));
+            let mut flushed = false;
+            assert!(sync_display(true, || {
+                flushed = true;
+                true
+            }));
+            assert!(flushed);
+            assert!(!sync_display(true, || false));
+        }
+
+        #[test]
+        fn test_fb_info_reply_matches_usys_layout() {
+            // usys::FbInfo is four usizes and a u32 format, padded to 8
+            // (and libc's struct fb_info the same with an explicit reserved)
+            assert_eq!(size_of::<FbInfoReply>(), 40);
+            assert_eq!(align_of::<FbInfoReply>(), align_of::<usize>());
+            assert_eq!(offset_of!(FbInfoReply, width), 0);
+            assert_eq!(offset_of!(FbInfoReply, height), 8);
+            assert_eq!(offset_of!(FbInfoReply, stride), 16);
+            assert_eq!(offset_of!(FbInfoReply, addr), 24);
+            assert_eq!(offset_of!(FbInfoReply, format), 32);
+        }
+    }
+}
@@ -10,10 +10,17 @@
 //! - INFO: Informational messages
 //! - WARN: Warning messages
 //! - ERROR: Error messages
+//!
+//! The level tag is wrapped in ANSI SGR colors unless turned off with
+//! [`set_color`] (or `logcolor=off` on the command line) for dumb terminals.
+//!
+//! Everything `klog!` prints is also kept in an in-memory ring buffer so
+//! userspace can read it back with the `dmesg` syscall.
 
 #![allow(dead_code)]
 
-use core::sync::atomic::{AtomicU8, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use spin::Mutex;
 use uapi::LogLevel;
 
 /// Global log level filter. Messages below this level are suppressed.
@@ -41,6 +48,64 @@ pub fn should_log(level: LogLevel) -> bool {
     level as u8 >= LOG_LEVEL.load(Ordering::Relaxed)
 }
 
+/// Whether `klog!` colors the level tag.
+static COLOR: AtomicBool = AtomicBool::new(true);
+
+/// Turn ANSI colors around the level tag on or off.
+pub fn set_color(on: bool) {
+    COLOR.store(on, Ordering::Relaxed);
+}
+
+pub fn color_enabled() -> bool {
+    COLOR.load(Ordering::Relaxed)
+}
+
+/// SGR sequence that starts the color for `level`'s tag.
+pub fn level_color(level: LogLevel) -> &'static str {
+    match level {
+        LogLevel::Trace => "\x1b[90m", // bright black
+        LogLevel::Debug => "\x1b[36m", // cyan
+        LogLevel::Info => "\x1b[32m",  // green
+        LogLevel::Warn => "\x1b[33m",  // yellow
+        LogLevel::Error => "\x1b[31m", // red
+    }
+}
+
+/// SGR reset, ending a [`level_color`].
+pub const COLOR_RESET: &str = "\x1b[0m";
+
+/// The bracketed tag for `level`, without padding.
+pub fn level_tag(level: LogLevel) -> &'static str {
+    match level {
+        LogLevel::Trace => "[TRACE]",
+        LogLevel::Debug => "[DEBUG]",
+        LogLevel::Info => "[INFO]",
+        LogLevel::Warn => "[WARN]",
+        LogLevel::Error => "[ERROR]",
+    }
+}
+
+/// Write the level tag as `klog!` does, colored if [`color_enabled`].
+pub fn write_prefix<W: core::fmt::Write>(w: &mut W, level: LogLevel) {
+    write_prefix_with(w, level, color_enabled());
+}
+
+/// Write the level tag, colored if `color`, padded so messages line up.
+pub fn write_prefix_with<W: core::fmt::Write>(w: &mut W, level: LogLevel, color: bool) {
+    let tag = level_tag(level);
+    if color {
+        let _ = w.write_str(level_color(level));
+        let _ = w.write_str(tag);
+        let _ = w.write_str(COLOR_RESET);
+    } else {
+        let _ = w.write_str(tag);
+    }
+    // "[ERROR] " is the widest prefix
+    for _ in tag.len()..8 {
+        let _ = w.write_char(' ');
+    }
+}
+
 /// Write a debug/log message to the serial port (UART).
 /// This always goes to serial, regardless of GPU mode.
 pub fn debug_write(s: &str) {
@@ -57,21 +122,98 @@ pub fn debug_writeln(s: &str) {
     let _ = uart.write_str("\r\n");
 }
 
+/// Size of the kernel log ring buffer in bytes.
+pub const LOG_RING_SIZE: usize = 8192;
+
+/// Fixed-size byte ring: once full, new bytes overwrite the oldest ones.
+pub struct LogRing<const N: usize> {
+    buf: [u8; N],
+    /// Index of the oldest byte.
+    start: usize,
+    len: usize,
+}
+
+impl<const N: usize> Default for LogRing<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> LogRing<N> {
+    pub const fn new() -> Self {
+        Self { buf: [0; N], start: 0, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Append `bytes`, dropping the oldest contents if they don't fit.
+    pub fn push(&mut self, bytes: &[u8]) {
+        // Only the last N bytes can survive anyway
+        let bytes = &bytes[bytes.len().saturating_sub(N)..];
+        for &b in bytes {
+            let end = (self.start + self.len) % N;
+            self.buf[end] = b;
+            if self.len == N {
+                self.start = (self.start + 1) % N;
+            } else {
+                self.len += 1;
+            }
+        }
+    }
+
+    /// The most recent `max` bytes (or all of them, if fewer), oldest first,
+    /// as two slices because the data may wrap around the end of the buffer.
+    pub fn recent(&self, max: usize) -> (&[u8], &[u8]) {
+        let n = core::cmp::min(max, self.len);
+        let first = (self.start + self.len - n) % N;
+        if first + n <= N {
+            (&self.buf[first..first + n], &[])
+        } else {
+            (&self.buf[first..], &self.buf[..first + n - N])
+        }
+    }
+
+    /// Copy the most recent bytes into `out`, oldest first. Returns the
+    /// number of bytes written.
+    pub fn copy_recent(&self, out: &mut [u8]) -> usize {
+        let (a, b) = self.recent(out.len());
+        out[..a.len()].copy_from_slice(a);
+        out[a.len()..a.len() + b.len()].copy_from_slice(b);
+        a.len() + b.len()
+    }
+}
+
+pub static LOG_RING: Mutex<LogRing<LOG_RING_SIZE>> = Mutex::new(LogRing::new());
+
+/// Writer used by `klog!`: output goes to the UART and into [`LOG_RING`].
+pub struct LogWriter;
+
+impl core::fmt::Write for LogWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let _ = crate::uart::Uart::new().write_str(s);
+        // A trap that logs while the ring is held loses its copy rather
+        // than deadlocking; the UART still has it.
+        if let Some(mut ring) = LOG_RING.try_lock() {
+            ring.push(s.as_bytes());
+        }
+        Ok(())
+    }
+}
+
 /// Internal macro for kernel logging with level filtering.
 #[macro_export]
 macro_rules! klog {
     ($level:expr, $($arg:tt)*) => {{
         if $crate::logging::should_log($level) {
             use core::fmt::Write;
-            let mut uart = $crate::uart::Uart::new();
-            let level_str = match $level {
-                uapi::LogLevel::Trace => "[TRACE] ",
-                uapi::LogLevel::Debug => "[DEBUG] ",
-                uapi::LogLevel::Info =>  "[INFO]  ",
-                uapi::LogLevel::Warn =>  "[WARN]  ",
-                uapi::LogLevel::Error => "[ERROR] ",
-            };
-            let _ = uart.write_str(level_str);
+            let mut uart = $crate::logging::LogWriter;
+            $crate::logging::write_prefix(&mut uart, $level);
             let _ = write!(uart, $($arg)*);
             let _ = uart.write_str("\r\n");
         }
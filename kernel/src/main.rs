@@ -6,12 +6,16 @@ mod entry;
 mod fs;
 mod kalloc;
 mod keyboard;
+mod keymap;
 mod logging;
+mod proc;
 mod sbi;
 mod sv39;
 mod timer;
 mod trap;
 mod trap_entry;
+mod trapframe;
+mod tty;
 mod uart;
 mod user;
 // mod user_blob;
@@ -20,6 +24,7 @@ mod console;
 mod display;
 mod elf;
 mod stack;
+mod syscall;
 
 use alloc::boxed::Box;
 use alloc::vec::Vec;
@@ -83,8 +88,18 @@ extern "C" fn rust_start() -> ! {
     trap::init(); // set stvec + enable SIE/STIE
     let _ = writeln!(uart, "traps enabled");
 
-    timer::init(); // arm first tick
-    let _ = writeln!(uart, "timers initialized");
+    // --- Parse cmdline early: it configures the timer and the display ---
+    // For testing, support compile-time display mode selection via feature flag
+    // In a real implementation, this would come from bootloader/device tree /chosen/bootargs
+    #[cfg(not(feature = "gpu"))]
+    let cmdline = ""; // Empty by default, meaning display=ansi
+    #[cfg(feature = "gpu")]
+    let cmdline = "display=gpu"; // GPU mode for testing
+
+    boot::cmdline::parse_cmdline(cmdline);
+
+    timer::init(boot::cmdline::tick_hz()); // arm first tick
+    let _ = writeln!(uart, "timers initialized ({} Hz)", timer::tick_hz());
 
     unsafe {
         sv39::enable_sv39();
@@ -92,6 +107,7 @@ extern "C" fn rust_start() -> ! {
     let _ = writeln!(uart, "SV39 paging enabled (identity map + UART)");
 
     // --- init kernel heap ---
+    sv39::assert_layout();
     kalloc::init();
     let _ = writeln!(uart, "Heap init OK.");
 
@@ -112,19 +128,10 @@ extern "C" fn rust_start() -> ! {
     let bogomips = timer::calibrate_bogomips();
     let _ = writeln!(uart, "BogoMIPS: {}.{:02}", bogomips / 100, bogomips % 100);
 
-    // --- Initialize writable filesystem with embedded files ---
-    fs::init_writable_fs();
-    let _ = writeln!(uart, "Writable filesystem initialized with embedded files");
+    // --- Filesystem: embedded files are shadowed on write, not copied ---
+    let _ = writeln!(uart, "Filesystem: {} embedded files (copy-on-write)", fs::FILES.len());
 
-    // --- Parse cmdline and initialize console/display ---
-    // For testing, support compile-time display mode selection via feature flag
-    // In a real implementation, this would come from bootloader/device tree /chosen/bootargs
-    #[cfg(not(feature = "gpu"))]
-    let cmdline = ""; // Empty by default, meaning display=ansi
-    #[cfg(feature = "gpu")]
-    let cmdline = "display=gpu"; // GPU mode for testing
-
-    boot::cmdline::parse_cmdline(cmdline);
+    // --- Initialize console/display (cmdline was parsed before the timer) ---
     console::init_console();
 
     // --- Initialize keyboard driver ---
@@ -154,16 +161,19 @@ extern "C" fn rust_start() -> ! {
 
     // --- Load the user ELF ---
 
-    // --- Load the shell ---
-    // Now get shell from writable filesystem
-    let shell_data = fs::get_file_data("shell.elf").expect("shell.elf not found in writable fs");
+    // --- Load the init program (shell.elf unless init= says otherwise) ---
+    let init = boot::cmdline::init_program();
+    let shell_data = match fs::get_file_data(init) {
+        Some(data) => data,
+        None => panic!("init program {} not found", init),
+    };
 
     // Example argv/envp
-    let argv = ["shell"];
+    let argv = [init];
     let envp = ["PATH=/"];
 
-    let user_stack_top_va: usize = 0x4000_8000;
-    let user_stack_bytes: usize = 16 * 1024;
+    let (user_stack_bottom, user_stack_top_va) = boot::cmdline::user_stack();
+    let user_stack_bytes = user_stack_top_va - user_stack_bottom;
 
     match elf::load_user_elf(
         &shell_data,
@@ -177,12 +187,13 @@ extern "C" fn rust_start() -> ! {
             let mut uart = crate::uart::Uart::new();
             let _ = writeln!(
                 uart,
-                "Loaded shell: entry=0x{:x}, sp=0x{:x}",
-                img.entry_va, img.user_sp
+                "Loaded {}: entry=0x{:x}, sp=0x{:x}",
+                init, img.entry_va, img.user_sp
             );
 
             unsafe {
                 crate::trap::USER_BRK = img.brk;
+                crate::trap::USER_STACK = (user_stack_bottom, user_stack_top_va);
             }
 
             enter_user_with(
@@ -196,7 +207,7 @@ extern "C" fn rust_start() -> ! {
         Err(e) => {
             use core::fmt::Write;
             let mut uart = crate::uart::Uart::new();
-            let _ = writeln!(uart, "*** Shell load error: {:?}", e);
+            let _ = writeln!(uart, "*** {} load error: {:?}", init, e);
             loop {
                 riscv::asm::wfi()
             }
@@ -0,0 +1,208 @@
+//! Bookkeeping for `spawn` and `wait`. The kernel runs one program at a
+//! time, so a spawning parent is parked, its trap frame plus whatever else
+//! the kernel has to put back (`T`), while the child runs to completion.
+//! The child's exit code is kept for the parent's next `wait`. There is a
+//! single parked slot: a spawned child cannot spawn in turn. Kept free of
+//! CSR access so kernel_lib can test it.
+
+use crate::trapframe::TrapFrame;
+use uapi::Pid;
+
+/// Pid of the first program; it keeps this pid across restarts.
+pub const INIT_PID: Pid = 1;
+
+/// Exit code reported for a program killed by a fault
+pub const FAULT_EXIT_CODE: i32 = -1;
+
+struct Parked<T> {
+    pid: Pid,
+    tf: TrapFrame,
+    saved: T,
+}
+
+/// Why [`Procs::spawn`] failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnError {
+    /// A parent is already parked in the single slot
+    SlotTaken,
+}
+
+/// A parent that got control back, as returned by [`Procs::exit`]
+pub struct Resumed<T> {
+    /// Pid of the child that ended
+    pub child: Pid,
+    /// The parent's registers as they were at its `spawn`
+    pub tf: TrapFrame,
+    pub saved: T,
+}
+
+pub struct Procs<T> {
+    current: Pid,
+    next_pid: Pid,
+    parked: Option<Parked<T>>,
+    /// Last child to exit and its code, until collected by `wait`
+    exited: Option<(Pid, i32)>,
+}
+
+impl<T> Default for Procs<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Procs<T> {
+    pub const fn new() -> Self {
+        Self { current: INIT_PID, next_pid: INIT_PID + 1, parked: None, exited: None }
+    }
+
+    /// Pid of the running program
+    pub fn current(&self) -> Pid {
+        self.current
+    }
+
+    /// False while a parent is parked: the slot is taken.
+    pub fn can_spawn(&self) -> bool {
+        self.parked.is_none()
+    }
+
+    /// Park the running program (`tf` as of its `spawn` call) and make a
+    /// new pid current for the child. Fails if the slot is taken.
+    pub fn spawn(&mut self, tf: &TrapFrame, saved: T) -> Result<Pid, SpawnError> {
+        if !self.can_spawn() {
+            return Err(SpawnError::SlotTaken);
+        }
+        let child = self.next_pid;
+        self.next_pid += 1;
+        self.parked = Some(Parked { pid: self.current, tf: tf.clone(), saved });
+        self.current = child;
+        Ok(child)
+    }
+
+    /// The running program ended with `code`: hand back the parked parent
+    /// and make it current again. `None` if nothing is parked, in which
+    /// case the caller restarts init and the code is dropped.
+    pub fn exit(&mut self, code: i32) -> Option<Resumed<T>> {
+        let back = self.unpark()?;
+        self.exited = Some((back.child, code));
+        Some(back)
+    }
+
+    /// Undo a `spawn` whose child never started. Unlike `exit` this leaves
+    /// nothing for `wait`.
+    pub fn abort_spawn(&mut self) -> Option<Resumed<T>> {
+        self.unpark()
+    }
+
+    fn unpark(&mut self) -> Option<Resumed<T>> {
+        let parent = self.parked.take()?;
+        let child = core::mem::replace(&mut self.current, parent.pid);
+        Some(Resumed { child, tf: parent.tf, saved: parent.saved })
+    }
+
+    /// Collect the pid and exit code of the child that last exited. Since
+    /// `spawn` only returns once the child is gone there is never anything
+    /// to block on; `None` if no child has exited since the last `wait`.
+    pub fn wait(&mut self) -> Option<(Pid, i32)> {
+        self.exited.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(sepc: usize) -> TrapFrame {
+        TrapFrame { sepc, sp: 0x4000_7f00, a0: 0x4000_1000, ..Default::default() }
+    }
+
+    #[test]
+    fn test_spawn_parks_parent_until_exit() {
+        let mut procs: Procs<&str> = Procs::new();
+        assert_eq!(procs.current(), INIT_PID);
+
+        let child = procs.spawn(&frame(0x4000_0120), "shell state").unwrap();
+        assert_eq!(child, 2);
+        assert_eq!(procs.current(), child);
+        // One slot: the child can't spawn again
+        assert!(!procs.can_spawn());
+        assert_eq!(procs.spawn(&frame(0x4000_0400), "child state"), Err(SpawnError::SlotTaken));
+        assert_eq!(procs.current(), child);
+
+        let back = procs.exit(0).unwrap();
+        assert_eq!(back.child, child);
+        assert_eq!(back.saved, "shell state");
+        assert_eq!((back.tf.sepc, back.tf.sp, back.tf.a0), (0x4000_0120, 0x4000_7f00, 0x4000_1000));
+        assert_eq!(procs.current(), INIT_PID);
+        assert!(procs.can_spawn());
+    }
+
+    #[test]
+    fn test_parked_frame_keeps_callee_saved_registers() {
+        let mut procs: Procs<()> = Procs::new();
+        let mut tf = frame(0x4000_0120);
+        tf.s = core::array::from_fn(|i| 0x5000 + i);
+        tf.gp = 0x4000_0800;
+        tf.tp = 0x4000_9000;
+        procs.spawn(&tf, ()).unwrap();
+
+        // The child runs on the same frame and leaves its own values behind
+        tf.s = [0xdead; 12];
+        tf.gp = 0;
+        tf.tp = 0;
+        tf.ra = 0xbad;
+
+        let back = procs.exit(0).unwrap();
+        assert_ne!((back.tf.s, back.tf.gp, back.tf.tp, back.tf.ra), (tf.s, tf.gp, tf.tp, tf.ra));
+        assert_eq!(back.tf.s, core::array::from_fn(|i| 0x5000 + i));
+        assert_eq!((back.tf.gp, back.tf.tp, back.tf.ra), (0x4000_0800, 0x4000_9000, 0));
+    }
+
+    #[test]
+    fn test_exit_without_parent_keeps_pid() {
+        let mut procs: Procs<()> = Procs::new();
+        assert!(procs.exit(3).is_none());
+        assert_eq!(procs.current(), INIT_PID);
+        assert_eq!(procs.wait(), None);
+
+        // Pids are not reused
+        assert_eq!(procs.spawn(&frame(0), ()), Ok(2));
+        assert!(procs.exit(0).is_some());
+        assert_eq!(procs.spawn(&frame(0), ()), Ok(3));
+    }
+
+    /// A child that runs and exits with `code`, as sys_spawn/sys_exit drive it
+    fn run_child(procs: &mut Procs<u32>, code: i32) -> Pid {
+        let child = procs.spawn(&frame(0x4000_0200), 7).unwrap();
+        assert_eq!(procs.current(), child);
+        let back = procs.exit(code).unwrap();
+        assert_eq!((back.child, back.saved, back.tf.sepc), (child, 7, 0x4000_0200));
+        child
+    }
+
+    #[test]
+    fn test_wait_collects_exit_code_once() {
+        let mut procs = Procs::new();
+        assert_eq!(procs.wait(), None);
+
+        let child = run_child(&mut procs, 42);
+        assert_eq!(procs.current(), INIT_PID);
+        assert_eq!(procs.wait(), Some((child, 42)));
+        assert_eq!(procs.wait(), None);
+
+        // A later child replaces an uncollected code
+        run_child(&mut procs, 1);
+        let last = run_child(&mut procs, FAULT_EXIT_CODE);
+        assert_eq!(procs.wait(), Some((last, -1)));
+    }
+
+    #[test]
+    fn test_abort_spawn_leaves_nothing_to_wait_for() {
+        let mut procs = Procs::new();
+        let child = procs.spawn(&frame(0x4000_0300), 1u32).unwrap();
+        let back = procs.abort_spawn().unwrap();
+        assert_eq!((back.child, back.saved), (child, 1));
+        assert_eq!(procs.current(), INIT_PID);
+        assert_eq!(procs.wait(), None);
+        assert!(procs.abort_spawn().is_none());
+    }
+}
@@ -2,6 +2,7 @@
 //! and map UART (0x1000_0000) as RW (no exec) with a 4 KiB page.
 #![allow(dead_code)]
 
+use alloc::vec::Vec;
 use core::mem::MaybeUninit;
 
 // ----- Sv39 constants -----
@@ -96,28 +97,95 @@ pub fn vpn_indices(va: usize) -> [usize; 3] {
 const USER_PA_POOL_START: usize = 0x8800_0000 - 0x0100_000 - 0x10000; // start 1 MiB below top - 64 KiB
 const USER_PA_POOL_END: usize = 0x8800_0000 - 0x10000; // 1 MiB pool
 static mut USER_NEXT_PA: usize = USER_PA_POOL_START;
+// Pages below this belong to a program parked by `spawn`
+static mut USER_POOL_FLOOR: usize = USER_PA_POOL_START;
 
-pub unsafe fn alloc_user_page() -> usize {
+/// Take a zeroed page from the user pool, or `None` once the pool is exhausted.
+pub unsafe fn alloc_user_page() -> Option<usize> {
     let pa = USER_NEXT_PA;
     let next_pa = pa + PAGE_SIZE;
     
     // Check if we're out of space in the user page pool
     if next_pa > USER_PA_POOL_END {
-        panic!(
-            "Out of user pages! Pool exhausted at address 0x{:x}, limit 0x{:x}",
-            pa, USER_PA_POOL_END
-        );
+        return None;
     }
     
     USER_NEXT_PA = next_pa;
     // Zero the page to avoid stale data from previous programs
     core::ptr::write_bytes(pa as *mut u8, 0, PAGE_SIZE);
-    pa
+    Some(pa)
+}
+
+// Hart0 boot stack that memory.ld reserves just below `_stack_top`
+const BOOT_STACK_SIZE: usize = 0x10000;
+
+/// A named physical range `[start, end)` used by the layout checks.
+pub type Region = (&'static str, usize, usize);
+
+/// First pair of regions that share at least one byte, if any.
+pub fn find_overlap(regions: &[Region]) -> Option<(Region, Region)> {
+    for (i, &a) in regions.iter().enumerate() {
+        for &b in &regions[i + 1..] {
+            if a.1 < b.2 && b.1 < a.2 {
+                return Some((a, b));
+            }
+        }
+    }
+    None
+}
+
+/// Panic at boot if the kernel heap, the user page pool and the boot stack
+/// overlap each other or spill outside DRAM. Call before `kalloc::init`.
+pub fn assert_layout() {
+    extern "C" {
+        static __heap_start: u8;
+        static __heap_end: u8;
+        static _stack_top: u8;
+    }
+    let heap_start = unsafe { &__heap_start as *const u8 as usize };
+    let heap_end = unsafe { &__heap_end as *const u8 as usize };
+    let stack_top = unsafe { &_stack_top as *const u8 as usize };
+
+    let regions: [Region; 3] = [
+        ("kernel heap", heap_start, heap_end),
+        ("user page pool", USER_PA_POOL_START, USER_PA_POOL_END),
+        ("boot stack", stack_top - BOOT_STACK_SIZE, stack_top),
+    ];
+    for &(name, start, end) in &regions {
+        if start >= end || start < DRAM_BASE || end > DRAM_BASE + DRAM_SIZE {
+            panic!("memory layout: {} {:#x}..{:#x} is empty or outside DRAM", name, start, end);
+        }
+    }
+    if let Some((a, b)) = find_overlap(&regions) {
+        panic!(
+            "memory layout: {} {:#x}..{:#x} overlaps {} {:#x}..{:#x}",
+            a.0, a.1, a.2, b.0, b.1, b.2
+        );
+    }
+}
+
+/// Number of pages still available in the user page pool
+pub fn free_user_pages() -> usize {
+    unsafe { (USER_PA_POOL_END - USER_NEXT_PA) / PAGE_SIZE }
 }
 
 /// Reset the user page allocator to the initial state, effectively freeing all user pages
+/// above the floor set by `pin_user_pages`
 pub unsafe fn reset_user_pages() {
-    USER_NEXT_PA = USER_PA_POOL_START;
+    USER_NEXT_PA = USER_POOL_FLOOR;
+}
+
+/// Keep every user page handed out so far across later `reset_user_pages`
+/// calls, so a parked program's memory survives its child. Returns the old
+/// floor, to be put back with `set_user_pool_floor` once the child is gone.
+pub unsafe fn pin_user_pages() -> usize {
+    let old = USER_POOL_FLOOR;
+    USER_POOL_FLOOR = USER_NEXT_PA;
+    old
+}
+
+pub unsafe fn set_user_pool_floor(floor: usize) {
+    USER_POOL_FLOOR = floor;
 }
 
 /// Helper to extract physical address from a PTE
@@ -199,6 +267,68 @@ pub unsafe fn clear_user_mappings() {
     }
 }
 
+/// Every 4 KiB user leaf as `(va, pte)`, for `restore_user_mappings`.
+/// User programs are only ever mapped with 4 KiB pages.
+pub unsafe fn user_mappings() -> Vec<(usize, u64)> {
+    let mut maps = Vec::new();
+    let root = PT_ROOT;
+    if root.is_null() {
+        return maps;
+    }
+    for i2 in 0..ENTRIES {
+        let entry2 = *root.add(i2);
+        if (entry2 & PTE_V) == 0 || (entry2 & (PTE_R | PTE_W | PTE_X)) != 0 {
+            continue;
+        }
+        let l1 = pte_to_pa(entry2) as *const u64;
+        for i1 in 0..ENTRIES {
+            let entry1 = *l1.add(i1);
+            if (entry1 & PTE_V) == 0 || (entry1 & (PTE_R | PTE_W | PTE_X)) != 0 {
+                continue;
+            }
+            let l0 = pte_to_pa(entry1) as *const u64;
+            for i0 in 0..ENTRIES {
+                let entry0 = *l0.add(i0);
+                if (entry0 & PTE_V) != 0 && (entry0 & PTE_U) != 0 {
+                    maps.push(((i2 << 30) | (i1 << 21) | (i0 << 12), entry0));
+                }
+            }
+        }
+    }
+    maps
+}
+
+/// Put back mappings saved by `user_mappings`. The intermediate tables are
+/// never freed, so this doesn't need new page-table pages.
+pub unsafe fn restore_user_mappings(maps: &[(usize, u64)]) {
+    for &(va, pte) in maps {
+        map_4k(PT_ROOT, va, pte_to_pa(pte), pte & ((1 << PTE_PPN_SHIFT) - 1));
+    }
+}
+
+/// Translate a VA to its PA by walking the page table.
+/// Handles 4 KiB, 2 MiB and 1 GiB leaves; returns `None` if `va` is unmapped.
+pub unsafe fn translate(root: *mut u64, va: usize) -> Option<usize> {
+    if root.is_null() {
+        return None;
+    }
+    let idx = vpn_indices(va);
+    let mut table = root;
+    for level in (0..3).rev() {
+        let entry = *table.add(idx[level]);
+        if (entry & PTE_V) == 0 {
+            return None;
+        }
+        if (entry & (PTE_R | PTE_W | PTE_X)) != 0 {
+            // Leaf: the low bits of the VA become the page offset
+            let offset_mask = (1usize << (PAGE_OFFSET_BITS + 9 * level)) - 1;
+            return Some((pte_to_pa(entry) & !offset_mask) | (va & offset_mask));
+        }
+        table = pte_to_pa(entry) as *mut u64;
+    }
+    None
+}
+
 // ----- Mapping helpers -----
 
 pub(crate) unsafe fn map_4k(root: *mut u64, va: usize, pa: usize, flags: u64) {
@@ -309,28 +439,87 @@ pub unsafe fn enable_sv39() {
     riscv::asm::sfence_vma_all();
 }
 
+/// User VA window reserved for the framebuffer mapping, above the ELF image
+/// area and the mmap window.
+pub const FB_VA_BASE: usize = 0x3000_0000;
+pub const FB_VA_LIMIT: usize = 0x4000_0000;
+
+/// Work out how to map `fb_size` bytes at `fb_pa` into the framebuffer window.
+/// Returns `(first_pa, pages, user_va)` where `first_pa` is page aligned and
+/// `user_va` is the address of byte 0 of the framebuffer, or `None` if the
+/// buffer is empty or does not fit below `FB_VA_LIMIT`.
+pub fn fb_map_plan(fb_pa: usize, fb_size: usize) -> Option<(usize, usize, usize)> {
+    if fb_size == 0 {
+        return None;
+    }
+    let offset = fb_pa & (PAGE_SIZE - 1);
+    let pages = offset.checked_add(fb_size)?.div_ceil(PAGE_SIZE);
+    let end = FB_VA_BASE.checked_add(pages.checked_mul(PAGE_SIZE)?)?;
+    if end > FB_VA_LIMIT {
+        return None;
+    }
+    Some((fb_pa - offset, pages, FB_VA_BASE + offset))
+}
+
+/// User VA window for anonymous `mmap` regions: above the ELF image area and
+/// below the framebuffer window.
+pub const MMAP_VA_BASE: usize = 0x2000_0000;
+pub const MMAP_VA_LIMIT: usize = FB_VA_BASE;
+
+/// Place an anonymous mapping of `len` bytes at `next`, the first unused VA in
+/// the mmap window. Returns `(va, pages)`, or `None` if `len` is 0 or the
+/// region would run past `MMAP_VA_LIMIT`.
+pub fn mmap_plan(next: usize, len: usize) -> Option<(usize, usize)> {
+    if len == 0 || next < MMAP_VA_BASE || !next.is_multiple_of(PAGE_SIZE) {
+        return None;
+    }
+    let pages = len.div_ceil(PAGE_SIZE);
+    let end = next.checked_add(pages.checked_mul(PAGE_SIZE)?)?;
+    if end > MMAP_VA_LIMIT {
+        return None;
+    }
+    Some((next, pages))
+}
+
+/// True if growing the heap from `cur_brk` to `new_brk` would map pages in
+/// the stack `(bottom, top)`, the guard page below it, or the mmap or
+/// framebuffer window. Only the pages the growth adds are checked.
+pub fn brk_collides(cur_brk: usize, new_brk: usize, stack: (usize, usize)) -> bool {
+    let start = cur_brk.next_multiple_of(PAGE_SIZE);
+    let Some(end) = new_brk.checked_next_multiple_of(PAGE_SIZE) else {
+        return true;
+    };
+    let (bottom, top) = stack;
+    let reserved = [
+        (bottom.saturating_sub(PAGE_SIZE), top),
+        (MMAP_VA_BASE, MMAP_VA_LIMIT),
+        (FB_VA_BASE, FB_VA_LIMIT),
+    ];
+    start < end && reserved.iter().any(|&(s, e)| start < e && s < end)
+}
+
 /// Map framebuffer memory into user space
-/// Maps the physical framebuffer at fb_pa to a virtual address in user space
-/// Returns the user VA where the framebuffer was mapped
+/// Maps the physical framebuffer at fb_pa page by page (RW|U) into the
+/// `FB_VA_BASE..FB_VA_LIMIT` window.
+/// Returns the user VA where the framebuffer was mapped, or 0 if it doesn't fit
 pub unsafe fn map_framebuffer_to_user(fb_pa: usize, fb_size: usize) -> usize {
     let root = root_pt();
     if root.is_null() {
         return 0;
     }
-    
-    // Choose a virtual address high in user space (but below kernel space)
-    // User space goes up to 0x40000000, so let's use 0x30000000 for framebuffer
-    const FB_VA_BASE: usize = 0x30000000;
-    
+
+    let Some((first_pa, pages, user_va)) = fb_map_plan(fb_pa, fb_size) else {
+        return 0;
+    };
+
     // Map each page of the framebuffer
-    let num_pages = (fb_size + PAGE_SIZE - 1) / PAGE_SIZE;
-    for i in 0..num_pages {
+    for i in 0..pages {
         let va = FB_VA_BASE + i * PAGE_SIZE;
-        let pa = fb_pa + i * PAGE_SIZE;
+        let pa = first_pa + i * PAGE_SIZE;
         map_4k(root, va, pa, URW); // User read-write
     }
     
-    FB_VA_BASE
+    user_va
 }
 
 #[cfg(test)]
@@ -0,0 +1,118 @@
+//! Syscall dispatch table: one handler slot per `uapi::nr` number. trap.rs
+//! fills the table; lookups, the completeness check and what a handler's
+//! result does to the caller's registers live here, free of CSR access, so
+//! kernel_lib can test them.
+
+use crate::trapframe::TrapFrame;
+use uapi::nr;
+
+/// How a syscall handler finished. The dispatcher applies it, so no handler
+/// moves `sepc` itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyscallResult {
+    /// Return the value in a0 and resume after the `ecall`
+    Return(usize),
+    /// The frame now starts a different program (exec, spawn, exit to
+    /// init); leave it as it is
+    ReplaceImage,
+}
+
+impl SyscallResult {
+    /// Finish the syscall whose frame is `tf`
+    pub fn apply(self, tf: &mut TrapFrame) {
+        match self {
+            SyscallResult::Return(value) => tf.return_from_ecall(value),
+            SyscallResult::ReplaceImage => {}
+        }
+    }
+}
+
+pub type Handler = fn(&mut TrapFrame) -> SyscallResult;
+
+/// Handlers indexed by syscall number; `None` where no syscall is defined
+pub type Table = [Option<Handler>; nr::COUNT];
+
+/// The handler for `nr`, or `None` for numbers past the end of the table
+/// and for gaps in it
+pub fn lookup(table: &Table, nr: usize) -> Option<Handler> {
+    table.get(nr).copied().flatten()
+}
+
+/// First number in `nrs` without a handler. Const so trap.rs can check its
+/// table against [`nr::ALL`] at compile time.
+pub const fn first_missing(table: &Table, nrs: &[usize]) -> Option<usize> {
+    let mut i = 0;
+    while i < nrs.len() {
+        let n = nrs[i];
+        if n >= table.len() || table[n].is_none() {
+            return Some(n);
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mark(tf: &mut TrapFrame) -> SyscallResult {
+        SyscallResult::Return(tf.a0 + 7)
+    }
+
+    /// A table with a handler for every defined number
+    fn full() -> Table {
+        let mut t: Table = [None; nr::COUNT];
+        for &n in nr::ALL {
+            t[n] = Some(mark);
+        }
+        t
+    }
+
+    #[test]
+    fn test_return_sets_a0_and_skips_the_ecall() {
+        let mut tf = TrapFrame { sepc: 0x1_0040, a0: 3, ..Default::default() };
+        SyscallResult::Return(42).apply(&mut tf);
+        assert_eq!((tf.sepc, tf.a0), (0x1_0044, 42));
+        SyscallResult::Return(usize::MAX).apply(&mut tf);
+        assert_eq!((tf.sepc, tf.a0), (0x1_0048, usize::MAX));
+    }
+
+    #[test]
+    fn test_replace_image_leaves_the_frame_alone() {
+        // As load_program leaves it: at the new program's entry
+        let mut tf = TrapFrame { sepc: 0x1_0000, a0: 1, a1: 0x0fff_ff00, ..Default::default() };
+        SyscallResult::ReplaceImage.apply(&mut tf);
+        assert_eq!((tf.sepc, tf.a0, tf.a1), (0x1_0000, 1, 0x0fff_ff00));
+    }
+
+    #[test]
+    fn test_lookup_runs_the_handler() {
+        let t = full();
+        let mut tf = TrapFrame::default();
+        assert_eq!(lookup(&t, nr::WRITE).unwrap()(&mut tf), SyscallResult::Return(7));
+        assert!(lookup(&t, nr::CHECKSUM).is_some());
+    }
+
+    #[test]
+    fn test_lookup_out_of_range_and_gaps() {
+        let mut t = full();
+        // 0 is not a syscall
+        assert!(lookup(&t, 0).is_none());
+        assert!(lookup(&t, nr::COUNT).is_none());
+        assert!(lookup(&t, usize::MAX).is_none());
+        t[nr::STAT] = None;
+        assert!(lookup(&t, nr::STAT).is_none());
+    }
+
+    #[test]
+    fn test_first_missing_finds_gaps() {
+        let mut t = full();
+        assert_eq!(first_missing(&t, nr::ALL), None);
+        t[nr::MMAP] = None;
+        t[nr::SYNC] = None;
+        assert_eq!(first_missing(&t, nr::ALL), Some(nr::MMAP));
+        // A number the table has no room for counts as missing
+        assert_eq!(first_missing(&full(), &[nr::EXIT, nr::COUNT]), Some(nr::COUNT));
+    }
+}
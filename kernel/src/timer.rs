@@ -7,30 +7,89 @@ use crate::sbi;
 pub static TICKS: AtomicU64 = AtomicU64::new(0);
 
 // 10ms at ~10_000_000 Hz?  NOTE: On QEMU/OpenSBI, the timebase is usually
-// 10 MHz. We use 100_000 cycles ≈ 10ms. Adjust if you want faster/slower.
-const TICK_INTERVAL: u64 = 100_000;
+// 10 MHz. We use 100_000 cycles ≈ 10ms by default; override with `tickhz=`.
+const DEFAULT_TICK_INTERVAL: u64 = 100_000;
 
 // Timebase frequency in Hz (10 MHz on QEMU virt with OpenSBI)
 const TIMEBASE_HZ: u64 = 10_000_000;
 
-pub fn init() {
+// Cycles between timer interrupts, fixed by `init`
+static TICK_INTERVAL: AtomicU64 = AtomicU64::new(DEFAULT_TICK_INTERVAL);
+
+/// Timebase cycles per tick for a requested rate; falls back to the default
+/// when no rate is given or it is 0 or faster than the timebase itself.
+pub fn interval_for_hz(tick_hz: Option<u64>, timebase: u64) -> u64 {
+    match tick_hz {
+        Some(hz) if hz > 0 && hz <= timebase => timebase / hz,
+        _ => DEFAULT_TICK_INTERVAL,
+    }
+}
+
+fn tick_interval() -> u64 {
+    TICK_INTERVAL.load(Ordering::Relaxed)
+}
+
+/// Tick rate in Hz
+pub fn tick_hz() -> u64 {
+    TIMEBASE_HZ / tick_interval()
+}
+
+/// Set the tick rate (from `tickhz=`, or the default) and arm the first tick.
+pub fn init(tick_hz: Option<u64>) {
+    TICK_INTERVAL.store(interval_for_hz(tick_hz, TIMEBASE_HZ), Ordering::Relaxed);
+
     // Arm first tick
     let now: u64 = time::read().try_into().unwrap(); // allowed in S-mode on QEMU virt (OpenSBI delegates time)
-    sbi::set_timer(now + TICK_INTERVAL); // program next interrupt
+    sbi::set_timer(now + tick_interval()); // program next interrupt
+}
+
+/// Number of timer ticks since boot.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
 }
 
-// Cursor blink rate in timer ticks (50 ticks * 10ms = 500ms)
-const CURSOR_BLINK_TICKS: u64 = 50;
+/// Timebase cycles since boot, to tick resolution (what an emulated
+/// `rdtime` returns).
+pub fn uptime_cycles() -> u64 {
+    ticks().saturating_mul(tick_interval())
+}
+
+/// Milliseconds since boot, derived from the tick count.
+pub fn uptime_ms() -> u64 {
+    ticks_to_ms(ticks(), tick_interval(), TIMEBASE_HZ)
+}
+
+/// Whole seconds since boot.
+pub fn seconds() -> u64 {
+    ticks_to_secs(ticks(), tick_interval(), TIMEBASE_HZ)
+}
+
+/// Convert `ticks` of `interval` cycles each at `timebase` Hz to milliseconds.
+pub fn ticks_to_ms(ticks: u64, interval: u64, timebase: u64) -> u64 {
+    let ms = (ticks as u128 * interval as u128 * 1000) / timebase as u128;
+    u64::try_from(ms).unwrap_or(u64::MAX)
+}
+
+/// Convert `ticks` of `interval` cycles each at `timebase` Hz to whole seconds.
+pub fn ticks_to_secs(ticks: u64, interval: u64, timebase: u64) -> u64 {
+    let secs = (ticks as u128 * interval as u128) / timebase as u128;
+    u64::try_from(secs).unwrap_or(u64::MAX)
+}
+
+// Cursor blink period in milliseconds
+const CURSOR_BLINK_MS: u64 = 500;
 
 pub fn on_timer() {
     // acknowledge and schedule next
+    let interval = tick_interval();
     let now: u64 = time::read().try_into().unwrap();
-    sbi::set_timer(now + TICK_INTERVAL);
+    sbi::set_timer(now + interval);
 
     let t = TICKS.fetch_add(1, Ordering::Relaxed) + 1;
 
-    // Update cursor blink every CURSOR_BLINK_TICKS (~500ms with 10ms ticks)
-    if t % CURSOR_BLINK_TICKS == 0 {
+    // Update cursor blink every ~500ms whatever the tick rate
+    let blink_ticks = (TIMEBASE_HZ * CURSOR_BLINK_MS / 1000 / interval).max(1);
+    if t.is_multiple_of(blink_ticks) {
         crate::console::update_cursor_blink();
     }
 
@@ -64,13 +123,22 @@ pub fn calibrate_bogomips() -> u64 {
     }
     
     let end_time: u64 = time::read().try_into().unwrap();
-    let elapsed_cycles = end_time - start_time;
-    
-    // Calculate loops per second
-    let total_loops = loops * 1000; // multiply by inner loop count
-    let loops_per_second = (total_loops * TIMEBASE_HZ) / elapsed_cycles;
+    let elapsed_cycles = end_time.saturating_sub(start_time);
     
-    // BogoMIPS = (loops per second * 100) / 1,000,000
-    // This gives us a value scaled by 100 for XX.YY format
-    (loops_per_second * 100) / 1_000_000
+    // multiply by inner loop count
+    bogomips_from(loops.saturating_mul(1000), TIMEBASE_HZ, elapsed_cycles)
+}
+
+/// BogoMIPS scaled by 100 (for XX.YY format) from `loops` delay iterations
+/// that took `elapsed` timer cycles at `timebase` Hz.
+///
+/// BogoMIPS = (loops per second * 100) / 1,000,000. Uses `u128` intermediates
+/// so large loop counts can't overflow; returns 0 if `elapsed` is 0.
+pub fn bogomips_from(loops: u64, timebase: u64, elapsed: u64) -> u64 {
+    if elapsed == 0 {
+        return 0;
+    }
+    // x * 100 / (e * 1_000_000) == x / (e * 10_000), and loops * timebase fits in u128
+    let scaled = (loops as u128 * timebase as u128) / (elapsed as u128 * 10_000);
+    u64::try_from(scaled).unwrap_or(u64::MAX)
 }
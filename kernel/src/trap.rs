@@ -1,6 +1,9 @@
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::fmt::Write;
 use spin::Mutex;
 use uapi::nr;
+use uapi::path::{PathBuf, PATH_MAX};
 
 // kernel/src/trap.rs
 use riscv::{
@@ -17,29 +20,9 @@ use crate::boot::cmdline;
 #[cfg(feature = "gpu")]
 use crate::display::{fb_console, DisplayMode};
 use crate::fs;
+use crate::syscall::SyscallResult::{self, Return, ReplaceImage};
 
-#[repr(C)]
-pub struct TrapFrame {
-    pub ra: usize, // x1
-    pub sp: usize, // x2 (interrupted SP)
-    pub t0: usize, // x5
-    pub t1: usize, // x6
-    pub t2: usize, // x7
-    pub a0: usize, // x10
-    pub a1: usize, // x11
-    pub a2: usize, // x12
-    pub a3: usize, // x13
-    pub a4: usize, // x14
-    pub a5: usize, // x15
-    pub a6: usize, // x16
-    pub a7: usize, // x17
-    pub t3: usize, // x28
-    pub t4: usize, // x29
-    pub t5: usize, // x30
-    pub t6: usize, // x31
-    pub sepc: usize,
-    pub sstatus_bits: usize,
-}
+pub use crate::trapframe::TrapFrame;
 
 extern "C" {
     fn __trap_entry();
@@ -54,6 +37,62 @@ pub fn init() {
     }
 }
 
+/// Syscall handlers by `nr` number
+const SYSCALLS: crate::syscall::Table = {
+    let mut t: crate::syscall::Table = [None; nr::COUNT];
+    t[nr::WRITE] = Some(sys_write_ptrlen);                // write(ptr, len)
+    t[nr::EXIT] = Some(sys_exit);                         // exit(code)
+    t[nr::WRITE_CSTR] = Some(sys_write_cstr);             // write_cstr(ptr)
+    t[nr::OPEN] = Some(sys_open);                         // open_cstr(path)
+    t[nr::READ] = Some(sys_read);                         // read(fd, buf, len)
+    t[nr::WRITE_FD] = Some(sys_write_fd);                 // write(fd, buf, len)
+    t[nr::CLOSE] = Some(sys_close);                       // close(fd)
+    t[nr::LSEEK] = Some(sys_lseek);                       // lseek(fd, offset, whence)
+    t[nr::BRK] = Some(sys_brk);                           // brk(addr)
+    t[nr::GETTIME] = Some(sys_gettime);                   // gettime(ts_ptr)
+    t[nr::POWEROFF] = Some(sys_poweroff);                 // poweroff()
+    t[nr::EXEC] = Some(sys_exec);                         // exec(path)
+    t[nr::EXECV] = Some(sys_execv);                       // execv(path, argv)
+    t[nr::CREAT] = Some(sys_creat);                       // creat(path, mode)
+    t[nr::UNLINK] = Some(sys_unlink);                     // unlink(path)
+    t[nr::STAT] = Some(sys_stat);                         // stat(path, buf)
+    t[nr::CHMOD] = Some(sys_chmod);                       // chmod(path, mode)
+    t[nr::READDIR] = Some(sys_readdir);                   // readdir(buf, len)
+    t[nr::GET_FB_INFO] = Some(sys_get_fb_info);           // get_fb_info(buf)
+    t[nr::FB_FLUSH] = Some(sys_fb_flush);                 // fb_flush()
+    t[nr::READV] = Some(sys_readv);                       // readv(fd, iov, iovcnt)
+    t[nr::FTRUNCATE] = Some(sys_ftruncate);               // ftruncate(fd, len)
+    t[nr::SENDFILE] = Some(sys_sendfile);                 // sendfile(out_fd, in_fd, len)
+    t[nr::GETDENTS] = Some(sys_getdents);                 // getdents(buf, len, start)
+    t[nr::WRITE_RAW] = Some(sys_write_raw);               // write_raw(fd, buf, len)
+    t[nr::SET_TERM_MODE] = Some(sys_set_term_mode);       // set_term_mode(flags)
+    t[nr::MEMINFO] = Some(sys_meminfo);                   // meminfo(buf)
+    t[nr::POLLIN] = Some(sys_pollin);                     // pollin()
+    t[nr::MMAP] = Some(sys_mmap);                         // mmap(len)
+    t[nr::GET_DISPLAY_MODE] = Some(sys_get_display_mode); // get_display_mode()
+    t[nr::SET_DISPLAY_MODE] = Some(sys_set_display_mode); // set_display_mode(mode)
+    t[nr::DMESG] = Some(sys_dmesg);                       // dmesg(buf, len)
+    t[nr::YIELD] = Some(sys_yield);                       // yield()
+    t[nr::MKDIR] = Some(sys_mkdir);                       // mkdir(path)
+    t[nr::EXECVE] = Some(sys_execve);                     // execve(path, argv, envp)
+    t[nr::RESOLVE] = Some(sys_resolve);                   // resolve(name, buf, len)
+    t[nr::RENAME] = Some(sys_rename);                     // rename(src, dst)
+    t[nr::CHDIR] = Some(sys_chdir);                       // chdir(path)
+    t[nr::GETCWD] = Some(sys_getcwd);                     // getcwd(buf, len)
+    t[nr::SYNC] = Some(sys_sync);                         // sync()
+    t[nr::SPAWN] = Some(sys_spawn);                       // spawn(path, argv)
+    t[nr::WAIT] = Some(sys_wait);                         // wait(code_ptr)
+    t[nr::CHECKSUM] = Some(sys_checksum);                 // checksum(path)
+    t[nr::REGDUMP] = Some(sys_regdump);                   // regdump()
+    t
+};
+
+// Every number in uapi::nr needs a slot filled above
+const _: () = assert!(
+    crate::syscall::first_missing(&SYSCALLS, nr::ALL).is_none(),
+    "a uapi::nr syscall has no handler in SYSCALLS"
+);
+
 #[no_mangle]
 extern "C" fn rust_trap(tf: &mut TrapFrame) {
     let raw_trap: Trap<usize, usize> = scause::read().cause();
@@ -64,61 +103,131 @@ extern "C" fn rust_trap(tf: &mut TrapFrame) {
             crate::timer::on_timer();
         }
         Trap::Exception(Exception::UserEnvCall) => {
-            // DEBUG: see what user passed
-            /*
-            {
-                let mut uart = crate::uart::Uart::new();
-                let _ = writeln!(uart, "[syscall a7={} a0=0x{:x} a1=0x{:x}]", tf.a7, tf.a0, tf.a1);
-            }
-            */
-
             // Syscall ABI: a7 = nr, a0.. = args; ecall is 4-byte insn
-            match tf.a7 {
-                nr::WRITE => sys_write_ptrlen(tf),    // write(ptr, len)
-                nr::EXIT => sys_exit(tf),             // exit()
-                nr::WRITE_CSTR => sys_write_cstr(tf), // write_cstr(ptr)
-                nr::OPEN => sys_open(tf),             // open_cstr(path)
-                nr::READ => sys_read(tf),             // read(fd, buf, len)
-                nr::WRITE_FD => sys_write_fd(tf),     // write(fd, buf, len)
-                nr::CLOSE => sys_close(tf),           // close(fd)
-                nr::LSEEK => sys_lseek(tf),           // lseek(fd, offset, whence)
-                nr::BRK => sys_brk(tf),               // brk(addr)
-                nr::GETTIME => sys_gettime(tf),       // gettime(ts_ptr)
-                nr::POWEROFF => sys_poweroff(tf),     // poweroff()
-                nr::EXEC => sys_exec(tf),             // exec(path)
-                nr::EXECV => sys_execv(tf),           // execv(path, argv)
-                nr::CREAT => sys_creat(tf),           // creat(path, mode)
-                nr::UNLINK => sys_unlink(tf),         // unlink(path)
-                nr::STAT => sys_stat(tf),             // stat(path, buf)
-                nr::CHMOD => sys_chmod(tf),           // chmod(path, mode)
-                nr::READDIR => sys_readdir(tf),       // readdir(buf, len)
-                nr::GET_FB_INFO => sys_get_fb_info(tf), // get_fb_info(buf)
-                nr::FB_FLUSH => sys_fb_flush(tf),     // fb_flush()
-                nr => {
+            // (loglevel=trace shows every call)
+            let num = tf.a7;
+            crate::ktrace!(
+                "syscall {}({}) a0={:#x} a1={:#x} a2={:#x}",
+                nr::name(num),
+                num,
+                tf.a0,
+                tf.a1,
+                tf.a2
+            );
+            // The handler's result sets a0 and moves sepc past the ecall
+            let result = match crate::syscall::lookup(&SYSCALLS, num) {
+                Some(handler) => handler(tf),
+                None => {
                     let mut uart = crate::uart::Uart::new();
-                    let _ = writeln!(uart, "\r\nunknown syscall: {}", nr);
-                    tf.a0 = usize::MAX;
-                    tf.sepc = tf.sepc.wrapping_add(4);
+                    let _ = writeln!(uart, "\r\nunknown syscall: {}", num);
+                    Return(usize::MAX)
                 }
+            };
+            match result {
+                Return(v) => crate::ktrace!("syscall {} -> {:#x}", nr::name(num), v),
+                ReplaceImage => crate::ktrace!("syscall {} -> new image at {:#x}", nr::name(num), tf.sepc),
+            }
+            result.apply(tf);
+        }
+        Trap::Exception(Exception::IllegalInstruction) if crate::trapframe::is_user_fault(tf.sstatus_bits) => {
+            if !emulate_counter_read(tf) {
+                kill_user(tf, standard_trap);
             }
         }
+        other if crate::trapframe::is_user_fault(tf.sstatus_bits) => kill_user(tf, other),
         other => {
-            use core::fmt::Write;
             let mut uart = crate::uart::Uart::new();
-            let _ = writeln!(
-                uart,
-                "\r\n*** TRAP *** scause={:?} sepc=0x{:016x} stval={:#x}",
+            let _ = writeln!(uart, "\r\nkernel fault, registers at the trap:");
+            let _ = crate::trapframe::dump_trapframe(&mut uart, tf, scause::read().bits(), stval::read());
+            panic!(
+                "kernel trap: scause={:?} sepc=0x{:016x} stval={:#x}",
                 other,
                 sepc::read(),
                 stval::read()
             );
-            loop {
-                unsafe { core::arch::asm!("wfi") }
-            }
         }
     }
 }
 
+/// True if `addr` is in the page just below a stack whose lowest byte is at
+/// `stack_bottom`, i.e. the stack ran off its end. A bottom of 0 means no
+/// stack is known.
+pub fn in_stack_guard(addr: usize, stack_bottom: usize) -> bool {
+    stack_bottom != 0 && addr < stack_bottom && addr >= stack_bottom.saturating_sub(4096)
+}
+
+/// Log a user fault and end the faulting program as if it had exited.
+fn kill_user(tf: &mut TrapFrame, cause: Trap<Interrupt, Exception>) {
+    let addr = stval::read();
+    let (stack_bottom, stack_top) = unsafe { USER_STACK };
+    let page_fault = matches!(
+        cause,
+        Trap::Exception(Exception::LoadPageFault | Exception::StorePageFault)
+    );
+    let pid = PROCS.lock().current();
+    if page_fault && in_stack_guard(addr, stack_bottom) {
+        crate::kerror!(
+            "user stack overflow: addr={:#x} below stack {:#x}..{:#x} sepc=0x{:016x}, killing pid {}",
+            addr,
+            stack_bottom,
+            stack_top,
+            tf.sepc,
+            pid
+        );
+    } else {
+        crate::kerror!(
+            "user fault: scause={:?} stval={:#x} sepc=0x{:016x}, killing pid {}",
+            cause,
+            addr,
+            tf.sepc,
+            pid
+        );
+    }
+    exit_current(tf, crate::proc::FAULT_EXIT_CODE).apply(tf);
+}
+
+/// CSR numbers of the user counters (`rdcycle`, `rdtime`, `rdinstret`)
+pub const CSR_CYCLE: u16 = 0xC00;
+pub const CSR_TIME: u16 = 0xC01;
+pub const CSR_INSTRET: u16 = 0xC02;
+
+/// Decode a plain CSR read, `csrrs rd, csr, x0` (what `csrr`/`rdtime`
+/// assemble to), into `(rd, csr)`. Anything else gives `None`.
+pub fn decode_csr_read(inst: u32) -> Option<(usize, u16)> {
+    let opcode = inst & 0x7f;
+    let funct3 = (inst >> 12) & 0x7;
+    let rs1 = (inst >> 15) & 0x1f;
+    if opcode != 0x73 || funct3 != 0b010 || rs1 != 0 {
+        return None;
+    }
+    Some((((inst >> 7) & 0x1f) as usize, (inst >> 20) as u16))
+}
+
+/// Emulate `rdcycle`/`rdtime`/`rdinstret` for a user program whose counter
+/// access trapped, answering with the time since boot in timebase cycles.
+/// Returns false if the instruction is anything else.
+fn emulate_counter_read(tf: &mut TrapFrame) -> bool {
+    // QEMU reports the faulting instruction in stval; fetch it if not
+    let inst = match stval::read() as u32 {
+        0 => unsafe {
+            with_sum_no_timer(|| {
+                let p = tf.sepc as *const u16;
+                core::ptr::read(p) as u32 | (core::ptr::read(p.add(1)) as u32) << 16
+            })
+        },
+        inst => inst,
+    };
+    let Some((rd, csr)) = decode_csr_read(inst) else { return false };
+    if !matches!(csr, CSR_CYCLE | CSR_TIME | CSR_INSTRET) {
+        return false;
+    }
+    if !tf.set_reg(rd, crate::timer::uptime_cycles() as usize) {
+        return false;
+    }
+    tf.sepc = tf.sepc.wrapping_add(4);
+    true
+}
+
 // helper: temporarily allow S-mode to load/store user pages
 #[inline(always)]
 unsafe fn with_sum<F, R>(f: F) -> R
@@ -132,14 +241,37 @@ where
     r
 }
 
-/// Helper to write a byte to UART with CR/LF handling.
-#[inline(always)]
+/// Feed `bytes` to `out`, inserting `\r` before each `\n` unless `raw`.
+/// Terminal output is "cooked" this way; `WRITE_RAW` skips the translation.
+/// Other bytes, including CP437 0xA0-0xFF, pass through untouched.
+fn emit_bytes<F: FnMut(u8)>(bytes: &[u8], raw: bool, mut out: F) {
+    for &b in bytes {
+        if !raw && b == b'\n' {
+            out(b'\r');
+        }
+        out(b);
+    }
+}
+
 fn uart_write_byte(b: u8) {
     let mut uart = crate::uart::Uart::new();
-    if b == b'\n' {
-        uart.write_byte(b'\r');
+    emit_bytes(&[b], false, |c| uart.write_byte(c));
+}
+
+/// Write bytes to the terminal verbatim (no CRLF translation).
+fn console_write_raw(bytes: &[u8], is_stderr: bool) {
+    #[cfg(feature = "gpu")]
+    {
+        if should_use_framebuffer(is_stderr) {
+            for &b in bytes {
+                fb_console::write_char(b);
+            }
+            return;
+        }
     }
-    uart.write_byte(b);
+    let _ = is_stderr;
+    let mut uart = crate::uart::Uart::new();
+    emit_bytes(bytes, true, |c| uart.write_byte(c));
 }
 
 /// Helper to check if output should go to framebuffer.
@@ -196,7 +328,7 @@ fn console_write_str(s: &str, is_stderr: bool) {
     }
 }
 
-fn sys_write_ptrlen(tf: &mut super::trap::TrapFrame) {
+fn sys_write_ptrlen(tf: &mut TrapFrame) -> SyscallResult {
     let uptr = tf.a0 as *const u8; // user VA
     let len = tf.a1 as usize;
     
@@ -209,12 +341,11 @@ fn sys_write_ptrlen(tf: &mut super::trap::TrapFrame) {
             }
         });
     }
-    tf.a0 = len; // return value (bytes "written")
-    tf.sepc = tf.sepc.wrapping_add(4);
+    Return(len)
 }
 
 // write a NUL-terminated user string; returns byte count
-fn sys_write_cstr(tf: &mut super::trap::TrapFrame) {
+fn sys_write_cstr(tf: &mut TrapFrame) -> SyscallResult {
     let uptr = tf.a0 as *const u8;
     let mut wrote = 0usize;
 
@@ -235,11 +366,27 @@ fn sys_write_cstr(tf: &mut super::trap::TrapFrame) {
         });
     }
 
-    tf.a0 = wrote; // return value
-    tf.sepc = tf.sepc.wrapping_add(4); // advance past ecall
+    Return(wrote)
+}
+
+fn sys_exit(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = exit code
+    exit_current(tf, tf.a0 as i32)
+}
+
+/// End the running program: a spawned child hands control back to its
+/// parent (which can `wait` for `code`), anything else is replaced by init.
+fn exit_current(tf: &mut TrapFrame, code: i32) -> SyscallResult {
+    let resumed = PROCS.lock().exit(code);
+    match resumed {
+        Some(parent) => resume_parent(tf, parent),
+        None => restart_init(tf),
+    }
 }
 
-fn sys_exit(tf: &mut TrapFrame) {
+/// Drop the current program's files and terminal mode and load init in its
+/// place. Used on exit and when a user program faults.
+fn restart_init(tf: &mut TrapFrame) -> SyscallResult {
     // Clear the FD table - use lock() which will wait if needed
     {
         let mut tbl = FD_TABLE.lock();
@@ -247,9 +394,19 @@ fn sys_exit(tf: &mut TrapFrame) {
             tbl[fd] = FdEntry::EMPTY;
         }
     } // Guard is dropped here
-    
-    // Reload shell.elf
-    load_program(tf, "shell.elf", &["shell.elf"]);
+
+    // The shell does its own line editing and echo
+    crate::tty::set_mode(uapi::term::RAW);
+
+    // Reload the init program (shell.elf unless init= says otherwise)
+    // with a fresh environment. Without it there is nothing to go back to:
+    // returning would resume the image that just ended, or one already
+    // unmapped, and fault straight back here.
+    let init = crate::boot::cmdline::init_program();
+    match load_program(tf, init, &[init], &DEFAULT_ENV) {
+        ReplaceImage => ReplaceImage,
+        Return(_) => panic!("cannot restart init program '{}'", init),
+    }
 }
 
 // File system stuff
@@ -259,7 +416,7 @@ const MAX_FD: usize = 32;
 #[derive(Clone, Copy)]
 enum FileType {
     ReadOnly(usize), // index into fs::FILES
-    Writable(usize), // index into writable files
+    Writable(usize), // fs id of a writable file
 }
 
 #[derive(Clone, Copy)]
@@ -347,29 +504,68 @@ fn cap_to_page(va: usize, len: usize) -> usize {
     core::cmp::min(len, page_end.saturating_sub(va))
 }
 
-// Read a NUL-terminated user string (up to max bytes) that must fit in one page.
-fn read_user_cstr_in_page(va: usize, max: usize, out: &mut [u8]) -> Result<&str, ()> {
+/// Why a user string couldn't be copied in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CStrError {
+    /// NULL, or it runs into an unmapped page or off the end of memory
+    BadPointer,
+    /// No NUL within the allowed length
+    TooLong,
+}
+
+/// Copy the NUL-terminated string at `va` into `out`, following it across
+/// page boundaries. `mapped(page_va)` is asked before the first byte of each
+/// page is read and `read(va)` fetches one byte. Returns the length without
+/// the NUL; fails if the string runs into an unmapped page or has no NUL
+/// within `max` bytes (or `out.len()`).
+fn copy_cstr<M, R>(va: usize, max: usize, out: &mut [u8], mut mapped: M, mut read: R) -> Result<usize, CStrError>
+where
+    M: FnMut(usize) -> bool,
+    R: FnMut(usize) -> u8,
+{
     if va == 0 {
-        return Err(());
+        return Err(CStrError::BadPointer);
     }
     let max = max.min(out.len());
-    unsafe {
+    for (n, slot) in out[..max].iter_mut().enumerate() {
+        let addr = va.checked_add(n).ok_or(CStrError::BadPointer)?;
+        if (n == 0 || addr.is_multiple_of(4096)) && !mapped(addr & !4095) {
+            return Err(CStrError::BadPointer);
+        }
+        let b = read(addr);
+        if b == 0 {
+            return Ok(n);
+        }
+        *slot = b;
+    }
+    Err(CStrError::TooLong)
+}
+
+// Read a NUL-terminated user path into `out`; like any PathBuf it has to
+// be UTF-8 without interior NULs, and here no longer than 255 bytes.
+fn read_user_path(va: usize, out: &mut PathBuf) -> Result<&str, ()> {
+    let mut raw = [0u8; PATH_MAX];
+    let path = read_user_cstr(va, PATH_MAX - 1, &mut raw)?;
+    *out = path.parse().map_err(|_| ())?;
+    Ok(out.as_str())
+}
+
+// Read a NUL-terminated user string of up to `max` bytes; it may span pages.
+fn read_user_cstr(va: usize, max: usize, out: &mut [u8]) -> Result<&str, ()> {
+    let n = unsafe {
+        let root = crate::sv39::root_pt();
         with_sum_no_timer(|| {
-            let mut p = va as *const u8;
-            let page_end = ((va + 4096) & !4095) as *const u8;
-            let mut n = 0usize;
-            while p < page_end && n < max {
-                let b = core::ptr::read(p);
-                if b == 0 {
-                    return core::str::from_utf8(&out[..n]).map_err(|_| ());
-                }
-                out[n] = b;
-                n += 1;
-                p = p.add(1);
-            }
-            Err(())
+            copy_cstr(
+                va,
+                max,
+                out,
+                |page| crate::sv39::translate(root, page).is_some(),
+                |addr| core::ptr::read(addr as *const u8),
+            )
         })
     }
+    .map_err(|_| ())?;
+    core::str::from_utf8(&out[..n]).map_err(|_| ())
 }
 
 // Copy bytes from kernel slice to user buffer
@@ -386,145 +582,303 @@ fn copy_to_user(dst_va: usize, src: &[u8]) -> usize {
     n
 }
 
-fn sys_open(tf: &mut TrapFrame) {
+// Like copy_to_user, but keeps going across page boundaries.
+fn copy_all_to_user(dst_va: usize, src: &[u8]) -> usize {
+    let mut done = 0;
+    while done < src.len() {
+        let n = copy_to_user(dst_va + done, &src[done..]);
+        if n == 0 {
+            break;
+        }
+        done += n;
+    }
+    done
+}
+
+fn sys_open(tf: &mut TrapFrame) -> SyscallResult {
     // a0 = path (C string in user VA)
     let path_va = tf.a0;
-    let mut buf = [0u8; 256];
-    let path = match read_user_cstr_in_page(path_va, 255, &mut buf) {
+    let mut buf = PathBuf::new();
+    let path = match read_user_path(path_va, &mut buf) {
         Ok(s) => s,
         Err(_) => {
-            tf.a0 = usize::MAX;
-            tf.sepc = tf.sepc.wrapping_add(4);
-            return;
+            return Return(usize::MAX);
         }
     };
+    let path = cwd_path(path);
+
+    // open() is read-only; the owner read bit must be set
+    if fs::check_access(&path, false) == Err(fs::FsError::Access) {
+        return Return(usize::MAX);
+    }
 
     // First check writable files
-    if let Some(idx) = fs::lookup_writable(path) {
-        if let Some(fd) = fd_alloc(FileType::Writable(idx), false) {
-            tf.a0 = fd;
-        } else {
-            tf.a0 = usize::MAX; // no fds
-        }
+    let file_type = if let Some(idx) = fs::lookup_writable(&path) {
+        FileType::Writable(idx)
     } else if let Some((idx, _f)) = fs::FILES.iter().enumerate().find(|(_, f)| f.name == path) {
-        if let Some(fd) = fd_alloc(FileType::ReadOnly(idx), false) {
-            tf.a0 = fd;
-        } else {
-            tf.a0 = usize::MAX; // no fds
-        }
+        FileType::ReadOnly(idx)
     } else {
-        tf.a0 = usize::MAX; // not found
-    }
-    tf.sepc = tf.sepc.wrapping_add(4);
+        return Return(usize::MAX); // not found
+    };
+    Return(fd_alloc(file_type, false).unwrap_or(usize::MAX)) // MAX: no fds
 }
 
-fn sys_poweroff(_tf: &mut TrapFrame) {
-    crate::sbi::shutdown();
+fn sys_poweroff(_tf: &mut TrapFrame) -> SyscallResult {
+    crate::sbi::shutdown()
 }
 
-fn sys_exec(tf: &mut TrapFrame) {
+fn sys_exec(tf: &mut TrapFrame) -> SyscallResult {
     // a0 = path
     let path_va = tf.a0;
-    let mut buf = [0u8; 256];
-    let path = match read_user_cstr_in_page(path_va, 255, &mut buf) {
+    let mut buf = PathBuf::new();
+    let path = match read_user_path(path_va, &mut buf) {
         Ok(s) => s,
         Err(_) => {
-            tf.a0 = usize::MAX;
-            tf.sepc = tf.sepc.wrapping_add(4);
-            return;
+            return Return(usize::MAX);
         }
     };
 
     // Use path as argv[0]
-    load_program(tf, path, &[path]);
+    let env = inherited_env();
+    let envp: Vec<&str> = env.iter().map(|s| s.as_str()).collect();
+    load_program(tf, path, &[path], &envp)
 }
 
-fn sys_execv(tf: &mut TrapFrame) {
+fn sys_execv(tf: &mut TrapFrame) -> SyscallResult {
     // a0 = path, a1 = argv (NULL-terminated array of C string pointers)
     let path_va = tf.a0;
     let argv_va = tf.a1;
 
-    let mut path_buf = [0u8; 256];
-    let path = match read_user_cstr_in_page(path_va, 255, &mut path_buf) {
+    let mut path_buf = PathBuf::new();
+    let path = match read_user_path(path_va, &mut path_buf) {
         Ok(s) => s,
         Err(_) => {
-            tf.a0 = usize::MAX;
-            tf.sepc = tf.sepc.wrapping_add(4);
-            return;
+            return Return(usize::MAX);
         }
     };
 
-    // Read argv array from user memory using fixed arrays
-    let mut argv_bufs: [[u8; 64]; 16] = [[0; 64]; 16];
-    let mut argv_lens: [usize; 16] = [0; 16];
-    let mut argv_count = 0usize;
+    let argv_bufs = read_user_str_array(argv_va);
+    let argv_strs: Vec<&str> = argv_bufs.iter().map(|s| s.as_str()).collect();
+    let env = inherited_env();
+    let envp: Vec<&str> = env.iter().map(|s| s.as_str()).collect();
+
+    load_program(tf, path, &argv_strs, &envp)
+}
 
+fn sys_execve(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = path, a1 = argv, a2 = envp (both NULL-terminated pointer arrays)
+    let path_va = tf.a0;
+    let argv_va = tf.a1;
+    let envp_va = tf.a2;
+
+    let mut path_buf = PathBuf::new();
+    let path = match read_user_path(path_va, &mut path_buf) {
+        Ok(s) => s,
+        Err(_) => {
+            return Return(usize::MAX);
+        }
+    };
+
+    let argv_bufs = read_user_str_array(argv_va);
+    let env_bufs = read_user_str_array(envp_va);
+    let argv_strs: Vec<&str> = argv_bufs.iter().map(|s| s.as_str()).collect();
+    let envp: Vec<&str> = env_bufs.iter().map(|s| s.as_str()).collect();
+
+    load_program(tf, path, &argv_strs, &envp)
+}
+
+/// Everything besides its registers that a parked parent gets back when its
+/// child exits. The parent's pages stay where they are: they're pinned in
+/// the user pool and only unmapped while the child runs.
+struct ParentState {
+    mappings: Vec<(usize, u64)>,
+    pool_floor: usize,
+    brk: usize,
+    stack: (usize, usize),
+    mmap_next: usize,
+    fds: [FdEntry; MAX_FD],
+    env: Option<Vec<String>>,
+    cwd: String,
+    term_mode: usize,
+}
+
+static PROCS: Mutex<crate::proc::Procs<ParentState>> = Mutex::new(crate::proc::Procs::new());
+
+fn sys_spawn(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = path, a1 = argv (NULL-terminated array of C string pointers)
+    let mut path_buf = PathBuf::new();
+    let path = match read_user_path(tf.a0, &mut path_buf) {
+        Ok(s) => s,
+        Err(_) => return Return(usize::MAX),
+    };
+    if !PROCS.lock().can_spawn() || fs::get_file_data(path).is_none() {
+        return Return(usize::MAX);
+    }
+
+    let argv_bufs = read_user_str_array(tf.a1);
+    let argv_strs: Vec<&str> = argv_bufs.iter().map(|s| s.as_str()).collect();
+    let env = inherited_env();
+    let envp: Vec<&str> = env.iter().map(|s| s.as_str()).collect();
+
+    let cwd = CWD.lock().clone();
+    let saved = unsafe {
+        ParentState {
+            mappings: crate::sv39::user_mappings(),
+            pool_floor: crate::sv39::pin_user_pages(),
+            brk: USER_BRK,
+            stack: USER_STACK,
+            mmap_next: USER_MMAP_NEXT,
+            fds: *FD_TABLE.lock(),
+            env: USER_ENV.lock().clone(),
+            cwd: cwd.clone(),
+            term_mode: crate::tty::mode(),
+        }
+    };
+    if PROCS.lock().spawn(tf, saved).is_err() {
+        return Return(usize::MAX);
+    }
+
+    let result = load_program(tf, path, &argv_strs, &envp);
+    if result == ReplaceImage {
+        // The child starts out in its parent's directory
+        *CWD.lock() = cwd;
+        return result;
+    }
+    // The load failed, possibly after unmapping the parent
+    if let Some(parent) = PROCS.lock().abort_spawn() {
+        resume_parent(tf, parent);
+    }
+    Return(usize::MAX)
+}
+
+fn sys_wait(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = &mut i32 for the exit code (or 0)
+    let Some((pid, code)) = PROCS.lock().wait() else {
+        return Return(usize::MAX);
+    };
+    if tf.a0 != 0 && copy_all_to_user(tf.a0, &code.to_le_bytes()) != 4 {
+        return Return(usize::MAX);
+    }
+    Return(pid)
+}
+
+/// Swap a parked parent back in after its child ended; it returns from
+/// `spawn` with the child's pid.
+fn resume_parent(tf: &mut TrapFrame, parent: crate::proc::Resumed<ParentState>) -> SyscallResult {
+    let st = parent.saved;
     unsafe {
-        with_sum_no_timer(|| {
-            let mut i = 0usize;
-            loop {
-                if i >= 16 {
-                    break;
-                } // Max 16 arguments
+        // Free the child's pages, then the floor drops back to the parent's
+        crate::sv39::reset_user_pages();
+        crate::sv39::set_user_pool_floor(st.pool_floor);
+        crate::sv39::clear_user_mappings();
+        crate::sv39::restore_user_mappings(&st.mappings);
+        riscv::asm::sfence_vma_all();
+        USER_BRK = st.brk;
+        USER_STACK = st.stack;
+        USER_MMAP_NEXT = st.mmap_next;
+    }
+    *FD_TABLE.lock() = st.fds;
+    *USER_ENV.lock() = st.env;
+    *CWD.lock() = st.cwd;
+    crate::tty::set_mode(st.term_mode);
+
+    *tf = parent.tf;
+    Return(parent.child)
+}
 
-                // Read pointer from argv array
-                let ptr_addr = argv_va + i * core::mem::size_of::<usize>();
-                let arg_ptr = core::ptr::read(ptr_addr as *const usize);
+/// Environment for the first program and for the shell after an exit.
+const DEFAULT_ENV: [&str; 1] = ["PATH=/"];
 
-                if arg_ptr == 0 {
-                    break;
-                } // NULL terminator
+/// Environment the running program was started with, handed on by `exec`
+/// and `execv`. `None` until the first exec from userspace.
+static USER_ENV: Mutex<Option<Vec<String>>> = Mutex::new(None);
 
-                // Read the string
-                let mut arg_len = 0usize;
-                let page_end = ((arg_ptr + 4096) & !4095) as *const u8;
-                let mut p = arg_ptr as *const u8;
+/// Working directory of the running program as a root-relative name (`""`
+/// is `/`). Relative paths given to the file syscalls are resolved against
+/// it; every successful exec starts the new program back at `/`.
+static CWD: Mutex<String> = Mutex::new(String::new());
 
-                while p < page_end && arg_len < 63 {
-                    let b = core::ptr::read(p);
-                    if b == 0 {
-                        break;
-                    }
-                    argv_bufs[argv_count][arg_len] = b;
-                    arg_len += 1;
-                    p = p.add(1);
-                }
+/// Resolve a user-supplied path against `CWD`
+fn cwd_path(path: &str) -> String {
+    fs::resolve_path(&CWD.lock(), path)
+}
 
-                argv_lens[argv_count] = arg_len;
-                argv_count += 1;
-                i += 1;
-            }
-        });
+fn inherited_env() -> Vec<String> {
+    match &*USER_ENV.lock() {
+        Some(env) => env.clone(),
+        None => DEFAULT_ENV.iter().map(|s| String::from(*s)).collect(),
     }
+}
 
-    // Convert to &[&str] for load_program
-    let mut argv_strs: [&str; 16] = [""; 16];
-    for i in 0..argv_count {
-        if let Ok(s) = core::str::from_utf8(&argv_bufs[i][..argv_lens[i]]) {
-            argv_strs[i] = s;
+/// Call `f` with each pointer of the NULL-terminated array at `array_va`
+/// (argv/envp), stopping after `max` entries. `read_word(va)` loads one
+/// pointer. A NULL array is treated as empty.
+fn walk_ptr_array<R, F>(array_va: usize, max: usize, mut read_word: R, mut f: F)
+where
+    R: FnMut(usize) -> usize,
+    F: FnMut(usize),
+{
+    if array_va == 0 {
+        return;
+    }
+    for i in 0..max {
+        let ptr = read_word(array_va + i * core::mem::size_of::<usize>());
+        if ptr == 0 {
+            break; // NULL terminator
         }
+        f(ptr);
     }
+}
 
-    load_program(tf, path, &argv_strs[..argv_count]);
+// Read a user argv/envp array (at most MAX_ARGS entries). Each string is
+// truncated to 63 bytes and may not cross its page.
+fn read_user_str_array(array_va: usize) -> Vec<String> {
+    let mut strs: Vec<String> = Vec::new();
+
+    unsafe {
+        with_sum_no_timer(|| {
+            walk_ptr_array(
+                array_va,
+                uapi::MAX_ARGS,
+                |va| core::ptr::read(va as *const usize),
+                |str_ptr| {
+                    let mut s: Vec<u8> = Vec::new();
+                    let page_end = ((str_ptr + 4096) & !4095) as *const u8;
+                    let mut p = str_ptr as *const u8;
+
+                    while p < page_end && s.len() < 63 {
+                        let b = core::ptr::read(p);
+                        if b == 0 {
+                            break;
+                        }
+                        s.push(b);
+                        p = p.add(1);
+                    }
+
+                    strs.push(String::from_utf8(s).unwrap_or_default());
+                },
+            );
+        });
+    }
+
+    strs
 }
 
-fn load_program(tf: &mut TrapFrame, name: &str, argv: &[&str]) {
+/// Replace the running program with `name`, leaving `tf` at its entry. On
+/// failure the error return comes back; note the old image may already be
+/// unmapped.
+fn load_program(tf: &mut TrapFrame, name: &str, argv: &[&str], envp: &[&str]) -> SyscallResult {
     // Find file in writable filesystem
     let file_data = match fs::get_file_data(name) {
         Some(data) => data,
         None => {
             let _ = writeln!(crate::uart::Uart::new(), "exec: file not found '{}'", name);
-            tf.a0 = usize::MAX;
-            tf.sepc = tf.sepc.wrapping_add(4);
-            return;
+            return Return(usize::MAX);
         }
     };
-    
-    let envp = ["PATH=/"];
 
-    // Reuse stack constants from main.rs (should be shared)
-    let user_stack_top_va: usize = 0x4000_8000;
-    let user_stack_bytes: usize = 16 * 1024;
+    let (user_stack_bottom, user_stack_top_va) = crate::boot::cmdline::user_stack();
+    let user_stack_bytes = user_stack_top_va - user_stack_bottom;
 
     // CRITICAL: Clear old user mappings and reset allocator before loading new program
     unsafe {
@@ -532,7 +886,7 @@ fn load_program(tf: &mut TrapFrame, name: &str, argv: &[&str]) {
         crate::sv39::clear_user_mappings();
     }
 
-    match crate::elf::load_user_elf(&file_data, user_stack_top_va, user_stack_bytes, argv, &envp) {
+    match crate::elf::load_user_elf(&file_data, user_stack_top_va, user_stack_bytes, argv, envp) {
         Ok(img) => {
             // Flush TLB to ensure old mappings are invalidated
             riscv::asm::sfence_vma_all();
@@ -546,170 +900,387 @@ fn load_program(tf: &mut TrapFrame, name: &str, argv: &[&str]) {
 
             unsafe {
                 USER_BRK = img.brk;
+                USER_STACK = (user_stack_bottom, user_stack_top_va);
+                USER_MMAP_NEXT = crate::sv39::MMAP_VA_BASE;
             }
+            *USER_ENV.lock() = Some(envp.iter().map(|s| String::from(*s)).collect());
+            CWD.lock().clear();
 
-            // Success: do NOT increment sepc, just return to new entry
+            // Success: sepc stays at the new entry
+            ReplaceImage
         }
         Err(e) => {
             let _ = writeln!(crate::uart::Uart::new(), "exec failed: {:?}", e);
-            tf.a0 = usize::MAX;
-            tf.sepc = tf.sepc.wrapping_add(4);
+            Return(usize::MAX)
         }
     }
 }
 
-fn sys_read(tf: &mut TrapFrame) {
+/// Block until a byte is available from the keyboard buffer or the UART.
+fn read_stdin_byte(uart: &mut crate::uart::Uart) -> u8 {
+    loop {
+        // Poll keyboard for any pending events, and check its buffer first
+        crate::keyboard::poll();
+        if let Some(b) = crate::keyboard::pop_input() {
+            return b;
+        }
+        if let Some(b) = uart.try_read_byte() {
+            return b;
+        }
+        // Small busy wait to avoid spinning too fast
+        core::hint::spin_loop();
+    }
+}
+
+/// Whether a `read(0, ..)` has something to return without waiting: the rest
+/// of a finished canonical line, or a byte from the keyboard or the UART.
+fn stdin_ready(line_pending: bool, keyboard: bool, uart: bool) -> bool {
+    line_pending || keyboard || uart
+}
+
+fn sys_pollin(_tf: &mut TrapFrame) -> SyscallResult {
+    crate::keyboard::poll();
+    let line_pending = crate::tty::is_canonical() && crate::tty::LINE.lock().line_ready();
+    let uart = crate::uart::Uart::new();
+    Return(stdin_ready(line_pending, crate::keyboard::has_input(), uart.can_read()) as usize)
+}
+
+/// Most bytes one raw-mode `read(0, ..)` returns
+const RAW_READ_MAX: usize = 64;
+
+/// `read(fd, buf, len)`. On stdin it blocks until there is input, then
+/// returns without waiting again: in canonical mode the next line (or its
+/// first `len` bytes, the rest coming on later reads), in raw mode the
+/// first keystroke plus whatever is already buffered, up to `len`.
+fn sys_read(tf: &mut TrapFrame) -> SyscallResult {
     // a0 = fd, a1 = buf (user VA), a2 = len
     let fd = tf.a0 as isize;
     let buf = tf.a1;
     let mut len = tf.a2;
 
     if buf == 0 || len == 0 {
-        tf.a0 = 0;
-        tf.sepc = tf.sepc.wrapping_add(4);
-        return;
+        return Return(0);
     }
-    len = cap_to_page(buf, len);
 
     // --- STDIN (UART RX + Keyboard) ---
     if fd == 0 {
+        len = cap_to_page(buf, len);
         let mut uart = crate::uart::Uart::new();
-        let mut n = 0usize;
-
-        // Block for the first byte - check both keyboard buffer and UART
-        loop {
-            // Poll keyboard for any pending events
-            crate::keyboard::poll();
-            
-            // Check keyboard buffer first
-            if let Some(b) = crate::keyboard::pop_input() {
-                unsafe {
-                    with_sum_no_timer(|| {
-                        core::ptr::write((buf as *mut u8).add(n), b);
-                    });
+
+        if crate::tty::is_canonical() {
+            // Collect a full line, echoing as we go if asked, then hand it out
+            let echo = crate::tty::is_echo();
+            let mut line = [0u8; crate::tty::LINE_MAX];
+            let n = loop {
+                let mut lb = crate::tty::LINE.lock();
+                if lb.line_ready() {
+                    break lb.take(&mut line[..len.min(crate::tty::LINE_MAX)]);
                 }
-                n += 1;
-                break;
-            }
-            
-            // Check UART
-            if let Some(b) = uart.try_read_byte() {
-                unsafe {
-                    with_sum_no_timer(|| {
-                        core::ptr::write((buf as *mut u8).add(n), b);
-                    });
+                let b = read_stdin_byte(&mut uart);
+                match lb.feed(b) {
+                    _ if !echo => {}
+                    crate::tty::Echo::None => {}
+                    crate::tty::Echo::Byte(c) => console_write_byte(c, false),
+                    crate::tty::Echo::Erase => {
+                        for &c in b"\x08 \x08" {
+                            console_write_byte(c, false);
+                        }
+                    }
+                    crate::tty::Echo::Newline => console_write_byte(b'\n', false),
                 }
-                n += 1;
-                break;
+            };
+            unsafe {
+                with_sum_no_timer(|| {
+                    core::ptr::copy_nonoverlapping(line.as_ptr(), buf as *mut u8, n);
+                });
             }
-            
-            // Small busy wait to avoid spinning too fast
-            core::hint::spin_loop();
-        }
-
-        // Drain any immediately available bytes without blocking further
-        while n < len {
-            // Poll keyboard
-            crate::keyboard::poll();
-            
-            // Try keyboard buffer first
-            if let Some(b) = crate::keyboard::pop_input() {
-                unsafe {
-                    with_sum_no_timer(|| {
-                        core::ptr::write((buf as *mut u8).add(n), b);
-                    });
-                }
-                n += 1;
-                continue;
+            return Return(n);
+        }
+
+        // Raw mode: wait for one keystroke, then add whatever else is
+        // already buffered, up to len, without waiting again
+        let want = len.min(RAW_READ_MAX);
+        let mut bytes = [0u8; RAW_READ_MAX];
+        bytes[0] = read_stdin_byte(&mut uart);
+        let mut n = 1 + crate::keyboard::read_input(&mut bytes[1..want]);
+        while n < want {
+            match uart.try_read_byte() {
+                Some(b) => bytes[n] = b,
+                None => break,
             }
-            
-            // Try UART
-            if let Some(b) = uart.try_read_byte() {
-                unsafe {
-                    with_sum_no_timer(|| {
-                        core::ptr::write((buf as *mut u8).add(n), b);
-                    });
-                }
-                n += 1;
-            } else {
-                break;
+            n += 1;
+        }
+        if crate::tty::is_echo() {
+            for &b in &bytes[..n] {
+                console_write_byte(b, false);
             }
         }
+        unsafe {
+            with_sum_no_timer(|| {
+                core::ptr::copy_nonoverlapping(bytes.as_ptr(), buf as *mut u8, n);
+            });
+        }
 
-        tf.a0 = n;
-        tf.sepc = tf.sepc.wrapping_add(4);
-        return;
+        return Return(n);
     }
 
     // --- Not readable: stdout/stderr ---
     if fd == 1 || fd == 2 {
-        tf.a0 = usize::MAX; // error
-        tf.sepc = tf.sepc.wrapping_add(4);
-        return;
+        return Return(usize::MAX);
     }
 
     // --- Regular files via RAMFS or writable files ---
     let entry = match fd_get(fd as usize) {
         Some(e) => e,
         None => {
-            tf.a0 = usize::MAX;
-            tf.sepc = tf.sepc.wrapping_add(4);
-            return;
+            return Return(usize::MAX);
         }
     };
 
-    match entry.file_type {
+    let root = unsafe { crate::sv39::root_pt() };
+    let read = read_pages(
+        buf,
+        len,
+        entry.offset,
+        |page| unsafe { crate::sv39::translate(root, page).is_some() },
+        |off, va, n| read_file_at(entry.file_type, off, va, n),
+    );
+    Return(match read {
+        Ok(n) => {
+            fd_advance(fd as usize, n);
+            n
+        }
+        Err(_) => usize::MAX,
+    })
+}
+
+/// Read up to `len` bytes of a regular file at `offset` into the user buffer at `va`.
+/// `len` must already be capped to the page containing `va`.
+fn read_file_at(file_type: FileType, offset: usize, va: usize, len: usize) -> Result<usize, ReadError> {
+    match file_type {
         FileType::ReadOnly(idx) => {
             let file = &crate::fs::FILES[idx];
-            if entry.offset >= file.data.len() {
-                tf.a0 = 0;
-                tf.sepc = tf.sepc.wrapping_add(4);
-                return;
+            if offset >= file.data.len() {
+                return Ok(0);
             }
-            let remain = &file.data[entry.offset..];
+            let remain = &file.data[offset..];
             let chunk = &remain[..core::cmp::min(len, remain.len())];
+            Ok(copy_to_user(va, chunk))
+        }
+        // Straight from the file's storage to the user buffer
+        FileType::Writable(idx) => fs::read_file_with(idx, offset, len, |src| copy_to_user(va, src)).map_err(ReadError::File),
+    }
+}
 
-            let n = copy_to_user(buf, chunk);
-            fd_advance(fd as usize, n);
-            tf.a0 = n;
+/// Why a read into a user buffer got nothing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadError {
+    /// The buffer's first page isn't mapped
+    Unmapped,
+    /// The file itself couldn't be read
+    File(fs::FsError),
+}
+
+/// Read `len` bytes into the user buffer at `va` one page-sized piece at a
+/// time, so a single `read` isn't limited to the page `va` starts in.
+/// `mapped(page_va)` is asked before each page is touched and
+/// `read_at(offset, va, n)` fills one piece; an unmapped page or a short
+/// piece (end of file) ends the read. Fails only if nothing could be read
+/// because the first page isn't mapped.
+fn read_pages<M, F>(va: usize, len: usize, offset: usize, mut mapped: M, mut read_at: F) -> Result<usize, ReadError>
+where
+    M: FnMut(usize) -> bool,
+    F: FnMut(usize, usize, usize) -> Result<usize, ReadError>,
+{
+    let mut total = 0usize;
+    while total < len {
+        let piece_va = va + total;
+        if !mapped(piece_va & !4095) {
+            return if total == 0 { Err(ReadError::Unmapped) } else { Ok(total) };
         }
-        FileType::Writable(idx) => {
-            // Read from writable file
-            let mut temp_buf = [0u8; 4096];
-            let read_len = core::cmp::min(len, temp_buf.len());
+        let piece = cap_to_page(piece_va, len - total);
+        let n = read_at(offset + total, piece_va, piece)?;
+        total += n;
+        if n < piece {
+            break;
+        }
+    }
+    Ok(total)
+}
 
-            match fs::read_file(idx, entry.offset, &mut temp_buf[..read_len]) {
-                Ok(n) => {
-                    let copied = copy_to_user(buf, &temp_buf[..n]);
-                    fd_advance(fd as usize, copied);
-                    tf.a0 = copied;
-                }
-                Err(_) => {
-                    tf.a0 = usize::MAX;
-                }
-            }
+/// Scatter a sequential read across `segs` (`(va, len)` pairs) starting at `offset`.
+/// `read_at(offset, va, len)` fills one segment; a short read ends the scatter.
+/// Returns the total number of bytes read. A failing segment ends the
+/// scatter too, and is only an error if nothing was read before it.
+fn scatter_read<F, E>(segs: &[(usize, usize)], offset: usize, mut read_at: F) -> Result<usize, E>
+where
+    F: FnMut(usize, usize, usize) -> Result<usize, E>,
+{
+    let mut total = 0usize;
+    for &(va, len) in segs {
+        if len == 0 {
+            continue;
+        }
+        let n = match read_at(offset + total, va, len) {
+            Ok(n) => n,
+            Err(e) if total == 0 => return Err(e),
+            Err(_) => break,
+        };
+        total += n;
+        if n < len {
+            break;
         }
     }
+    Ok(total)
+}
 
-    tf.sepc = tf.sepc.wrapping_add(4);
+fn sys_readv(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = fd, a1 = iovec array (user VA), a2 = iovcnt
+    let fd = tf.a0;
+    let iov_va = tf.a1;
+    let iovcnt = tf.a2;
+
+    // Only regular files can be scattered; stdin/stdout/stderr are not supported
+    let entry = match fd_get(fd) {
+        Some(e) if iov_va != 0 && iovcnt <= uapi::MAX_IOV => e,
+        _ => {
+            return Return(usize::MAX);
+        }
+    };
+
+    // Copy the iovec array in; each segment is filled page by page below
+    let mut segs = [(0usize, 0usize); uapi::MAX_IOV];
+    unsafe {
+        with_sum_no_timer(|| {
+            for (i, seg) in segs.iter_mut().enumerate().take(iovcnt) {
+                let iov = core::ptr::read(
+                    (iov_va + i * core::mem::size_of::<uapi::IoVec>()) as *const uapi::IoVec,
+                );
+                let len = if iov.base == 0 { 0 } else { iov.len };
+                *seg = (iov.base, len);
+            }
+        });
+    }
+
+    let root = unsafe { crate::sv39::root_pt() };
+    Return(match scatter_read(&segs[..iovcnt], entry.offset, |off, va, len| {
+        read_pages(
+            va,
+            len,
+            off,
+            |page| unsafe { crate::sv39::translate(root, page).is_some() },
+            |off, va, n| read_file_at(entry.file_type, off, va, n),
+        )
+    }) {
+        Ok(n) => {
+            fd_advance(fd, n);
+            n
+        }
+        Err(_) => usize::MAX,
+    })
 }
 
-fn sys_close(tf: &mut TrapFrame) {
+fn sys_close(tf: &mut TrapFrame) -> SyscallResult {
     let fd = tf.a0;
-    tf.a0 = if fd_close(fd) { 0 } else { usize::MAX };
-    tf.sepc = tf.sepc.wrapping_add(4);
+    Return(if fd_close(fd) { 0 } else { usize::MAX })
+}
+
+fn sys_write_raw(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = fd, a1 = buf, a2 = len; like write_fd but terminal bytes go out untranslated
+    let fd = tf.a0;
+    if fd != 1 && fd != 2 {
+        // Files never get translated
+        return sys_write_fd(tf);
+    }
+
+    let buf = tf.a1;
+    let len = cap_to_page(buf, tf.a2);
+    let mut temp_buf = [0u8; 4096];
+    let n = core::cmp::min(len, temp_buf.len());
+    if buf != 0 && n > 0 {
+        unsafe {
+            with_sum_no_timer(|| {
+                core::ptr::copy_nonoverlapping(buf as *const u8, temp_buf.as_mut_ptr(), n);
+            });
+        }
+        console_write_raw(&temp_buf[..n], fd == 2);
+    }
+    Return(if buf == 0 { 0 } else { n })
+}
+
+fn sys_set_term_mode(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = uapi::term flags
+    let flags = tf.a0;
+    if !crate::tty::valid_mode(flags) {
+        return Return(usize::MAX);
+    }
+    crate::tty::set_mode(flags);
+    Return(0)
+}
+
+fn sys_get_display_mode(_tf: &mut TrapFrame) -> SyscallResult {
+    Return(crate::boot::cmdline::display_mode().as_raw())
+}
+
+fn sys_yield(_tf: &mut TrapFrame) -> SyscallResult {
+    // Sleep until the next interrupt (at worst the timer tick). SIE is off in
+    // the trap handler, so it is taken right after we return to user mode.
+    riscv::asm::wfi();
+    Return(0)
+}
+
+fn sys_dmesg(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = buf (user VA), a1 = len
+    let buf = tf.a0;
+    if buf == 0 {
+        return Return(usize::MAX);
+    }
+    let ring = crate::logging::LOG_RING.lock();
+    let (older, newer) = ring.recent(tf.a1);
+    let n = copy_all_to_user(buf, older);
+    Return(n + copy_all_to_user(buf + n, newer))
+}
+
+fn sys_set_display_mode(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = uapi::display_mode value
+    let have_fb = crate::display::get_framebuffer().is_some();
+    Return(match crate::display::mode_for_request(tf.a0, have_fb) {
+        Ok(mode) => {
+            if mode != crate::boot::cmdline::display_mode() {
+                crate::boot::cmdline::set_display_mode(mode);
+                crate::console::init_console();
+            }
+            0
+        }
+        Err(_) => usize::MAX,
+    })
 }
 
-fn sys_write_fd(tf: &mut TrapFrame) {
+fn sys_meminfo(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = pointer to uapi::MemInfo in user space
+    let info_va = tf.a0;
+    if info_va == 0 || !info_va.is_multiple_of(core::mem::align_of::<uapi::MemInfo>()) {
+        return Return(usize::MAX);
+    }
+
+    let (used, free, total) = crate::kalloc::stats();
+    let reply = uapi::MemInfo { used: used as u64, free: free as u64, total: total as u64 };
+    unsafe {
+        with_sum_no_timer(|| {
+            core::ptr::write(info_va as *mut uapi::MemInfo, reply);
+        });
+    }
+    Return(0)
+}
+
+fn sys_write_fd(tf: &mut TrapFrame) -> SyscallResult {
     // a0 = fd, a1 = buf, a2 = len
     let fd = tf.a0 as isize;
     let buf = tf.a1;
     let mut len = tf.a2;
 
     if buf == 0 || len == 0 {
-        tf.a0 = 0;
-        tf.sepc = tf.sepc.wrapping_add(4);
-        return;
+        return Return(0);
     }
     len = cap_to_page(buf, len);
 
@@ -726,32 +1297,28 @@ fn sys_write_fd(tf: &mut TrapFrame) {
                 }
             });
         }
-        tf.a0 = len;
-        tf.sepc = tf.sepc.wrapping_add(4);
-        return;
+        return Return(len);
     }
 
     // Handle file writes
     let entry = match fd_get(fd as usize) {
         Some(e) => e,
         None => {
-            tf.a0 = usize::MAX;
-            tf.sepc = tf.sepc.wrapping_add(4);
-            return;
+            return Return(usize::MAX);
         }
     };
 
     if !entry.writable {
         // Read-only file
-        tf.a0 = usize::MAX;
-        tf.sepc = tf.sepc.wrapping_add(4);
-        return;
+        return Return(usize::MAX);
     }
 
-    match entry.file_type {
+    Return(match entry.file_type {
         FileType::Writable(idx) => {
-            // Copy from user to kernel buffer, then write
-            let mut temp_buf = [0u8; 4096];
+            // Copy from user to kernel buffer, then write. A write never
+            // crosses a page or moves more than WRITE_CHUNK bytes, so it may
+            // be short; the count returned is what actually got written.
+            let mut temp_buf = [0u8; fs::WRITE_CHUNK];
             let write_len = core::cmp::min(len, temp_buf.len());
 
             unsafe {
@@ -764,26 +1331,20 @@ fn sys_write_fd(tf: &mut TrapFrame) {
                 });
             }
 
-            match fs::write_file(idx, entry.offset, &temp_buf[..write_len]) {
+            match fs::write_file_chunk(idx, entry.offset, &temp_buf[..write_len]) {
                 Ok(n) => {
                     fd_advance(fd as usize, n);
-                    tf.a0 = n;
-                }
-                Err(_) => {
-                    tf.a0 = usize::MAX;
+                    n
                 }
+                Err(_) => usize::MAX,
             }
         }
-        FileType::ReadOnly(_) => {
-            // Should not happen (checked writable above)
-            tf.a0 = usize::MAX;
-        }
-    }
-
-    tf.sepc = tf.sepc.wrapping_add(4);
+        // Should not happen (checked writable above)
+        FileType::ReadOnly(_) => usize::MAX,
+    })
 }
 
-fn sys_lseek(tf: &mut TrapFrame) {
+fn sys_lseek(tf: &mut TrapFrame) -> SyscallResult {
     // a0 = fd, a1 = offset, a2 = whence
     let fd = tf.a0;
     let offset = tf.a1 as isize;
@@ -792,9 +1353,7 @@ fn sys_lseek(tf: &mut TrapFrame) {
     let entry = match fd_get(fd) {
         Some(e) => e,
         None => {
-            tf.a0 = usize::MAX;
-            tf.sepc = tf.sepc.wrapping_add(4);
-            return;
+            return Return(usize::MAX);
         }
     };
 
@@ -803,9 +1362,7 @@ fn sys_lseek(tf: &mut TrapFrame) {
         FileType::Writable(idx) => match fs::file_size(idx) {
             Some(sz) => sz,
             None => {
-                tf.a0 = usize::MAX;
-                tf.sepc = tf.sepc.wrapping_add(4);
-                return;
+                return Return(usize::MAX);
             }
         },
     };
@@ -818,37 +1375,125 @@ fn sys_lseek(tf: &mut TrapFrame) {
     };
 
     if new_off < 0 {
-        tf.a0 = usize::MAX;
-    } else {
-        let new_off = new_off as usize;
-        fd_seek(fd, new_off);
-        tf.a0 = new_off;
+        return Return(usize::MAX);
     }
-    tf.sepc = tf.sepc.wrapping_add(4);
+    let new_off = new_off as usize;
+    fd_seek(fd, new_off);
+    Return(new_off)
+}
+
+fn sys_ftruncate(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = fd, a1 = new length
+    let fd = tf.a0;
+    let len = tf.a1;
+
+    Return(match fd_get(fd) {
+        Some(FdEntry { file_type: FileType::Writable(idx), writable: true, offset, .. }) => {
+            match fs::truncate_file(idx, len) {
+                Ok(_) => {
+                    // Keep the offset inside the file
+                    if offset > len {
+                        fd_seek(fd, len);
+                    }
+                    0
+                }
+                Err(_) => usize::MAX,
+            }
+        }
+        _ => usize::MAX, // read-only, not open for writing, or bad fd
+    })
+}
+
+fn sys_sendfile(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = out_fd, a1 = in_fd, a2 = len; data never leaves the kernel
+    let out_fd = tf.a0;
+    let in_fd = tf.a1;
+    let len = tf.a2;
+
+    Return(sendfile(out_fd, in_fd, len).unwrap_or(usize::MAX))
+}
+
+/// Copy up to `len` bytes from `in_fd`'s offset to `out_fd` (a writable file,
+/// stdout or stderr), advancing both offsets.
+fn sendfile(out_fd: usize, in_fd: usize, len: usize) -> Result<usize, ()> {
+    let input = fd_get(in_fd).ok_or(())?;
+
+    // Validate the destination before consuming any input
+    let output = match out_fd {
+        1 | 2 => None,
+        _ => match fd_get(out_fd) {
+            Some(e @ FdEntry { file_type: FileType::Writable(_), writable: true, .. }) => Some(e),
+            _ => return Err(()),
+        },
+    };
+
+    let data: Vec<u8> = match input.file_type {
+        FileType::ReadOnly(idx) => {
+            let file = crate::fs::FILES[idx].data;
+            let start = core::cmp::min(input.offset, file.len());
+            let end = start + core::cmp::min(len, file.len() - start);
+            Vec::from(&file[start..end])
+        }
+        FileType::Writable(idx) => {
+            let avail = fs::file_size(idx).ok_or(())?.saturating_sub(input.offset);
+            let mut buf = alloc::vec![0u8; core::cmp::min(len, avail)];
+            let n = fs::read_file(idx, input.offset, &mut buf).map_err(|_| ())?;
+            buf.truncate(n);
+            buf
+        }
+    };
+
+    let n = match output {
+        None => {
+            for &b in &data {
+                console_write_byte(b, out_fd == 2);
+            }
+            data.len()
+        }
+        Some(FdEntry { file_type: FileType::Writable(idx), offset, .. }) => {
+            let n = fs::write_file(idx, offset, &data).map_err(|_| ())?;
+            fd_advance(out_fd, n);
+            n
+        }
+        Some(_) => return Err(()),
+    };
+    fd_advance(in_fd, n);
+    Ok(n)
 }
 
 pub static mut USER_BRK: usize = 0;
 
-fn sys_brk(tf: &mut TrapFrame) {
+/// The current program's stack as `(bottom, top)`; (0, 0) until one is loaded
+pub static mut USER_STACK: (usize, usize) = (0, 0);
+
+fn sys_brk(tf: &mut TrapFrame) -> SyscallResult {
     // a0 = new_brk
     let req_brk = tf.a0;
     let cur_brk = unsafe { USER_BRK };
 
     if req_brk == 0 {
-        tf.a0 = cur_brk;
+        Return(cur_brk)
+    } else if req_brk > cur_brk && crate::sv39::brk_collides(cur_brk, req_brk, unsafe { USER_STACK }) {
+        // Growing into the stack or a window the kernel maps: keep the old break
+        crate::kwarn!("brk: {:#x} would run into a reserved region, staying at {:#x}", req_brk, cur_brk);
+        Return(cur_brk)
     } else if req_brk > cur_brk {
         // Allocate pages
         let page_mask = 4095;
         let old_page_end = (cur_brk + page_mask) & !page_mask;
         let new_page_end = (req_brk + page_mask) & !page_mask;
 
-        if new_page_end > old_page_end {
-            let pages_needed = (new_page_end - old_page_end) / 4096;
+        let pages_needed = new_page_end.saturating_sub(old_page_end) / 4096;
+        if pages_needed > crate::sv39::free_user_pages() {
+            return Return(usize::MAX);
+        }
+        if pages_needed > 0 {
             let root = unsafe { crate::sv39::root_pt() };
             for i in 0..pages_needed {
                 let va = old_page_end + i * 4096;
                 unsafe {
-                    let pa = crate::sv39::alloc_user_page();
+                    // Can't fail: the pool was checked above
+                    let Some(pa) = crate::sv39::alloc_user_page() else { break };
                     crate::sv39::map_4k(
                         root,
                         va,
@@ -864,138 +1509,290 @@ fn sys_brk(tf: &mut TrapFrame) {
             }
         }
         unsafe { USER_BRK = req_brk };
-        tf.a0 = req_brk;
+        Return(req_brk)
     } else {
         // Shrink? Not implemented, just accept
         unsafe { USER_BRK = req_brk };
-        tf.a0 = req_brk;
+        Return(req_brk)
     }
-    tf.sepc = tf.sepc.wrapping_add(4);
 }
 
-fn sys_gettime(tf: &mut TrapFrame) {
-    // a0 = ptr to timeval/timespec (ignored for now, just return ticks)
+/// First unused VA in the mmap window; reset when a new program is loaded
+pub static mut USER_MMAP_NEXT: usize = crate::sv39::MMAP_VA_BASE;
+
+fn sys_mmap(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = len; anonymous, zeroed, read-write memory only
+    let next = unsafe { USER_MMAP_NEXT };
+    let plan = crate::sv39::mmap_plan(next, tf.a0)
+        .filter(|&(_, pages)| pages <= crate::sv39::free_user_pages());
+    let Some((va, pages)) = plan else {
+        return Return(usize::MAX);
+    };
+
+    let root = unsafe { crate::sv39::root_pt() };
+    for i in 0..pages {
+        unsafe {
+            // Can't fail: the pool was checked above
+            let Some(pa) = crate::sv39::alloc_user_page() else { break };
+            crate::sv39::map_4k(
+                root,
+                va + i * 4096,
+                pa,
+                crate::sv39::PTE_V
+                    | crate::sv39::PTE_U
+                    | crate::sv39::PTE_R
+                    | crate::sv39::PTE_W
+                    | crate::sv39::PTE_A
+                    | crate::sv39::PTE_D,
+            );
+        }
+    }
+    unsafe { USER_MMAP_NEXT = va + pages * 4096 };
+    Return(va)
+}
+
+fn sys_gettime(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = optional ptr to a u64 that receives uptime in milliseconds
     // Return ticks in a0
-    tf.a0 = crate::timer::TICKS.load(core::sync::atomic::Ordering::Relaxed) as usize;
-    tf.sepc = tf.sepc.wrapping_add(4);
+    let ms_ptr = tf.a0;
+    if ms_ptr != 0 && ms_ptr.is_multiple_of(core::mem::align_of::<u64>()) {
+        let ms = crate::timer::uptime_ms();
+        copy_to_user(ms_ptr, &ms.to_ne_bytes());
+    }
+    Return(crate::timer::ticks() as usize)
 }
 
-fn sys_creat(tf: &mut TrapFrame) {
+fn sys_creat(tf: &mut TrapFrame) -> SyscallResult {
     // a0 = path (C string in user VA), a1 = mode (ignored for now)
     let path_va = tf.a0;
-    let mut buf = [0u8; 256];
-    let path = match read_user_cstr_in_page(path_va, 255, &mut buf) {
+    let mut buf = PathBuf::new();
+    let path = match read_user_path(path_va, &mut buf) {
         Ok(s) => s,
         Err(_) => {
-            tf.a0 = usize::MAX;
-            tf.sepc = tf.sepc.wrapping_add(4);
-            return;
+            return Return(usize::MAX);
         }
     };
+    let path = cwd_path(path);
 
-    match fs::create_file(path) {
-        Ok(idx) => {
-            if let Some(fd) = fd_alloc(FileType::Writable(idx), true) {
-                tf.a0 = fd;
-            } else {
-                tf.a0 = usize::MAX;
-            }
-        }
+    // Truncating an existing file needs its owner write bit
+    if fs::check_access(&path, true) == Err(fs::FsError::Access) {
+        return Return(usize::MAX);
+    }
+
+    match fs::create_file(&path) {
+        Ok(idx) => Return(fd_alloc(FileType::Writable(idx), true).unwrap_or(usize::MAX)),
+        Err(_) => Return(usize::MAX),
+    }
+}
+
+fn sys_unlink(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = path (C string in user VA)
+    let path_va = tf.a0;
+    let mut buf = PathBuf::new();
+    let path = match read_user_path(path_va, &mut buf) {
+        Ok(s) => s,
         Err(_) => {
-            tf.a0 = usize::MAX;
+            return Return(usize::MAX);
         }
+    };
+    let path = cwd_path(path);
+
+    match fs::unlink_file(&path) {
+        Ok(_) => Return(0),
+        Err(_) => Return(usize::MAX),
     }
-    tf.sepc = tf.sepc.wrapping_add(4);
 }
 
-fn sys_unlink(tf: &mut TrapFrame) {
+fn sys_mkdir(tf: &mut TrapFrame) -> SyscallResult {
     // a0 = path (C string in user VA)
     let path_va = tf.a0;
-    let mut buf = [0u8; 256];
-    let path = match read_user_cstr_in_page(path_va, 255, &mut buf) {
+    let mut buf = PathBuf::new();
+    let path = match read_user_path(path_va, &mut buf) {
         Ok(s) => s,
         Err(_) => {
-            tf.a0 = usize::MAX;
-            tf.sepc = tf.sepc.wrapping_add(4);
-            return;
+            return Return(usize::MAX);
         }
     };
+    let path = cwd_path(path);
 
-    match fs::unlink_file(path) {
-        Ok(_) => tf.a0 = 0,
-        Err(_) => tf.a0 = usize::MAX,
+    match fs::mkdir(&path) {
+        Ok(_) => Return(0),
+        Err(_) => Return(usize::MAX),
     }
-    tf.sepc = tf.sepc.wrapping_add(4);
 }
 
-fn sys_stat(tf: &mut TrapFrame) {
+fn sys_rename(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = src path, a1 = dst path (C strings in user VA)
+    let mut src_buf = PathBuf::new();
+    let mut dst_buf = PathBuf::new();
+    let paths = read_user_path(tf.a0, &mut src_buf)
+        .and_then(|src| read_user_path(tf.a1, &mut dst_buf).map(|dst| (src, dst)));
+
+    Return(match paths {
+        Ok((src, dst)) => match fs::rename_file(&cwd_path(src), &cwd_path(dst)) {
+            Ok(_) => 0,
+            Err(_) => usize::MAX,
+        },
+        Err(_) => usize::MAX,
+    })
+}
+
+fn sys_resolve(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = command name (C string in user VA), a1 = out buffer, a2 = len
+    let name_va = tf.a0;
+    let out_va = tf.a1;
+    let out_len = tf.a2;
+
+    let mut buf = PathBuf::new();
+    let name = match read_user_path(name_va, &mut buf) {
+        Ok(s) => s,
+        Err(_) => {
+            return Return(usize::MAX);
+        }
+    };
+
+    Return(match fs::which(name) {
+        // The path and its NUL must fit
+        Some(path) if path.len() < out_len => {
+            let n = copy_all_to_user(out_va, path.as_bytes());
+            if n == path.len() && copy_to_user(out_va + n, &[0]) == 1 {
+                n
+            } else {
+                usize::MAX
+            }
+        }
+        _ => usize::MAX,
+    })
+}
+
+fn sys_chdir(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = path (C string in user VA)
+    let mut buf = PathBuf::new();
+    Return(match read_user_path(tf.a0, &mut buf) {
+        Ok(path) => {
+            let dir = cwd_path(path);
+            // Only the root and directories made by mkdir can be entered
+            if dir.is_empty() || fs::stat_file(&dir).is_some_and(|st| st.is_dir) {
+                *CWD.lock() = dir;
+                0
+            } else {
+                usize::MAX
+            }
+        }
+        Err(_) => usize::MAX,
+    })
+}
+
+fn sys_getcwd(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = out buffer (user VA), a1 = len
+    let out_va = tf.a0;
+    let out_len = tf.a1;
+
+    let mut path = String::from("/");
+    path.push_str(&CWD.lock());
+    // The path and its NUL must fit
+    Return(if path.len() < out_len {
+        let n = copy_all_to_user(out_va, path.as_bytes());
+        if n == path.len() && copy_to_user(out_va + n, &[0]) == 1 {
+            n
+        } else {
+            usize::MAX
+        }
+    } else {
+        usize::MAX
+    })
+}
+
+fn sys_stat(tf: &mut TrapFrame) -> SyscallResult {
     // a0 = path (C string in user VA), a1 = stat buffer (user VA)
     let path_va = tf.a0;
     let stat_buf = tf.a1;
 
-    let mut path_buf = [0u8; 256];
-    let path = match read_user_cstr_in_page(path_va, 255, &mut path_buf) {
+    let mut path_buf = PathBuf::new();
+    let path = match read_user_path(path_va, &mut path_buf) {
         Ok(s) => s,
         Err(_) => {
-            tf.a0 = usize::MAX;
-            tf.sepc = tf.sepc.wrapping_add(4);
-            return;
+            return Return(usize::MAX);
         }
     };
+    let path = cwd_path(path);
 
-    match fs::stat_file(path) {
-        Some(stat) => {
-            // Write simplified stat structure to user buffer
-            // For now, just write size and mode (8 bytes each)
-            let stat_data = [stat.size as u64, stat.mode as u64];
+    if stat_buf == 0 || !stat_buf.is_multiple_of(core::mem::align_of::<uapi::Stat>()) {
+        return Return(usize::MAX);
+    }
 
+    match fs::stat_file(&path) {
+        Some(stat) => {
+            let reply = uapi::Stat {
+                size: stat.size as u64,
+                mode: stat.mode,
+                is_dir: stat.is_dir as u32,
+                mtime: stat.mtime,
+            };
             unsafe {
                 with_sum_no_timer(|| {
-                    let ptr = stat_buf as *mut u64;
-                    core::ptr::write(ptr, stat_data[0]);
-                    core::ptr::write(ptr.add(1), stat_data[1]);
+                    core::ptr::write(stat_buf as *mut uapi::Stat, reply);
                 });
             }
-            tf.a0 = 0;
+            Return(0)
         }
-        None => {
-            tf.a0 = usize::MAX;
+        None => Return(usize::MAX),
+    }
+}
+
+fn sys_checksum(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = path
+    let mut path_buf = PathBuf::new();
+    let path = match read_user_path(tf.a0, &mut path_buf) {
+        Ok(s) => cwd_path(s),
+        Err(_) => return Return(usize::MAX),
+    };
+    match fs::checksum_file(&path) {
+        Some(crc) => Return(crc as usize),
+        None => Return(usize::MAX),
+    }
+}
+
+fn sys_regdump(tf: &mut TrapFrame) -> SyscallResult {
+    // The same table a kernel fault prints, one log line per row. sepc is
+    // the caller's ecall and a7 holds REGDUMP.
+    if crate::logging::should_log(uapi::LogLevel::Trace) {
+        let mut dump = String::new();
+        let _ = crate::trapframe::dump_trapframe(&mut dump, tf, scause::read().bits(), stval::read());
+        for line in dump.split_terminator("\r\n") {
+            crate::ktrace!("regdump: {}", line);
         }
     }
-    tf.sepc = tf.sepc.wrapping_add(4);
+    Return(0)
 }
 
-fn sys_chmod(tf: &mut TrapFrame) {
+fn sys_chmod(tf: &mut TrapFrame) -> SyscallResult {
     // a0 = path (C string in user VA), a1 = mode
     let path_va = tf.a0;
     let mode = tf.a1 as u32;
 
-    let mut buf = [0u8; 256];
-    let path = match read_user_cstr_in_page(path_va, 255, &mut buf) {
+    let mut buf = PathBuf::new();
+    let path = match read_user_path(path_va, &mut buf) {
         Ok(s) => s,
         Err(_) => {
-            tf.a0 = usize::MAX;
-            tf.sepc = tf.sepc.wrapping_add(4);
-            return;
+            return Return(usize::MAX);
         }
     };
+    let path = cwd_path(path);
 
-    match fs::chmod_file(path, mode) {
-        Ok(_) => tf.a0 = 0,
-        Err(_) => tf.a0 = usize::MAX,
+    match fs::chmod_file(&path, mode) {
+        Ok(_) => Return(0),
+        Err(_) => Return(usize::MAX),
     }
-    tf.sepc = tf.sepc.wrapping_add(4);
 }
 
-fn sys_readdir(tf: &mut TrapFrame) {
+fn sys_readdir(tf: &mut TrapFrame) -> SyscallResult {
     // a0 = buffer (user VA), a1 = buffer length
     let buf_va = tf.a0;
     let mut len = tf.a1;
 
     if buf_va == 0 || len == 0 {
-        tf.a0 = 0;
-        tf.sepc = tf.sepc.wrapping_add(4);
-        return;
+        return Return(0);
     }
 
     // Cap to page boundary
@@ -1008,38 +1805,53 @@ fn sys_readdir(tf: &mut TrapFrame) {
     // Get list of writable files
     let count = fs::list_writable_files(&mut kernel_buf[..safe_len]);
 
-    // Copy to user space
-    if count > 0 {
-        // Find actual bytes used (up to the last null terminator)
-        let mut bytes_used = 0usize;
-        let mut nulls_found = 0usize;
-        for i in 0..safe_len {
-            if kernel_buf[i] == 0 {
-                nulls_found += 1;
-                bytes_used = i + 1;
-                if nulls_found == count {
-                    break;
-                }
-            }
-        }
-
-        let _copied = copy_to_user(buf_va, &kernel_buf[..bytes_used]);
-        tf.a0 = count;
+    // Only hand out complete names, never more than were listed
+    let (names, bytes_used) = fs::name_list_len(&kernel_buf[..safe_len], count);
+    Return(if names == 0 {
+        0
+    } else if copy_to_user(buf_va, &kernel_buf[..bytes_used]) == bytes_used {
+        names
     } else {
-        tf.a0 = 0;
+        usize::MAX
+    })
+}
+
+fn sys_getdents(tf: &mut TrapFrame) -> SyscallResult {
+    // a0 = buffer (user VA), a1 = buffer length, a2 = index of first entry
+    let buf_va = tf.a0;
+    let start = tf.a2;
+
+    if buf_va == 0 || tf.a1 == 0 {
+        return Return(0);
     }
 
-    tf.sepc = tf.sepc.wrapping_add(4);
+    // Records are packed in a kernel buffer, then copied out within one page
+    let mut kernel_buf = [0u8; 4096];
+    let len = cap_to_page(buf_va, core::cmp::min(tf.a1, kernel_buf.len()));
+    let used = fs::list_dirents(&mut kernel_buf[..len], start);
+
+    Return(copy_to_user(buf_va, &kernel_buf[..used]))
+}
+
+/// Reply written by `get_fb_info` (must match usys::FbInfo and libc's struct fb_info)
+#[repr(C)]
+struct FbInfoReply {
+    width: usize,
+    height: usize,
+    stride: usize,
+    addr: usize,
+    format: u32,
+    _reserved: u32, // keeps the padding defined when copied out
 }
 
-fn sys_get_fb_info(tf: &mut TrapFrame) {
+const _: () = assert!(core::mem::size_of::<FbInfoReply>() == 40);
+
+fn sys_get_fb_info(tf: &mut TrapFrame) -> SyscallResult {
     // a0 = pointer to FbInfo struct in user space
     let info_va = tf.a0;
     
     if info_va == 0 {
-        tf.a0 = usize::MAX;
-        tf.sepc = tf.sepc.wrapping_add(4);
-        return;
+        return Return(usize::MAX);
     }
     
     // Get framebuffer info from display subsystem
@@ -1052,18 +1864,7 @@ fn sys_get_fb_info(tf: &mut TrapFrame) {
         };
         
         if user_fb_va == 0 {
-            tf.a0 = usize::MAX;
-            tf.sepc = tf.sepc.wrapping_add(4);
-            return;
-        }
-        
-        // Struct to write to user space (must match usys::FbInfo)
-        #[repr(C)]
-        struct FbInfoReply {
-            width: usize,
-            height: usize,
-            stride: usize,
-            addr: usize,
+            return Return(usize::MAX);
         }
         
         let reply = FbInfoReply {
@@ -1071,6 +1872,8 @@ fn sys_get_fb_info(tf: &mut TrapFrame) {
             height: fb_info.height,
             stride: fb_info.stride,
             addr: user_fb_va, // Return user VA, not physical address
+            format: fb_info.format,
+            _reserved: 0,
         };
         
         // Copy to user space
@@ -1085,21 +1888,24 @@ fn sys_get_fb_info(tf: &mut TrapFrame) {
             });
         }
         
-        tf.a0 = 0; // Success
+        Return(0) // Success
     } else {
-        tf.a0 = usize::MAX; // No framebuffer available
+        Return(usize::MAX) // No framebuffer available
     }
-    
-    tf.sepc = tf.sepc.wrapping_add(4);
 }
 
-fn sys_fb_flush(tf: &mut TrapFrame) {
+fn sys_sync(_tf: &mut TrapFrame) -> SyscallResult {
+    // The RAM filesystem has nothing to write back; only the display needs
+    // presenting
+    let have_fb = crate::display::get_framebuffer().is_some();
+    Return(if crate::display::sync_display(have_fb, crate::display::flush_framebuffer) { 0 } else { usize::MAX })
+}
+
+fn sys_fb_flush(_tf: &mut TrapFrame) -> SyscallResult {
     // Flush framebuffer changes to the display device
     if crate::display::flush_framebuffer() {
-        tf.a0 = 0; // Success
+        Return(0) // Success
     } else {
-        tf.a0 = usize::MAX; // No framebuffer or flush failed
+        Return(usize::MAX) // No framebuffer or flush failed
     }
-    
-    tf.sepc = tf.sepc.wrapping_add(4);
 }
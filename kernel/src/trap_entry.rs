@@ -9,9 +9,9 @@ __trap_entry:
     csrrw   sp, sscratch, sp
 
     // Make space for TrapFrame on the *kernel* stack
-    addi    sp, sp, -152
+    addi    sp, sp, -264
 
-    // Save registers (callee/caller selection kept minimal)
+    // Save registers; all but t0 come back from the frame on return
     sd      ra,   0(sp)
 
     // Save *user* SP that we just moved into sscratch
@@ -33,6 +33,20 @@ __trap_entry:
     sd      t4, 112(sp)
     sd      t5, 120(sp)
     sd      t6, 128(sp)
+    sd      gp, 152(sp)
+    sd      tp, 160(sp)
+    sd      s0, 168(sp)
+    sd      s1, 176(sp)
+    sd      s2, 184(sp)
+    sd      s3, 192(sp)
+    sd      s4, 200(sp)
+    sd      s5, 208(sp)
+    sd      s6, 216(sp)
+    sd      s7, 224(sp)
+    sd      s8, 232(sp)
+    sd      s9, 240(sp)
+    sd      s10, 248(sp)
+    sd      s11, 256(sp)
 
     csrr    t0, sepc
     sd      t0, 136(sp)
@@ -65,12 +79,26 @@ __trap_entry:
     ld      t4, 112(sp)
     ld      t5, 120(sp)
     ld      t6, 128(sp)
+    ld      gp, 152(sp)
+    ld      tp, 160(sp)
+    ld      s0, 168(sp)
+    ld      s1, 176(sp)
+    ld      s2, 184(sp)
+    ld      s3, 192(sp)
+    ld      s4, 200(sp)
+    ld      s5, 208(sp)
+    ld      s6, 216(sp)
+    ld      s7, 224(sp)
+    ld      s8, 232(sp)
+    ld      s9, 240(sp)
+    ld      s10, 248(sp)
+    ld      s11, 256(sp)
 
     // Recover the *user* SP from TrapFrame slot
     ld      t0,   8(sp)
 
     // Pop TrapFrame from kernel stack
-    addi    sp, sp, 152
+    addi    sp, sp, 264
 
     // Put user SP back into sscratch, then swap stacks back (kernel->user)
     csrw    sscratch, t0
@@ -0,0 +1,213 @@
+//! Registers saved by `__trap_entry`. The layout must match the offsets used
+//! in trap_entry.rs. Kept free of CSR access so kernel_lib can test it.
+
+use core::fmt;
+
+#[repr(C)]
+#[derive(Clone, Default)]
+pub struct TrapFrame {
+    pub ra: usize, // x1
+    pub sp: usize, // x2 (interrupted SP)
+    pub t0: usize, // x5
+    pub t1: usize, // x6
+    pub t2: usize, // x7
+    pub a0: usize, // x10
+    pub a1: usize, // x11
+    pub a2: usize, // x12
+    pub a3: usize, // x13
+    pub a4: usize, // x14
+    pub a5: usize, // x15
+    pub a6: usize, // x16
+    pub a7: usize, // x17
+    pub t3: usize, // x28
+    pub t4: usize, // x29
+    pub t5: usize, // x30
+    pub t6: usize, // x31
+    pub sepc: usize,
+    pub sstatus_bits: usize,
+    // Registers the kernel's own code never changes, saved all the same:
+    // a parked parent must get back its values, not its child's
+    pub gp: usize, // x3
+    pub tp: usize, // x4
+    pub s: [usize; 12], // x8, x9, x18..x27
+}
+
+/// `sstatus.SPP`: set when the trap was taken from S-mode
+pub const SSTATUS_SPP: usize = 1 << 8;
+
+/// True if a trap with these saved `sstatus` bits came from U-mode.
+pub fn is_user_fault(sstatus_bits: usize) -> bool {
+    sstatus_bits & SSTATUS_SPP == 0
+}
+
+impl TrapFrame {
+    /// Set general register `x<reg>` as it will be restored on return.
+    /// Fails for t0, which the trap entry uses as scratch and doesn't
+    /// restore; writes to x0 are dropped.
+    pub fn set_reg(&mut self, reg: usize, value: usize) -> bool {
+        let slot = match reg {
+            0 => return true,
+            1 => &mut self.ra,
+            2 => &mut self.sp,
+            3 => &mut self.gp,
+            4 => &mut self.tp,
+            6 => &mut self.t1,
+            7 => &mut self.t2,
+            8 | 9 => &mut self.s[reg - 8],
+            10 => &mut self.a0,
+            11 => &mut self.a1,
+            12 => &mut self.a2,
+            13 => &mut self.a3,
+            14 => &mut self.a4,
+            15 => &mut self.a5,
+            16 => &mut self.a6,
+            17 => &mut self.a7,
+            18..=27 => &mut self.s[reg - 16],
+            28 => &mut self.t3,
+            29 => &mut self.t4,
+            30 => &mut self.t5,
+            31 => &mut self.t6,
+            _ => return false,
+        };
+        *slot = value;
+        true
+    }
+
+    /// Finish a syscall: return `value` in a0 and resume after the `ecall`.
+    pub fn return_from_ecall(&mut self, value: usize) {
+        self.a0 = value;
+        self.sepc = self.sepc.wrapping_add(4);
+    }
+
+    /// The saved general registers by ABI name, in register number order
+    pub fn gprs(&self) -> [(&'static str, usize); 17] {
+        [
+            ("ra", self.ra),
+            ("sp", self.sp),
+            ("t0", self.t0),
+            ("t1", self.t1),
+            ("t2", self.t2),
+            ("a0", self.a0),
+            ("a1", self.a1),
+            ("a2", self.a2),
+            ("a3", self.a3),
+            ("a4", self.a4),
+            ("a5", self.a5),
+            ("a6", self.a6),
+            ("a7", self.a7),
+            ("t3", self.t3),
+            ("t4", self.t4),
+            ("t5", self.t5),
+            ("t6", self.t6),
+        ]
+    }
+}
+
+/// Write the saved registers as a table, four to a line, followed by the
+/// trap CSRs. Used when a fault is about to take the kernel down, and by
+/// `regdump` to log a user program's registers.
+pub fn dump_trapframe<W: fmt::Write>(out: &mut W, tf: &TrapFrame, scause: usize, stval: usize) -> fmt::Result {
+    for row in tf.gprs().chunks(4) {
+        for (i, (name, value)) in row.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "  " };
+            write!(out, "{}{:>2}={:#018x}", sep, name, value)?;
+        }
+        out.write_str("\r\n")?;
+    }
+    write!(
+        out,
+        "sepc={:#018x}  scause={:#018x}  stval={:#018x}  sstatus={:#018x}\r\n",
+        tf.sepc, scause, stval, tf.sstatus_bits
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_return_from_ecall_skips_the_ecall() {
+        // What a syscall's `Return(0)` does
+        let mut tf = TrapFrame { a0: 7, sepc: 0x1000, ..Default::default() };
+        tf.return_from_ecall(0);
+        assert_eq!(tf.a0, 0);
+        assert_eq!(tf.sepc, 0x1004);
+    }
+
+    #[test]
+    fn test_set_reg_by_number() {
+        let mut tf = TrapFrame::default();
+        assert!(tf.set_reg(10, 5));
+        assert!(tf.set_reg(31, 6));
+        assert!(tf.set_reg(0, 7));
+        assert_eq!((tf.a0, tf.t6), (5, 6));
+        // The s registers are numbered in two runs
+        assert!(tf.set_reg(8, 8) && tf.set_reg(9, 9) && tf.set_reg(18, 18) && tf.set_reg(27, 27));
+        assert_eq!((tf.s[0], tf.s[1], tf.s[2], tf.s[11]), (8, 9, 18, 27));
+        assert!(tf.set_reg(3, 3) && tf.set_reg(4, 4));
+        assert_eq!((tf.gp, tf.tp), (3, 4));
+        // t0 is the entry code's scratch register
+        assert!(!tf.set_reg(5, 1));
+        assert!(!tf.set_reg(32, 1));
+    }
+
+    #[test]
+    fn test_dump_trapframe_lines() {
+        let mut tf = TrapFrame { ra: 0x8020_1234, sp: 0x8021_0000, a0: 1, a7: 0x2a, t6: usize::MAX, ..Default::default() };
+        tf.sepc = 0x8020_0abc;
+        tf.sstatus_bits = SSTATUS_SPP;
+        let mut out = String::new();
+        dump_trapframe(&mut out, &tf, 0xd, 0x10).unwrap();
+        let lines: Vec<&str> = out.split_terminator("\r\n").collect();
+        assert_eq!(
+            lines,
+            [
+                "ra=0x0000000080201234  sp=0x0000000080210000  t0=0x0000000000000000  t1=0x0000000000000000",
+                "t2=0x0000000000000000  a0=0x0000000000000001  a1=0x0000000000000000  a2=0x0000000000000000",
+                "a3=0x0000000000000000  a4=0x0000000000000000  a5=0x0000000000000000  a6=0x0000000000000000",
+                "a7=0x000000000000002a  t3=0x0000000000000000  t4=0x0000000000000000  t5=0x0000000000000000",
+                "t6=0xffffffffffffffff",
+                "sepc=0x0000000080200abc  scause=0x000000000000000d  stval=0x0000000000000010  sstatus=0x0000000000000100",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dump_trapframe_at_ecall() {
+        // What regdump sees: a7 is the syscall number, scause a user ecall
+        let tf = TrapFrame { a0: 0x10, a7: 44, sepc: 0x1_0040, sp: 0x3fff_ff00, ..Default::default() };
+        let mut out = String::new();
+        dump_trapframe(&mut out, &tf, 8, 0).unwrap();
+        assert!(out.ends_with("\r\n"));
+        // Each line is logged separately, so none may be empty
+        let lines: Vec<&str> = out.split_terminator("\r\n").collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines.iter().all(|l| !l.is_empty()));
+        assert_eq!(lines[0], "ra=0x0000000000000000  sp=0x000000003fffff00  t0=0x0000000000000000  t1=0x0000000000000000");
+        assert!(lines[1].contains("a0=0x0000000000000010"));
+        assert!(lines[3].starts_with("a7=0x000000000000002c  "));
+        assert_eq!(lines[5], "sepc=0x0000000000010040  scause=0x0000000000000008  stval=0x0000000000000000  sstatus=0x0000000000000000");
+    }
+
+    #[test]
+    fn test_layout_matches_trap_entry() {
+        use core::mem::{offset_of, size_of};
+        // The offsets trap_entry.rs stores to and loads from
+        assert_eq!(offset_of!(TrapFrame, t6), 128);
+        assert_eq!(offset_of!(TrapFrame, sepc), 136);
+        assert_eq!(offset_of!(TrapFrame, sstatus_bits), 144);
+        assert_eq!(offset_of!(TrapFrame, gp), 152);
+        assert_eq!(offset_of!(TrapFrame, tp), 160);
+        assert_eq!(offset_of!(TrapFrame, s), 168);
+        assert_eq!(size_of::<TrapFrame>(), 264);
+    }
+
+    #[test]
+    fn test_is_user_fault_checks_spp() {
+        assert!(is_user_fault(0));
+        assert!(!is_user_fault(SSTATUS_SPP));
+        // SIE, SUM and the other bits don't matter
+        assert!(is_user_fault(!SSTATUS_SPP));
+        assert!(!is_user_fault(SSTATUS_SPP | (1 << 1) | (1 << 18)));
+    }
+}
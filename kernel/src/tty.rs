@@ -0,0 +1,238 @@
+//! Terminal line discipline for stdin.
+//!
+//! In raw mode (the default) `read(0, ..)` returns as soon as a single
+//! keystroke is available. In canonical mode the kernel collects a whole line
+//! in a [`LineBuffer`], handling backspace itself, and only hands it to
+//! userspace once Enter is pressed. Either way input is echoed only with
+//! `uapi::term::ECHO`, so a program that echoes for itself sees the same
+//! terminal whichever mode it reads in.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use uapi::term;
+use spin::Mutex;
+
+/// Maximum length of a canonical line, including the trailing `\n`.
+pub const LINE_MAX: usize = 256;
+
+/// What the caller should echo back after feeding a byte.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Echo {
+    None,
+    Byte(u8),
+    /// Erase the previous character ("\x08 \x08").
+    Erase,
+    /// The line is complete ("\r\n").
+    Newline,
+}
+
+/// Canonical-mode line buffer. Editing follows `usys::read_line`: `\r` or
+/// `\n` ends the line, 0x08/0x7f delete the previous byte, and input past
+/// the end of the buffer is dropped until Enter.
+pub struct LineBuffer {
+    buf: [u8; LINE_MAX],
+    len: usize,
+    /// Read cursor into a completed line; `None` while still editing.
+    done: Option<usize>,
+    last_cr: bool,
+}
+
+impl Default for LineBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineBuffer {
+    pub const fn new() -> Self {
+        Self { buf: [0; LINE_MAX], len: 0, done: None, last_cr: false }
+    }
+
+    /// True once a full line is waiting to be taken.
+    pub fn line_ready(&self) -> bool {
+        self.done.is_some()
+    }
+
+    /// Feed one input byte. Bytes arriving while a finished line is still
+    /// pending are ignored.
+    pub fn feed(&mut self, b: u8) -> Echo {
+        if self.done.is_some() {
+            return Echo::None;
+        }
+        let after_cr = core::mem::replace(&mut self.last_cr, b == b'\r');
+        match b {
+            // "\r\n" from a terminal is a single Enter
+            b'\n' if after_cr => Echo::None,
+            b'\r' | b'\n' => {
+                self.buf[self.len] = b'\n';
+                self.len += 1;
+                self.done = Some(0);
+                Echo::Newline
+            }
+            0x08 | 0x7f => {
+                if self.len > 0 {
+                    self.len -= 1;
+                    Echo::Erase
+                } else {
+                    Echo::None
+                }
+            }
+            _ => {
+                // keep one slot for the terminating '\n'
+                if self.len < LINE_MAX - 1 {
+                    self.buf[self.len] = b;
+                    self.len += 1;
+                    Echo::Byte(b)
+                } else {
+                    Echo::None
+                }
+            }
+        }
+    }
+
+    /// Copy out up to `out.len()` bytes of the completed line. Once the whole
+    /// line has been consumed the buffer starts collecting the next one.
+    pub fn take(&mut self, out: &mut [u8]) -> usize {
+        let Some(pos) = self.done else { return 0 };
+        let n = core::cmp::min(out.len(), self.len - pos);
+        out[..n].copy_from_slice(&self.buf[pos..pos + n]);
+        if pos + n == self.len {
+            self.len = 0;
+            self.done = None;
+        } else {
+            self.done = Some(pos + n);
+        }
+        n
+    }
+
+    /// Discard any partial or pending line.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.done = None;
+        self.last_cr = false;
+    }
+}
+
+/// Whether `flags` are all known `uapi::term` flags
+pub const fn valid_mode(flags: usize) -> bool {
+    flags & !(term::CANONICAL | term::ECHO) == 0
+}
+
+/// Whether `read` echoes its input under mode `flags`
+pub const fn echoes(flags: usize) -> bool {
+    flags & term::ECHO != 0
+}
+
+// Current `uapi::term` flags
+static MODE: AtomicUsize = AtomicUsize::new(term::RAW);
+pub static LINE: Mutex<LineBuffer> = Mutex::new(LineBuffer::new());
+
+/// The `uapi::term` flags last set
+pub fn mode() -> usize {
+    MODE.load(Ordering::Relaxed)
+}
+
+pub fn is_canonical() -> bool {
+    mode() & term::CANONICAL != 0
+}
+
+pub fn is_echo() -> bool {
+    echoes(mode())
+}
+
+/// Switch to mode `flags`, dropping any buffered line.
+pub fn set_mode(flags: usize) {
+    LINE.lock().clear();
+    MODE.store(flags, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(lb: &mut LineBuffer, s: &[u8]) -> usize {
+        s.iter().filter(|&&b| lb.feed(b) == Echo::Erase).count()
+    }
+
+    fn take_line(lb: &mut LineBuffer) -> ([u8; LINE_MAX], usize) {
+        let mut out = [0u8; LINE_MAX];
+        let n = lb.take(&mut out);
+        (out, n)
+    }
+
+    #[test]
+    fn test_echo_follows_the_mode() {
+        // What usys::set_raw_mode(false) and (true) ask for
+        assert!(echoes(term::LINE));
+        assert!(!echoes(term::RAW));
+        // Each flag on its own
+        assert!(!echoes(term::CANONICAL));
+        assert!(echoes(term::RAW | term::ECHO));
+        assert!(valid_mode(term::LINE) && valid_mode(term::RAW));
+        assert!(!valid_mode(1 << 5));
+    }
+
+    #[test]
+    fn test_backspace_removes_previous_byte() {
+        let mut lb = LineBuffer::new();
+        assert_eq!(lb.feed(b'a'), Echo::Byte(b'a'));
+        assert_eq!(lb.feed(b'b'), Echo::Byte(b'b'));
+        assert_eq!(lb.feed(0x7f), Echo::Erase);
+        assert_eq!(lb.feed(b'c'), Echo::Byte(b'c'));
+        assert!(!lb.line_ready());
+        assert_eq!(lb.feed(b'\r'), Echo::Newline);
+        let (out, n) = take_line(&mut lb);
+        assert_eq!(&out[..n], b"ac\n");
+    }
+
+    #[test]
+    fn test_backspace_at_line_start_is_ignored() {
+        let mut lb = LineBuffer::new();
+        assert_eq!(lb.feed(0x08), Echo::None);
+        assert_eq!(lb.feed(0x7f), Echo::None);
+        // Deleting more than was typed stops at the start of the line
+        assert_eq!(feed_all(&mut lb, b"xy\x08\x08\x08z\n"), 2);
+        let (out, n) = take_line(&mut lb);
+        assert_eq!(&out[..n], b"z\n");
+    }
+
+    #[test]
+    fn test_crlf_is_one_enter() {
+        let mut lb = LineBuffer::new();
+        feed_all(&mut lb, b"ls\r");
+        let (out, n) = take_line(&mut lb);
+        assert_eq!(&out[..n], b"ls\n");
+        assert_eq!(lb.feed(b'\n'), Echo::None);
+        assert!(!lb.line_ready());
+        assert_eq!(lb.feed(b'\n'), Echo::Newline);
+    }
+
+    #[test]
+    fn test_full_buffer_still_accepts_enter() {
+        let mut lb = LineBuffer::new();
+        for _ in 0..LINE_MAX + 10 {
+            lb.feed(b'a');
+        }
+        assert_eq!(lb.feed(b'a'), Echo::None);
+        assert_eq!(lb.feed(0x7f), Echo::Erase);
+        assert_eq!(lb.feed(b'\n'), Echo::Newline);
+        let (out, n) = take_line(&mut lb);
+        assert_eq!(n, LINE_MAX - 1);
+        assert_eq!(out[n - 1], b'\n');
+    }
+
+    #[test]
+    fn test_short_reads_drain_the_line() {
+        let mut lb = LineBuffer::new();
+        feed_all(&mut lb, b"hello\n");
+        // Input while a line is pending is dropped
+        assert_eq!(lb.feed(b'q'), Echo::None);
+        let mut out = [0u8; 4];
+        assert_eq!(lb.take(&mut out), 4);
+        assert_eq!(&out, b"hell");
+        assert!(lb.line_ready());
+        assert_eq!(lb.take(&mut out), 2);
+        assert_eq!(&out[..2], b"o\n");
+        assert!(!lb.line_ready());
+        assert_eq!(lb.take(&mut out), 0);
+    }
+}
@@ -11,6 +11,8 @@ struct fb_info {
     unsigned long height;
     unsigned long stride;
     unsigned long addr;
+    unsigned int format;   /* VIRTIO_GPU_FORMAT_* (2 = B8G8R8X8) */
+    unsigned int reserved;
 };
 
 /* Get framebuffer information 
@@ -10,8 +10,8 @@ extern long syscall2(long nr, long a0, long a1);
 
 /* stat - get file status */
 int stat(const char *pathname, struct stat *statbuf) {
-    /* Call kernel stat syscall with simplified buffer */
-    unsigned long stat_buf[2];  /* [0]=size, [1]=mode */
+    /* Call kernel stat syscall with simplified buffer (uapi::Stat) */
+    unsigned long stat_buf[3];  /* [0]=size, [1]=mode | is_dir << 32, [2]=mtime */
     long ret = syscall2(SYS_STAT, (long)pathname, (long)stat_buf);
     
     if (ret == -1UL) {  /* usize::MAX from kernel */
@@ -22,7 +22,8 @@ int stat(const char *pathname, struct stat *statbuf) {
     /* Fill in the stat structure */
     if (statbuf) {
         statbuf->st_size = stat_buf[0];
-        statbuf->st_mode = stat_buf[1];
+        statbuf->st_mode = (unsigned int)stat_buf[1] |
+                           ((stat_buf[1] >> 32) ? S_IFDIR : S_IFREG);
         /* Other fields not supported yet */
         statbuf->st_dev = 0;
         statbuf->st_ino = 0;
@@ -33,7 +34,7 @@ int stat(const char *pathname, struct stat *statbuf) {
         statbuf->st_blksize = 4096;
         statbuf->st_blocks = (stat_buf[0] + 4095) / 4096;
         statbuf->st_atime = 0;
-        statbuf->st_mtime = 0;
+        statbuf->st_mtime = stat_buf[2];  /* seconds since boot */
         statbuf->st_ctime = 0;
     }
     
@@ -26,6 +26,7 @@ def run_test():
     )
     
     output_lines = []
+    ls_output = ""
     
     def read_until_prompt(timeout=5):
         """Read output until we see the shell prompt"""
@@ -65,6 +66,21 @@ def run_test():
         # Read output
         read_until_prompt(timeout=5)
         
+        # Create files, then check they survive the exit back to the shell
+        print("\nSending 'mkfiles' command...")
+        proc.stdin.write("mkfiles\n")
+        proc.stdin.flush()
+        time.sleep(2)
+        read_until_prompt(timeout=5)
+
+        print("\nSending 'ls' command...")
+        ls_start = len(output_lines)
+        proc.stdin.write("ls\n")
+        proc.stdin.flush()
+        time.sleep(1)
+        read_until_prompt(timeout=5)
+        ls_output = ''.join(output_lines[ls_start:])
+
         # Send "shutdown" command
         print("\nSending 'shutdown' command...")
         proc.stdin.write("shutdown\n")
@@ -98,6 +114,13 @@ def run_test():
         ("Shutdown initiated", "Shutting down..."),
     ]
     
+    tests_total += 1
+    if "mydata.txt" in ls_output:
+        print("[PASS] Files persist across exit")
+        tests_passed += 1
+    else:
+        print("[FAIL] Files persist across exit - 'ls' did not list mydata.txt")
+
     for test_name, expected_string in tests:
         tests_total += 1
         if expected_string in full_output:
@@ -0,0 +1,125 @@
+//! Directory entry records returned by the `getdents` syscall
+//!
+//! The kernel packs entries back to back, each laid out as:
+//!
+//! | bytes | field                         |
+//! |-------|-------------------------------|
+//! | 8     | size (u64, little endian)     |
+//! | 4     | mode (u32, little endian)     |
+//! | 1     | flags (`FLAG_DIR`)            |
+//! | 1     | name length                   |
+//! | n     | name bytes (no NUL)           |
+
+/// Bytes before the name in every record
+pub const HEADER_LEN: usize = 14;
+
+/// Longest name a record can carry
+pub const NAME_MAX: usize = 255;
+
+/// Record flag: entry is a directory
+pub const FLAG_DIR: u8 = 1 << 0;
+
+/// One decoded directory entry (the name is copied out of the buffer)
+#[derive(Clone)]
+pub struct Dirent {
+    name: [u8; NAME_MAX],
+    name_len: u8,
+    pub size: u64,
+    pub mode: u32,
+    pub is_dir: bool,
+}
+
+impl Dirent {
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len as usize]).unwrap_or("?")
+    }
+}
+
+/// Append one record to `buf` at `pos`. Returns the position after it,
+/// or `None` if it doesn't fit (nothing is written in that case).
+pub fn encode(buf: &mut [u8], pos: usize, name: &str, size: u64, mode: u32, is_dir: bool) -> Option<usize> {
+    let name = name.as_bytes();
+    if name.len() > NAME_MAX {
+        return None;
+    }
+    let end = pos.checked_add(HEADER_LEN + name.len())?;
+    if end > buf.len() {
+        return None;
+    }
+    let rec = &mut buf[pos..end];
+    rec[0..8].copy_from_slice(&size.to_le_bytes());
+    rec[8..12].copy_from_slice(&mode.to_le_bytes());
+    rec[12] = if is_dir { FLAG_DIR } else { 0 };
+    rec[13] = name.len() as u8;
+    rec[HEADER_LEN..].copy_from_slice(name);
+    Some(end)
+}
+
+/// Decode the record at `pos`. Returns the entry and the position of the
+/// next record, or `None` at the end of the buffer or on a truncated record.
+pub fn decode(buf: &[u8], pos: usize) -> Option<(Dirent, usize)> {
+    let header = buf.get(pos..pos.checked_add(HEADER_LEN)?)?;
+    let name_len = header[13] as usize;
+    let name_start = pos + HEADER_LEN;
+    let name_bytes = buf.get(name_start..name_start + name_len)?;
+
+    let mut name = [0u8; NAME_MAX];
+    name[..name_len].copy_from_slice(name_bytes);
+    let entry = Dirent {
+        name,
+        name_len: name_len as u8,
+        size: u64::from_le_bytes(header[0..8].try_into().ok()?),
+        mode: u32::from_le_bytes(header[8..12].try_into().ok()?),
+        is_dir: header[12] & FLAG_DIR != 0,
+    };
+    Some((entry, name_start + name_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let mut buf = [0u8; 64];
+        let pos = encode(&mut buf, 0, "hello.txt", 42, 0o644, false).unwrap();
+        let end = encode(&mut buf, pos, "bin", 0, 0o755, true).unwrap();
+
+        let (a, next) = decode(&buf[..end], 0).unwrap();
+        assert_eq!(a.name(), "hello.txt");
+        assert_eq!(a.size, 42);
+        assert_eq!(a.mode, 0o644);
+        assert!(!a.is_dir);
+        assert_eq!(next, pos);
+
+        let (b, next) = decode(&buf[..end], next).unwrap();
+        assert_eq!(b.name(), "bin");
+        assert!(b.is_dir);
+        assert_eq!(next, end);
+
+        assert!(decode(&buf[..end], end).is_none());
+    }
+
+    #[test]
+    fn test_encode_does_not_fit() {
+        let mut buf = [0u8; HEADER_LEN + 3];
+        assert!(encode(&mut buf, 0, "four", 0, 0, false).is_none());
+        assert_eq!(encode(&mut buf, 0, "abc", 0, 0, false), Some(HEADER_LEN + 3));
+    }
+
+    #[test]
+    fn test_decode_truncated_final_entry() {
+        let mut buf = [0u8; 64];
+        let pos = encode(&mut buf, 0, "first", 1, 0o600, false).unwrap();
+        let end = encode(&mut buf, pos, "second", 2, 0o600, false).unwrap();
+
+        // Cut the second record in the middle of its name
+        let cut = &buf[..end - 2];
+        let (a, next) = decode(cut, 0).unwrap();
+        assert_eq!(a.name(), "first");
+        assert!(decode(cut, next).is_none());
+
+        // ...and in the middle of its header
+        assert!(decode(&buf[..pos + 5], pos).is_none());
+    }
+}
@@ -201,16 +201,232 @@ pub const FONT_8X16: [[u8; 16]; 95] = [
     [0x00, 0x00, 0x76, 0xDC, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
 ];
 
+/// First byte covered by [`FONT_CP437_HIGH`]
+pub const CP437_HIGH_START: u8 = 0xA0;
+
+/// Glyphs for bytes 0xA0-0xFF in code page 437: accented letters, shades,
+/// box-drawing, block and math symbols. Console output is treated as CP437,
+/// not UTF-8, so `b"\xC9\xCD\xBB"` draws the top of a double-line box.
+pub const FONT_CP437_HIGH: [[u8; 16]; 96] = [
+    // 160: á
+    [0x00, 0x00, 0x0C, 0x18, 0x00, 0x78, 0x0C, 0x7C, 0xCC, 0xCC, 0xCC, 0x76, 0x00, 0x00, 0x00, 0x00],
+    // 161: í
+    [0x00, 0x00, 0x0C, 0x18, 0x00, 0x38, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, 0x00, 0x00, 0x00, 0x00],
+    // 162: ó
+    [0x00, 0x00, 0x0C, 0x18, 0x00, 0x7C, 0xC6, 0xC6, 0xC6, 0xC6, 0xC6, 0x7C, 0x00, 0x00, 0x00, 0x00],
+    // 163: ú
+    [0x00, 0x00, 0x0C, 0x18, 0x00, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0x76, 0x00, 0x00, 0x00, 0x00],
+    // 164: ñ
+    [0x00, 0x00, 0x76, 0xDC, 0x00, 0xDC, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00],
+    // 165: Ñ
+    [0x76, 0xDC, 0x00, 0xC6, 0xE6, 0xF6, 0xFE, 0xDE, 0xCE, 0xC6, 0xC6, 0xC6, 0xC6, 0x00, 0x00, 0x00],
+    // 166: ª
+    [0x00, 0x00, 0x3C, 0x6C, 0x6C, 0x3A, 0x00, 0x7E, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 167: º
+    [0x00, 0x00, 0x38, 0x6C, 0x6C, 0x38, 0x00, 0x7C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 168: ¿
+    [0x00, 0x00, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x0C, 0xC6, 0xC6, 0x7C, 0x00, 0x00, 0x00, 0x00],
+    // 169: ⌐
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFE, 0xC0, 0xC0, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 170: ¬
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFE, 0x06, 0x06, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 171: ½
+    [0x00, 0x40, 0xC0, 0x42, 0x44, 0xE8, 0x10, 0x2E, 0x41, 0x82, 0x04, 0x08, 0x1F, 0x00, 0x00, 0x00],
+    // 172: ¼
+    [0x00, 0x40, 0xC0, 0x42, 0x44, 0xE8, 0x10, 0x22, 0x46, 0x8A, 0x1F, 0x02, 0x02, 0x00, 0x00, 0x00],
+    // 173: ¡
+    [0x00, 0x00, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x3C, 0x3C, 0x3C, 0x18, 0x00, 0x00, 0x00, 0x00],
+    // 174: «
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x36, 0x6C, 0xD8, 0x6C, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 175: »
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xD8, 0x6C, 0x36, 0x6C, 0xD8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 176: ░
+    [0x22, 0x88, 0x22, 0x88, 0x22, 0x88, 0x22, 0x88, 0x22, 0x88, 0x22, 0x88, 0x22, 0x88, 0x22, 0x88],
+    // 177: ▒
+    [0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA],
+    // 178: ▓
+    [0xDD, 0x77, 0xDD, 0x77, 0xDD, 0x77, 0xDD, 0x77, 0xDD, 0x77, 0xDD, 0x77, 0xDD, 0x77, 0xDD, 0x77],
+    // 179: │
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 180: ┤
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0xF8, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 181: ╡
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0xF8, 0x18, 0xF8, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 182: ╢
+    [0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0xF6, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36],
+    // 183: ╖
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFE, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36],
+    // 184: ╕
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xF8, 0x18, 0xF8, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 185: ╣
+    [0x36, 0x36, 0x36, 0x36, 0x36, 0xF6, 0x06, 0xF6, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36],
+    // 186: ║
+    [0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36],
+    // 187: ╗
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xFE, 0x06, 0xF6, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36],
+    // 188: ╝
+    [0x36, 0x36, 0x36, 0x36, 0x36, 0xF6, 0x06, 0xFE, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 189: ╜
+    [0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0xFE, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 190: ╛
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0xF8, 0x18, 0xF8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 191: ┐
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF8, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 192: └
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 193: ┴
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 194: ┬
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 195: ├
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1F, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 196: ─
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 197: ┼
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 198: ╞
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0x1F, 0x18, 0x1F, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 199: ╟
+    [0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x37, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36],
+    // 200: ╚
+    [0x36, 0x36, 0x36, 0x36, 0x36, 0x37, 0x30, 0x3F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 201: ╔
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3F, 0x30, 0x37, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36],
+    // 202: ╩
+    [0x36, 0x36, 0x36, 0x36, 0x36, 0xF7, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 203: ╦
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0xF7, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36],
+    // 204: ╠
+    [0x36, 0x36, 0x36, 0x36, 0x36, 0x37, 0x30, 0x37, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36],
+    // 205: ═
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 206: ╬
+    [0x36, 0x36, 0x36, 0x36, 0x36, 0xF7, 0x00, 0xF7, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36],
+    // 207: ╧
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 208: ╨
+    [0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 209: ╤
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0xFF, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 210: ╥
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36],
+    // 211: ╙
+    [0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x3F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 212: ╘
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0x1F, 0x18, 0x1F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 213: ╒
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x1F, 0x18, 0x1F, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 214: ╓
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3F, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36],
+    // 215: ╫
+    [0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0xFF, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36, 0x36],
+    // 216: ╪
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0x18, 0xFF, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 217: ┘
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0xF8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 218: ┌
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 219: █
+    [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+    // 220: ▄
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+    // 221: ▌
+    [0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0],
+    // 222: ▐
+    [0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F],
+    // 223: ▀
+    [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 224: α
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x76, 0xDC, 0xCC, 0xCC, 0xCC, 0xDC, 0x76, 0x00, 0x00, 0x00, 0x00],
+    // 225: ß
+    [0x00, 0x00, 0x78, 0xCC, 0xCC, 0xD8, 0xCC, 0xC6, 0xC6, 0xC6, 0xCC, 0xD8, 0xC0, 0x00, 0x00, 0x00],
+    // 226: Γ
+    [0x00, 0x00, 0xFE, 0xC6, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0x00, 0x00, 0x00, 0x00],
+    // 227: π
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xFE, 0x6C, 0x6C, 0x6C, 0x6C, 0x6C, 0x6C, 0x00, 0x00, 0x00, 0x00],
+    // 228: Σ
+    [0x00, 0x00, 0xFE, 0xC6, 0x60, 0x30, 0x18, 0x18, 0x30, 0x60, 0xC6, 0xFE, 0x00, 0x00, 0x00, 0x00],
+    // 229: σ
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7E, 0xD8, 0xCC, 0xCC, 0xCC, 0xCC, 0x70, 0x00, 0x00, 0x00, 0x00],
+    // 230: µ
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x66, 0x66, 0x66, 0x66, 0x7C, 0x60, 0x60, 0xC0, 0x00, 0x00],
+    // 231: τ
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7E, 0xD8, 0x18, 0x18, 0x18, 0x18, 0x1C, 0x00, 0x00, 0x00, 0x00],
+    // 232: Φ
+    [0x00, 0x00, 0x3C, 0x18, 0x7E, 0xDB, 0xDB, 0xDB, 0xDB, 0x7E, 0x18, 0x3C, 0x00, 0x00, 0x00, 0x00],
+    // 233: Θ
+    [0x00, 0x00, 0x38, 0x6C, 0xC6, 0xC6, 0xFE, 0xC6, 0xC6, 0xC6, 0x6C, 0x38, 0x00, 0x00, 0x00, 0x00],
+    // 234: Ω
+    [0x00, 0x00, 0x38, 0x6C, 0xC6, 0xC6, 0xC6, 0x6C, 0x6C, 0x6C, 0x6C, 0xEE, 0x00, 0x00, 0x00, 0x00],
+    // 235: δ
+    [0x00, 0x00, 0x1E, 0x30, 0x18, 0x0C, 0x3E, 0x66, 0x66, 0x66, 0x66, 0x3C, 0x00, 0x00, 0x00, 0x00],
+    // 236: ∞
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x6C, 0xB6, 0x92, 0xDA, 0x6C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 237: φ
+    [0x00, 0x00, 0x00, 0x03, 0x06, 0x7E, 0xDB, 0xDB, 0xF3, 0x7E, 0x60, 0xC0, 0x00, 0x00, 0x00, 0x00],
+    // 238: ε
+    [0x00, 0x00, 0x1C, 0x30, 0x60, 0x60, 0x7C, 0x60, 0x60, 0x60, 0x30, 0x1C, 0x00, 0x00, 0x00, 0x00],
+    // 239: ∩
+    [0x00, 0x00, 0x00, 0x7C, 0xC6, 0xC6, 0xC6, 0xC6, 0xC6, 0xC6, 0xC6, 0xC6, 0x00, 0x00, 0x00, 0x00],
+    // 240: ≡
+    [0x00, 0x00, 0x00, 0x00, 0xFE, 0x00, 0x00, 0xFE, 0x00, 0x00, 0xFE, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 241: ±
+    [0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x7E, 0x18, 0x18, 0x00, 0x00, 0x7E, 0x00, 0x00, 0x00, 0x00],
+    // 242: ≥
+    [0x00, 0x00, 0x00, 0x30, 0x18, 0x0C, 0x06, 0x0C, 0x18, 0x30, 0x00, 0x7E, 0x00, 0x00, 0x00, 0x00],
+    // 243: ≤
+    [0x00, 0x00, 0x00, 0x0C, 0x18, 0x30, 0x60, 0x30, 0x18, 0x0C, 0x00, 0x7E, 0x00, 0x00, 0x00, 0x00],
+    // 244: ⌠
+    [0x00, 0x00, 0x0E, 0x1B, 0x1B, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+    // 245: ⌡
+    [0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0xD8, 0xD8, 0xD8, 0x70, 0x00, 0x00, 0x00, 0x00],
+    // 246: ÷
+    [0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x7E, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 247: ≈
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x76, 0xDC, 0x00, 0x76, 0xDC, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 248: °
+    [0x00, 0x38, 0x6C, 0x6C, 0x38, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 249: ∙
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 250: ·
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 251: √
+    [0x00, 0x0F, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0xEC, 0x6C, 0x6C, 0x3C, 0x1C, 0x0C, 0x00, 0x00, 0x00],
+    // 252: ⁿ
+    [0x00, 0xD8, 0x6C, 0x6C, 0x6C, 0x6C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 253: ²
+    [0x00, 0x70, 0xD8, 0x30, 0x60, 0xF8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 254: ■
+    [0x00, 0x00, 0x00, 0x00, 0x7C, 0x7C, 0x7C, 0x7C, 0x7C, 0x7C, 0x7C, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 255: NBSP
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+];
+
 /// Get font bitmap for a character (8x16 font)
-/// Returns None for unsupported characters (outside ASCII 32-126)
+/// Returns None for unsupported characters (controls, DEL and 0x80-0x9F)
 pub fn get_char_bitmap(c: u8) -> Option<&'static [u8; 16]> {
     if c >= 32 && c <= 126 {
         Some(&FONT_8X16[(c - 32) as usize])
+    } else if c >= CP437_HIGH_START {
+        Some(&FONT_CP437_HIGH[(c - CP437_HIGH_START) as usize])
     } else {
         None
     }
 }
 
+/// Whether `c` has a glyph, i.e. prints rather than being a control byte
+#[inline]
+pub fn is_printable(c: u8) -> bool {
+    get_char_bitmap(c).is_some()
+}
+
+/// Test whether the pixel at `col` (0 = leftmost) is set in a glyph row.
+/// Rows are stored MSB-left, so column 0 is bit 7.
+#[inline]
+pub fn glyph_pixel(bitmap_row: u8, col: usize) -> bool {
+    col < FONT_WIDTH && (bitmap_row & (0x80 >> col)) != 0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -232,6 +448,71 @@ mod tests {
         assert!(get_char_bitmap(127).is_none());
     }
 
+    #[test]
+    fn test_full_printable_ascii_coverage() {
+        for c in 0u8..=255 {
+            let bitmap = get_char_bitmap(c);
+            if (0x20..=0x7e).contains(&c) || c >= 0xa0 {
+                assert!(bitmap.is_some(), "missing glyph for {:#04x}", c);
+            } else {
+                assert!(bitmap.is_none(), "unexpected glyph for {:#04x}", c);
+            }
+        }
+        // Every visible character must have at least one pixel set.
+        for c in 0x21u8..=0x7e {
+            let bitmap = get_char_bitmap(c).unwrap();
+            assert!(bitmap.iter().any(|&row| row != 0), "blank glyph for {:#04x}", c);
+        }
+    }
+
+    #[test]
+    fn test_cp437_box_drawing() {
+        // Double-line top-left corner: horizontal and vertical strokes meet
+        let corner = get_char_bitmap(0xC9).unwrap();
+        assert_eq!(corner[0], 0);
+        assert!(corner[15] != 0, "vertical stroke should reach the bottom");
+        assert!(corner.iter().any(|&row| row & 0x01 != 0), "horizontal stroke should reach the right edge");
+        // Full block and the non-breaking space
+        assert!(get_char_bitmap(0xDB).unwrap().iter().all(|&row| row == 0xFF));
+        assert!(get_char_bitmap(0xFF).unwrap().iter().all(|&row| row == 0));
+        assert!(is_printable(0xC4));
+        assert!(!is_printable(0x9F));
+        // Every high glyph but NBSP draws something
+        for c in 0xA0u8..0xFF {
+            assert!(get_char_bitmap(c).unwrap().iter().any(|&row| row != 0), "blank glyph for {:#04x}", c);
+        }
+    }
+
+    #[test]
+    fn test_glyph_not_mirrored() {
+        // Render 'F' into a small buffer the same way fb_console does
+        let bitmap = get_char_bitmap(b'F').unwrap();
+        let mut pixels = [[false; FONT_WIDTH]; FONT_HEIGHT];
+        for (row, &bits) in bitmap.iter().enumerate() {
+            for col in 0..FONT_WIDTH {
+                pixels[row][col] = glyph_pixel(bits, col);
+            }
+        }
+
+        // Top bar runs from the left edge; the rightmost column stays clear
+        assert_eq!(pixels[2], [true, true, true, true, true, true, true, false]);
+        // Vertical stem sits on the left side for the whole height of the glyph
+        for row in 3..11 {
+            assert!(pixels[row][1] && pixels[row][2], "stem missing on row {}", row);
+            assert!(!pixels[row][7], "mirrored pixel on row {}", row);
+        }
+        // Bottom serif is left-aligned
+        assert_eq!(pixels[11], [true, true, true, true, false, false, false, false]);
+    }
+
+    #[test]
+    fn test_glyph_pixel_bit_order() {
+        assert!(glyph_pixel(0x80, 0));
+        assert!(!glyph_pixel(0x80, 7));
+        assert!(glyph_pixel(0x01, 7));
+        assert!(!glyph_pixel(0xFF, FONT_WIDTH));
+    }
+
     #[test]
     fn test_font_dimensions() {
         assert_eq!(FONT_WIDTH, 8);
@@ -0,0 +1,23 @@
+//! Tiny read/write traits for file descriptors and anything that acts like one
+//!
+//! usys implements them for its file types; code written against them can be
+//! tested on the host with an in-memory reader or writer.
+
+use crate::{SysErr, SysResult};
+
+pub trait IoWrite {
+    /// Write some of `buf`; may be short, see `write_fd`.
+    fn write(&self, buf: &[u8]) -> SysResult<usize>;
+    /// Keep calling `write` until all of `buf` is written.
+    fn write_all(&self, mut buf: &[u8]) -> SysResult<()> {
+        while !buf.is_empty() {
+            let n = self.write(buf)?;
+            if n == 0 { return Err(SysErr::Fail); }
+            buf = &buf[n..];
+        }
+        Ok(())
+    }
+}
+pub trait IoRead {
+    fn read(&self, buf: &mut [u8]) -> SysResult<usize>;
+}
@@ -1,10 +1,15 @@
 #![no_std]
 
+pub mod dirent;
 pub mod font;
+pub mod io;
+pub mod path;
+
+pub use path::PathBuf;
 
 pub mod nr {
     pub const WRITE: usize = 1; // write(ptr,len) -> usize
-    pub const EXIT: usize = 2; // exit()
+    pub const EXIT: usize = 2; // exit(code)
     pub const WRITE_CSTR: usize = 3; // write_cstr(ptr) -> usize
     pub const OPEN: usize = 4; // open_cstr(path) -> fd or usize::MAX
     pub const READ: usize = 5; // read(fd, buf, len) -> n or usize::MAX
@@ -12,19 +17,169 @@ pub mod nr {
     pub const CLOSE: usize = 7; // close(fd) -> 0 or usize::MAX
     pub const LSEEK: usize = 8; // lseek(fd, offset, whence) -> new_offset
     pub const BRK: usize = 9; // brk(addr) -> new_brk
-    pub const GETTIME: usize = 10; // gettime(ts_ptr) -> 0
+    pub const GETTIME: usize = 10; // gettime(ms_ptr or 0) -> ticks
     pub const POWEROFF: usize = 11; // poweroff() -> !
     pub const EXEC: usize = 12; // exec(path) -> !
     pub const EXECV: usize = 13; // execv(path, argv) -> !
     pub const CREAT: usize = 14; // creat(path, mode) -> fd or usize::MAX
     pub const UNLINK: usize = 15; // unlink(path) -> 0 or usize::MAX
-    pub const STAT: usize = 16; // stat(path, buf) -> 0 or usize::MAX
+    pub const STAT: usize = 16; // stat(path, &mut Stat) -> 0 or usize::MAX
     pub const CHMOD: usize = 17; // chmod(path, mode) -> 0 or usize::MAX
     pub const READDIR: usize = 18; // readdir(buf, len) -> n or usize::MAX
     pub const GET_FB_INFO: usize = 19; // get_fb_info(buf) -> 0 or usize::MAX
     pub const FB_FLUSH: usize = 20; // fb_flush() -> 0 or usize::MAX
+    pub const READV: usize = 21; // readv(fd, iov, iovcnt) -> n or usize::MAX
+    pub const FTRUNCATE: usize = 22; // ftruncate(fd, len) -> 0 or usize::MAX
+    pub const SENDFILE: usize = 23; // sendfile(out_fd, in_fd, len) -> n or usize::MAX
+    pub const GETDENTS: usize = 24; // getdents(buf, len, start) -> bytes or usize::MAX
+    pub const WRITE_RAW: usize = 25; // write_raw(fd, buf, len) -> n, no \n -> \r\n on the terminal
+    pub const SET_TERM_MODE: usize = 26; // set_term_mode(flags) -> 0 or usize::MAX
+    pub const MEMINFO: usize = 27; // meminfo(buf) -> 0 or usize::MAX
+    pub const POLLIN: usize = 28; // pollin() -> 1 if stdin has input, else 0
+    pub const MMAP: usize = 29; // mmap(len) -> va of zeroed RW pages or usize::MAX
+    pub const GET_DISPLAY_MODE: usize = 30; // get_display_mode() -> uapi::display_mode value
+    pub const SET_DISPLAY_MODE: usize = 31; // set_display_mode(mode) -> 0 or usize::MAX
+    pub const DMESG: usize = 32; // dmesg(buf, len) -> n, most recent kernel log bytes
+    pub const YIELD: usize = 33; // yield() -> 0 after sleeping until the next interrupt
+    pub const MKDIR: usize = 34; // mkdir(path) -> 0 or usize::MAX
+    pub const EXECVE: usize = 35; // execve(path, argv, envp) -> !
+    pub const RESOLVE: usize = 36; // resolve(name, buf, len) -> path len (NUL written) or usize::MAX
+    pub const RENAME: usize = 37; // rename(src, dst) -> 0 or usize::MAX
+    pub const CHDIR: usize = 38; // chdir(path) -> 0 or usize::MAX
+    pub const GETCWD: usize = 39; // getcwd(buf, len) -> path len (NUL written) or usize::MAX
+    pub const SYNC: usize = 40; // sync() -> 0 or usize::MAX, presents the framebuffer if any
+    pub const SPAWN: usize = 41; // spawn(path, argv) -> child pid once it exits, or usize::MAX
+    pub const WAIT: usize = 42; // wait(&mut code) -> pid of the exited child or usize::MAX
+    pub const CHECKSUM: usize = 43; // checksum(path) -> CRC-32 of the file or usize::MAX
+    pub const REGDUMP: usize = 44; // regdump() -> 0 after logging the caller's registers at trace level
+
+    /// One past the highest syscall number
+    pub const COUNT: usize = 45;
+
+    /// Every syscall number above, in order. The kernel checks at build
+    /// time that each one has a handler.
+    pub const ALL: &[usize] = &[
+        WRITE, EXIT, WRITE_CSTR, OPEN, READ, WRITE_FD, CLOSE, LSEEK, BRK, GETTIME, POWEROFF, EXEC,
+        EXECV, CREAT, UNLINK, STAT, CHMOD, READDIR, GET_FB_INFO, FB_FLUSH, READV, FTRUNCATE,
+        SENDFILE, GETDENTS, WRITE_RAW, SET_TERM_MODE, MEMINFO, POLLIN, MMAP, GET_DISPLAY_MODE,
+        SET_DISPLAY_MODE, DMESG, YIELD, MKDIR, EXECVE, RESOLVE, RENAME, CHDIR, GETCWD, SYNC, SPAWN,
+        WAIT, CHECKSUM, REGDUMP,
+    ];
+
+    /// Lower-case name of syscall `n` for traces, `"unknown"` if there is none
+    pub const fn name(n: usize) -> &'static str {
+        match n {
+            WRITE => "write",
+            EXIT => "exit",
+            WRITE_CSTR => "write_cstr",
+            OPEN => "open",
+            READ => "read",
+            WRITE_FD => "write_fd",
+            CLOSE => "close",
+            LSEEK => "lseek",
+            BRK => "brk",
+            GETTIME => "gettime",
+            POWEROFF => "poweroff",
+            EXEC => "exec",
+            EXECV => "execv",
+            CREAT => "creat",
+            UNLINK => "unlink",
+            STAT => "stat",
+            CHMOD => "chmod",
+            READDIR => "readdir",
+            GET_FB_INFO => "get_fb_info",
+            FB_FLUSH => "fb_flush",
+            READV => "readv",
+            FTRUNCATE => "ftruncate",
+            SENDFILE => "sendfile",
+            GETDENTS => "getdents",
+            WRITE_RAW => "write_raw",
+            SET_TERM_MODE => "set_term_mode",
+            MEMINFO => "meminfo",
+            POLLIN => "pollin",
+            MMAP => "mmap",
+            GET_DISPLAY_MODE => "get_display_mode",
+            SET_DISPLAY_MODE => "set_display_mode",
+            DMESG => "dmesg",
+            YIELD => "yield",
+            MKDIR => "mkdir",
+            EXECVE => "execve",
+            RESOLVE => "resolve",
+            RENAME => "rename",
+            CHDIR => "chdir",
+            GETCWD => "getcwd",
+            SYNC => "sync",
+            SPAWN => "spawn",
+            WAIT => "wait",
+            CHECKSUM => "checksum",
+            REGDUMP => "regdump",
+            _ => "unknown",
+        }
+    }
+}
+
+/// Flags for `set_term_mode`. With no flags set stdin is raw: each `read`
+/// returns as soon as one keystroke arrives and nothing is echoed.
+pub mod term {
+    pub const RAW: usize = 0;
+    /// Line-buffered input with in-kernel backspace handling.
+    pub const CANONICAL: usize = 1 << 0;
+    /// The kernel echoes what `read` receives: each keystroke in raw mode,
+    /// the line being edited in canonical mode.
+    pub const ECHO: usize = 1 << 1;
+    /// Line editing as a terminal user expects it, as `usys::set_raw_mode(false)` asks for
+    pub const LINE: usize = CANONICAL | ECHO;
+}
+
+/// Values for `get_display_mode`/`set_display_mode`.
+pub mod display_mode {
+    /// Console output on the UART, for ANSI terminals.
+    pub const ANSI: usize = 0;
+    /// Console output on the virtio-gpu framebuffer.
+    pub const GPU: usize = 1;
+}
+
+/// One scatter/gather segment as passed to `readv` (base VA + length).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct IoVec {
+    pub base: usize,
+    pub len: usize,
+}
+
+/// Maximum number of segments accepted by `readv`.
+pub const MAX_IOV: usize = 16;
+
+/// Kernel heap usage as filled in by `meminfo` (bytes).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct MemInfo {
+    pub used: u64,
+    pub free: u64,
+    pub total: u64,
+}
+
+/// File metadata as filled in by `stat`. The first 16 bytes match the
+/// original `[size, mode]` reply, so callers that read the second word as
+/// the mode when truncating it to 32 bits still get the right answer.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct Stat {
+    pub size: u64,
+    pub mode: u32,
+    /// Nonzero for directories
+    pub is_dir: u32,
+    /// Last modification, in seconds since boot; embedded files report 0
+    pub mtime: u64,
 }
 
+/// Process id, as returned by `spawn`. The first program is pid 1.
+pub type Pid = usize;
+
+/// Maximum number of argv (and envp) entries passed to a new program.
+/// Shared by the kernel's exec path, the ELF stack builder and `usys::execv`.
+pub const MAX_ARGS: usize = 64;
+
 /// Log levels similar to log4j, ordered from least to most severe.
 /// - TRACE: Fine-grained debugging information
 /// - DEBUG: Debugging information  
@@ -41,9 +196,39 @@ pub enum LogLevel {
     Error = 4,
 }
 
+impl LogLevel {
+    /// Every level, least severe first
+    pub const ALL: [LogLevel; 5] = [LogLevel::Trace, LogLevel::Debug, LogLevel::Info, LogLevel::Warn, LogLevel::Error];
+
+    /// Lower-case name, as `loglevel=` takes it
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => "trace",
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+
+    /// The level called `s`, in any case
+    #[allow(clippy::should_implement_trait)] // Option, so no error type to name
+    pub fn from_str(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|l| l.as_str().eq_ignore_ascii_case(s))
+    }
+}
+
+impl core::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum SysErr {
     Fail,
+    /// No such file or command
+    NoEnt,
 }
 
 pub type SysResult<T> = core::result::Result<T, SysErr>;
@@ -52,3 +237,61 @@ pub type SysResult<T> = core::result::Result<T, SysErr>;
 pub const fn is_err_sentinel(v: usize) -> bool {
     v == usize::MAX
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::{offset_of, size_of};
+
+    #[test]
+    fn test_stat_starts_with_the_old_two_word_reply() {
+        assert_eq!(size_of::<Stat>(), 24);
+        assert_eq!(offset_of!(Stat, size), 0);
+        // Low half of the old mode word, little-endian
+        assert_eq!(offset_of!(Stat, mode), 8);
+        assert_eq!(offset_of!(Stat, is_dir), 12);
+        assert_eq!(offset_of!(Stat, mtime), 16);
+    }
+
+    #[test]
+    fn test_log_level_names_round_trip() {
+        for level in LogLevel::ALL {
+            assert_eq!(LogLevel::from_str(level.as_str()), Some(level));
+        }
+        assert!(LogLevel::ALL.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(LogLevel::Warn.as_str(), "warn");
+    }
+
+    #[test]
+    fn test_log_level_from_str_ignores_case() {
+        assert_eq!(LogLevel::from_str("debug"), Some(LogLevel::Debug));
+        assert_eq!(LogLevel::from_str("DEBUG"), Some(LogLevel::Debug));
+        assert_eq!(LogLevel::from_str("Error"), Some(LogLevel::Error));
+        assert_eq!(LogLevel::from_str(""), None);
+        assert_eq!(LogLevel::from_str("warning"), None);
+        assert_eq!(LogLevel::from_str(" info"), None);
+    }
+
+    #[test]
+    fn test_nr_all_is_sorted_and_below_count() {
+        assert!(nr::ALL.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(nr::ALL.first(), Some(&nr::WRITE));
+        assert_eq!(nr::ALL.last(), Some(&(nr::COUNT - 1)));
+        // Numbers are dense from 1
+        assert_eq!(nr::ALL.len(), nr::COUNT - 1);
+    }
+
+    #[test]
+    fn test_nr_names_cover_every_syscall() {
+        for (i, &n) in nr::ALL.iter().enumerate() {
+            assert_ne!(nr::name(n), "unknown", "syscall {} has no name", n);
+            // Names are distinct
+            assert!(nr::ALL[..i].iter().all(|&m| nr::name(m) != nr::name(n)));
+        }
+        assert_eq!(nr::name(nr::WRITE), "write");
+        assert_eq!(nr::name(nr::GET_FB_INFO), "get_fb_info");
+        assert_eq!(nr::name(0), "unknown");
+        assert_eq!(nr::name(nr::COUNT), "unknown");
+        assert_eq!(nr::name(usize::MAX), "unknown");
+    }
+}
@@ -0,0 +1,150 @@
+//! Fixed-capacity path buffer shared by the kernel and userspace
+//!
+//! A [`PathBuf`] holds up to [`PATH_MAX`] - 1 bytes of UTF-8 followed by a
+//! NUL, so it can be handed to a syscall as a C string or read back from
+//! one without another copy. Longer input is cut at a character boundary.
+
+use core::ffi::CStr;
+
+/// Bytes in a path buffer, NUL included: paths are at most 255 bytes
+pub const PATH_MAX: usize = 256;
+
+/// Why a path was refused
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PathError {
+    /// A NUL byte before the end, which would cut the C string short
+    InteriorNul,
+    NotUtf8,
+}
+
+#[derive(Clone)]
+pub struct PathBuf {
+    buf: [u8; PATH_MAX],
+    len: usize, // bytes before the NUL (0..=PATH_MAX-1)
+}
+
+impl PathBuf {
+    pub const fn new() -> Self {
+        Self { buf: [0; PATH_MAX], len: 0 }
+    }
+
+    /// Like `parse`, for bytes that still have to be checked for UTF-8
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PathError> {
+        core::str::from_utf8(bytes).map_err(|_| PathError::NotUtf8)?.parse()
+    }
+
+    /// Copy a C string, as `from_bytes` does with its bytes
+    pub fn from_cstr(s: &CStr) -> Result<Self, PathError> {
+        Self::from_bytes(s.to_bytes())
+    }
+
+    /// Append `s`, keeping as much as fits in whole characters. Nothing is
+    /// appended if `s` contains a NUL.
+    pub fn push(&mut self, s: &str) -> Result<(), PathError> {
+        if s.as_bytes().contains(&0) {
+            return Err(PathError::InteriorNul);
+        }
+        let mut n = core::cmp::min(s.len(), PATH_MAX - 1 - self.len);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        self.buf[self.len] = 0;
+        Ok(())
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only whole characters of a `&str` are ever copied in
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+
+    /// Borrow as `&CStr`
+    pub fn as_cstr(&self) -> &CStr {
+        // SAFETY: `push` rejects NULs and always writes one after the text
+        unsafe { CStr::from_bytes_with_nul_unchecked(&self.buf[..=self.len]) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Copy `s`, truncated to 255 bytes. Fails if `s` contains a NUL.
+impl core::str::FromStr for PathBuf {
+    type Err = PathError;
+
+    fn from_str(s: &str) -> Result<Self, PathError> {
+        let mut out = Self::new();
+        out.push(s)?;
+        Ok(out)
+    }
+}
+
+impl Default for PathBuf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Debug for PathBuf {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncates_at_255_bytes() {
+        let bytes = [b'a'; 300];
+        let long = core::str::from_utf8(&bytes).unwrap();
+        let p = long.parse::<PathBuf>().unwrap();
+        assert_eq!(p.len(), PATH_MAX - 1);
+        assert_eq!(p.as_str(), &long[..255]);
+        assert_eq!(p.as_cstr().to_bytes().len(), 255);
+        assert_eq!(long[..255].parse::<PathBuf>().unwrap().len(), 255);
+
+        // push stops at the same limit
+        let mut p = long[..250].parse::<PathBuf>().unwrap();
+        p.push("/0123456789").unwrap();
+        assert_eq!(&p.as_str()[..250], &long[..250]);
+        assert_eq!(&p.as_str()[250..], "/0123");
+
+        // A character that doesn't fit whole is dropped
+        let mut p = long[..254].parse::<PathBuf>().unwrap();
+        p.push("é").unwrap();
+        assert_eq!(p.as_str(), &long[..254]);
+    }
+
+    #[test]
+    fn test_rejects_interior_nul() {
+        assert_eq!("/bin/a\0b".parse::<PathBuf>().unwrap_err(), PathError::InteriorNul);
+        assert_eq!(PathBuf::from_bytes(b"\0").unwrap_err(), PathError::InteriorNul);
+        assert_eq!(PathBuf::from_bytes(b"/f\xff").unwrap_err(), PathError::NotUtf8);
+        // A failed push leaves the path as it was
+        let mut p = "/home".parse::<PathBuf>().unwrap();
+        assert_eq!(p.push("/x\0y"), Err(PathError::InteriorNul));
+        assert_eq!(p.as_str(), "/home");
+    }
+
+    #[test]
+    fn test_cstr_round_trip() {
+        let p = "/home/notes.txt".parse::<PathBuf>().unwrap();
+        let c = p.as_cstr();
+        assert_eq!(c.to_bytes(), b"/home/notes.txt");
+        let back = PathBuf::from_cstr(c).unwrap();
+        assert_eq!(back.as_str(), "/home/notes.txt");
+
+        let empty = PathBuf::new();
+        assert!(empty.is_empty());
+        assert_eq!(empty.as_cstr().to_bytes_with_nul(), b"\0");
+        assert_eq!(PathBuf::from_cstr(c"").unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,25 @@
+#![no_std]
+#![no_main]
+
+use userapp::echo::render;
+
+/// Longest line echo will print; longer output is cut short.
+const LINE_MAX: usize = 1024;
+
+#[panic_handler]
+fn on_panic(_info: &core::panic::PanicInfo) -> ! {
+    usys::println!("PANIC: {}", _info);
+    usys::exit();
+}
+
+/// # Safety
+/// Only the kernel calls this, with argv/envp on the initial user stack.
+#[no_mangle]
+pub unsafe extern "C" fn _start(argc: usize, argv: *const *const u8, _envp: *const *const u8) -> ! {
+    usys::init_args(argc, argv);
+    let mut line = [0u8; LINE_MAX];
+    let n = render(usys::args().skip(1), &mut line);
+    // Raw write: the bytes go out exactly as rendered, with no \r added
+    let _ = usys::write_raw(usys::STDOUT, &line[..n]);
+    usys::exit();
+}
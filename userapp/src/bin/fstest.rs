@@ -74,12 +74,11 @@ fn main() -> ! {
 
     // Test 4: stat() the file
     usys::println!("\n[Test 4] Checking file stats...");
-    let mut stat_buf = [0u64; 2];
-    match stat(path, &mut stat_buf) {
-        Ok(_) => {
+    match stat(path) {
+        Ok(st) => {
             usys::println!("V File exists");
-            usys::println!("  Size: {} bytes", stat_buf[0]);
-            usys::println!("  Mode: 0{:o}", stat_buf[1]);
+            usys::println!("  Size: {} bytes", st.size);
+            usys::println!("  Mode: 0{:o}", st.mode);
         }
         Err(_) => {
             usys::println!("X stat() failed");
@@ -93,11 +92,8 @@ fn main() -> ! {
             usys::println!("V chmod() succeeded");
 
             // Verify with stat
-            match stat(path, &mut stat_buf) {
-                Ok(_) => {
-                    usys::println!("  New mode: 0{:o}", stat_buf[1]);
-                }
-                Err(_) => {}
+            if let Ok(st) = stat(path) {
+                usys::println!("  New mode: 0{:o}", st.mode);
             }
         }
         Err(_) => {
@@ -112,7 +108,7 @@ fn main() -> ! {
             usys::println!("V File deleted");
 
             // Verify it's gone
-            match stat(path, &mut stat_buf) {
+            match stat(path) {
                 Ok(_) => {
                     usys::println!("X File still exists!");
                 }
@@ -1,7 +1,7 @@
 #![no_std]
 #![no_main]
 
-use usys::{exit, fb_flush, get_fb_info, println, FbInfo};
+use usys::{exit, fb_present, get_fb_info, println, FbInfo, PixelFormat};
 
 #[no_mangle]
 pub extern "C" fn _start(_argc: usize, _argv: *const *const u8, _envp: *const *const u8) -> ! {
@@ -14,12 +14,7 @@ fn main() {
     println!("====================");
     
     // Get framebuffer info
-    let mut fb_info = FbInfo {
-        width: 0,
-        height: 0,
-        stride: 0,
-        addr: 0,
-    };
+    let mut fb_info = FbInfo::default();
     
     match get_fb_info(&mut fb_info) {
         Ok(()) => {
@@ -28,16 +23,23 @@ fn main() {
             println!("  Height: {} pixels", fb_info.height);
             println!("  Stride: {} bytes", fb_info.stride);
             println!("  Address: 0x{:x}", fb_info.addr);
+            println!("  Format: {:?}", fb_info.pixel_format());
             
             if fb_info.addr == 0 {
                 println!("Error: Invalid framebuffer address");
                 exit();
             }
+
+            // The pattern below writes B8G8R8X8 (XRGB8888 as a little-endian u32)
+            if fb_info.pixel_format() != Some(PixelFormat::B8G8R8X8) {
+                println!("Error: Unsupported pixel format {}", fb_info.format);
+                exit();
+            }
             
             // Access the framebuffer
             let fb_ptr = fb_info.addr as *mut u32;
-            // Calculate pixel count using stride (stride is in bytes, divide by 4 for u32)
-            let pixels_per_row = fb_info.stride / 4;
+            // Calculate pixel count using stride (stride is in bytes)
+            let pixels_per_row = fb_info.stride / PixelFormat::B8G8R8X8.bytes_per_pixel();
             let pixel_count = pixels_per_row * fb_info.height;
             
             println!("\nDrawing test pattern...");
@@ -96,7 +98,7 @@ fn main() {
             
             // Flush the framebuffer to the GPU
             println!("Flushing framebuffer to display...");
-            match fb_flush() {
+            match fb_present() {
                 Ok(()) => {
                     println!("Framebuffer flushed! You should see 8 colored horizontal bars with a white square in the center.");
                 }
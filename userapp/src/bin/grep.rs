@@ -0,0 +1,82 @@
+#![no_std]
+#![no_main]
+
+//! Print the lines containing a fixed string. Usage: `grep <needle> [file...]`;
+//! with no files it reads stdin until Ctrl-D.
+
+use userapp::grep::line_matches;
+use usys::{eprintln, BufReader, File, IoRead, IoWrite, STDOUT};
+
+const LINE_MAX: usize = 512;
+const CTRL_D: u8 = 0x04;
+
+/// Print the matching lines from `r`, prefixed with `prefix` (the file name
+/// when searching several files). Returns the number of matches.
+fn grep<R: IoRead>(r: R, needle: &[u8], prefix: Option<&str>, stdin: bool) -> usys::SysResult<usize> {
+    let mut reader = BufReader::new(r);
+    let mut line = [0u8; LINE_MAX];
+    let mut matches = 0;
+    loop {
+        let n = reader.read_line(&mut line)?;
+        if n == 0 || (stdin && line[0] == CTRL_D) {
+            return Ok(matches);
+        }
+        let text = &line[..n];
+        if line_matches(text, needle) {
+            matches += 1;
+            if let Some(p) = prefix {
+                let _ = STDOUT.write_all(p.as_bytes());
+                let _ = STDOUT.write_all(b":");
+            }
+            let _ = STDOUT.write_all(text);
+            if text[n - 1] != b'\n' {
+                let _ = STDOUT.write_all(b"\n");
+            }
+        }
+    }
+}
+
+#[panic_handler]
+fn on_panic(_info: &core::panic::PanicInfo) -> ! {
+    usys::println!("PANIC: {}", _info);
+    usys::exit();
+}
+
+/// # Safety
+/// Only the kernel calls this, with argv/envp on the initial user stack.
+#[no_mangle]
+pub unsafe extern "C" fn _start(argc: usize, argv: *const *const u8, _envp: *const *const u8) -> ! {
+    usys::init_args(argc, argv);
+    main();
+    usys::exit();
+}
+
+fn main() {
+    let mut args = usys::args().skip(1);
+    let Some(needle) = args.next() else {
+        eprintln!("usage: grep <needle> [file...]");
+        return;
+    };
+
+    let files = usys::args().count().saturating_sub(2);
+    if files == 0 {
+        // Line-buffered so the user can edit; finish with Ctrl-D
+        let _ = usys::set_raw_mode(false);
+        if grep(usys::STDIN, needle.as_bytes(), None, true).is_err() {
+            eprintln!("grep: read error");
+        }
+        return;
+    }
+
+    for path in args {
+        let prefix = if files > 1 { Some(path) } else { None };
+        match File::open(path) {
+            Ok(f) => {
+                if grep(f, needle.as_bytes(), prefix, false).is_err() {
+                    eprintln!("grep: {}: read error", path);
+                }
+            }
+            Err(_) => eprintln!("grep: {}: cannot open", path),
+        }
+    }
+}
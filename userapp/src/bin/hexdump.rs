@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+//! Classic hex+ASCII dump of a file. Usage: `hexdump <file> [offset] [length]`,
+//! where offset and length are decimal or `0x` hex.
+
+use userapp::hexdump::{format_line, parse_num, BYTES_PER_LINE};
+use usys::{eprintln, println, File, IoRead};
+
+#[panic_handler]
+fn on_panic(_info: &core::panic::PanicInfo) -> ! {
+    usys::println!("PANIC: {}", _info);
+    usys::exit();
+}
+
+/// # Safety
+/// Only the kernel calls this, with argv/envp on the initial user stack.
+#[no_mangle]
+pub unsafe extern "C" fn _start(argc: usize, argv: *const *const u8, _envp: *const *const u8) -> ! {
+    usys::init_args(argc, argv);
+    main();
+    usys::exit();
+}
+
+fn main() {
+    let mut args = usys::args().skip(1);
+    let Some(path) = args.next() else {
+        eprintln!("usage: hexdump <file> [offset] [length]");
+        return;
+    };
+    let offset = match args.next().map(parse_num) {
+        None => 0,
+        Some(Some(n)) => n,
+        Some(None) => {
+            eprintln!("hexdump: bad offset");
+            return;
+        }
+    };
+    let mut remaining = match args.next().map(parse_num) {
+        None => usize::MAX,
+        Some(Some(n)) => n,
+        Some(None) => {
+            eprintln!("hexdump: bad length");
+            return;
+        }
+    };
+
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(_) => {
+            eprintln!("hexdump: {}: cannot open", path);
+            return;
+        }
+    };
+    if offset > 0 && usys::lseek(file.fd(), offset as isize, usys::SEEK_SET).is_err() {
+        eprintln!("hexdump: cannot seek to {:#x}", offset);
+        return;
+    }
+
+    let mut addr = offset;
+    let mut buf = [0u8; BYTES_PER_LINE];
+    while remaining > 0 {
+        // Fill a whole line unless the file ends first
+        let want = remaining.min(BYTES_PER_LINE);
+        let mut have = 0;
+        while have < want {
+            match file.read(&mut buf[have..want]) {
+                Ok(0) => break,
+                Ok(n) => have += n,
+                Err(_) => {
+                    eprintln!("hexdump: read error");
+                    return;
+                }
+            }
+        }
+        if have == 0 {
+            break;
+        }
+        println!("{}", format_line(addr, &buf[..have]));
+        addr += have;
+        remaining -= have;
+        if have < want {
+            break;
+        }
+    }
+    println!("{:08x}", addr);
+}
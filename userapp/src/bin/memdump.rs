@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+//! Exercise the mmap syscall: map a region, fill it with a pattern, read it
+//! back and dump the start of it. Usage: `memdump [bytes] [dump_len]`.
+
+use userapp::memdump::{fill_pattern, verify_pattern};
+use usys::{eprintln, println};
+
+const DEFAULT_LEN: usize = 3 * 4096 + 100;
+const DEFAULT_DUMP: usize = 64;
+
+#[panic_handler]
+fn on_panic(_info: &core::panic::PanicInfo) -> ! {
+    usys::println!("PANIC: {}", _info);
+    usys::exit();
+}
+
+/// # Safety
+/// Only the kernel calls this, with argv/envp on the initial user stack.
+#[no_mangle]
+pub unsafe extern "C" fn _start(argc: usize, argv: *const *const u8, _envp: *const *const u8) -> ! {
+    usys::init_args(argc, argv);
+    main();
+    usys::exit();
+}
+
+fn main() {
+    let mut args = usys::args().skip(1);
+    let len = args.next().and_then(|a| a.parse().ok()).unwrap_or(DEFAULT_LEN);
+    let dump = args.next().and_then(|a| a.parse().ok()).unwrap_or(DEFAULT_DUMP);
+
+    let region = match usys::mmap(len) {
+        Ok(r) => r,
+        Err(_) => {
+            eprintln!("memdump: mmap({}) failed", len);
+            return;
+        }
+    };
+    println!("mapped {} bytes at {:#x}", len, region.as_ptr() as usize);
+
+    if region.iter().any(|&b| b != 0) {
+        eprintln!("memdump: fresh mapping is not zeroed");
+    }
+
+    fill_pattern(region);
+    match verify_pattern(region) {
+        (0, _) => println!("pattern ok"),
+        (bad, first) => eprintln!("memdump: {} bad bytes, first at {:#x}", bad, first.unwrap_or(0)),
+    }
+
+    usys::put_hex(&region[..dump.min(len)]);
+}
@@ -1,17 +1,13 @@
 #![no_std]
 #![no_main]
 
+use userapp::rogue::{try_move, Dir};
 use usys::{cstr, println, print, IoRead, FbInfo};
 
 /// Clear screen by filling framebuffer with black pixels if GPU mode is available,
 /// otherwise use ANSI escape codes.
 fn clear_screen() {
-    let mut info = FbInfo {
-        width: 0,
-        height: 0,
-        stride: 0,
-        addr: 0,
-    };
+    let mut info = FbInfo::default();
     
     if usys::get_fb_info(&mut info).is_ok() && info.addr != 0 && info.width > 0 && info.height > 0 {
         // GPU mode: clear framebuffer directly by filling with black pixels
@@ -24,14 +20,38 @@ fn clear_screen() {
             }
         }
         
-        // Flush the framebuffer to display
-        let _ = usys::fb_flush();
+        // Present the framebuffer on the display
+        let _ = usys::fb_present();
     } else {
         // ANSI mode: use escape codes
         print!("\x1b[2J\x1b[H");
     }
 }
 
+/// How long to idle between input checks
+const FRAME_MS: u64 = 50;
+
+/// Draw the map with the player on it (works with both ANSI and GPU framebuffer)
+fn draw(map: &str, player_x: usize, player_y: usize) {
+    clear_screen();
+    for (y, line) in map.lines().enumerate() {
+        if y == player_y {
+            // Draw line with player
+            for (x, ch) in line.chars().enumerate() {
+                if x == player_x {
+                    print!("@");
+                } else {
+                    print!("{}", ch);
+                }
+            }
+            println!();
+        } else {
+            println!("{}", line);
+        }
+    }
+    println!("Pos: ({}, {}) - Use WASD to move, Q to quit.", player_x, player_y);
+}
+
 #[no_mangle]
 pub extern "C" fn _start(_argc: usize, _argv: *const *const u8, _envp: *const *const u8) -> ! {
     main();
@@ -78,78 +98,42 @@ fn main() {
 
     let map_str = core::str::from_utf8(&map_data[..n]).unwrap_or("");
     
-    // Find dimensions and player start
+    // Find player start
     let mut player_x = 0;
     let mut player_y = 0;
-    let mut rows = 0;
-    let mut cols = 0;
-    
     for (y, line) in map_str.lines().enumerate() {
-        rows = y + 1;
-        if line.len() > cols { cols = line.len(); }
         if let Some(x) = line.find('<') {
             player_x = x;
             player_y = y;
         }
     }
-    let width = cols;
-    let height = rows;
 
-    // Game Loop
+    // Game Loop: redraw only after a move, and idle between keypresses
+    let mut dirty = true;
     loop {
-        // Clear screen (works with both ANSI and GPU framebuffer)
-        clear_screen();
-        
-        // Draw Map
-        for (y, line) in map_str.lines().enumerate() {
-            if y == player_y {
-                // Draw line with player
-                for (x, ch) in line.chars().enumerate() {
-                    if x == player_x {
-                        print!("@");
-                    } else {
-                        print!("{}", ch);
-                    }
-                }
-                println!();
-            } else {
-                println!("{}", line);
-            }
+        if dirty {
+            draw(map_str, player_x, player_y);
+            dirty = false;
         }
-        
-        println!("Pos: ({}, {}) - Use WASD to move, Q to quit.", player_x, player_y);
 
-        // Read Input
         let mut input = [0u8; 1];
-        if let Ok(1) = usys::STDIN.read(&mut input) {
-            let ch = input[0];
-            let mut next_x = player_x;
-            let mut next_y = player_y;
-
-            match ch {
-                b'w' => if next_y > 0 { next_y -= 1; },
-                b's' => if next_y < height - 1 { next_y += 1; },
-                b'a' => if next_x > 0 { next_x -= 1; },
-                b'd' => if next_x < width - 1 { next_x += 1; },
+        if let Ok(1) = usys::try_read(&mut input) {
+            let dir = match input[0] {
+                b'w' => Dir::Up,
+                b's' => Dir::Down,
+                b'a' => Dir::Left,
+                b'd' => Dir::Right,
                 b'q' => break,
-                _ => {},
-            }
-
-            // Collision Check
-            // Re-parse map to check collision (inefficient but simple)
-            let mut valid = false;
-            if let Some(line) = map_str.lines().nth(next_y) {
-                if let Some(c) = line.chars().nth(next_x) {
-                    if c == '.' || c == '<' || c == '>' || c == ' ' {
-                        valid = true;
-                    }
-                }
-            }
-
-            if valid {
-                player_x = next_x;
-                player_y = next_y;
+                _ => continue,
+            };
+            let (x, y) = try_move(map_str, player_x, player_y, dir);
+            if (x, y) != (player_x, player_y) {
+                player_x = x;
+                player_y = y;
+                dirty = true;
             }
+        } else {
+            usys::sleep_ms(FRAME_MS);
         }
     }
     
@@ -160,4 +144,3 @@ fn main() {
 fn panic(_: &core::panic::PanicInfo) -> ! {
     loop {}
 }
-
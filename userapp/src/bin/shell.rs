@@ -1,13 +1,32 @@
 #![no_std]
 #![no_main]
 
+use userapp::shell::{chain_op, expand_prompt, format_stat, is_program, run_chain, tokenize, DEFAULT_PROMPT};
 use usys::{print, println, debug, IoRead};
 
 // Maximum number of command-line arguments
-const MAX_ARGS: usize = 16;
+const MAX_ARGS: usize = usys::MAX_ARGS;
 
+/// Print the names of the files in the working directory that are (or are
+/// not) programs after `label`, `.elf` dropped from program names
+fn list_files(label: &str, programs: bool) {
+    print!("{}", label);
+    let mut any = false;
+    for entry in usys::read_dir() {
+        let name = entry.name();
+        if !entry.is_dir && is_program(name, entry.mode, entry.is_dir) == programs {
+            print!(" {}", if programs { name.strip_suffix(".elf").unwrap_or(name) } else { name });
+            any = true;
+        }
+    }
+    println!("{}", if any { "" } else { " (none)" });
+}
+
+/// # Safety
+/// Only the kernel calls this, with argv/envp on the initial user stack.
 #[no_mangle]
-pub extern "C" fn _start(_argc: usize, _argv: *const *const u8, _envp: *const *const u8) -> ! {
+pub unsafe extern "C" fn _start(_argc: usize, _argv: *const *const u8, envp: *const *const u8) -> ! {
+    usys::env::init(envp);
     main();
     usys::exit();
 }
@@ -15,13 +34,20 @@ pub extern "C" fn _start(_argc: usize, _argv: *const *const u8, _envp: *const *c
 fn main() {
     // Debug output goes to serial port, console output goes to framebuffer (when GPU enabled)
     debug!("BogoShell starting up");
+    // Line editing and echo happen here, not in the kernel
+    let _ = usys::set_raw_mode(true);
     
     println!("Welcome to BogoShell!");
     println!("Type 'help' for available commands, 'ls' to list programs");
 
+    let ps1 = usys::env::get("PS1").unwrap_or(DEFAULT_PROMPT);
     let mut buf = [0u8; 64];
+    // Exit status of the last command that ran
+    let mut status = 0;
     loop {
-        print!("> ");
+        let mut cwd_buf = [0u8; 256];
+        let cwd = usys::getcwd(&mut cwd_buf).unwrap_or("?");
+        print!("{}", expand_prompt(ps1, cwd, status));
         
         // Simple line reader
         let mut len = 0;
@@ -49,141 +75,203 @@ fn main() {
         
         let input = core::str::from_utf8(&buf[..len]).unwrap_or("");
         
-        // Parse command line: split by whitespace
+        // Parse command line: split by whitespace, keeping "quoted args" together
         let mut tokens: [&str; MAX_ARGS] = [""; MAX_ARGS];
-        let mut token_count = 0;
-        for token in input.split_whitespace() {
-            if token_count < MAX_ARGS {
-                tokens[token_count] = token;
-                token_count += 1;
-            }
-        }
+        let token_count = tokenize(input, &mut tokens);
+        let tokens = &tokens[..token_count];
         
-        if token_count == 0 { continue; }
+        if tokens.is_empty() { continue; }
         
-        let cmd = tokens[0];
-        
-        // Check for built-in commands first
-        match cmd {
-            "help" => {
-                println!("Built-in commands: ls, help, shutdown");
-                println!("To run a program, type its name without the .elf extension");
-                println!("Example: hello, rogue, crogue, bigrogue, curses_test, fstest, mkfiles, lisp");
-            },
-            "ls" => {
-                // List files in writable filesystem
-                let mut buf = [0u8; 4096];
-                match usys::readdir(&mut buf) {
-                    Ok(count) => {
-                        if count == 0 {
-                            println!("No files in writable filesystem");
-                        } else {
-                            println!("Files in writable filesystem:");
-                            let mut offset = 0;
-                            for _ in 0..count {
-                                // Find the null terminator, but cap search to buffer size
-                                let mut end = offset;
-                                while end < buf.len() && buf[end] != 0 {
-                                    end += 1;
-                                }
-                                
-                                // If we found a valid filename, print it
-                                if end > offset && end < buf.len() {
-                                    if let Ok(filename) = core::str::from_utf8(&buf[offset..end]) {
-                                        println!("  {}", filename);
-                                    }
-                                }
-                                
-                                // Move to next filename (past the null terminator)
-                                offset = end + 1;
-                                
-                                // Safety check: if we've gone past the buffer, stop
-                                if offset >= buf.len() {
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                    Err(_) => println!("Error listing files"),
+        // Only a lone command may replace the shell when it can't be spawned
+        let alone = !tokens.iter().any(|t| chain_op(t).is_some());
+        status = match run_chain(tokens, status, |args| run_command(args, alone)) {
+            Ok(code) => code,
+            Err(e) => {
+                println!("{}", e);
+                2
+            }
+        };
+    }
+}
+
+/// Run one command, a built-in or a program, and return its exit status.
+/// With `may_exec` a program that can't be spawned (no room next to the
+/// shell, or the shell was spawned itself) replaces the shell instead.
+fn run_command(args: &[&str], may_exec: bool) -> i32 {
+    let cmd = args[0];
+    // Check for built-in commands first
+    match cmd {
+        "help" => {
+            println!("Built-in commands: ls, cd, pwd, mkdir, touch, stat, free, dmesg, true, false, help, shutdown");
+            list_files("Programs:", true);
+            list_files("Data files:", false);
+            println!("To run a program, type its name without the .elf extension");
+            println!("Chain commands with && (run if the last succeeded) and || (if it failed)");
+            0
+        },
+        "ls" => {
+            // List files in writable filesystem
+            let mut count = 0;
+            for entry in usys::read_dir() {
+                if count == 0 {
+                    println!("{:<24} {:>8}  {}", "NAME", "SIZE", "MODE");
                 }
-            },
-            "shutdown" => {
-                println!("Shutting down...");
-                usys::poweroff();
-            },
-            _ => {
-                // Try to execute as a program from the filesystem
-                // Append .elf if not already present
-                let mut filename_buf = [0u8; 64];
-                let filename = if cmd.ends_with(".elf") {
-                    cmd
-                } else {
-                    // Build filename with .elf extension
-                    let mut len = 0;
-                    for (i, &b) in cmd.as_bytes().iter().enumerate() {
-                        if i >= filename_buf.len() - 5 {
-                            break;
-                        }
-                        filename_buf[i] = b;
-                        len = i + 1;
-                    }
-                    // Add .elf extension
-                    if len + 4 < filename_buf.len() {
-                        filename_buf[len] = b'.';
-                        filename_buf[len + 1] = b'e';
-                        filename_buf[len + 2] = b'l';
-                        filename_buf[len + 3] = b'f';
-                        len += 4;
-                    }
-                    core::str::from_utf8(&filename_buf[..len]).unwrap_or(cmd)
-                };
-                
-                // Check if file exists before trying to execute
-                if let Ok(filename_cstr) = usys::CStrBuf::<64>::from_str(filename) {
-                    let mut stat_buf = [0u64; 2];
-                    match usys::stat(filename_cstr.as_cstr(), &mut stat_buf) {
-                        Ok(_) => {
-                            // File exists, proceed with execution
-                            debug!("Executing: {}", filename);
-                            
-                            // Build argv array with command line arguments
-                            let mut argv_cstrs: [usys::CStrBuf<64>; MAX_ARGS] = Default::default();
-                            
-                            // First arg is program name
-                            argv_cstrs[0] = filename_cstr;
-                            let mut argv_count = 1;
-                            
-                            // Add remaining arguments
-                            for i in 1..token_count {
-                                if argv_count >= MAX_ARGS {
-                                    break;
-                                }
-                                if let Ok(cstr) = usys::CStrBuf::<64>::from_str(tokens[i]) {
-                                    argv_cstrs[argv_count] = cstr;
-                                    argv_count += 1;
-                                }
-                            }
-                            
-                            // Build references array
-                            let mut argv_refs: [&core::ffi::CStr; MAX_ARGS] = [usys::cstr!(""); MAX_ARGS];
-                            for i in 0..argv_count {
-                                argv_refs[i] = argv_cstrs[i].as_cstr();
-                            }
-                            
-                            // Execute the program
-                            usys::execv(argv_cstrs[0].as_cstr(), &argv_refs[..argv_count]);
-                        }
-                        Err(_) => {
-                            println!("Command not found: {}", filename);
-                            println!("Type 'help' for available commands or 'ls' to see programs");
-                        }
-                    }
-                } else {
-                    println!("Error: Invalid filename '{}'", filename);
+                let (kind, slash) = if entry.is_dir { ('d', "/") } else { ('-', "") };
+                let pad = 24usize.saturating_sub(entry.name().len() + slash.len());
+                println!("{}{}{:pad$} {:>8}  {}{:03o}", entry.name(), slash, "", entry.size, kind, entry.mode & 0o777, pad = pad);
+                count += 1;
+            }
+            if count == 0 {
+                println!("No files in writable filesystem");
+            }
+            0
+        },
+        "mkdir" => {
+            if args.len() < 2 {
+                println!("usage: mkdir <dir>");
+                return 2;
+            }
+            match usys::CStrBuf::<64>::from_str(args[1]) {
+                Ok(path) if usys::mkdir(path.as_cstr()).is_ok() => 0,
+                _ => {
+                    println!("mkdir: cannot create '{}'", args[1]);
+                    1
+                }
+            }
+        },
+        "touch" => {
+            // Create an empty file; an existing one is left alone
+            if args.len() < 2 {
+                println!("usage: touch <file>");
+                return 2;
+            }
+            let created = match usys::CStrBuf::<64>::from_str(args[1]) {
+                Ok(path) => usys::stat(path.as_cstr()).is_ok()
+                    || usys::creat(path.as_cstr(), 0o644).and_then(|fd| fd.close()).is_ok(),
+                Err(_) => false,
+            };
+            if !created {
+                println!("touch: cannot create '{}'", args[1]);
+                return 1;
+            }
+            0
+        },
+        "stat" => {
+            if args.len() < 2 {
+                println!("usage: stat <file>");
+                return 2;
+            }
+            match usys::CStrBuf::<64>::from_str(args[1]).map(|path| usys::stat(path.as_cstr())) {
+                Ok(Ok(st)) => {
+                    println!("{}", format_stat(args[1], &st));
+                    0
+                }
+                _ => {
+                    println!("stat: no such file '{}'", args[1]);
+                    1
+                }
+            }
+        },
+        "cd" => {
+            // A program started with spawn comes back to this directory;
+            // one that replaced the shell leaves the next shell at /
+            let dir = args.get(1).copied().unwrap_or("/");
+            match usys::CStrBuf::<64>::from_str(dir) {
+                Ok(path) if usys::chdir(path.as_cstr()).is_ok() => 0,
+                _ => {
+                    println!("cd: no such directory '{}'", dir);
+                    1
                 }
             }
+        },
+        "pwd" => {
+            let mut buf = [0u8; 256];
+            match usys::getcwd(&mut buf) {
+                Ok(cwd) => {
+                    println!("{}", cwd);
+                    0
+                }
+                Err(_) => {
+                    println!("pwd: cannot read working directory");
+                    1
+                }
+            }
+        },
+        "free" => {
+            match usys::meminfo() {
+                Ok(m) => {
+                    println!("{:>10} {:>10} {:>10}", "total", "used", "free");
+                    println!("{:>10} {:>10} {:>10}", m.total, m.used, m.free);
+                    0
+                }
+                Err(_) => {
+                    println!("free: cannot read kernel heap info");
+                    1
+                }
+            }
+        },
+        "dmesg" => {
+            // Only the newest 4 KiB of the kernel's 8 KiB ring; the user
+            // stack is 16 KiB. Lines already end in "\r\n".
+            let mut buf = [0u8; 4096];
+            let n = usys::dmesg(&mut buf);
+            let _ = usys::write_raw(usys::STDOUT, &buf[..n]);
+            0
+        },
+        "true" => 0,
+        "false" => 1,
+        "shutdown" => {
+            println!("Shutting down...");
+            usys::poweroff();
+        },
+        _ => run_program(args, may_exec),
+    }
+}
+
+/// Start the program named by `args[0]` and wait for its exit status
+fn run_program(args: &[&str], may_exec: bool) -> i32 {
+    let cmd = args[0];
+    // Try to execute as a program from the filesystem
+    let Ok(filename_cstr) = usys::which(cmd) else {
+        println!("Command not found: {}", cmd);
+        println!("Type 'help' for available commands or 'ls' to see programs");
+        return 127;
+    };
+    debug!("Executing: {}", cmd);
+
+    // Build argv array with command line arguments
+    let mut argv_cstrs: [usys::CStrBuf<64>; MAX_ARGS] = core::array::from_fn(|_| Default::default());
+
+    // First arg is program name
+    argv_cstrs[0] = filename_cstr;
+    let mut argv_count = 1;
+
+    // Add remaining arguments
+    for arg in &args[1..] {
+        if argv_count >= MAX_ARGS {
+            break;
+        }
+        if let Ok(cstr) = usys::CStrBuf::<64>::from_str(arg) {
+            argv_cstrs[argv_count] = cstr;
+            argv_count += 1;
         }
     }
+
+    // Build references array
+    let mut argv_refs: [&core::ffi::CStr; MAX_ARGS] = [usys::cstr!(""); MAX_ARGS];
+    for i in 0..argv_count {
+        argv_refs[i] = argv_cstrs[i].as_cstr();
+    }
+
+    // Run it as a child so the shell gets its exit code back
+    if usys::spawn(argv_cstrs[0].as_cstr(), &argv_refs[..argv_count]).is_ok() {
+        return usys::wait().map_or(1, |(_, code)| code);
+    }
+    if may_exec {
+        usys::execv(argv_cstrs[0].as_cstr(), &argv_refs[..argv_count]);
+    }
+    println!("{}: cannot start", cmd);
+    126
 }
 
 #[panic_handler]
@@ -0,0 +1,17 @@
+#![no_std]
+#![no_main]
+
+use userapp::uptime::format_uptime;
+
+#[panic_handler]
+fn on_panic(_info: &core::panic::PanicInfo) -> ! {
+    usys::println!("PANIC: {}", _info);
+    usys::exit();
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    let ms = usys::uptime_ms();
+    usys::println!("up {}", format_uptime(ms / 1000));
+    usys::exit();
+}
@@ -0,0 +1,78 @@
+#![no_std]
+#![no_main]
+
+use userapp::wc::Counts;
+use usys::{eprintln, println, File, IoRead};
+
+/// End-of-input marker when counting stdin, which never reaches EOF itself.
+const CTRL_D: u8 = 0x04;
+
+/// Count everything `r` yields. With `stop_at_ctrl_d`, a Ctrl-D byte ends the input.
+fn count_reader<R: IoRead>(r: &R, stop_at_ctrl_d: bool) -> usys::SysResult<Counts> {
+    let mut c = Counts::default();
+    let mut buf = [0u8; 512];
+    loop {
+        let n = r.read(&mut buf)?;
+        if n == 0 {
+            return Ok(c);
+        }
+        let chunk = &buf[..n];
+        if stop_at_ctrl_d {
+            if let Some(end) = chunk.iter().position(|&b| b == CTRL_D) {
+                c.add(&chunk[..end]);
+                return Ok(c);
+            }
+        }
+        c.add(chunk);
+    }
+}
+
+fn report(c: &Counts, name: &str) {
+    println!("{:>7} {:>7} {:>7} {}", c.lines, c.words, c.bytes, name);
+}
+
+#[panic_handler]
+fn on_panic(_info: &core::panic::PanicInfo) -> ! {
+    usys::println!("PANIC: {}", _info);
+    usys::exit();
+}
+
+/// # Safety
+/// Only the kernel calls this, with argv/envp on the initial user stack.
+#[no_mangle]
+pub unsafe extern "C" fn _start(argc: usize, argv: *const *const u8, _envp: *const *const u8) -> ! {
+    usys::init_args(argc, argv);
+    main();
+}
+
+fn main() -> ! {
+    if usys::args().count() <= 1 {
+        // Line-buffered so the user can edit; finish with Ctrl-D
+        let _ = usys::set_raw_mode(false);
+        match count_reader(&usys::STDIN, true) {
+            Ok(c) => report(&c, ""),
+            Err(_) => eprintln!("wc: read error"),
+        }
+        usys::exit();
+    }
+
+    let mut total = Counts::default();
+    let mut files = 0;
+    for path in usys::args().skip(1) {
+        let counted = File::open(path).and_then(|f| count_reader(&f, false));
+        match counted {
+            Ok(c) => {
+                report(&c, path);
+                total.lines += c.lines;
+                total.words += c.words;
+                total.bytes += c.bytes;
+                files += 1;
+            }
+            Err(_) => eprintln!("wc: {}: cannot read", path),
+        }
+    }
+    if files > 1 {
+        report(&total, "total");
+    }
+    usys::exit();
+}
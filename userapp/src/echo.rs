@@ -0,0 +1,64 @@
+//! Argument rendering for `echo`
+
+/// Render the arguments (without `argv[0]`) the way `echo` prints them:
+/// joined by single spaces and ended by `\n`, unless the first argument is `-n`.
+/// Returns the number of bytes written to `out`.
+pub fn render<'a, I: IntoIterator<Item = &'a str>>(args: I, out: &mut [u8]) -> usize {
+    let mut args = args.into_iter().peekable();
+    let newline = if args.peek() == Some(&"-n") {
+        args.next();
+        false
+    } else {
+        true
+    };
+
+    let mut len = 0;
+    let mut push = |bytes: &[u8], len: &mut usize| {
+        let n = bytes.len().min(out.len() - *len);
+        out[*len..*len + n].copy_from_slice(&bytes[..n]);
+        *len += n;
+    };
+    for (i, arg) in args.enumerate() {
+        if i > 0 {
+            push(b" ", &mut len);
+        }
+        push(arg.as_bytes(), &mut len);
+    }
+    if newline {
+        push(b"\n", &mut len);
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn echo(args: &[&str]) -> ([u8; 64], usize) {
+        let mut out = [0u8; 64];
+        let n = render(args.iter().copied(), &mut out);
+        (out, n)
+    }
+
+    #[test]
+    fn test_no_args_prints_newline() {
+        let (out, n) = echo(&[]);
+        assert_eq!(&out[..n], b"\n");
+    }
+
+    #[test]
+    fn test_dash_n_suppresses_newline() {
+        let (out, n) = echo(&["-n", "a", "b"]);
+        assert_eq!(&out[..n], b"a b");
+        // Only a leading -n is a flag
+        let (out, n) = echo(&["a", "-n"]);
+        assert_eq!(&out[..n], b"a -n\n");
+    }
+
+    #[test]
+    fn test_output_is_truncated_to_buffer() {
+        let mut out = [0u8; 4];
+        assert_eq!(render(["hello", "world"], &mut out), 4);
+        assert_eq!(&out, b"hell");
+    }
+}
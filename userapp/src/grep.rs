@@ -0,0 +1,31 @@
+//! Line matching for `grep`
+
+/// Plain substring match; an empty needle matches every line.
+pub fn line_matches(line: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty() || line.windows(needle.len()).any(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_needle_matches_all() {
+        assert!(line_matches(b"anything\n", b""));
+        assert!(line_matches(b"", b""));
+    }
+
+    #[test]
+    fn test_needle_positions() {
+        assert!(line_matches(b"kernel panic\n", b"kernel"));
+        assert!(line_matches(b"a kernel panic\n", b"nel pa"));
+        assert!(line_matches(b"the end", b"end"));
+    }
+
+    #[test]
+    fn test_no_match() {
+        assert!(!line_matches(b"hello\n", b"world"));
+        assert!(!line_matches(b"ab", b"abc"));
+        assert!(!line_matches(b"", b"a"));
+    }
+}
@@ -0,0 +1,68 @@
+//! Number parsing and line layout for `hexdump`
+
+use core::fmt::Write;
+use heapless::String;
+
+pub const BYTES_PER_LINE: usize = 16;
+
+/// Parse a decimal or `0x`-prefixed hex number.
+pub fn parse_num(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// One dump line: 8-digit address, up to 16 hex bytes split in two groups of
+/// eight, then the ASCII gutter with non-printables shown as `.`.
+pub fn format_line(addr: usize, bytes: &[u8]) -> String<80> {
+    let bytes = &bytes[..bytes.len().min(BYTES_PER_LINE)];
+    let mut line = String::new();
+    let _ = write!(line, "{:08x} ", addr);
+    for i in 0..BYTES_PER_LINE {
+        if i == 8 {
+            let _ = line.push(' ');
+        }
+        let _ = match bytes.get(i) {
+            Some(b) => write!(line, " {:02x}", b),
+            None => line.write_str("   "),
+        };
+    }
+    let _ = line.push_str("  |");
+    for &b in bytes {
+        let _ = line.push(if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' });
+    }
+    let _ = line.push('|');
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_full_line() {
+        let line = format_line(0x10, b"Hello, world!\n\x00\x7f");
+        assert_eq!(
+            line.as_str(),
+            "00000010  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 7f  |Hello, world!...|"
+        );
+    }
+
+    #[test]
+    fn test_format_short_line_keeps_gutter_aligned() {
+        let line = format_line(0x1234, b"ab");
+        assert_eq!(
+            line.as_str(),
+            "00001234  61 62                                             |ab|"
+        );
+        assert_eq!(line.find('|'), format_line(0, &[0; 16]).find('|'));
+    }
+
+    #[test]
+    fn test_parse_num() {
+        assert_eq!(parse_num("42"), Some(42));
+        assert_eq!(parse_num("0x1f"), Some(0x1f));
+        assert_eq!(parse_num("zz"), None);
+    }
+}
@@ -0,0 +1,29 @@
+#![cfg_attr(not(test), no_std)]
+
+// The parsing and formatting behind the userapp programs, kept free of
+// syscalls so it can be tested on the host. The programs themselves live in
+// src/bin and are built for RISC-V only.
+
+/// Line, word and byte counts for wc
+pub mod wc;
+
+/// How echo joins its arguments
+pub mod echo;
+
+/// Map movement for rogue
+pub mod rogue;
+
+/// Page pattern checks for memdump
+pub mod memdump;
+
+/// Dump lines and numeric arguments for hexdump
+pub mod hexdump;
+
+/// Fixed-string matching for grep
+pub mod grep;
+
+/// H:MM:SS formatting for uptime
+pub mod uptime;
+
+/// Parsing, prompts and listings for the shell
+pub mod shell;
@@ -0,0 +1,49 @@
+//! The test pattern memdump writes into a fresh mapping and checks
+
+/// Byte expected at offset `i`; varies within and across pages.
+pub fn pattern_byte(i: usize) -> u8 {
+    (i ^ (i >> 8) ^ (i >> 16)) as u8
+}
+
+pub fn fill_pattern(buf: &mut [u8]) {
+    for (i, b) in buf.iter_mut().enumerate() {
+        *b = pattern_byte(i);
+    }
+}
+
+/// Number of bytes that don't match the pattern, and the first such offset.
+pub fn verify_pattern(buf: &[u8]) -> (usize, Option<usize>) {
+    let mut bad = buf.iter().enumerate().filter(|&(i, &b)| b != pattern_byte(i));
+    let first = bad.next().map(|(i, _)| i);
+    let count = first.map_or(0, |_| 1 + bad.count());
+    (count, first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_clean_pattern() {
+        let mut buf = [0u8; 1024];
+        fill_pattern(&mut buf);
+        assert_eq!(verify_pattern(&buf), (0, None));
+        assert_eq!(verify_pattern(&[]), (0, None));
+    }
+
+    #[test]
+    fn test_verify_finds_single_corrupted_byte() {
+        let mut buf = [0u8; 1024];
+        fill_pattern(&mut buf);
+        buf[700] ^= 0x40;
+        assert_eq!(verify_pattern(&buf), (1, Some(700)));
+    }
+
+    #[test]
+    fn test_pattern_differs_between_pages() {
+        // A page mapped twice would repeat the same bytes
+        let first: [u8; 16] = core::array::from_fn(pattern_byte);
+        let second: [u8; 16] = core::array::from_fn(|i| pattern_byte(4096 + i));
+        assert_ne!(first, second);
+    }
+}
@@ -0,0 +1,60 @@
+//! Movement on a rogue map, one row per line of text
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dir {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Tiles the player can stand on
+pub fn walkable(c: char) -> bool {
+    c == '.' || c == '<' || c == '>' || c == ' '
+}
+
+/// Where the player ends up after trying to step in `dir` from `(x, y)`.
+/// Walls and the edge of the map leave the position unchanged.
+pub fn try_move(map: &str, x: usize, y: usize, dir: Dir) -> (usize, usize) {
+    let (nx, ny) = match dir {
+        Dir::Up if y > 0 => (x, y - 1),
+        Dir::Down => (x, y + 1),
+        Dir::Left if x > 0 => (x - 1, y),
+        Dir::Right => (x + 1, y),
+        _ => return (x, y),
+    };
+    match map.lines().nth(ny).and_then(|line| line.chars().nth(nx)) {
+        Some(c) if walkable(c) => (nx, ny),
+        _ => (x, y),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "#####\n#<..#\n#.#>#\n#####";
+
+    #[test]
+    fn test_walls_block() {
+        assert_eq!(try_move(MAP, 1, 1, Dir::Up), (1, 1));
+        assert_eq!(try_move(MAP, 1, 1, Dir::Left), (1, 1));
+        // Interior wall at (2, 2)
+        assert_eq!(try_move(MAP, 2, 1, Dir::Down), (2, 1));
+    }
+
+    #[test]
+    fn test_valid_moves() {
+        assert_eq!(try_move(MAP, 1, 1, Dir::Right), (2, 1));
+        assert_eq!(try_move(MAP, 1, 1, Dir::Down), (1, 2));
+        assert_eq!(try_move(MAP, 3, 1, Dir::Down), (3, 2)); // onto the '>'
+    }
+
+    #[test]
+    fn test_edge_of_map() {
+        assert_eq!(try_move(MAP, 0, 0, Dir::Up), (0, 0));
+        assert_eq!(try_move(MAP, 0, 0, Dir::Left), (0, 0));
+        assert_eq!(try_move("..", 1, 0, Dir::Right), (1, 0));
+        assert_eq!(try_move("..", 1, 0, Dir::Down), (1, 0));
+    }
+}
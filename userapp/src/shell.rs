@@ -0,0 +1,297 @@
+//! Command-line handling for the shell
+
+use core::fmt::Write;
+use heapless::String;
+use uapi::Stat;
+
+use crate::uptime::format_uptime;
+
+/// One line describing `name` for the `stat` builtin, e.g.
+/// `notes.txt: file, 1234 bytes, mode 644, modified 0:02:05`. The
+/// modification time counts from boot, so embedded files show `0:00:00`.
+pub fn format_stat(name: &str, st: &Stat) -> String<64> {
+    let mut out = String::new();
+    let kind = if st.is_dir != 0 { "directory" } else { "file" };
+    let mode = st.mode & 0o777;
+    let mtime = format_uptime(st.mtime);
+    if write!(out, "{}: {}, {} bytes, mode {:03o}, modified {}", name, kind, st.size, mode, mtime).is_err() {
+        // Too long for one line: keep the numbers, drop the name
+        out.clear();
+        let _ = write!(out, "{}, {} bytes, mode {:03o}, modified {}", kind, st.size, mode, mtime);
+    }
+    out
+}
+
+// Prompt used when PS1 is not set
+pub const DEFAULT_PROMPT: &str = "> ";
+
+/// Expand the escapes in a PS1 `template`: `\w` is the working directory,
+/// `\?` the exit status of the last command, `\$` a `$` and `\\` a
+/// backslash. Anything else is copied as is, and a prompt too long for the
+/// buffer is cut short.
+pub fn expand_prompt(template: &str, cwd: &str, status: i32) -> String<64> {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        let fits = match (c, chars.peek()) {
+            ('\\', Some('w')) => {
+                chars.next();
+                out.push_str(cwd).is_ok()
+            }
+            ('\\', Some('?')) => {
+                chars.next();
+                write!(out, "{}", status).is_ok()
+            }
+            ('\\', Some(&e @ ('$' | '\\'))) => {
+                chars.next();
+                out.push(e).is_ok()
+            }
+            _ => out.push(c).is_ok(),
+        };
+        if !fits {
+            break;
+        }
+    }
+    out
+}
+
+/// Whether a directory entry is a program the shell can run: a file
+/// ending in `.elf` or with an execute bit set
+pub fn is_program(name: &str, mode: u32, is_dir: bool) -> bool {
+    !is_dir && (name.ends_with(".elf") || mode & 0o111 != 0)
+}
+
+/// Split a command line into `out`, returning how many tokens were found
+/// (tokens past `out.len()` are dropped). Tokens are separated by
+/// whitespace, except that one starting with `"` runs to the next `"` and
+/// comes out without the quotes, spaces and all. An unterminated quote takes
+/// the rest of the line. Quotes inside a token are kept as they are.
+pub fn tokenize<'a>(line: &'a str, out: &mut [&'a str]) -> usize {
+    let mut count = 0;
+    let mut rest = line.trim_start();
+    while !rest.is_empty() {
+        let (token, after) = match rest.strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((inside, after)) => (inside, after),
+                None => (quoted, ""),
+            },
+            None => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+        };
+        if count < out.len() {
+            out[count] = token;
+            count += 1;
+        }
+        rest = after.trim_start();
+    }
+    count
+}
+
+/// How a command is joined to the one before it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chain {
+    /// `a && b`: run b if a succeeded
+    And,
+    /// `a || b`: run b if a failed
+    Or,
+}
+
+/// The chaining operator a token stands for. Operators must be words of
+/// their own, and a quoted `"&&"` still counts as one.
+pub fn chain_op(token: &str) -> Option<Chain> {
+    match token {
+        "&&" => Some(Chain::And),
+        "||" => Some(Chain::Or),
+        _ => None,
+    }
+}
+
+/// Whether the command after `op` runs, given the status of the last
+/// command that did run. A skipped command leaves the status alone, so in
+/// `false && a || b` the `b` still runs.
+pub fn chain_runs(op: Chain, status: i32) -> bool {
+    match op {
+        Chain::And => status == 0,
+        Chain::Or => status != 0,
+    }
+}
+
+/// Run the commands in `tokens`, split at `&&` and `||`, calling `run` for
+/// each one the chain lets through. `status` is the last status before the
+/// line; the status of the last command run is returned. Nothing runs if
+/// an operator is missing a command on either side.
+pub fn run_chain<'a>(tokens: &[&'a str], status: i32, mut run: impl FnMut(&[&'a str]) -> i32) -> Result<i32, &'static str> {
+    let mut prev_was_op = true;
+    for &t in tokens {
+        let is_op = chain_op(t).is_some();
+        if is_op && prev_was_op {
+            return Err("syntax error: missing command before && or ||");
+        }
+        prev_was_op = is_op;
+    }
+    if prev_was_op {
+        return Err("syntax error: missing command after && or ||");
+    }
+
+    let mut status = status;
+    let mut start = 0;
+    let mut go = true;
+    for end in 0..=tokens.len() {
+        // Each command ends at an operator or the end of the line
+        let op = tokens.get(end).and_then(|t| chain_op(t));
+        if end < tokens.len() && op.is_none() {
+            continue;
+        }
+        if go {
+            status = run(&tokens[start..end]);
+        }
+        if let Some(op) = op {
+            go = chain_runs(op, status);
+        }
+        start = end + 1;
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_stat() {
+        let file = Stat { size: 1234, mode: 0o644, is_dir: 0, mtime: 125 };
+        assert_eq!(format_stat("notes.txt", &file).as_str(), "notes.txt: file, 1234 bytes, mode 644, modified 0:02:05");
+        let dir = Stat { size: 0, mode: 0o755, is_dir: 1, mtime: 3600 };
+        assert_eq!(format_stat("docs", &dir).as_str(), "docs: directory, 0 bytes, mode 755, modified 1:00:00");
+        // Type bits above the permissions are not shown; embedded files have no mtime
+        let odd = Stat { size: 1, mode: 0o100600, is_dir: 0, mtime: 0 };
+        assert_eq!(format_stat("a", &odd).as_str(), "a: file, 1 bytes, mode 600, modified 0:00:00");
+    }
+
+    #[test]
+    fn test_format_stat_long_name() {
+        let st = Stat { size: 9, mode: 0o644, is_dir: 0, mtime: 42 };
+        let name = "n".repeat(40);
+        assert_eq!(format_stat(&name, &st).as_str(), "file, 9 bytes, mode 644, modified 0:00:42");
+    }
+
+    #[test]
+    fn test_expand_prompt_literal() {
+        assert_eq!(expand_prompt(DEFAULT_PROMPT, "/", 0).as_str(), "> ");
+        assert_eq!(expand_prompt("bogo% ", "/bin", 0).as_str(), "bogo% ");
+        assert_eq!(expand_prompt("", "/", 0).as_str(), "");
+    }
+
+    #[test]
+    fn test_expand_prompt_escapes() {
+        assert_eq!(expand_prompt("\\w\\$ ", "/home", 0).as_str(), "/home$ ");
+        assert_eq!(expand_prompt("[\\w] ", "/", 0).as_str(), "[/] ");
+        assert_eq!(expand_prompt("a\\\\b", "/", 0).as_str(), "a\\b");
+        // Unknown escapes and a trailing backslash are kept
+        assert_eq!(expand_prompt("\\x\\", "/", 0).as_str(), "\\x\\");
+    }
+
+    #[test]
+    fn test_expand_prompt_truncates() {
+        let long = "d".repeat(80);
+        let p = expand_prompt("\\w> ", &long, 0);
+        // The cwd alone does not fit, so it is dropped rather than cut
+        assert_eq!(p.as_str(), "");
+        let p = expand_prompt(&"x".repeat(70), "/", 0);
+        assert_eq!(p.len(), 64);
+    }
+
+    #[test]
+    fn test_expand_prompt_status() {
+        assert_eq!(expand_prompt("[\\?] ", "/", 0).as_str(), "[0] ");
+        assert_eq!(expand_prompt("\\?\\$ ", "/", 127).as_str(), "127$ ");
+        assert_eq!(expand_prompt("\\?", "/", -1).as_str(), "-1");
+    }
+
+    #[test]
+    fn test_is_program() {
+        assert!(is_program("shell.elf", 0o755, false));
+        assert!(!is_program("dungeon.map", 0o644, false));
+        // Either the extension or an execute bit is enough
+        assert!(is_program("forth.elf", 0o644, false));
+        assert!(is_program("script", 0o700, false));
+        assert!(is_program("run", 0o001, false));
+        // Directories are never programs
+        assert!(!is_program("bin", 0o755, true));
+        assert!(!is_program("old.elf", 0o755, true));
+    }
+
+    fn split(line: &str) -> Vec<&str> {
+        let mut out = [""; 8];
+        let n = tokenize(line, &mut out);
+        out[..n].to_vec()
+    }
+
+    #[test]
+    fn test_tokenize_whitespace() {
+        assert_eq!(split("  ls   -l\t/bin "), ["ls", "-l", "/bin"]);
+        assert!(split("   ").is_empty());
+        // Extra tokens are dropped, not wrapped
+        assert_eq!(split("a b c d e f g h i j").len(), 8);
+    }
+
+    #[test]
+    fn test_tokenize_quoted_args() {
+        assert_eq!(split("echo \"hello world\" !"), ["echo", "hello world", "!"]);
+        assert_eq!(split("echo \"a\"\"b\""), ["echo", "a", "b"]);
+        assert_eq!(split("echo \"\" x"), ["echo", "", "x"]);
+        // A quote inside a token is an ordinary character
+        assert_eq!(split("say it\"s"), ["say", "it\"s"]);
+    }
+
+    #[test]
+    fn test_tokenize_unterminated_quote() {
+        assert_eq!(split("echo \"rest of  line"), ["echo", "rest of  line"]);
+        assert_eq!(split("echo \""), ["echo", ""]);
+    }
+
+    /// Run `line` with `false` failing and everything else succeeding,
+    /// returning the final status and each command that ran
+    fn chain(line: &str, status: i32) -> (Result<i32, &'static str>, Vec<Vec<&str>>) {
+        let mut ran = Vec::new();
+        let got = run_chain(&split(line), status, |cmd| {
+            ran.push(cmd.to_vec());
+            match cmd[0] {
+                "false" => 1,
+                _ => 0,
+            }
+        });
+        (got, ran)
+    }
+
+    #[test]
+    fn test_chain_and() {
+        assert_eq!(chain("true && echo hi", 0), (Ok(0), vec![vec!["true"], vec!["echo", "hi"]]));
+        assert_eq!(chain("false && echo hi", 0), (Ok(1), vec![vec!["false"]]));
+    }
+
+    #[test]
+    fn test_chain_or() {
+        assert_eq!(chain("false || echo hi", 0), (Ok(0), vec![vec!["false"], vec!["echo", "hi"]]));
+        assert_eq!(chain("true || echo hi", 0), (Ok(0), vec![vec!["true"]]));
+    }
+
+    #[test]
+    fn test_chain_skip_keeps_status() {
+        // The skipped `a` leaves false's status for the ||
+        assert_eq!(chain("false && a || b", 0), (Ok(0), vec![vec!["false"], vec!["b"]]));
+        assert_eq!(chain("true || a && b", 0), (Ok(0), vec![vec!["true"], vec!["b"]]));
+        // A single command ignores the previous status
+        assert_eq!(chain("ls", 1), (Ok(0), vec![vec!["ls"]]));
+        assert!(chain_runs(Chain::And, 0) && !chain_runs(Chain::And, 127));
+        assert!(chain_runs(Chain::Or, 1) && !chain_runs(Chain::Or, 0));
+    }
+
+    #[test]
+    fn test_chain_syntax_errors_run_nothing() {
+        for line in ["&& ls", "ls ||", "ls && || pwd", "&&"] {
+            let (got, ran) = chain(line, 0);
+            assert!(got.is_err(), "{:?} was accepted", line);
+            assert!(ran.is_empty());
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! Uptime formatting
+
+use core::fmt::Write;
+use heapless::String;
+
+/// Format seconds as `H:MM:SS`; hours keep counting past a day.
+pub fn format_uptime(secs: u64) -> String<16> {
+    let mut out = String::new();
+    let _ = write!(out, "{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(0).as_str(), "0:00:00");
+        assert_eq!(format_uptime(59).as_str(), "0:00:59");
+        assert_eq!(format_uptime(3600).as_str(), "1:00:00");
+        // 1 day, 1 hour, 1 minute, 1 second
+        assert_eq!(format_uptime(90061).as_str(), "25:01:01");
+    }
+}
@@ -0,0 +1,64 @@
+//! Counting for `wc`, a chunk at a time
+
+/// Count `(lines, words, bytes)` in one buffer, like `wc`.
+pub fn count(bytes: &[u8]) -> (usize, usize, usize) {
+    let lines = bytes.iter().filter(|&&b| b == b'\n').count();
+    let words = bytes.split(|b| b.is_ascii_whitespace()).filter(|w| !w.is_empty()).count();
+    (lines, words, bytes.len())
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Counts {
+    pub lines: usize,
+    pub words: usize,
+    pub bytes: usize,
+    /// The last byte added was part of a word
+    in_word: bool,
+}
+
+impl Counts {
+    /// Add the next chunk of input; a word cut by the chunk boundary counts once.
+    pub fn add(&mut self, chunk: &[u8]) {
+        let Some(&first) = chunk.first() else { return };
+        let (lines, mut words, bytes) = count(chunk);
+        if self.in_word && !first.is_ascii_whitespace() {
+            words -= 1;
+        }
+        self.lines += lines;
+        self.words += words;
+        self.bytes += bytes;
+        self.in_word = !chunk[chunk.len() - 1].is_ascii_whitespace();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_empty() {
+        assert_eq!(count(b""), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_count_trailing_newline() {
+        assert_eq!(count(b"one two\nthree\n"), (2, 3, 14));
+        // No final newline: the last line isn't counted, its word is
+        assert_eq!(count(b"one two\nthree"), (1, 3, 13));
+    }
+
+    #[test]
+    fn test_count_multiple_spaces() {
+        assert_eq!(count(b"  a   b\t\tc  \n"), (1, 3, 13));
+        assert_eq!(count(b"   \n\n"), (2, 0, 5));
+    }
+
+    #[test]
+    fn test_word_split_across_chunks() {
+        let mut c = Counts::default();
+        c.add(b"hel");
+        c.add(b"lo wor");
+        c.add(b"ld\n");
+        assert_eq!((c.lines, c.words, c.bytes), (1, 2, 12));
+    }
+}
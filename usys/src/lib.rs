@@ -1,11 +1,18 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 use core::ffi::CStr;
 use core::fmt::{self, Write};
-use uapi::{is_err_sentinel, nr, SysErr, SysResult};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use uapi::{is_err_sentinel, nr};
 
 // Re-export LogLevel for user applications
 pub use uapi::LogLevel;
+pub use uapi::MAX_ARGS;
+pub use uapi::MemInfo;
+pub use uapi::Pid;
+pub use uapi::PathBuf;
+pub use uapi::Stat;
+pub use uapi::{SysErr, SysResult};
 
 #[repr(transparent)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -15,6 +22,7 @@ pub const STDIN:  Fd = Fd(0);
 pub const STDOUT: Fd = Fd(1);
 pub const STDERR: Fd = Fd(2);
 
+#[cfg(not(test))]
 #[inline(always)]
 unsafe fn sys_ecall3(nr: usize, a0: usize, a1: usize, a2: usize) -> usize {
     let mut ret: usize;
@@ -29,6 +37,7 @@ unsafe fn sys_ecall3(nr: usize, a0: usize, a1: usize, a2: usize) -> usize {
     );
     ret
 }
+#[cfg(not(test))]
 #[inline(always)]
 unsafe fn sys_ecall0(nr: usize) -> usize {
     let mut ret: usize;
@@ -41,6 +50,7 @@ unsafe fn sys_ecall0(nr: usize) -> usize {
     ret
 }
 
+#[cfg(not(test))]
 unsafe fn sys_ecall1(nr: usize, a0: usize) -> usize {
     let mut ret: usize;
     core::arch::asm!(
@@ -52,6 +62,7 @@ unsafe fn sys_ecall1(nr: usize, a0: usize) -> usize {
     );
     ret
 }
+#[cfg(not(test))]
 #[inline(always)]
 unsafe fn sys_ecall2(nr: usize, a0: usize, a1: usize) -> usize {
     let mut ret: usize;
@@ -65,11 +76,50 @@ unsafe fn sys_ecall2(nr: usize, a0: usize, a1: usize) -> usize {
     );
     ret
 }
+#[cfg(not(test))]
 #[inline(always)]
-unsafe fn sys_ecall0_noreturn(nr: usize) -> ! {
-    core::arch::asm!("ecall", in("a7") nr, options(noreturn, nostack));
+unsafe fn sys_ecall1_noreturn(nr: usize, a0: usize) -> ! {
+    core::arch::asm!("ecall", in("a7") nr, in("a0") a0, options(noreturn, nostack));
 }
 
+/// Stand-ins for the ecalls in host tests, where there is no kernel to trap
+/// into. Only exiting is modelled: it unwinds with an `Exited` record.
+#[cfg(test)]
+mod host {
+    use std::cell::Cell;
+
+    thread_local! {
+        /// `flush_stdout` calls so far on this thread
+        pub static FLUSHES: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// A no-return ecall, and how many flushes came before it
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct Exited {
+        pub nr: usize,
+        pub a0: usize,
+        pub flushes: usize,
+    }
+
+    pub unsafe fn sys_ecall0(nr: usize) -> usize {
+        panic!("ecall {} in a host test", nr)
+    }
+    pub unsafe fn sys_ecall1(nr: usize, _a0: usize) -> usize {
+        panic!("ecall {} in a host test", nr)
+    }
+    pub unsafe fn sys_ecall2(nr: usize, _a0: usize, _a1: usize) -> usize {
+        panic!("ecall {} in a host test", nr)
+    }
+    pub unsafe fn sys_ecall3(nr: usize, _a0: usize, _a1: usize, _a2: usize) -> usize {
+        panic!("ecall {} in a host test", nr)
+    }
+    pub unsafe fn sys_ecall1_noreturn(nr: usize, a0: usize) -> ! {
+        std::panic::panic_any(Exited { nr, a0, flushes: FLUSHES.with(Cell::get) })
+    }
+}
+#[cfg(test)]
+use host::{sys_ecall0, sys_ecall1, sys_ecall1_noreturn, sys_ecall2, sys_ecall3};
+
 /* -------- basic I/O ---------- */
 
 pub fn write(buf: &[u8]) -> usize {
@@ -78,12 +128,54 @@ pub fn write(buf: &[u8]) -> usize {
 pub fn write_cstr(s: &CStr) -> usize {
     unsafe { sys_ecall1(nr::WRITE_CSTR, s.as_ptr() as usize) }
 }
+/// Write to `fd`, returning how many bytes went out. Writes to a file stop
+/// at a page boundary of `buf` and at 4 KiB, so this can be short; use
+/// `IoWrite::write_all` to write everything.
 pub fn write_fd(fd: Fd, buf: &[u8]) -> SysResult<usize> {
     let r = unsafe { sys_ecall3(nr::WRITE_FD, fd.0 as usize, buf.as_ptr() as usize, buf.len()) };
     if is_err_sentinel(r) { Err(SysErr::Fail) } else { Ok(r) }
 }
+
+/// Like `write_fd`, but bytes sent to the terminal are not CRLF-translated.
+pub fn write_raw(fd: Fd, buf: &[u8]) -> SysResult<usize> {
+    let r = unsafe { sys_ecall3(nr::WRITE_RAW, fd.0 as usize, buf.as_ptr() as usize, buf.len()) };
+    if is_err_sentinel(r) { Err(SysErr::Fail) } else { Ok(r) }
+}
+
+/// Switch stdin between raw (one keystroke per `read`, no echo) and
+/// canonical (kernel-side line editing and echo, `read` returns on Enter)
+/// mode. The kernel resets the terminal to raw when a program exits.
+pub fn set_raw_mode(raw: bool) -> SysResult<()> {
+    set_term_mode(if raw { uapi::term::RAW } else { uapi::term::LINE })
+}
+
+/// Set the `uapi::term` flags directly, e.g. canonical without echo.
+pub fn set_term_mode(flags: usize) -> SysResult<()> {
+    let r = unsafe { sys_ecall1(nr::SET_TERM_MODE, flags) };
+    if is_err_sentinel(r) { Err(SysErr::Fail) } else { Ok(()) }
+}
+
+/// Push out anything written to stdout but not yet handed to the kernel.
+/// Stdout is unbuffered for now, so this has nothing to do; it is the hook
+/// `exit` and `exit_code` go through so buffered output is never lost.
+/// Calling it more than once is harmless.
+pub fn flush_stdout() {
+    #[cfg(test)]
+    host::FLUSHES.with(|n| n.set(n.get() + 1));
+    let _ = Stdout.flush();
+}
+
+/// End the program (flushing stdout first) and return to the shell, or to
+/// the program that spawned this one. Same as `exit_code(0)`.
 pub fn exit() -> ! {
-    unsafe { sys_ecall0_noreturn(nr::EXIT) }
+    exit_code(0)
+}
+
+/// Like `exit`, passing `code` to the kernel in a0; a parent that used
+/// `spawn` gets it from `wait`.
+pub fn exit_code(code: i32) -> ! {
+    flush_stdout();
+    unsafe { sys_ecall1_noreturn(nr::EXIT, code as usize) }
 }
 
 /* -------- file-like API ---------- */
@@ -124,20 +216,71 @@ pub fn open(path: &CStr) -> SysResult<Fd> {
     }
 }
 
+/// `whence` values for `lseek`
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
 pub fn lseek(fd: Fd, offset: isize, whence: usize) -> SysResult<usize> {
     let r = unsafe { sys_ecall3(nr::LSEEK, fd.0 as usize, offset as usize, whence) };
     if is_err_sentinel(r) { Err(SysErr::Fail) } else { Ok(r) }
 }
 
+/// Resize an open writable file to `len` bytes (zero-filling when growing).
+pub fn ftruncate(fd: Fd, len: usize) -> SysResult<()> {
+    let r = unsafe { sys_ecall2(nr::FTRUNCATE, fd.0 as usize, len) };
+    if is_err_sentinel(r) { Err(SysErr::Fail) } else { Ok(()) }
+}
+
+/// Copy up to `len` bytes from `in_fd` to `out_fd` inside the kernel.
+/// Both offsets advance; returns the number of bytes copied (0 at EOF).
+pub fn sendfile(out_fd: Fd, in_fd: Fd, len: usize) -> SysResult<usize> {
+    let r = unsafe { sys_ecall3(nr::SENDFILE, out_fd.0 as usize, in_fd.0 as usize, len) };
+    if is_err_sentinel(r) { Err(SysErr::Fail) } else { Ok(r) }
+}
+
 pub fn brk(addr: usize) -> SysResult<usize> {
     let r = unsafe { sys_ecall1(nr::BRK, addr) };
     Ok(r)
 }
 
+/// Map `len` bytes of fresh zeroed memory. It stays mapped until the program exits.
+pub fn mmap(len: usize) -> SysResult<&'static mut [u8]> {
+    let r = unsafe { sys_ecall1(nr::MMAP, len) };
+    if is_err_sentinel(r) {
+        Err(SysErr::Fail)
+    } else {
+        // SAFETY: the kernel just mapped `len` bytes at `r` for us alone
+        Ok(unsafe { core::slice::from_raw_parts_mut(r as *mut u8, len) })
+    }
+}
+
 pub fn gettime() -> usize {
     unsafe { sys_ecall1(nr::GETTIME, 0) }
 }
 
+/// Milliseconds since boot.
+pub fn uptime_ms() -> u64 {
+    let mut ms = 0u64;
+    unsafe { sys_ecall1(nr::GETTIME, &mut ms as *mut u64 as usize) };
+    ms
+}
+
+/// Wait at least `ms` milliseconds. There is no sleep syscall, so this
+/// polls the clock, yielding the CPU between checks.
+pub fn sleep_ms(ms: u64) {
+    let start = uptime_ms();
+    while uptime_ms().wrapping_sub(start) < ms {
+        yield_now();
+    }
+}
+
+/// Give up the CPU until the next interrupt (at most one timer tick).
+/// Cheaper than spinning in a polling loop.
+pub fn yield_now() {
+    unsafe { sys_ecall0(nr::YIELD); }
+}
+
 pub fn poweroff() -> ! {
     unsafe { sys_ecall0(nr::POWEROFF); }
     loop {}
@@ -148,22 +291,65 @@ pub fn exec(path: &CStr) -> ! {
     loop {}
 }
 
+// NULL-terminated pointer array for argv/envp (extra entries are dropped)
+fn ptr_array(strs: &[&CStr]) -> [usize; MAX_ARGS + 1] {
+    let mut ptrs: [usize; MAX_ARGS + 1] = [0; MAX_ARGS + 1];
+    for (slot, s) in ptrs.iter_mut().zip(strs.iter().take(MAX_ARGS)) {
+        *slot = s.as_ptr() as usize;
+    }
+    ptrs
+}
+
+/// Replace this program, keeping the current environment.
 pub fn execv(path: &CStr, argv: &[&CStr]) -> ! {
-    // Build NULL-terminated argv array on stack
-    let mut argv_ptrs: [usize; 17] = [0; 17];
-    let len = core::cmp::min(argv.len(), 16);
-    
-    for i in 0..len {
-        argv_ptrs[i] = argv[i].as_ptr() as usize;
-    }
-    argv_ptrs[len] = 0; // NULL terminator
-    
+    let argv_ptrs = ptr_array(argv);
     unsafe { 
         sys_ecall2(nr::EXECV, path.as_ptr() as usize, argv_ptrs.as_ptr() as usize);
     }
     loop {}
 }
 
+/// Replace this program, giving it `envp` ("KEY=value" strings) as its environment.
+pub fn execve(path: &CStr, argv: &[&CStr], envp: &[&CStr]) -> ! {
+    let argv_ptrs = ptr_array(argv);
+    let envp_ptrs = ptr_array(envp);
+    unsafe {
+        sys_ecall3(nr::EXECVE, path.as_ptr() as usize, argv_ptrs.as_ptr() as usize, envp_ptrs.as_ptr() as usize);
+    }
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Run `path` as a child program, keeping this one. The kernel runs one
+/// program at a time, so this program is parked until the child exits and
+/// then `spawn` returns the child's pid. The child inherits the open files,
+/// environment and working directory. Only one program can be parked: a
+/// spawned child calling `spawn` gets an error.
+pub fn spawn(path: &CStr, argv: &[&CStr]) -> SysResult<Pid> {
+    flush_stdout();
+    let argv_ptrs = ptr_array(argv);
+    let r = unsafe { sys_ecall2(nr::SPAWN, path.as_ptr() as usize, argv_ptrs.as_ptr() as usize) };
+    if is_err_sentinel(r) {
+        Err(SysErr::Fail)
+    } else {
+        Ok(r)
+    }
+}
+
+/// Pid and exit code of the last child started with `spawn`. As `spawn`
+/// only returns once the child is done this never blocks; it fails if no
+/// child has exited since the last `wait`.
+pub fn wait() -> SysResult<(Pid, i32)> {
+    let mut code: i32 = 0;
+    let r = unsafe { sys_ecall1(nr::WAIT, &mut code as *mut i32 as usize) };
+    if is_err_sentinel(r) {
+        Err(SysErr::Fail)
+    } else {
+        Ok((r, code))
+    }
+}
+
 pub fn creat(path: &CStr, mode: u32) -> SysResult<Fd> {
     let r = unsafe { sys_ecall2(nr::CREAT, path.as_ptr() as usize, mode as usize) };
     if is_err_sentinel(r) {
@@ -182,8 +368,42 @@ pub fn unlink(path: &CStr) -> SysResult<()> {
     }
 }
 
-pub fn stat(path: &CStr, buf: &mut [u64; 2]) -> SysResult<()> {
-    let r = unsafe { sys_ecall2(nr::STAT, path.as_ptr() as usize, buf.as_mut_ptr() as usize) };
+pub fn stat(path: &CStr) -> SysResult<Stat> {
+    let mut st = Stat::default();
+    let r = unsafe { sys_ecall2(nr::STAT, path.as_ptr() as usize, &mut st as *mut _ as usize) };
+    if is_err_sentinel(r) {
+        Err(SysErr::Fail)
+    } else {
+        Ok(st)
+    }
+}
+
+/// Find the program the shell would run for `name` (`name` or `name.elf`).
+pub fn which(name: &str) -> SysResult<CStrBuf<64>> {
+    let name = CStrBuf::<64>::from_str(name).map_err(|_| SysErr::Fail)?;
+    let mut out = [0u8; 64];
+    let r = unsafe {
+        sys_ecall3(nr::RESOLVE, name.as_cstr().as_ptr() as usize, out.as_mut_ptr() as usize, out.len())
+    };
+    if is_err_sentinel(r) {
+        return Err(SysErr::NoEnt);
+    }
+    let path = core::str::from_utf8(&out[..r]).map_err(|_| SysErr::Fail)?;
+    CStrBuf::from_str(path).map_err(|_| SysErr::Fail)
+}
+
+pub fn mkdir(path: &CStr) -> SysResult<()> {
+    let r = unsafe { sys_ecall1(nr::MKDIR, path.as_ptr() as usize) };
+    if is_err_sentinel(r) {
+        Err(SysErr::Fail)
+    } else {
+        Ok(())
+    }
+}
+
+/// Rename `src` to `dst`, replacing any existing file called `dst`.
+pub fn rename(src: &CStr, dst: &CStr) -> SysResult<()> {
+    let r = unsafe { sys_ecall2(nr::RENAME, src.as_ptr() as usize, dst.as_ptr() as usize) };
     if is_err_sentinel(r) {
         Err(SysErr::Fail)
     } else {
@@ -191,6 +411,76 @@ pub fn stat(path: &CStr, buf: &mut [u64; 2]) -> SysResult<()> {
     }
 }
 
+/// Change the working directory that relative paths are resolved against.
+/// Each exec starts the new program at `/`.
+pub fn chdir(path: &CStr) -> SysResult<()> {
+    let r = unsafe { sys_ecall1(nr::CHDIR, path.as_ptr() as usize) };
+    if is_err_sentinel(r) {
+        Err(SysErr::Fail)
+    } else {
+        Ok(())
+    }
+}
+
+/// CRC-32 (as zlib computes it) of the file at `path`, worked out by the
+/// kernel. Fails for a missing file or a directory.
+pub fn checksum(path: &CStr) -> SysResult<u32> {
+    let r = unsafe { sys_ecall1(nr::CHECKSUM, path.as_ptr() as usize) };
+    if is_err_sentinel(r) {
+        Err(SysErr::Fail)
+    } else {
+        Ok(r as u32)
+    }
+}
+
+/// Ask the kernel to log this program's registers as they are at the call,
+/// for debugging. The dump only shows up with `loglevel=trace`.
+pub fn regdump() {
+    unsafe { sys_ecall0(nr::REGDUMP); }
+}
+
+/// Write the absolute working directory (NUL-terminated) into `buf` and
+/// return it.
+pub fn getcwd(buf: &mut [u8]) -> SysResult<&str> {
+    let r = unsafe { sys_ecall2(nr::GETCWD, buf.as_mut_ptr() as usize, buf.len()) };
+    if is_err_sentinel(r) {
+        return Err(SysErr::Fail);
+    }
+    core::str::from_utf8(&buf[..r]).map_err(|_| SysErr::Fail)
+}
+
+/// Replace the contents of `path` with `data` without anyone seeing a
+/// half-written file: the data goes to `<path>.tmp~`, which is then renamed
+/// over `path`. An existing target keeps its mode and is left untouched if
+/// anything fails.
+pub fn replace_file(path: &str, data: &[u8]) -> SysResult<()> {
+    const SUFFIX: &str = ".tmp~";
+    // Both names must fit whole, or the temporary file could be the target
+    if path.len() + SUFFIX.len() >= uapi::path::PATH_MAX {
+        return Err(SysErr::Fail);
+    }
+    let target = path.parse::<PathBuf>().map_err(|_| SysErr::Fail)?;
+    let mut tmp = target.clone();
+    tmp.push(SUFFIX).map_err(|_| SysErr::Fail)?;
+
+    let mode = match stat(target.as_cstr()) {
+        Ok(st) if st.is_dir != 0 || st.mode & 0o200 == 0 => return Err(SysErr::Fail),
+        Ok(st) => st.mode,
+        Err(_) => 0o600,
+    };
+
+    let fd = creat(tmp.as_cstr(), mode)?;
+    let written = fd.write_all(data);
+    let _ = fd.close();
+    let done = written
+        .and_then(|_| chmod(tmp.as_cstr(), mode))
+        .and_then(|_| rename(tmp.as_cstr(), target.as_cstr()));
+    if done.is_err() {
+        let _ = unlink(tmp.as_cstr());
+    }
+    done
+}
+
 pub fn chmod(path: &CStr, mode: u32) -> SysResult<()> {
     let r = unsafe { sys_ecall2(nr::CHMOD, path.as_ptr() as usize, mode as usize) };
     if is_err_sentinel(r) {
@@ -209,13 +499,135 @@ pub fn readdir(buf: &mut [u8]) -> SysResult<usize> {
     }
 }
 
+pub use uapi::dirent::Dirent;
+
+/// Iterator over the files in the filesystem, fetched in batches via `getdents`.
+pub struct ReadDir {
+    buf: [u8; 1024],
+    len: usize,
+    pos: usize,
+    next_index: usize,
+}
+
+/// List the filesystem; yields one `Dirent` per file.
+pub fn read_dir() -> ReadDir {
+    ReadDir { buf: [0; 1024], len: 0, pos: 0, next_index: 0 }
+}
+
+impl ReadDir {
+    fn refill(&mut self) -> bool {
+        let r = unsafe {
+            sys_ecall3(nr::GETDENTS, self.buf.as_mut_ptr() as usize, self.buf.len(), self.next_index)
+        };
+        if is_err_sentinel(r) || r == 0 {
+            return false;
+        }
+        self.len = core::cmp::min(r, self.buf.len());
+        self.pos = 0;
+        true
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = Dirent;
+
+    fn next(&mut self) -> Option<Dirent> {
+        if let Some((entry, next)) = uapi::dirent::decode(&self.buf[..self.len], self.pos) {
+            self.pos = next;
+            self.next_index += 1;
+            return Some(entry);
+        }
+        // Batch used up (or ended in a partial record): ask for more
+        if !self.refill() {
+            return None;
+        }
+        let (entry, next) = uapi::dirent::decode(&self.buf[..self.len], 0)?;
+        self.pos = next;
+        self.next_index += 1;
+        Some(entry)
+    }
+}
+
+/// A mutable buffer for `readv`, laid out as a `uapi::IoVec`.
+#[repr(transparent)]
+pub struct IoSliceMut<'a> {
+    vec: uapi::IoVec,
+    _buf: core::marker::PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> IoSliceMut<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        IoSliceMut {
+            vec: uapi::IoVec { base: buf.as_mut_ptr() as usize, len: buf.len() },
+            _buf: core::marker::PhantomData,
+        }
+    }
+}
+
+/// Scatter one sequential read from `fd` across `bufs`, filling each in turn.
+/// Returns the total number of bytes read. At most `uapi::MAX_IOV` buffers.
+pub fn readv(fd: Fd, bufs: &mut [IoSliceMut<'_>]) -> SysResult<usize> {
+    let r = unsafe { sys_ecall3(nr::READV, fd.0 as usize, bufs.as_mut_ptr() as usize, bufs.len()) };
+    if is_err_sentinel(r) {
+        Err(SysErr::Fail)
+    } else {
+        Ok(r)
+    }
+}
+
 // Framebuffer info structure (must match kernel side)
 #[repr(C)]
+#[derive(Default)]
 pub struct FbInfo {
     pub width: usize,
     pub height: usize,
     pub stride: usize,
     pub addr: usize,
+    pub format: u32, // raw VIRTIO_GPU_FORMAT_* value, see `pixel_format()`
+}
+
+const _: () = assert!(core::mem::size_of::<FbInfo>() == 40);
+
+impl FbInfo {
+    pub fn pixel_format(&self) -> Option<PixelFormat> {
+        PixelFormat::from_raw(self.format)
+    }
+}
+
+/// Framebuffer pixel layouts, numbered as virtio-gpu's `VIRTIO_GPU_FORMAT_*`.
+/// Names list the bytes in memory order.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum PixelFormat {
+    B8G8R8A8 = 1,
+    B8G8R8X8 = 2,
+    A8R8G8B8 = 3,
+    X8R8G8B8 = 4,
+    R8G8B8A8 = 67,
+    X8B8G8R8 = 68,
+    A8B8G8R8 = 121,
+    R8G8B8X8 = 134,
+}
+
+impl PixelFormat {
+    pub fn from_raw(v: u32) -> Option<Self> {
+        Some(match v {
+            1 => Self::B8G8R8A8,
+            2 => Self::B8G8R8X8,
+            3 => Self::A8R8G8B8,
+            4 => Self::X8R8G8B8,
+            67 => Self::R8G8B8A8,
+            68 => Self::X8B8G8R8,
+            121 => Self::A8B8G8R8,
+            134 => Self::R8G8B8X8,
+            _ => return None,
+        })
+    }
+
+    pub fn bytes_per_pixel(self) -> usize {
+        // every format virtio-gpu defines is 32-bit
+        4
+    }
 }
 
 pub fn get_fb_info(info: &mut FbInfo) -> SysResult<()> {
@@ -227,6 +639,65 @@ pub fn get_fb_info(info: &mut FbInfo) -> SysResult<()> {
     }
 }
 
+/// True if a `read` from stdin would return without blocking.
+pub fn poll_stdin() -> bool {
+    let r = unsafe { sys_ecall0(nr::POLLIN) };
+    !is_err_sentinel(r) && r != 0
+}
+
+/// Read from stdin only if input is already waiting; `Ok(0)` otherwise.
+pub fn try_read(buf: &mut [u8]) -> SysResult<usize> {
+    if !poll_stdin() {
+        return Ok(0);
+    }
+    STDIN.read_priv(buf)
+}
+
+/// Where console output goes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DisplayMode {
+    /// The UART, for ANSI terminals
+    Ansi,
+    /// The virtio-gpu framebuffer console
+    Gpu,
+}
+
+pub fn display_mode() -> SysResult<DisplayMode> {
+    match unsafe { sys_ecall0(nr::GET_DISPLAY_MODE) } {
+        uapi::display_mode::ANSI => Ok(DisplayMode::Ansi),
+        uapi::display_mode::GPU => Ok(DisplayMode::Gpu),
+        _ => Err(SysErr::Fail),
+    }
+}
+
+/// Switch console output. Fails for `Gpu` if the kernel found no framebuffer.
+pub fn set_display_mode(mode: DisplayMode) -> SysResult<()> {
+    let raw = match mode {
+        DisplayMode::Ansi => uapi::display_mode::ANSI,
+        DisplayMode::Gpu => uapi::display_mode::GPU,
+    };
+    let r = unsafe { sys_ecall1(nr::SET_DISPLAY_MODE, raw) };
+    if is_err_sentinel(r) { Err(SysErr::Fail) } else { Ok(()) }
+}
+
+/// Kernel heap usage in bytes.
+pub fn meminfo() -> SysResult<MemInfo> {
+    let mut info = MemInfo::default();
+    let r = unsafe { sys_ecall1(nr::MEMINFO, &mut info as *mut _ as usize) };
+    if is_err_sentinel(r) {
+        Err(SysErr::Fail)
+    } else {
+        Ok(info)
+    }
+}
+
+/// Copy the most recent kernel log bytes into `buf`, oldest first.
+/// Returns the number of bytes copied (0 on error).
+pub fn dmesg(buf: &mut [u8]) -> usize {
+    let r = unsafe { sys_ecall2(nr::DMESG, buf.as_mut_ptr() as usize, buf.len()) };
+    if is_err_sentinel(r) { 0 } else { r }
+}
+
 pub fn fb_flush() -> SysResult<()> {
     let r = unsafe { sys_ecall0(nr::FB_FLUSH) };
     if is_err_sentinel(r) {
@@ -236,22 +707,27 @@ pub fn fb_flush() -> SysResult<()> {
     }
 }
 
-/* ---------- tiny io traits ---------- */
+/// Show what has been drawn to the framebuffer mapped by `get_fb_info`.
+/// Drawing goes to an off-screen buffer; nothing appears until this is called.
+pub fn fb_present() -> SysResult<()> {
+    fb_flush()
+}
 
-pub trait IoWrite {
-    fn write(&self, buf: &[u8]) -> SysResult<usize>;
-    fn write_all(&self, mut buf: &[u8]) -> SysResult<()> {
-        while !buf.is_empty() {
-            let n = self.write(buf)?;
-            if n == 0 { return Err(SysErr::Fail); }
-            buf = &buf[n..];
-        }
+/// Make everything written so far visible: presents the framebuffer when
+/// there is one. Files need no flushing, so this also succeeds without a
+/// display.
+pub fn sync() -> SysResult<()> {
+    let r = unsafe { sys_ecall0(nr::SYNC) };
+    if is_err_sentinel(r) {
+        Err(SysErr::Fail)
+    } else {
         Ok(())
     }
 }
-pub trait IoRead {
-    fn read(&self, buf: &mut [u8]) -> SysResult<usize>;
-}
+
+/* ---------- tiny io traits ---------- */
+
+pub use uapi::io::{IoRead, IoWrite};
 
 impl IoWrite for Fd {
     fn write(&self, b: &[u8]) -> SysResult<usize> { write_fd(*self, b) }
@@ -259,12 +735,90 @@ impl IoWrite for Fd {
 
 impl IoRead for Fd { fn read(&self, b: &mut [u8]) -> SysResult<usize> { self.read_priv(b) } }
 
+/// An open file that is closed when dropped.
+pub struct File {
+    fd: Fd,
+}
+
+impl File {
+    /// Open an existing file by name.
+    pub fn open(path: &str) -> SysResult<File> {
+        let path = path.parse::<PathBuf>().map_err(|_| SysErr::Fail)?;
+        open(path.as_cstr()).map(|fd| File { fd })
+    }
+
+    pub fn fd(&self) -> Fd {
+        self.fd
+    }
+}
+
+impl IoRead for File {
+    fn read(&self, b: &mut [u8]) -> SysResult<usize> { self.fd.read_priv(b) }
+}
+
+impl IoWrite for File {
+    fn write(&self, b: &[u8]) -> SysResult<usize> { write_fd(self.fd, b) }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let _ = self.fd.close();
+    }
+}
+
+
+/// Buffers reads from an `IoRead` so lines can be pulled out one at a time
+/// without a syscall per byte.
+pub struct BufReader<R: IoRead> {
+    inner: R,
+    buf: [u8; 512],
+    pos: usize,
+    len: usize,
+}
+
+impl<R: IoRead> BufReader<R> {
+    pub fn new(inner: R) -> Self {
+        BufReader { inner, buf: [0; 512], pos: 0, len: 0 }
+    }
+
+    /// Copy bytes up to and including the next `\n` into `out` and return how
+    /// many were stored; 0 means EOF. A line longer than `out` comes back in
+    /// pieces over several calls.
+    pub fn read_line(&mut self, out: &mut [u8]) -> SysResult<usize> {
+        let mut n = 0;
+        while n < out.len() {
+            if self.pos == self.len {
+                self.len = self.inner.read(&mut self.buf)?;
+                self.pos = 0;
+                if self.len == 0 {
+                    break;
+                }
+            }
+            let b = self.buf[self.pos];
+            self.pos += 1;
+            out[n] = b;
+            n += 1;
+            if b == b'\n' {
+                break;
+            }
+        }
+        Ok(n)
+    }
+}
 
 /* -------- tiny stdio-style helpers ---------- */
 
 pub struct Stdout;
 pub struct Stderr;
 
+impl Stdout {
+    /// Write out buffered output. `write_str` goes straight to the kernel,
+    /// so there is never anything pending yet.
+    pub fn flush(&mut self) -> SysResult<()> {
+        Ok(())
+    }
+}
+
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         let _ = STDOUT.write_all(s.as_bytes());
@@ -279,6 +833,9 @@ impl Write for Stderr {
     }
 }
 
+/// Formatted output to stdout. Strings go out as their UTF-8 bytes; the
+/// framebuffer console reads bytes 0xA0-0xFF as CP437 glyphs, not UTF-8, so
+/// box-drawing has to be written as raw bytes (e.g. `write(b"\xC9\xCD\xBB")`).
 #[macro_export]
 macro_rules! print {
     ($($arg:tt)*) => {{
@@ -309,6 +866,18 @@ macro_rules! eprintln {
     }}
 }
 
+/// Print `bytes` as hex, 16 per line, each line starting with its offset.
+pub fn put_hex(bytes: &[u8]) {
+    let mut out = Stdout;
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let _ = write!(out, "{:08x}:", i * 16);
+        for b in line {
+            let _ = write!(out, " {:02x}", b);
+        }
+        let _ = out.write_str("\n");
+    }
+}
+
 /* ---------- debug/logging macros ---------- */
 
 /// Debug output macros that always write to stderr (serial port).
@@ -331,7 +900,17 @@ macro_rules! trace {
     }}
 }
 
-/// Write a debug-level message to stderr (serial port).
+/// Write a debug-level message to stderr, which the kernel always sends to
+/// the serial port, so it stays out of framebuffer stdout. Only built with
+/// the `debug` feature (on by default); without it the arguments are still
+/// type-checked but nothing is written.
+///
+/// ```ignore
+/// usys::debug!("loaded {} bytes", 42);
+/// // expands to roughly
+/// let _ = core::fmt::write(&mut usys::Stderr, format_args!("[DEBUG] loaded {} bytes\n", 42));
+/// ```
+#[cfg(feature = "debug")]
 #[macro_export]
 macro_rules! debug {
     ($($arg:tt)*) => {{
@@ -340,6 +919,17 @@ macro_rules! debug {
     }}
 }
 
+/// `debug!` with the `debug` feature off: compiles to nothing.
+#[cfg(not(feature = "debug"))]
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {{
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    }}
+}
+
 /// Write an info-level message to stderr (serial port).
 /// Note: This goes to serial for debugging, not to stdout.
 #[macro_export]
@@ -435,6 +1025,105 @@ pub fn read_line_stdin_silent(buf: &mut [u8]) -> SysResult<usize> {
 }
 
 
+/* ---------- program arguments ---------- */
+
+static ARGC: AtomicUsize = AtomicUsize::new(0);
+static ARGV: AtomicUsize = AtomicUsize::new(0);
+
+/// Remember the `argc`/`argv` handed to `_start` so [`args`] can find them.
+///
+/// # Safety
+/// `argv` must point to `argc` NUL-terminated strings that stay valid for the
+/// rest of the program, as the kernel's initial user stack does.
+pub unsafe fn init_args(argc: usize, argv: *const *const u8) {
+    ARGV.store(argv as usize, Ordering::Relaxed);
+    ARGC.store(if argv.is_null() { 0 } else { argc }, Ordering::Relaxed);
+}
+
+/// Iterate over the program arguments, `argv[0]` first. Empty unless
+/// [`init_args`] was called; arguments that aren't UTF-8 come out as "".
+pub fn args() -> Args {
+    Args {
+        argv: ARGV.load(Ordering::Relaxed) as *const *const u8,
+        next: 0,
+        argc: ARGC.load(Ordering::Relaxed),
+    }
+}
+
+pub struct Args {
+    argv: *const *const u8,
+    next: usize,
+    argc: usize,
+}
+
+impl Iterator for Args {
+    type Item = &'static str;
+
+    fn next(&mut self) -> Option<&'static str> {
+        if self.next >= self.argc {
+            return None;
+        }
+        // SAFETY: init_args promised argc valid, 'static C strings
+        let arg = unsafe {
+            let p = *self.argv.add(self.next);
+            if p.is_null() {
+                self.argc = self.next;
+                return None;
+            }
+            CStr::from_ptr(p as *const core::ffi::c_char)
+        };
+        self.next += 1;
+        Some(arg.to_str().unwrap_or(""))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.argc - self.next;
+        (0, Some(n))
+    }
+}
+
+/// The environment handed to `_start`, as "KEY=value" strings.
+pub mod env {
+    use core::ffi::CStr;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static ENVP: AtomicUsize = AtomicUsize::new(0);
+
+    /// Remember the `envp` handed to `_start` so [`get`] can search it.
+    ///
+    /// # Safety
+    /// `envp` must be null or a NULL-terminated array of NUL-terminated
+    /// strings that stay valid for the rest of the program, as the kernel's
+    /// initial user stack does.
+    pub unsafe fn init(envp: *const *const u8) {
+        ENVP.store(envp as usize, Ordering::Relaxed);
+    }
+
+    /// Iterate over the "KEY=value" entries. Empty unless [`init`] was
+    /// called; entries that aren't UTF-8 are skipped.
+    pub fn vars() -> impl Iterator<Item = &'static str> {
+        let envp = ENVP.load(Ordering::Relaxed) as *const *const u8;
+        (0..)
+            .map(move |i| {
+                if envp.is_null() {
+                    return None;
+                }
+                // SAFETY: init promised a NULL-terminated array of 'static C strings
+                unsafe {
+                    let p = *envp.add(i);
+                    (!p.is_null()).then(|| CStr::from_ptr(p as *const core::ffi::c_char))
+                }
+            })
+            .take_while(Option::is_some)
+            .filter_map(|var| var?.to_str().ok())
+    }
+
+    /// The value of `key`, if the environment has it.
+    pub fn get(key: &str) -> Option<&'static str> {
+        vars().find_map(|var| var.strip_prefix(key)?.strip_prefix('='))
+    }
+}
+
 /// -------- NUL-terminated literal helper ---------- */
 
 #[macro_export]
@@ -477,4 +1166,24 @@ impl<const N: usize> Default for CStrBuf<N> {
     fn default() -> Self {
         Self { buf: [0; N], len: 0 }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use host::{Exited, FLUSHES};
+
+    #[test]
+    fn test_exit_code_flushes_stdout_first() {
+        FLUSHES.with(|n| n.set(0));
+        let exited = std::panic::catch_unwind(|| exit_code(3)).unwrap_err();
+        assert_eq!(exited.downcast_ref::<Exited>(), Some(&Exited { nr: nr::EXIT, a0: 3, flushes: 1 }));
+    }
+
+    #[test]
+    fn test_exit_is_exit_code_zero() {
+        FLUSHES.with(|n| n.set(0));
+        let exited = std::panic::catch_unwind(|| exit()).unwrap_err();
+        assert_eq!(exited.downcast_ref::<Exited>(), Some(&Exited { nr: nr::EXIT, a0: 0, flushes: 1 }));
+    }
+}